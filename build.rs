@@ -0,0 +1,26 @@
+#[cfg(feature = "ffi")]
+extern crate cbindgen;
+
+#[cfg(feature = "ffi")]
+fn main() {
+    use std::env;
+    use std::path::Path;
+
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    // The header is generated into `OUT_DIR` (build scripts must not modify the source tree).
+    // The committed `include/libflate.h` is checked against it by `ffi::test::header_is_up_to_date`.
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let config = cbindgen::Config::from_file(Path::new(&dir).join("cbindgen.toml"))
+        .expect("Unable to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(Path::new(&dir).join("src/ffi.rs"))
+        .generate()
+        .expect("Unable to generate the C header")
+        .write_to_file(Path::new(&out_dir).join("libflate.h"));
+}
+
+#[cfg(not(feature = "ffi"))]
+fn main() {}
@@ -53,7 +53,8 @@ fn main() {
         Box::new(io::stdin())
     } else {
         Box::new(
-            fs::File::open(input_filename).expect(&format!("Can't open file: {}", input_filename)),
+            fs::File::open(input_filename)
+                .unwrap_or_else(|_| panic!("Can't open file: {}", input_filename)),
         )
     };
     let mut input = io::BufReader::new(input);
@@ -66,7 +67,7 @@ fn main() {
     } else {
         Box::new(
             fs::File::create(output_filename)
-                .expect(&format!("Can't create file: {}", output_filename)),
+                .unwrap_or_else(|_| panic!("Can't create file: {}", output_filename)),
         )
     };
     let mut output = io::BufWriter::new(output);
@@ -92,7 +93,7 @@ fn main() {
     } else if let Some(_matches) = matches.subcommand_matches("gzip-decode") {
         let mut decoder = gzip::Decoder::new(input).expect("Read GZIP header failed");
         if verbose {
-            let _ = writeln!(&mut io::stderr(), "HEADER: {:?}", decoder.header());
+            let _ = writeln!(&mut io::stderr(), "HEADER:\n{}", decoder.header());
         }
         io::copy(&mut decoder, &mut output).expect("Decoding GZIP stream failed");
     } else if let Some(_matches) = matches.subcommand_matches("gzip-decode-multi") {
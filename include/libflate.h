@@ -0,0 +1,108 @@
+#ifndef LIBFLATE_H
+#define LIBFLATE_H
+
+/* This file is generated by cbindgen from src/ffi.rs. Do not edit it manually. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// The operation succeeded.
+#define LIBFLATE_OK 0
+
+// The operation succeeded, but more output is available (call the function again).
+#define LIBFLATE_MORE_OUTPUT 1
+
+// A required pointer argument was null.
+#define LIBFLATE_ERROR_NULL_POINTER -1
+
+// An I/O error occurred (e.g., the compressed data are broken).
+#define LIBFLATE_ERROR_IO -2
+
+// The encoder has already been finished.
+#define LIBFLATE_ERROR_FINISHED -3
+
+// A panic occurred in the library.
+#define LIBFLATE_ERROR_PANIC -4
+
+// GZIP decoder which decodes the data given at the creation.
+typedef struct LibflateGzipDecoder LibflateGzipDecoder;
+
+// GZIP encoder which accumulates the encoded data in memory.
+typedef struct LibflateGzipEncoder LibflateGzipEncoder;
+
+// Makes a new GZIP encoder and stores it to `*encoder`.
+//
+// The encoder must be released by `libflate_gzip_encoder_free`.
+//
+// # Safety
+//
+// `encoder` must be null or a valid pointer to write a pointer.
+int libflate_gzip_encoder_new(struct LibflateGzipEncoder **encoder);
+
+// Encodes `len` bytes of `buf`.
+//
+// # Safety
+//
+// `encoder` must be null or a pointer made by `libflate_gzip_encoder_new`,
+// and `buf` must be null or valid for reads of `len` bytes.
+int libflate_gzip_encoder_write(struct LibflateGzipEncoder *encoder,
+                                const uint8_t *buf,
+                                size_t len);
+
+// Finishes the encoding and copies the encoded data to `out` (up to `out_len` bytes).
+//
+// The number of the copied bytes is stored to `*written`.
+// If the encoded data do not fit in `out`, `LIBFLATE_MORE_OUTPUT` is returned
+// and the rest of the data can be taken by calling this function again.
+//
+// # Safety
+//
+// `encoder` must be null or a pointer made by `libflate_gzip_encoder_new`,
+// `out` must be null or valid for writes of `out_len` bytes,
+// and `written` must be null or a valid pointer to write a `size_t`.
+int libflate_gzip_encoder_finish(struct LibflateGzipEncoder *encoder,
+                                 uint8_t *out,
+                                 size_t out_len,
+                                 size_t *written);
+
+// Releases the encoder made by `libflate_gzip_encoder_new`.
+//
+// # Safety
+//
+// `encoder` must be null or a pointer made by `libflate_gzip_encoder_new` which has not been released.
+void libflate_gzip_encoder_free(struct LibflateGzipEncoder *encoder);
+
+// Makes a new GZIP decoder which decodes `len` bytes of `buf`, and stores it to `*decoder`.
+//
+// The data are copied, so `buf` can be released after this call.
+// The decoder must be released by `libflate_gzip_decoder_free`.
+//
+// # Safety
+//
+// `buf` must be null or valid for reads of `len` bytes,
+// and `decoder` must be null or a valid pointer to write a pointer.
+int libflate_gzip_decoder_new(const uint8_t *buf, size_t len, struct LibflateGzipDecoder **decoder);
+
+// Decodes up to `out_len` bytes to `out`.
+//
+// The number of the decoded bytes is stored to `*read`.
+// Zero means that the end of the stream has been reached.
+//
+// # Safety
+//
+// `decoder` must be null or a pointer made by `libflate_gzip_decoder_new`,
+// `out` must be null or valid for writes of `out_len` bytes,
+// and `read` must be null or a valid pointer to write a `size_t`.
+int libflate_gzip_decoder_read(struct LibflateGzipDecoder *decoder,
+                               uint8_t *out,
+                               size_t out_len,
+                               size_t *read);
+
+// Releases the decoder made by `libflate_gzip_decoder_new`.
+//
+// # Safety
+//
+// `decoder` must be null or a pointer made by `libflate_gzip_decoder_new` which has not been released.
+void libflate_gzip_decoder_free(struct LibflateGzipDecoder *decoder);
+
+#endif  /* LIBFLATE_H */
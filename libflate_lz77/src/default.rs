@@ -1,16 +1,124 @@
-use std::cmp;
-use std::collections::HashMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp;
 
 use super::Code;
+use super::CompressionLevel;
 use super::Lz77Encode;
+use super::Lz77Stats;
 use super::Sink;
 
+const DEFAULT_GOOD_LENGTH: u16 = 8;
+const DEFAULT_MAX_LAZY: u16 = 16;
+const DEFAULT_NICE_LENGTH: u16 = 128;
+const DEFAULT_MAX_CHAIN: u16 = 128;
+
+// The parameters for each compression level.
+//
+// The tuple consists of `(good_length, max_lazy, nice_length, max_chain)`
+// and the values are the same as zlib's `configuration_table`.
+const LEVEL_PARAMS: [(u16, u16, u16, u16); 9] = [
+    (4, 4, 8, 4),
+    (4, 5, 16, 8),
+    (4, 6, 32, 32),
+    (4, 4, 16, 16),
+    (8, 16, 32, 32),
+    (8, 16, 128, 128),
+    (8, 32, 128, 256),
+    (32, 128, 258, 1024),
+    (32, 258, 258, 4096),
+];
+
+/// The tuning parameters of `DefaultLz77Encoder`.
+///
+/// The meanings of the fields are the same as zlib's parameters
+/// (see `DefaultLz77EncoderBuilder` for details).
+///
+/// # Examples
+/// ```
+/// use libflate_lz77::{DefaultLz77Encoder, Lz77Params};
+///
+/// let params = Lz77Params::from(9);
+/// assert_eq!(params.max_chain, 4096);
+///
+/// let encoder = DefaultLz77Encoder::with_params(params);
+/// assert_eq!(encoder.params(), params);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lz77Params {
+    /// The match length considered to be good enough.
+    pub good_length: u16,
+
+    /// The match length at which the search is stopped immediately.
+    pub nice_length: u16,
+
+    /// The maximum number of hash chain entries examined when searching a match.
+    pub max_chain: u16,
+
+    /// The maximum length of a match that is checked for a better match by lazy matching.
+    pub max_lazy: u16,
+}
+impl Default for Lz77Params {
+    fn default() -> Self {
+        Lz77Params {
+            good_length: DEFAULT_GOOD_LENGTH,
+            nice_length: DEFAULT_NICE_LENGTH,
+            max_chain: DEFAULT_MAX_CHAIN,
+            max_lazy: DEFAULT_MAX_LAZY,
+        }
+    }
+}
+impl From<u8> for Lz77Params {
+    /// Makes the zlib-equivalent parameters of the compression level.
+    ///
+    /// Levels greater than 9 are treated as 9.
+    /// Level 0 (no compression in zlib) is treated as 1.
+    fn from(level: u8) -> Self {
+        let (good_length, max_lazy, nice_length, max_chain) =
+            LEVEL_PARAMS[level.clamp(1, 9) as usize - 1];
+        Lz77Params {
+            good_length,
+            nice_length,
+            max_chain,
+            max_lazy,
+        }
+    }
+}
+
+// The format version of the snapshots taken by `DefaultLz77Encoder::window_snapshot`.
+const SNAPSHOT_VERSION: u8 = 1;
+
+// The minimum size of the hash table used for a small input
+const MIN_TABLE_BITS: u32 = 8;
+
+const MIN_HASH_BITS: u8 = 9;
+const MAX_HASH_BITS: u8 = 16;
+
+const MIN_HASH_BYTES: u8 = 3;
+const MAX_HASH_BYTES: u8 = 4;
+
+const MIN_MATCH_LEN_LOWER: u8 = 3;
+const MIN_MATCH_LEN_UPPER: u8 = 4;
+
 /// A `Lz77Encode` implementation used by default.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DefaultLz77Encoder {
     window_size: u16,
     max_length: u16,
+    max_chain: u16,
+    good_length: u16,
+    nice_length: u16,
+    max_lazy: u16,
+    lazy_match: bool,
+    hash_bytes: u8,
+    hash_bits: Option<u8>,
+    min_match_len: u8,
     buf: Vec<u8>,
+    dict_len: usize,
+    history: Vec<u8>,
+    stats: Lz77Stats,
 }
 
 impl DefaultLz77Encoder {
@@ -25,7 +133,7 @@ impl DefaultLz77Encoder {
     /// assert_eq!(lz77.window_size(), lz77::MAX_WINDOW_SIZE);
     ///
     /// let options = deflate::EncodeOptions::with_lz77(lz77);
-    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options);
+    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options).unwrap();
     /// ```
     pub fn new() -> Self {
         DefaultLz77EncoderBuilder::new().build()
@@ -45,13 +153,165 @@ impl DefaultLz77Encoder {
     /// assert_eq!(lz77.window_size(), 1024);
     ///
     /// let options = deflate::EncodeOptions::with_lz77(lz77);
-    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options);
+    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options).unwrap();
     /// ```
     pub fn with_window_size(size: u16) -> Self {
         DefaultLz77EncoderBuilder::new()
             .window_size(cmp::min(size, super::MAX_WINDOW_SIZE))
             .build()
     }
+
+    /// Makes a new encoder instance with specified tuning parameters.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate;
+    /// use libflate::lz77::{DefaultLz77Encoder, Lz77Params};
+    ///
+    /// let lz77 = DefaultLz77Encoder::with_params(Lz77Params::from(1));
+    ///
+    /// let options = deflate::EncodeOptions::with_lz77(lz77);
+    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn with_params(params: Lz77Params) -> Self {
+        DefaultLz77EncoderBuilder::new().params(params).build()
+    }
+
+    /// Makes a new encoder instance whose window is pre-loaded with `dict`.
+    ///
+    /// This is equivalent to calling `Lz77Encode::set_dictionary` on a new instance:
+    /// the last `window_size()` bytes of `dict` are loaded into the window,
+    /// so the encoded data can refer to them from the first byte.
+    /// The resulting stream must be decoded with the same dictionary
+    /// (e.g., by `deflate::Decoder::with_dictionary`).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Decoder, EncodeOptions, Encoder};
+    /// use libflate::lz77::DefaultLz77Encoder;
+    ///
+    /// let dict = b"Content-Type: text/html";
+    /// let lz77 = DefaultLz77Encoder::with_dictionary(dict);
+    /// let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::with_lz77(lz77)).unwrap();
+    /// encoder.write_all(b"Content-Type: text/plain").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoded_data = Vec::new();
+    /// Decoder::with_dictionary(&encoded_data[..], dict).read_to_end(&mut decoded_data).unwrap();
+    /// assert_eq!(decoded_data, b"Content-Type: text/plain");
+    /// ```
+    pub fn with_dictionary(dict: &[u8]) -> Self {
+        let mut encoder = Self::new();
+        encoder.set_dictionary(dict);
+        encoder
+    }
+
+    /// Makes a new encoder instance which hashes `n` bytes to find match candidates.
+    ///
+    /// See `DefaultLz77EncoderBuilder::hash_bytes` for details.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate;
+    /// use libflate::lz77::DefaultLz77Encoder;
+    ///
+    /// let lz77 = DefaultLz77Encoder::with_hash_bytes(4);
+    /// assert_eq!(lz77.hash_bytes(), 4);
+    ///
+    /// let options = deflate::EncodeOptions::with_lz77(lz77);
+    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn with_hash_bytes(n: u8) -> Self {
+        DefaultLz77EncoderBuilder::new().hash_bytes(n).build()
+    }
+
+    /// Returns the number of the bytes hashed to find match candidates.
+    pub fn hash_bytes(&self) -> u8 {
+        self.hash_bytes
+    }
+
+    /// Makes a new encoder instance which emits only the matches of at least `n` bytes.
+    ///
+    /// See `DefaultLz77EncoderBuilder::min_match_len` for details.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate;
+    /// use libflate::lz77::DefaultLz77Encoder;
+    ///
+    /// let lz77 = DefaultLz77Encoder::with_min_match_len(4);
+    /// assert_eq!(lz77.min_match_len(), 4);
+    ///
+    /// let options = deflate::EncodeOptions::with_lz77(lz77);
+    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn with_min_match_len(n: u8) -> Self {
+        DefaultLz77EncoderBuilder::new().min_match_len(n).build()
+    }
+
+    /// Returns the minimum length of the matches emitted by the encoder.
+    pub fn min_match_len(&self) -> u8 {
+        self.min_match_len
+    }
+
+    /// Makes a new encoder instance whose hash table has at most `2^bits` entries.
+    ///
+    /// See `DefaultLz77EncoderBuilder::hash_bits` for details.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate;
+    /// use libflate::lz77::DefaultLz77Encoder;
+    ///
+    /// let lz77 = DefaultLz77Encoder::with_hash_bits(10);
+    /// assert_eq!(lz77.hash_bits(), 10);
+    ///
+    /// let options = deflate::EncodeOptions::with_lz77(lz77);
+    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn with_hash_bits(bits: u8) -> Self {
+        DefaultLz77EncoderBuilder::new().hash_bits(bits).build()
+    }
+
+    /// Returns the base-2 logarithm of the maximum number of the entries of the hash table.
+    ///
+    /// If it is not specified by `DefaultLz77EncoderBuilder::hash_bits`, this is equal to `window_bits()`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::lz77::{DefaultLz77Encoder, DefaultLz77EncoderBuilder};
+    ///
+    /// assert_eq!(DefaultLz77Encoder::new().hash_bits(), 15);
+    /// assert_eq!(DefaultLz77EncoderBuilder::new().window_bits(10).build().hash_bits(), 10);
+    /// ```
+    pub fn hash_bits(&self) -> u8 {
+        self.hash_bits.unwrap_or_else(|| self.window_bits())
+    }
+
+    /// Returns the base-2 logarithm of the window size (rounded up).
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::lz77::{DefaultLz77Encoder, DefaultLz77EncoderBuilder};
+    ///
+    /// assert_eq!(DefaultLz77Encoder::new().window_bits(), 15);
+    /// assert_eq!(DefaultLz77EncoderBuilder::new().window_bits(10).build().window_bits(), 10);
+    /// ```
+    pub fn window_bits(&self) -> u8 {
+        (bit_length(usize::from(self.window_size).saturating_sub(1)) as u8)
+            .clamp(super::MIN_WINDOW_BITS, super::MAX_WINDOW_BITS)
+    }
+
+    /// Returns the tuning parameters of the encoder.
+    pub fn params(&self) -> Lz77Params {
+        Lz77Params {
+            good_length: self.good_length,
+            nice_length: self.nice_length,
+            max_chain: self.max_chain,
+            max_lazy: self.max_lazy,
+        }
+    }
 }
 
 impl Default for DefaultLz77Encoder {
@@ -61,131 +321,338 @@ impl Default for DefaultLz77Encoder {
 }
 
 impl Lz77Encode for DefaultLz77Encoder {
-    fn encode<S>(&mut self, buf: &[u8], sink: S)
-    where
-        S: Sink,
-    {
+    fn encode(&mut self, buf: &[u8], sink: &mut dyn Sink) {
         self.buf.extend_from_slice(buf);
         if self.buf.len() >= self.window_size as usize * 8 {
             self.flush(sink);
         }
     }
-    fn flush<S>(&mut self, mut sink: S)
+    fn flush(&mut self, sink: &mut dyn Sink) {
+        let mut sink = RecordingSink {
+            inner: sink,
+            stats: Lz77Stats::default(),
+        };
+        let mut hash_chain =
+            HashChain::new(self.buf.len(), u32::from(self.hash_bits()), self.hash_bytes);
+        let end = cmp::max(3, self.buf.len()) - 3;
+        self.insert_range(&mut hash_chain, 0, self.dict_len, end);
+        let i = if self.lazy_match {
+            self.encode_lazy(&mut hash_chain, end, &mut sink)
+        } else {
+            self.encode_greedy(&mut hash_chain, end, &mut sink)
+        };
+        for b in &self.buf[i..] {
+            sink.consume(Code::Literal(*b));
+        }
+        self.update_history();
+        self.buf.clear();
+        self.dict_len = 0;
+        self.stats.merge(&sink.stats);
+    }
+    fn reset(&mut self) {
+        self.buf.clear();
+        self.dict_len = 0;
+        self.history.clear();
+        self.stats = Lz77Stats::default();
+    }
+    fn set_dictionary(&mut self, dict: &[u8]) {
+        // The dictionary is kept at the head of the buffer until the next flush
+        let dict = &dict[dict.len().saturating_sub(self.window_size as usize)..];
+        self.buf.clear();
+        self.buf.extend_from_slice(dict);
+        self.dict_len = dict.len();
+        self.history.clear();
+        self.history.extend_from_slice(dict);
+    }
+    /// The snapshot consists of a version byte, `window_bits()` and the last `window_size()`
+    /// bytes of the flushed data.
+    ///
+    /// The hash chain is not included because it is rebuilt from the window bytes on restore.
+    fn window_snapshot(&self) -> Vec<u8> {
+        let mut snapshot = Vec::with_capacity(2 + self.history.len());
+        snapshot.push(SNAPSHOT_VERSION);
+        snapshot.push(self.window_bits());
+        snapshot.extend_from_slice(&self.history);
+        snapshot
+    }
+    fn window_restore(&mut self, snapshot: &[u8]) -> Result<(), String> {
+        if snapshot.len() < 2 {
+            return Err(format!(
+                "Too short window snapshot: {} bytes",
+                snapshot.len()
+            ));
+        }
+        if snapshot[0] != SNAPSHOT_VERSION {
+            return Err(format!(
+                "Unsupported window snapshot version: {}",
+                snapshot[0]
+            ));
+        }
+        let window_bits = snapshot[1];
+        let window = &snapshot[2..];
+        if !(super::MIN_WINDOW_BITS..=super::MAX_WINDOW_BITS).contains(&window_bits)
+            || window.len() > 1 << window_bits
+        {
+            return Err(format!(
+                "Malformed window snapshot: window_bits={}, window.len={}",
+                window_bits,
+                window.len()
+            ));
+        }
+        self.set_dictionary(window);
+        Ok(())
+    }
+    fn stats(&self) -> Lz77Stats {
+        self.stats.clone()
+    }
+    fn compression_level(&self) -> CompressionLevel {
+        match self.max_chain.cmp(&DEFAULT_MAX_CHAIN) {
+            cmp::Ordering::Less => CompressionLevel::Fast,
+            cmp::Ordering::Equal => CompressionLevel::Balance,
+            cmp::Ordering::Greater => CompressionLevel::Best,
+        }
+    }
+    fn window_size(&self) -> u16 {
+        self.window_size
+    }
+}
+impl DefaultLz77Encoder {
+    /// Appends the data encoded by the current flush to the history, keeping its last `window_size` bytes.
+    fn update_history(&mut self) {
+        let window_size = self.window_size as usize;
+        let data = &self.buf[self.dict_len..];
+        if data.len() >= window_size {
+            self.history.clear();
+            self.history
+                .extend_from_slice(&data[data.len() - window_size..]);
+        } else {
+            let excess = (self.history.len() + data.len()).saturating_sub(window_size);
+            self.history.drain(..excess);
+            self.history.extend_from_slice(data);
+        }
+    }
+
+    /// Encodes `self.buf[self.dict_len..end]` by greedy matching and returns the position where the encoding stopped.
+    fn encode_greedy<S>(&self, hash_chain: &mut HashChain, end: usize, sink: &mut S) -> usize
     where
         S: Sink,
     {
-        let mut prefix_table = PrefixTable::new(self.buf.len());
-        let mut i = 0;
-        let end = cmp::max(3, self.buf.len()) - 3;
+        let mut i = self.dict_len;
         while i < end {
-            let key = prefix(&self.buf[i..]);
-            let matched = prefix_table.insert(key, i as u32);
-            if let Some(j) = matched.map(|j| j as usize) {
-                let distance = i - j;
-                if distance <= self.window_size as usize {
-                    let length = 3 + longest_common_prefix(
-                        &self.buf,
-                        i + 3,
-                        j + 3,
-                        self.max_length as usize,
-                    );
-                    sink.consume(Code::Pointer {
-                        length,
-                        backward_distance: distance as u16,
-                    });
-                    for k in (i..).take(length as usize).skip(1) {
-                        if k >= end {
-                            break;
-                        }
-                        prefix_table.insert(prefix(&self.buf[k..]), k as u32);
-                    }
-                    i += length as usize;
-                    continue;
+            let candidate = hash_chain.insert(&self.buf, i);
+            let (length, distance) = self.longest_match(hash_chain, i, candidate);
+            if length >= u16::from(self.min_match_len) {
+                sink.consume(Code::Pointer {
+                    length,
+                    backward_distance: distance,
+                });
+                if length <= self.max_lazy {
+                    self.insert_range(hash_chain, i + 1, i + length as usize, end);
                 }
+                i += length as usize;
+                continue;
             }
             sink.consume(Code::Literal(self.buf[i]));
             i += 1;
         }
-        for b in &self.buf[i..] {
-            sink.consume(Code::Literal(*b));
+        i
+    }
+
+    /// Encodes `self.buf[self.dict_len..end]` by lazy matching and returns the position where the encoding stopped.
+    ///
+    /// A match found at a position is deferred until the match at the next position is examined,
+    /// and the longer one is emitted.
+    fn encode_lazy<S>(&self, hash_chain: &mut HashChain, end: usize, sink: &mut S) -> usize
+    where
+        S: Sink,
+    {
+        let mut i = self.dict_len;
+        let mut prev_match: Option<(u16, u16)> = None; // The deferred match at `i - 1`
+        while i < end {
+            let candidate = hash_chain.insert(&self.buf, i);
+            let (length, distance) = match prev_match {
+                Some((prev_length, _)) if prev_length >= self.max_lazy => (0, 0),
+                _ => self.longest_match(hash_chain, i, candidate),
+            };
+            match prev_match {
+                Some((prev_length, _)) if length > prev_length => {
+                    sink.consume(Code::Literal(self.buf[i - 1]));
+                    prev_match = Some((length, distance));
+                    i += 1;
+                }
+                Some((prev_length, prev_distance)) => {
+                    sink.consume(Code::Pointer {
+                        length: prev_length,
+                        backward_distance: prev_distance,
+                    });
+                    let next = i - 1 + prev_length as usize;
+                    self.insert_range(hash_chain, i + 1, next, end);
+                    prev_match = None;
+                    i = next;
+                }
+                None if length >= u16::from(self.min_match_len) => {
+                    prev_match = Some((length, distance));
+                    i += 1;
+                }
+                None => {
+                    sink.consume(Code::Literal(self.buf[i]));
+                    i += 1;
+                }
+            }
         }
-        self.buf.clear();
+        if let Some((prev_length, prev_distance)) = prev_match {
+            sink.consume(Code::Pointer {
+                length: prev_length,
+                backward_distance: prev_distance,
+            });
+            i = i - 1 + prev_length as usize;
+        }
+        i
     }
-    fn window_size(&self) -> u16 {
-        self.window_size
+
+    /// Inserts the positions `start..cmp::min(end, limit)` into the hash chain.
+    #[inline]
+    fn insert_range(&self, hash_chain: &mut HashChain, start: usize, end: usize, limit: usize) {
+        for k in start..cmp::min(end, limit) {
+            hash_chain.insert(&self.buf, k);
+        }
+    }
+
+    /// Searches the hash chain starting from `candidate` for the longest match of `self.buf[i..]`.
+    ///
+    /// Returns the pair of the length and the backward distance of the found match.
+    /// If no match is found, the length will be less than 3.
+    fn longest_match(
+        &self,
+        hash_chain: &HashChain,
+        i: usize,
+        mut candidate: Option<usize>,
+    ) -> (u16, u16) {
+        let max_length = cmp::min(self.max_length as usize, self.buf.len() - i);
+        let nice_length = cmp::min(self.nice_length as usize, max_length);
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        let mut remaining_chain = self.max_chain;
+        let mut is_good_enough = false;
+        while let Some(j) = candidate {
+            let distance = i - j;
+            if distance > self.window_size as usize {
+                break;
+            }
+            if self.buf[j + best_length] == self.buf[i + best_length] {
+                let length = longest_common_prefix(&self.buf, i, j, max_length);
+                if length > best_length {
+                    best_length = length;
+                    best_distance = distance;
+                    if length >= nice_length {
+                        break;
+                    }
+                    if length >= self.good_length as usize && !is_good_enough {
+                        // We already have a good match, so the remaining search is shortened.
+                        remaining_chain >>= 2;
+                        is_good_enough = true;
+                    }
+                }
+            }
+            if remaining_chain <= 1 {
+                break;
+            }
+            remaining_chain -= 1;
+            candidate = hash_chain.prev(j);
+        }
+        (best_length as u16, best_distance as u16)
     }
 }
 
-#[inline]
-fn prefix(input_buf: &[u8]) -> [u8; 3] {
-    let buf: &[u8] = &input_buf[..3]; // perform bounds check once
-    [buf[0], buf[1], buf[2]]
+/// A `Sink` that records the statistics of the codes passed to the inner sink.
+struct RecordingSink<S> {
+    inner: S,
+    stats: Lz77Stats,
+}
+impl<S> Sink for RecordingSink<S>
+where
+    S: Sink,
+{
+    fn consume(&mut self, code: Code) {
+        self.stats.record(&code);
+        self.inner.consume(code);
+    }
 }
 
 #[inline]
-fn longest_common_prefix(buf: &[u8], i: usize, j: usize, max: usize) -> u16 {
+fn longest_common_prefix(buf: &[u8], i: usize, j: usize, max: usize) -> usize {
     buf[i..]
         .iter()
-        .take(max - 3)
+        .take(max)
         .zip(&buf[j..])
         .take_while(|&(x, y)| x == y)
-        .count() as u16
+        .count()
 }
 
+const NIL: u32 = u32::MAX;
+
 #[derive(Debug)]
-enum PrefixTable {
-    Small(HashMap<[u8; 3], u32>),
-    Large(LargePrefixTable),
+struct HashChain {
+    hash_shift: u32,
+    hash_bytes: u8,
+    head: Vec<u32>,
+    prev: Vec<u32>,
 }
-impl PrefixTable {
-    fn new(bytes: usize) -> Self {
-        if bytes < super::MAX_WINDOW_SIZE as usize {
-            PrefixTable::Small(HashMap::new())
-        } else {
-            PrefixTable::Large(LargePrefixTable::new())
+impl HashChain {
+    fn new(bytes: usize, max_hash_bits: u32, hash_bytes: u8) -> Self {
+        let max_hash_bits = cmp::max(max_hash_bits, MIN_TABLE_BITS);
+        let hash_bits = bit_length(bytes).clamp(MIN_TABLE_BITS, max_hash_bits);
+        HashChain {
+            hash_shift: 32 - hash_bits,
+            hash_bytes,
+            head: vec![NIL; 1 << hash_bits],
+            prev: vec![NIL; bytes],
         }
     }
 
+    /// Hashes the `hash_bytes` bytes starting from `i`.
+    ///
+    /// The caller must ensure that `buf` has at least four bytes from `i`
+    /// (the encoder only inserts positions up to `buf.len() - 4`).
     #[inline]
-    fn insert(&mut self, prefix: [u8; 3], position: u32) -> Option<u32> {
-        match *self {
-            PrefixTable::Small(ref mut x) => x.insert(prefix, position),
-            PrefixTable::Large(ref mut x) => x.insert(prefix, position),
+    fn hash(&self, buf: &[u8], i: usize) -> usize {
+        let buf = &buf[i..i + self.hash_bytes as usize]; // perform bounds check once
+        let mut key = u32::from(buf[0]) | (u32::from(buf[1]) << 8) | (u32::from(buf[2]) << 16);
+        if self.hash_bytes == MAX_HASH_BYTES {
+            key |= u32::from(buf[3]) << 24;
         }
+        (key.wrapping_mul(0x9E37_79B1) >> self.hash_shift) as usize
     }
-}
 
-#[derive(Debug)]
-struct LargePrefixTable {
-    table: Vec<Vec<(u8, u32)>>,
-}
-impl LargePrefixTable {
-    fn new() -> Self {
-        LargePrefixTable {
-            table: (0..=0xFFFF).map(|_| Vec::new()).collect(),
+    /// Inserts the position `i` and returns the most recent position which has the same hash value.
+    #[inline]
+    fn insert(&mut self, buf: &[u8], i: usize) -> Option<usize> {
+        let h = self.hash(buf, i);
+        let old = self.head[h];
+        self.head[h] = i as u32;
+        self.prev[i] = old;
+        if old == NIL {
+            None
+        } else {
+            Some(old as usize)
         }
     }
 
     #[inline]
-    fn insert(&mut self, prefix: [u8; 3], position: u32) -> Option<u32> {
-        let p0 = prefix[0] as usize;
-        let p1 = prefix[1] as usize;
-        let p2 = prefix[2];
-
-        let i = (p0 << 8) + p1;
-        let positions = &mut self.table[i];
-        for &mut (key, ref mut value) in positions.iter_mut() {
-            if key == p2 {
-                let old = *value;
-                *value = position;
-                return Some(old);
-            }
+    fn prev(&self, i: usize) -> Option<usize> {
+        let j = self.prev[i];
+        if j == NIL {
+            None
+        } else {
+            Some(j as usize)
         }
-        positions.push((p2, position));
-        None
     }
 }
 
+fn bit_length(n: usize) -> u32 {
+    usize::BITS - n.leading_zeros()
+}
+
 /// Type for constructing instances of `DefaultLz77Encoder`.
 ///
 /// # Examples
@@ -206,6 +673,14 @@ impl LargePrefixTable {
 pub struct DefaultLz77EncoderBuilder {
     window_size: u16,
     max_length: u16,
+    max_chain: u16,
+    good_length: u16,
+    nice_length: u16,
+    max_lazy: u16,
+    lazy_match: bool,
+    hash_bytes: u8,
+    hash_bits: Option<u8>,
+    min_match_len: u8,
 }
 
 impl DefaultLz77EncoderBuilder {
@@ -214,6 +689,14 @@ impl DefaultLz77EncoderBuilder {
         DefaultLz77EncoderBuilder {
             window_size: super::MAX_WINDOW_SIZE,
             max_length: super::MAX_LENGTH,
+            max_chain: DEFAULT_MAX_CHAIN,
+            good_length: DEFAULT_GOOD_LENGTH,
+            nice_length: DEFAULT_NICE_LENGTH,
+            max_lazy: DEFAULT_MAX_LAZY,
+            lazy_match: true,
+            hash_bytes: MIN_HASH_BYTES,
+            hash_bits: None,
+            min_match_len: MIN_MATCH_LEN_LOWER,
         }
     }
 
@@ -230,6 +713,15 @@ impl DefaultLz77EncoderBuilder {
         }
     }
 
+    /// Set the size of the sliding search window to `1 << window_bits` bytes.
+    ///
+    /// `window_bits` is clamped to the range `MIN_WINDOW_BITS..=MAX_WINDOW_BITS`.
+    /// The buffers and the hash table of the encoder are scaled according to the window size.
+    pub fn window_bits(self, window_bits: u8) -> Self {
+        let window_bits = window_bits.clamp(super::MIN_WINDOW_BITS, super::MAX_WINDOW_BITS);
+        self.window_size(1 << window_bits)
+    }
+
     /// Set the maximum length of a pointer command this encoder will emit.
     ///
     /// Some uses of LZ77 may not be able to encode pointers of the standard
@@ -242,12 +734,131 @@ impl DefaultLz77EncoderBuilder {
         }
     }
 
+    /// Set the maximum number of hash chain entries examined when searching a match.
+    ///
+    /// Larger values make the encoder find longer matches at the cost of speed.
+    /// The value is clamped to at least 1.
+    pub fn max_chain(self, max_chain: u16) -> Self {
+        DefaultLz77EncoderBuilder {
+            max_chain: cmp::max(max_chain, 1),
+            ..self
+        }
+    }
+
+    /// Set the match length considered to be good enough.
+    ///
+    /// Once a match of this length has been found,
+    /// the rest of the hash chain is searched only a quarter as deep.
+    pub fn good_length(self, good_length: u16) -> Self {
+        DefaultLz77EncoderBuilder {
+            good_length,
+            ..self
+        }
+    }
+
+    /// Set the match length at which the search is stopped immediately.
+    pub fn nice_length(self, nice_length: u16) -> Self {
+        DefaultLz77EncoderBuilder {
+            nice_length: cmp::min(nice_length, super::MAX_LENGTH),
+            ..self
+        }
+    }
+
+    /// Set the maximum length of a match that is checked for a better match by lazy matching.
+    ///
+    /// If lazy matching is disabled, this is the maximum length of a match
+    /// whose substrings are inserted into the hash table instead.
+    /// Skipping the insertions for long matches speeds up the encoding
+    /// but may miss some of the subsequent matches.
+    pub fn max_lazy(self, max_lazy: u16) -> Self {
+        DefaultLz77EncoderBuilder { max_lazy, ..self }
+    }
+
+    /// Set the tuning parameters at once.
+    pub fn params(self, params: Lz77Params) -> Self {
+        self.good_length(params.good_length)
+            .nice_length(params.nice_length)
+            .max_chain(params.max_chain)
+            .max_lazy(params.max_lazy)
+    }
+
+    /// Set whether lazy matching is enabled.
+    ///
+    /// If enabled, a match found at a position is not emitted immediately.
+    /// Instead, the encoder checks whether the match at the next position is strictly longer,
+    /// and if so, emits a literal and takes the longer match.
+    /// This raises compression ratio at modest CPU cost.
+    ///
+    /// The default value is `true`.
+    pub fn lazy_match(self, lazy_match: bool) -> Self {
+        DefaultLz77EncoderBuilder { lazy_match, ..self }
+    }
+
+    /// Set the number of the bytes hashed to find match candidates.
+    ///
+    /// `hash_bytes` is clamped to the range `3..=4`.
+    /// Hashing four bytes reduces the hash collisions (and the hash chain traversal)
+    /// on inputs with many 3-byte repeats that differ at the fourth byte,
+    /// at the cost of missing some matches of length 3.
+    ///
+    /// The default value is `3`.
+    pub fn hash_bytes(self, hash_bytes: u8) -> Self {
+        DefaultLz77EncoderBuilder {
+            hash_bytes: hash_bytes.clamp(MIN_HASH_BYTES, MAX_HASH_BYTES),
+            ..self
+        }
+    }
+
+    /// Set the minimum length of the matches emitted by the encoder.
+    ///
+    /// `min_match_len` is clamped to the range `3..=4`.
+    /// A 3-byte match often costs as many bits as the literals it replaces,
+    /// so requiring four bytes speeds up the encoding of poorly compressible data
+    /// at the cost of a slightly lower compression ratio.
+    /// Combining it with `hash_bytes(4)` also avoids the hash chain traversals for 3-byte repeats.
+    ///
+    /// The default value is `3`.
+    pub fn min_match_len(self, min_match_len: u8) -> Self {
+        DefaultLz77EncoderBuilder {
+            min_match_len: min_match_len.clamp(MIN_MATCH_LEN_LOWER, MIN_MATCH_LEN_UPPER),
+            ..self
+        }
+    }
+
+    /// Set the maximum number of the entries of the hash table to `2^hash_bits`.
+    ///
+    /// `hash_bits` is clamped to the range `9..=16`.
+    /// Each entry takes four bytes, so the default table of the full window (`2^15` entries) takes 128 KB.
+    /// A smaller table saves memory (e.g., on embedded devices) but causes more hash collisions,
+    /// which lengthen the hash chains to be traversed and may lower the compression ratio.
+    /// A larger table reduces the collisions but is less cache friendly.
+    /// Note that a smaller table is used if the input to be encoded at once is small.
+    ///
+    /// The default value is the same as `window_bits` (i.e., `15` for the default window size).
+    pub fn hash_bits(self, hash_bits: u8) -> Self {
+        DefaultLz77EncoderBuilder {
+            hash_bits: Some(hash_bits.clamp(MIN_HASH_BITS, MAX_HASH_BITS)),
+            ..self
+        }
+    }
+
     /// Build the encoder with the builder state's parameters.
     pub fn build(self) -> DefaultLz77Encoder {
         DefaultLz77Encoder {
             window_size: self.window_size,
             max_length: self.max_length,
+            max_chain: self.max_chain,
+            good_length: self.good_length,
+            nice_length: self.nice_length,
+            max_lazy: self.max_lazy,
+            lazy_match: self.lazy_match,
+            hash_bytes: self.hash_bytes,
+            hash_bits: self.hash_bits,
+            min_match_len: self.min_match_len,
             buf: Vec::new(),
+            dict_len: 0,
+            history: Vec::new(),
+            stats: Lz77Stats::default(),
         }
     }
 }
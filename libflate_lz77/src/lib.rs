@@ -1,9 +1,24 @@
 //! The interface and implementations of LZ77 compression algorithm.
 //!
 //! LZ77 is a compression algorithm used in [DEFLATE](https://tools.ietf.org/html/rfc1951).
-pub use self::default::{DefaultLz77Encoder, DefaultLz77EncoderBuilder};
+//!
+//! This crate is `no_std` and only requires the `alloc` crate.
+#![no_std]
+extern crate alloc;
+
+pub use self::default::{DefaultLz77Encoder, DefaultLz77EncoderBuilder, Lz77Params};
+pub use self::suffix_array::SuffixArrayLz77Encoder;
+pub use self::zopfli::ZopfliLz77Encoder;
+
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp;
 
 mod default;
+mod suffix_array;
+mod zopfli;
 
 /// Maximum length of sharable bytes in a pointer.
 pub const MAX_LENGTH: u16 = 258;
@@ -14,8 +29,15 @@ pub const MAX_DISTANCE: u16 = 32_768;
 /// Maximum size of a sliding window.
 pub const MAX_WINDOW_SIZE: u16 = MAX_DISTANCE;
 
+/// Minimum base-2 logarithm of the size of a sliding window.
+pub const MIN_WINDOW_BITS: u8 = 8;
+
+/// Maximum base-2 logarithm of the size of a sliding window.
+pub const MAX_WINDOW_BITS: u8 = 15;
+
 /// A LZ77 encoded data.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Code {
     /// Literal byte.
     Literal(u8),
@@ -48,14 +70,77 @@ pub enum CompressionLevel {
     Best,
 }
 
+/// Statistics of the codes emitted by a LZ77 encoder.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Lz77Stats {
+    /// The number of the emitted literals.
+    pub literal_count: u64,
+
+    /// The number of the emitted pointers (back-references).
+    pub match_count: u64,
+
+    /// The sum of the lengths of the emitted pointers.
+    pub total_match_length: u64,
+
+    /// The length of the longest pointer (`0` if no pointer has been emitted).
+    pub longest_match: u16,
+
+    /// The length of the shortest pointer (`0` if no pointer has been emitted).
+    pub shortest_match: u16,
+}
+impl Lz77Stats {
+    /// Updates the statistics with an emitted code.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::lz77::{Code, Lz77Stats};
+    ///
+    /// let mut stats = Lz77Stats::default();
+    /// stats.record(&Code::Literal(0));
+    /// stats.record(&Code::Pointer { length: 10, backward_distance: 1 });
+    /// stats.record(&Code::Pointer { length: 4, backward_distance: 1 });
+    /// assert_eq!(stats.literal_count, 1);
+    /// assert_eq!(stats.match_count, 2);
+    /// assert_eq!(stats.total_match_length, 14);
+    /// assert_eq!(stats.longest_match, 10);
+    /// assert_eq!(stats.shortest_match, 4);
+    /// ```
+    pub fn record(&mut self, code: &Code) {
+        match *code {
+            Code::Literal(_) => self.literal_count += 1,
+            Code::Pointer { length, .. } => {
+                if self.match_count == 0 || length < self.shortest_match {
+                    self.shortest_match = length;
+                }
+                self.longest_match = cmp::max(self.longest_match, length);
+                self.match_count += 1;
+                self.total_match_length += u64::from(length);
+            }
+        }
+    }
+
+    /// Merges the statistics of `other` into `self`.
+    pub fn merge(&mut self, other: &Lz77Stats) {
+        if other.match_count > 0 {
+            if self.match_count == 0 || other.shortest_match < self.shortest_match {
+                self.shortest_match = other.shortest_match;
+            }
+            self.longest_match = cmp::max(self.longest_match, other.longest_match);
+        }
+        self.literal_count += other.literal_count;
+        self.match_count += other.match_count;
+        self.total_match_length += other.total_match_length;
+    }
+}
+
 /// The `Sink` trait represents a consumer of LZ77 encoded data.
 pub trait Sink {
     /// Consumes a LZ77 encoded `Code`.
     fn consume(&mut self, code: Code);
 }
-impl<'a, T> Sink for &'a mut T
+impl<T> Sink for &mut T
 where
-    T: Sink,
+    T: Sink + ?Sized,
 {
     fn consume(&mut self, code: Code) {
         (*self).consume(code);
@@ -70,17 +155,184 @@ where
     }
 }
 
+/// A `Sink` which counts the consumed literals and pointers (back-references).
+///
+/// # Examples
+/// ```
+/// use libflate::lz77::{DefaultLz77Encoder, Lz77Encode, StatsSink};
+///
+/// let mut sink = StatsSink::default();
+/// let mut encoder = DefaultLz77Encoder::new();
+/// encoder.encode(b"Hello World! Hello World!", &mut sink);
+/// encoder.flush(&mut sink);
+///
+/// let stats = sink.as_stats();
+/// assert_eq!(stats.literal_count, 13);
+/// assert_eq!(stats.match_count, 1);
+/// assert_eq!(stats.total_match_length, 12);
+/// assert_eq!(stats.longest_match, 12);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct StatsSink {
+    stats: Lz77Stats,
+}
+impl StatsSink {
+    /// Makes a new sink instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the statistics of the consumed codes.
+    pub fn as_stats(&self) -> &Lz77Stats {
+        &self.stats
+    }
+
+    /// Converts into the statistics of the consumed codes.
+    pub fn into_stats(self) -> Lz77Stats {
+        self.stats
+    }
+}
+impl Sink for StatsSink {
+    fn consume(&mut self, code: Code) {
+        self.stats.record(&code);
+    }
+}
+
+/// A `Sink` which forwards each code to both of the inner sinks.
+///
+/// # Examples
+/// ```
+/// use libflate::lz77::{Code, DefaultLz77Encoder, Lz77Encode, TeeSink};
+///
+/// let mut sink = TeeSink(Vec::<Code>::new(), Vec::<Code>::new());
+/// let mut encoder = DefaultLz77Encoder::new();
+/// encoder.encode(b"Hello World!", &mut sink);
+/// encoder.flush(&mut sink);
+///
+/// let TeeSink(a, b) = sink;
+/// assert_eq!(a.len(), 12);
+/// assert_eq!(a, b);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct TeeSink<A, B>(pub A, pub B);
+impl<A, B> Sink for TeeSink<A, B>
+where
+    A: Sink,
+    B: Sink,
+{
+    fn consume(&mut self, code: Code) {
+        self.0.consume(code.clone());
+        self.1.consume(code);
+    }
+}
+
+/// A `Sink` which forwards only the codes for which the filter returns `true` to the inner sink.
+///
+/// # Examples
+/// ```
+/// use libflate::lz77::{Code, DefaultLz77Encoder, FilterSink, Lz77Encode};
+///
+/// let mut pointers = Vec::<Code>::new();
+/// let mut sink = FilterSink::new(&mut pointers, |code: &Code| matches!(code, Code::Pointer { .. }));
+/// let mut encoder = DefaultLz77Encoder::new();
+/// encoder.encode(b"Hello World! Hello World!", &mut sink);
+/// encoder.flush(&mut sink);
+///
+/// assert_eq!(pointers, [Code::Pointer { length: 12, backward_distance: 13 }]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FilterSink<S, F> {
+    inner: S,
+    filter: F,
+}
+impl<S, F> FilterSink<S, F>
+where
+    S: Sink,
+    F: FnMut(&Code) -> bool,
+{
+    /// Makes a new sink instance.
+    pub fn new(inner: S, filter: F) -> Self {
+        FilterSink { inner, filter }
+    }
+
+    /// Unwraps this `FilterSink`, returning the inner sink.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+impl<S, F> Sink for FilterSink<S, F>
+where
+    S: Sink,
+    F: FnMut(&Code) -> bool,
+{
+    fn consume(&mut self, code: Code) {
+        if (self.filter)(&code) {
+            self.inner.consume(code);
+        }
+    }
+}
+
 /// The `LZ77Encode` trait defines the interface of LZ77 encoding algorithm.
+///
+/// This trait is object safe, so an encoder can be used as `Box<dyn Lz77Encode>`.
 pub trait Lz77Encode {
     /// Encodes a buffer and writes result LZ77 codes to `sink`.
-    fn encode<S>(&mut self, buf: &[u8], sink: S)
-    where
-        S: Sink;
+    fn encode(&mut self, buf: &[u8], sink: &mut dyn Sink);
 
     /// Flushes the encoder, ensuring that all intermediately buffered codes are consumed by `sink`.
-    fn flush<S>(&mut self, sink: S)
-    where
-        S: Sink;
+    fn flush(&mut self, sink: &mut dyn Sink);
+
+    /// Resets the encoder to its initial state, discarding any buffered data.
+    ///
+    /// The implementations should keep their allocated buffers for reuse.
+    ///
+    /// If the implementation is omitted, nothing will be done.
+    fn reset(&mut self) {}
+
+    /// Sets the preset dictionary which precedes the data to be encoded.
+    ///
+    /// This must be called before the first call of `encode`.
+    /// The encoder may emit back-references into the last `window_size()` bytes of `dict`,
+    /// so the decoder must be initialized with the same dictionary.
+    /// The dictionary is discarded by `reset`.
+    ///
+    /// If the implementation is omitted, the dictionary will be ignored
+    /// (the output is still decodable with the dictionary, but it is not compressed better).
+    fn set_dictionary(&mut self, _dict: &[u8]) {}
+
+    /// Takes a snapshot of the sliding window of the encoder.
+    ///
+    /// The snapshot covers the data which has been flushed (and the preset dictionary),
+    /// so `flush` should be called before taking it.
+    /// Restoring the snapshot into another encoder via `window_restore` makes it behave
+    /// as if it had encoded the same data, which is useful to encode consecutive streams
+    /// that share history.
+    ///
+    /// If the implementation is omitted, an empty snapshot will be returned.
+    fn window_snapshot(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores the sliding window from a snapshot taken by `window_snapshot`.
+    ///
+    /// Like `set_dictionary`, this must be called before the first call of `encode`,
+    /// and the decoder must be initialized with the bytes of the window as a dictionary.
+    ///
+    /// If the implementation is omitted, only an empty snapshot will be accepted.
+    fn window_restore(&mut self, snapshot: &[u8]) -> Result<(), String> {
+        if snapshot.is_empty() {
+            Ok(())
+        } else {
+            Err("This encoder does not support window snapshots".to_owned())
+        }
+    }
+
+    /// Returns the statistics of the codes emitted by the encoder so far.
+    ///
+    /// If the implementation is omitted, `Lz77Stats::default()` will be returned.
+    fn stats(&self) -> Lz77Stats {
+        Lz77Stats::default()
+    }
 
     /// Returns the compression level of the encoder.
     ///
@@ -96,9 +348,41 @@ pub trait Lz77Encode {
         MAX_WINDOW_SIZE
     }
 }
+impl<E> Lz77Encode for Box<E>
+where
+    E: Lz77Encode + ?Sized,
+{
+    fn encode(&mut self, buf: &[u8], sink: &mut dyn Sink) {
+        (**self).encode(buf, sink)
+    }
+    fn flush(&mut self, sink: &mut dyn Sink) {
+        (**self).flush(sink)
+    }
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+    fn set_dictionary(&mut self, dict: &[u8]) {
+        (**self).set_dictionary(dict)
+    }
+    fn window_snapshot(&self) -> Vec<u8> {
+        (**self).window_snapshot()
+    }
+    fn window_restore(&mut self, snapshot: &[u8]) -> Result<(), String> {
+        (**self).window_restore(snapshot)
+    }
+    fn stats(&self) -> Lz77Stats {
+        (**self).stats()
+    }
+    fn compression_level(&self) -> CompressionLevel {
+        (**self).compression_level()
+    }
+    fn window_size(&self) -> u16 {
+        (**self).window_size()
+    }
+}
 
 /// A no compression implementation of `LZ77Encode` trait.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct NoCompressionLz77Encoder;
 impl NoCompressionLz77Encoder {
     /// Makes a new encoder instance.
@@ -112,28 +396,61 @@ impl NoCompressionLz77Encoder {
     /// assert_eq!(lz77.compression_level(), CompressionLevel::None);
     ///
     /// let options = deflate::EncodeOptions::with_lz77(lz77);
-    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options);
+    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options).unwrap();
     /// ```
     pub fn new() -> Self {
         NoCompressionLz77Encoder
     }
 }
 impl Lz77Encode for NoCompressionLz77Encoder {
-    fn encode<S>(&mut self, buf: &[u8], mut sink: S)
-    where
-        S: Sink,
-    {
+    fn encode(&mut self, buf: &[u8], sink: &mut dyn Sink) {
         for c in buf.iter().cloned().map(Code::Literal) {
             sink.consume(c);
         }
     }
-    #[allow(unused_variables)]
-    fn flush<S>(&mut self, sink: S)
-    where
-        S: Sink,
-    {
-    }
+    fn flush(&mut self, _sink: &mut dyn Sink) {}
     fn compression_level(&self) -> CompressionLevel {
         CompressionLevel::None
     }
 }
+
+/// A `LZ77Encode` implementation that emits every byte as a literal.
+///
+/// Unlike `NoCompressionLz77Encoder`, this is intended to be used with huffman coding
+/// (e.g., `EncodeOptions::with_lz77(HuffmanOnlyLz77Encoder::new())`)
+/// to compress data that has a non-uniform byte distribution but few repeated strings
+/// (e.g., filtered PNG scanlines) without the cost of searching back-references.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct HuffmanOnlyLz77Encoder;
+impl HuffmanOnlyLz77Encoder {
+    /// Makes a new encoder instance.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate;
+    /// use libflate::lz77::{Lz77Encode, HuffmanOnlyLz77Encoder, CompressionLevel};
+    ///
+    /// let lz77 = HuffmanOnlyLz77Encoder::new();
+    /// assert_eq!(lz77.compression_level(), CompressionLevel::Fast);
+    ///
+    /// let options = deflate::EncodeOptions::with_lz77(lz77);
+    /// let mut encoder = deflate::Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(&[0; 1000]).unwrap();
+    /// assert!(encoder.finish().into_result().unwrap().len() < 1000);
+    /// ```
+    pub fn new() -> Self {
+        HuffmanOnlyLz77Encoder
+    }
+}
+impl Lz77Encode for HuffmanOnlyLz77Encoder {
+    fn encode(&mut self, buf: &[u8], sink: &mut dyn Sink) {
+        for c in buf.iter().cloned().map(Code::Literal) {
+            sink.consume(c);
+        }
+    }
+    fn flush(&mut self, _sink: &mut dyn Sink) {}
+    fn compression_level(&self) -> CompressionLevel {
+        CompressionLevel::Fast
+    }
+}
@@ -0,0 +1,257 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp;
+
+use super::Code;
+use super::CompressionLevel;
+use super::Lz77Encode;
+use super::Lz77Stats;
+use super::Sink;
+use super::{MAX_DISTANCE, MAX_LENGTH, MAX_WINDOW_SIZE};
+
+const MIN_LENGTH: usize = 3;
+
+// The maximum number of the suffixes examined at each position
+// (to bound the cost of the search for the nearest one among many equally long matches).
+const MAX_SCAN: usize = 4096;
+
+/// A `Lz77Encode` implementation which finds the longest matches using a suffix array.
+///
+/// For each DEFLATE block, the encoder builds the suffix array and the LCP array of the block
+/// (including the preceding window), and for each position it finds the longest match
+/// (the nearest one among the equally long matches) by scanning the neighboring suffixes.
+/// Unlike `DefaultLz77Encoder`, the search is not limited by the depth of a hash chain,
+/// so the matches are the longest possible ones at any CPU cost.
+/// The matches are selected by lazy matching.
+///
+/// The input is buffered until `flush` is called (i.e., each DEFLATE block is parsed at once).
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+/// use libflate::deflate::{Decoder, EncodeOptions, Encoder};
+///
+/// let plain = b"Hello World! Hello Suffix Array! Hello World!";
+/// let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::suffix_array_lz77()).unwrap();
+/// encoder.write_all(plain).unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let mut decoded_data = Vec::new();
+/// Decoder::new(&encoded_data[..]).read_to_end(&mut decoded_data).unwrap();
+/// assert_eq!(decoded_data, plain);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct SuffixArrayLz77Encoder {
+    history: Vec<u8>,
+    buf: Vec<u8>,
+    stats: Lz77Stats,
+}
+impl SuffixArrayLz77Encoder {
+    /// Makes a new encoder instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn parse(&self) -> Vec<Code> {
+        let mut data = Vec::with_capacity(self.history.len() + self.buf.len());
+        data.extend_from_slice(&self.history);
+        data.extend_from_slice(&self.buf);
+        let finder = MatchFinder::new(&data);
+
+        let mut codes = Vec::new();
+        let mut i = self.history.len();
+        let mut next = finder.longest_match(i);
+        while i < data.len() {
+            let current = next;
+            next = finder.longest_match(i + 1);
+            match current {
+                Some((length, distance)) if next.is_none_or(|(l, _)| l <= length) => {
+                    codes.push(Code::Pointer {
+                        length,
+                        backward_distance: distance,
+                    });
+                    i += usize::from(length);
+                    next = finder.longest_match(i);
+                }
+                _ => {
+                    codes.push(Code::Literal(data[i]));
+                    i += 1;
+                }
+            }
+        }
+        codes
+    }
+}
+impl Lz77Encode for SuffixArrayLz77Encoder {
+    fn encode(&mut self, buf: &[u8], _sink: &mut dyn Sink) {
+        self.buf.extend_from_slice(buf);
+    }
+    fn flush(&mut self, sink: &mut dyn Sink) {
+        if self.buf.is_empty() {
+            return;
+        }
+        for code in self.parse() {
+            self.stats.record(&code);
+            sink.consume(code);
+        }
+
+        let window_size = MAX_WINDOW_SIZE as usize;
+        if self.buf.len() >= window_size {
+            self.history.clear();
+        } else {
+            let excess = (self.history.len() + self.buf.len()).saturating_sub(window_size);
+            self.history.drain(..excess);
+        }
+        let start = self.buf.len().saturating_sub(window_size);
+        self.history.extend_from_slice(&self.buf[start..]);
+        self.buf.clear();
+    }
+    fn reset(&mut self) {
+        self.history.clear();
+        self.buf.clear();
+        self.stats = Lz77Stats::default();
+    }
+    fn set_dictionary(&mut self, dict: &[u8]) {
+        let start = dict.len().saturating_sub(MAX_WINDOW_SIZE as usize);
+        self.history.clear();
+        self.history.extend_from_slice(&dict[start..]);
+    }
+    fn stats(&self) -> Lz77Stats {
+        self.stats.clone()
+    }
+    fn compression_level(&self) -> CompressionLevel {
+        CompressionLevel::Best
+    }
+}
+
+#[derive(Debug)]
+struct MatchFinder {
+    suffixes: Vec<u32>,
+    ranks: Vec<u32>,
+    // `lcp[r]` is the length of the longest common prefix of `suffixes[r - 1]` and `suffixes[r]`.
+    lcp: Vec<u32>,
+}
+impl MatchFinder {
+    fn new(data: &[u8]) -> Self {
+        let suffixes = suffix_array(data);
+        let mut ranks = vec![0; data.len()];
+        for (r, &i) in suffixes.iter().enumerate() {
+            ranks[i as usize] = r as u32;
+        }
+        let lcp = lcp_array(data, &suffixes, &ranks);
+        MatchFinder {
+            suffixes,
+            ranks,
+            lcp,
+        }
+    }
+
+    // Returns the longest match `(length, distance)` at `i` (if any).
+    fn longest_match(&self, i: usize) -> Option<(u16, u16)> {
+        if i + MIN_LENGTH > self.ranks.len() {
+            return None;
+        }
+        let max_length = cmp::min(MAX_LENGTH as usize, self.ranks.len() - i);
+        let rank = self.ranks[i] as usize;
+        let mut best = (MIN_LENGTH - 1, usize::MAX);
+
+        // Suffixes preceding `i` in the suffix array
+        let mut length = max_length;
+        for (scanned, r) in (0..rank).rev().enumerate() {
+            length = cmp::min(length, self.lcp[r + 1] as usize);
+            if length < cmp::max(best.0, MIN_LENGTH) || scanned == MAX_SCAN {
+                break;
+            }
+            best = self.update_best(best, i, r, length);
+        }
+
+        // Suffixes following `i` in the suffix array
+        let mut length = max_length;
+        for (scanned, r) in (rank + 1..self.suffixes.len()).enumerate() {
+            length = cmp::min(length, self.lcp[r] as usize);
+            if length < cmp::max(best.0, MIN_LENGTH) || scanned == MAX_SCAN {
+                break;
+            }
+            best = self.update_best(best, i, r, length);
+        }
+
+        if best.1 == usize::MAX {
+            None
+        } else {
+            Some((best.0 as u16, best.1 as u16))
+        }
+    }
+
+    fn update_best(
+        &self,
+        best: (usize, usize),
+        i: usize,
+        r: usize,
+        length: usize,
+    ) -> (usize, usize) {
+        let j = self.suffixes[r] as usize;
+        if j >= i || i - j > MAX_DISTANCE as usize {
+            return best;
+        }
+        let distance = i - j;
+        if length > best.0 || distance < best.1 {
+            (length, distance)
+        } else {
+            best
+        }
+    }
+}
+
+// Builds the suffix array of `data` by prefix doubling.
+fn suffix_array(data: &[u8]) -> Vec<u32> {
+    let n = data.len();
+    let mut suffixes = (0..n as u32).collect::<Vec<_>>();
+    let mut ranks = data.iter().map(|&b| u32::from(b)).collect::<Vec<_>>();
+    let mut next_ranks = vec![0; n];
+    if n <= 1 {
+        return suffixes;
+    }
+    let mut k = 1;
+    loop {
+        {
+            let key = |i: u32| {
+                let i = i as usize;
+                let second = ranks.get(i + k).map_or(0, |&r| u64::from(r) + 1);
+                (u64::from(ranks[i]) << 32) | second
+            };
+            suffixes.sort_unstable_by_key(|&i| key(i));
+            next_ranks[suffixes[0] as usize] = 0;
+            for r in 1..n {
+                let (prev, current) = (suffixes[r - 1], suffixes[r]);
+                let increment = if key(prev) < key(current) { 1 } else { 0 };
+                next_ranks[current as usize] = next_ranks[prev as usize] + increment;
+            }
+        }
+        ::core::mem::swap(&mut ranks, &mut next_ranks);
+        if ranks[suffixes[n - 1] as usize] as usize == n - 1 {
+            break;
+        }
+        k *= 2;
+    }
+    suffixes
+}
+
+// Builds the LCP array by Kasai's algorithm.
+fn lcp_array(data: &[u8], suffixes: &[u32], ranks: &[u32]) -> Vec<u32> {
+    let mut lcp = vec![0; data.len()];
+    let mut h = 0;
+    for i in 0..data.len() {
+        let rank = ranks[i] as usize;
+        if rank == 0 {
+            h = 0;
+            continue;
+        }
+        let j = suffixes[rank - 1] as usize;
+        while i + h < data.len() && j + h < data.len() && data[i + h] == data[j + h] {
+            h += 1;
+        }
+        lcp[rank] = h as u32;
+        h = h.saturating_sub(1);
+    }
+    lcp
+}
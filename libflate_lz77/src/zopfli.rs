@@ -0,0 +1,394 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::{self, Reverse};
+
+use super::Code;
+use super::CompressionLevel;
+use super::Lz77Encode;
+use super::Lz77Stats;
+use super::Sink;
+use super::{MAX_DISTANCE, MAX_LENGTH, MAX_WINDOW_SIZE};
+
+const MIN_LENGTH: usize = 3;
+const DEFAULT_ITERATIONS: usize = 15;
+const MAX_CHAIN: usize = 4096;
+
+const HASH_BITS: u32 = 15;
+
+// The base values and the numbers of the extra bits of the length and distance codes (RFC-1951 3.2.5).
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// A `Lz77Encode` implementation that searches the optimal parse like [Zopfli].
+///
+/// For each position of the input, the encoder computes the minimum bit cost to reach it
+/// considering all the matches found there, and then traces back the cheapest path.
+/// Because the cost of a symbol depends on the huffman codes built from the resulting symbols,
+/// the parse is repeated with the costs of the previous result until it no longer improves
+/// (or the number of iterations reaches the limit).
+///
+/// This produces smaller output than `DefaultLz77Encoder` but is much slower,
+/// so it is intended for archival compression.
+///
+/// The input is buffered until `flush` is called (i.e., each DEFLATE block is parsed at once).
+///
+/// [Zopfli]: https://github.com/google/zopfli
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+/// use libflate::deflate::{Decoder, EncodeOptions, Encoder};
+///
+/// let plain = b"Hello World! Hello Zopfli! Hello World!";
+/// let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::zopfli_lz77()).unwrap();
+/// encoder.write_all(plain).unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let mut decoded_data = Vec::new();
+/// Decoder::new(&encoded_data[..]).read_to_end(&mut decoded_data).unwrap();
+/// assert_eq!(decoded_data, plain);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ZopfliLz77Encoder {
+    iterations: usize,
+    history: Vec<u8>,
+    buf: Vec<u8>,
+    stats: Lz77Stats,
+}
+impl ZopfliLz77Encoder {
+    /// Makes a new encoder instance.
+    pub fn new() -> Self {
+        Self::with_iterations(DEFAULT_ITERATIONS)
+    }
+
+    /// Makes a new encoder instance which parses each block at most `iterations` times.
+    ///
+    /// `iterations` is clamped to at least `1`. The default value is `15`.
+    pub fn with_iterations(iterations: usize) -> Self {
+        ZopfliLz77Encoder {
+            iterations: cmp::max(iterations, 1),
+            history: Vec::new(),
+            buf: Vec::new(),
+            stats: Lz77Stats::default(),
+        }
+    }
+
+    /// Returns the maximum number of the iterations of the parse of each block.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    fn parse(&self) -> Vec<Code> {
+        let mut data = Vec::with_capacity(self.history.len() + self.buf.len());
+        data.extend_from_slice(&self.history);
+        data.extend_from_slice(&self.buf);
+        let matches = MatchCache::new(&data, self.history.len());
+
+        let mut model = CostModel::fixed();
+        let mut best: Option<(u64, Vec<Code>)> = None;
+        for _ in 0..self.iterations {
+            let codes = optimal_parse(&data[self.history.len()..], &matches, &model);
+            model = CostModel::from_codes(&codes);
+            let cost = model.total_cost(&codes);
+            match best {
+                Some((best_cost, _)) if best_cost <= cost => break,
+                _ => best = Some((cost, codes)),
+            }
+        }
+        best.map(|(_, codes)| codes).unwrap_or_default()
+    }
+}
+impl Default for ZopfliLz77Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Lz77Encode for ZopfliLz77Encoder {
+    fn encode(&mut self, buf: &[u8], _sink: &mut dyn Sink) {
+        self.buf.extend_from_slice(buf);
+    }
+    fn flush(&mut self, sink: &mut dyn Sink) {
+        if self.buf.is_empty() {
+            return;
+        }
+        for code in self.parse() {
+            self.stats.record(&code);
+            sink.consume(code);
+        }
+
+        let window_size = MAX_WINDOW_SIZE as usize;
+        if self.buf.len() >= window_size {
+            self.history.clear();
+        } else {
+            let excess = (self.history.len() + self.buf.len()).saturating_sub(window_size);
+            self.history.drain(..excess);
+        }
+        let start = self.buf.len().saturating_sub(window_size);
+        self.history.extend_from_slice(&self.buf[start..]);
+        self.buf.clear();
+    }
+    fn reset(&mut self) {
+        self.history.clear();
+        self.buf.clear();
+        self.stats = Lz77Stats::default();
+    }
+    fn set_dictionary(&mut self, dict: &[u8]) {
+        let start = dict.len().saturating_sub(MAX_WINDOW_SIZE as usize);
+        self.history.clear();
+        self.history.extend_from_slice(&dict[start..]);
+    }
+    fn stats(&self) -> Lz77Stats {
+        self.stats.clone()
+    }
+    fn compression_level(&self) -> CompressionLevel {
+        CompressionLevel::Best
+    }
+}
+
+// The matches found at each position of the input.
+//
+// For each position, the pairs of `(length, distance)` are stored in ascending order of both values:
+// a pair means that the lengths up to `length` (and longer than the previous pair)
+// can be matched at `distance` at the shortest.
+#[derive(Debug)]
+struct MatchCache {
+    pairs: Vec<(u16, u16)>,
+    offsets: Vec<usize>,
+}
+impl MatchCache {
+    fn new(data: &[u8], start: usize) -> Self {
+        let mut head = vec![usize::MAX; 1 << HASH_BITS];
+        let mut prev = vec![usize::MAX; data.len()];
+        let mut pairs = Vec::new();
+        let mut offsets = Vec::with_capacity(data.len() - start + 1);
+        for i in 0..data.len() {
+            if i >= start {
+                offsets.push(pairs.len());
+            }
+            if i + MIN_LENGTH > data.len() {
+                continue;
+            }
+            let hash = hash(&data[i..]);
+            if i >= start {
+                let max_len = cmp::min(MAX_LENGTH as usize, data.len() - i);
+                let mut best_len = MIN_LENGTH - 1;
+                let mut candidate = head[hash];
+                let mut chain = 0;
+                while candidate != usize::MAX && chain < MAX_CHAIN {
+                    let distance = i - candidate;
+                    if distance > MAX_DISTANCE as usize {
+                        break;
+                    }
+                    let len = data[candidate..]
+                        .iter()
+                        .zip(&data[i..i + max_len])
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    if len > best_len {
+                        pairs.push((len as u16, distance as u16));
+                        best_len = len;
+                        if len == max_len {
+                            break;
+                        }
+                    }
+                    candidate = prev[candidate];
+                    chain += 1;
+                }
+            }
+            prev[i] = head[hash];
+            head[hash] = i;
+        }
+        offsets.push(pairs.len());
+        MatchCache { pairs, offsets }
+    }
+    fn get(&self, i: usize) -> &[(u16, u16)] {
+        &self.pairs[self.offsets[i]..self.offsets[i + 1]]
+    }
+}
+
+fn hash(data: &[u8]) -> usize {
+    let v = (u32::from(data[0]) << 16) | (u32::from(data[1]) << 8) | u32::from(data[2]);
+    (v.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+fn length_code(length: u16) -> usize {
+    LENGTH_BASES
+        .iter()
+        .rposition(|&base| base <= length)
+        .expect("Never fails")
+}
+
+fn distance_code(distance: u16) -> usize {
+    DISTANCE_BASES
+        .iter()
+        .rposition(|&base| base <= distance)
+        .expect("Never fails")
+}
+
+// The bit costs of the symbols.
+#[derive(Debug)]
+struct CostModel {
+    literals: [u32; 256],
+    lengths: [u32; MAX_LENGTH as usize + 1],
+    distance_codes: [u32; 30],
+}
+impl CostModel {
+    // The costs of the fixed huffman codes.
+    fn fixed() -> Self {
+        let mut litlen = [0; 286];
+        for (code, bits) in litlen.iter_mut().enumerate() {
+            *bits = match code {
+                0..=143 => 8,
+                144..=255 => 9,
+                256..=279 => 7,
+                _ => 8,
+            };
+        }
+        Self::new(&litlen, &[5; 30])
+    }
+    fn from_codes(codes: &[Code]) -> Self {
+        let mut litlen_counts = [0; 286];
+        let mut distance_counts = [0; 30];
+        litlen_counts[256] = 1; // end of block
+        for code in codes {
+            match *code {
+                Code::Literal(b) => litlen_counts[usize::from(b)] += 1,
+                Code::Pointer {
+                    length,
+                    backward_distance,
+                } => {
+                    litlen_counts[257 + length_code(length)] += 1;
+                    distance_counts[distance_code(backward_distance)] += 1;
+                }
+            }
+        }
+        Self::new(
+            &huffman_code_lengths(&litlen_counts),
+            &huffman_code_lengths(&distance_counts),
+        )
+    }
+    fn new(litlen: &[u32], distance: &[u32]) -> Self {
+        let mut literals = [0; 256];
+        literals.copy_from_slice(&litlen[..256]);
+        let mut lengths = [0; MAX_LENGTH as usize + 1];
+        for (length, cost) in lengths.iter_mut().enumerate().skip(MIN_LENGTH) {
+            let code = length_code(length as u16);
+            *cost = litlen[257 + code] + LENGTH_EXTRA_BITS[code];
+        }
+        let mut distance_codes = [0; 30];
+        for (code, cost) in distance_codes.iter_mut().enumerate() {
+            *cost = distance[code] + DISTANCE_EXTRA_BITS[code];
+        }
+        CostModel {
+            literals,
+            lengths,
+            distance_codes,
+        }
+    }
+    fn distance(&self, distance: u16) -> u32 {
+        self.distance_codes[distance_code(distance)]
+    }
+    fn total_cost(&self, codes: &[Code]) -> u64 {
+        codes
+            .iter()
+            .map(|code| match *code {
+                Code::Literal(b) => u64::from(self.literals[usize::from(b)]),
+                Code::Pointer {
+                    length,
+                    backward_distance,
+                } => {
+                    u64::from(self.lengths[usize::from(length)] + self.distance(backward_distance))
+                }
+            })
+            .sum()
+    }
+}
+
+// Returns the lengths of the huffman codes for the symbols which occur `counts` times.
+//
+// The counts are incremented by one, so that every symbol has a (finite) cost.
+// The lengths are not limited to 15 bits because they are only used to estimate the costs.
+fn huffman_code_lengths(counts: &[u64]) -> Vec<u32> {
+    let mut heap = counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| Reverse((count + 1, i)))
+        .collect::<BinaryHeap<_>>();
+    let mut parents = vec![0; counts.len()];
+    while heap.len() > 1 {
+        let Reverse((weight0, node0)) = heap.pop().expect("Never fails");
+        let Reverse((weight1, node1)) = heap.pop().expect("Never fails");
+        let node = parents.len();
+        parents.push(0);
+        parents[node0] = node;
+        parents[node1] = node;
+        heap.push(Reverse((weight0 + weight1, node)));
+    }
+
+    // Parents are created after their children, so the depths can be computed from the root.
+    let mut depths = vec![0; parents.len()];
+    for node in (0..parents.len().saturating_sub(1)).rev() {
+        depths[node] = depths[parents[node]] + 1;
+    }
+    depths.truncate(counts.len());
+    depths
+}
+
+fn optimal_parse(data: &[u8], matches: &MatchCache, model: &CostModel) -> Vec<Code> {
+    let mut costs = vec![u64::MAX; data.len() + 1];
+    let mut steps = vec![(0u16, 0u16); data.len() + 1];
+    costs[0] = 0;
+    for i in 0..data.len() {
+        let cost = costs[i];
+
+        let literal_cost = cost + u64::from(model.literals[usize::from(data[i])]);
+        if literal_cost < costs[i + 1] {
+            costs[i + 1] = literal_cost;
+            steps[i + 1] = (1, 0);
+        }
+
+        let mut length = MIN_LENGTH;
+        for &(max_length, distance) in matches.get(i) {
+            let distance_cost = cost + u64::from(model.distance(distance));
+            while length <= usize::from(max_length) {
+                let match_cost = distance_cost + u64::from(model.lengths[length]);
+                if match_cost < costs[i + length] {
+                    costs[i + length] = match_cost;
+                    steps[i + length] = (length as u16, distance);
+                }
+                length += 1;
+            }
+        }
+    }
+
+    let mut codes = Vec::new();
+    let mut i = data.len();
+    while i > 0 {
+        let (length, distance) = steps[i];
+        i -= usize::from(length);
+        if distance == 0 {
+            codes.push(Code::Literal(data[i]));
+        } else {
+            codes.push(Code::Pointer {
+                length,
+                backward_distance: distance,
+            });
+        }
+    }
+    codes.reverse();
+    codes
+}
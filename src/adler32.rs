@@ -0,0 +1,130 @@
+//! The Adler-32 checksum used by the ZLIB format.
+//!
+//! # Examples
+//! ```
+//! use std::io::Write;
+//! use libflate::adler32::{self, Adler32};
+//!
+//! let mut adler32 = Adler32::new();
+//! adler32.write_all(b"Hello World!").unwrap();
+//! assert_eq!(adler32.value(), 0x1C49043E);
+//!
+//! assert_eq!(adler32::update(adler32::INITIAL_VALUE, b"Hello World!"), 0x1C49043E);
+//! ```
+use core::fmt;
+use io;
+use rolling_adler32::RollingAdler32;
+
+/// The Adler-32 value of empty data.
+pub const INITIAL_VALUE: u32 = 1;
+
+// The largest prime smaller than 65536.
+const BASE: u64 = 65521;
+
+/// Returns the Adler-32 value of the concatenation of the data whose value is `state` and `buf`.
+///
+/// Use `INITIAL_VALUE` as `state` to compute the value of `buf` alone.
+pub fn update(state: u32, buf: &[u8]) -> u32 {
+    let mut adler32 = RollingAdler32::from_value(state);
+    adler32.update_buffer(buf);
+    adler32.hash()
+}
+
+/// Returns the Adler-32 value of the concatenation of two data
+/// from their values (`adler1` and `adler2`) and the length of the second data (`len2`).
+///
+/// This is the same as zlib's `adler32_combine`.
+///
+/// # Examples
+/// ```
+/// use libflate::adler32;
+///
+/// let adler1 = adler32::update(adler32::INITIAL_VALUE, b"Hello ");
+/// let adler2 = adler32::update(adler32::INITIAL_VALUE, b"World!");
+/// assert_eq!(adler32::combine(adler1, adler2, 6),
+///            adler32::update(adler32::INITIAL_VALUE, b"Hello World!"));
+/// ```
+pub fn combine(adler1: u32, adler2: u32, len2: u64) -> u32 {
+    let rem = len2 % BASE;
+    let mut sum1 = u64::from(adler1 & 0xFFFF);
+    let mut sum2 = (rem * sum1) % BASE;
+    sum1 += u64::from(adler2 & 0xFFFF) + BASE - 1;
+    sum2 += u64::from(adler1 >> 16) + u64::from(adler2 >> 16) + BASE - rem;
+    (((sum2 % BASE) << 16) | (sum1 % BASE)) as u32
+}
+
+/// A hasher that computes the Adler-32 value of the written bytes.
+#[derive(Clone)]
+pub struct Adler32(RollingAdler32);
+impl Adler32 {
+    /// Makes a new hasher instance.
+    pub fn new() -> Self {
+        Adler32(RollingAdler32::new())
+    }
+
+    /// Returns the Adler-32 value of the bytes written so far.
+    pub fn value(&self) -> u32 {
+        self.0.hash()
+    }
+
+    /// Updates the value with `buf`.
+    pub fn update(&mut self, buf: &[u8]) {
+        self.0.update_buffer(buf);
+    }
+
+    /// Resets the hasher to its initial state.
+    pub fn reset(&mut self) {
+        self.0 = RollingAdler32::new();
+    }
+}
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl fmt::Debug for Adler32 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Adler32(_)")
+    }
+}
+impl io::Write for Adler32 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn adler32_works() {
+        let mut adler32 = Adler32::new();
+        adler32.update(b"abcde");
+        assert_eq!(adler32.value(), 0x05C801F0);
+        assert_eq!(update(update(INITIAL_VALUE, b"ab"), b"cde"), 0x05C801F0);
+
+        adler32.reset();
+        assert_eq!(adler32.value(), INITIAL_VALUE);
+    }
+
+    #[test]
+    fn combine_works() {
+        let data = (0..100_000)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect::<Vec<_>>();
+        for &i in &[0, 1, 5_000, 65_521, 99_999, 100_000] {
+            let (a, b) = data.split_at(i);
+            let adler1 = update(INITIAL_VALUE, a);
+            let adler2 = update(INITIAL_VALUE, b);
+            assert_eq!(
+                combine(adler1, adler2, b.len() as u64),
+                update(INITIAL_VALUE, &data)
+            );
+        }
+    }
+}
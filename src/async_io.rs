@@ -0,0 +1,96 @@
+//! Adapters that drive the non-blocking implementations with tokio's asynchronous I/O.
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// A reader that polls the inner `AsyncRead` with the most recently registered waker.
+///
+/// `Poll::Pending` is converted to `io::ErrorKind::WouldBlock`,
+/// so the decoders in the `non_blocking` module can be stacked on this reader
+/// without losing their partial state.
+#[derive(Debug)]
+pub(crate) struct PollReader<R> {
+    inner: R,
+    waker: Option<Waker>,
+}
+impl<R> PollReader<R> {
+    pub fn new(inner: R) -> Self {
+        PollReader { inner, waker: None }
+    }
+    pub fn set_waker(&mut self, waker: &Waker) {
+        match self.waker {
+            Some(ref w) if w.will_wake(waker) => {}
+            _ => self.waker = Some(waker.clone()),
+        }
+    }
+    pub fn as_inner_ref(&self) -> &R {
+        &self.inner
+    }
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+impl<R> io::Read for PollReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let waker = match self.waker {
+            Some(ref waker) => waker,
+            None => return Err(io::ErrorKind::WouldBlock.into()),
+        };
+        let mut cx = Context::from_waker(waker);
+        let mut buf = ReadBuf::new(buf);
+        match Pin::new(&mut self.inner).poll_read(&mut cx, &mut buf) {
+            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            Poll::Ready(Err(e)) => Err(e),
+            Poll::Ready(Ok(())) => Ok(buf.filled().len()),
+        }
+    }
+}
+
+/// Reads from a non-blocking `decoder` into `buf`.
+///
+/// `io::ErrorKind::WouldBlock` is converted to `Poll::Pending`.
+pub(crate) fn poll_read<D>(decoder: &mut D, buf: &mut ReadBuf) -> Poll<io::Result<()>>
+where
+    D: io::Read,
+{
+    match decoder.read(buf.initialize_unfilled()) {
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
+        Err(e) => Poll::Ready(Err(e)),
+        Ok(size) => {
+            buf.advance(size);
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+/// Writes the staged bytes `buf[*offset..]` to `inner`.
+///
+/// Once all the bytes are written, `buf` is cleared and `*offset` is reset to zero.
+pub(crate) fn poll_drain<W>(
+    inner: &mut W,
+    cx: &mut Context,
+    buf: &mut Vec<u8>,
+    offset: &mut usize,
+) -> Poll<io::Result<()>>
+where
+    W: AsyncWrite + Unpin,
+{
+    while *offset < buf.len() {
+        match Pin::new(&mut *inner).poll_write(cx, &buf[*offset..]) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+            Poll::Ready(Ok(size)) => *offset += size,
+        }
+    }
+    buf.clear();
+    *offset = 0;
+    Poll::Ready(Ok(()))
+}
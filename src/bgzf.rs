@@ -0,0 +1,460 @@
+//! The encoder and decoder of the BGZF format.
+//!
+//! BGZF (Blocked GNU Zip Format) is a GZIP profile defined in the
+//! [SAM/BAM specification](https://samtools.github.io/hts-specs/SAMv1.pdf).
+//! A BGZF file is a series of GZIP members (blocks) each of which holds at most 64 KiB of data,
+//! and has a `BC` extra sub field which reports the size of the compressed block.
+//! Because of this, a BGZF file can be decoded by any GZIP decoder,
+//! and also supports random access by virtual file offsets.
+//!
+//! A virtual file offset consists of the offset of a block in the compressed file (upper 48 bits)
+//! and the offset of the data in the uncompressed block (lower 16 bits).
+//!
+//! # Examples
+//! ```
+//! use std::io::{Read, Write};
+//! use libflate::bgzf::{Encoder, Decoder};
+//!
+//! // Encoding
+//! let mut encoder = Encoder::new(Vec::new());
+//! encoder.write_all(b"Hello World!").unwrap();
+//! let encoded_data = encoder.finish().into_result().unwrap();
+//!
+//! // Decoding
+//! let mut decoder = Decoder::new(&encoded_data[..]);
+//! let mut decoded_data = Vec::new();
+//! decoder.read_to_end(&mut decoded_data).unwrap();
+//!
+//! assert_eq!(decoded_data, b"Hello World!");
+//! ```
+use std::cmp;
+use std::io::{self, Read, Write};
+
+use crc32;
+use deflate;
+use finish::{Complete, Finish};
+use gzip;
+
+/// The maximum number of the uncompressed bytes in a block.
+///
+/// This is less than 64 KiB so that the size of a block never exceeds 64 KiB
+/// even if the data is incompressible (the same limit as htslib).
+pub const MAX_BLOCK_DATA_SIZE: usize = 0xFF00;
+
+/// The empty block which marks the end of a BGZF file.
+pub const EOF_MARKER: [u8; 28] = [
+    0x1F, 0x8B, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1B, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+const MAX_BLOCK_SIZE: usize = 0x10000;
+const HEADER_SIZE: usize = 18;
+const TRAILER_SIZE: usize = 8;
+const FLG_EXTRA: u8 = 0b0000_0100;
+
+// The size of the BC sub field (SI1, SI2, SLEN and BSIZE).
+const BC_SUBFIELD_SIZE: usize = 6;
+
+/// BGZF encoder.
+///
+/// The written data is split into blocks of `MAX_BLOCK_DATA_SIZE` bytes,
+/// and each block is written to the inner stream as a GZIP member.
+#[derive(Debug)]
+pub struct Encoder<W> {
+    inner: W,
+    buf: Vec<u8>,
+    block: Vec<u8>,
+    block_offset: u64,
+}
+impl<W> Encoder<W>
+where
+    W: Write,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::bgzf::{Encoder, EOF_MARKER};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    /// assert!(encoded_data.ends_with(&EOF_MARKER));
+    /// ```
+    pub fn new(inner: W) -> Self {
+        Encoder {
+            inner,
+            buf: Vec::with_capacity(MAX_BLOCK_DATA_SIZE),
+            block: Vec::with_capacity(MAX_BLOCK_SIZE),
+            block_offset: 0,
+        }
+    }
+
+    /// Returns the virtual file offset of the next byte to be written.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::bgzf::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello").unwrap();
+    /// assert_eq!(encoder.virtual_offset(), 5);
+    ///
+    /// encoder.flush().unwrap();
+    /// let block_size = encoder.as_inner_ref().len() as u64;
+    /// assert_eq!(encoder.virtual_offset(), block_size << 16);
+    /// ```
+    pub fn virtual_offset(&self) -> u64 {
+        (self.block_offset << 16) | self.buf.len() as u64
+    }
+
+    /// Writes the buffered data and the end-of-file marker, and returns the inner stream.
+    pub fn finish(mut self) -> Finish<W, io::Error> {
+        let result = self
+            .write_block()
+            .and_then(|_| self.inner.write_all(&EOF_MARKER));
+        Finish::new(self.inner, result.err())
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwraps the `Encoder`, returning the inner stream.
+    ///
+    /// The buffered data is discarded.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        self.block.clear();
+        self.block.extend_from_slice(&EOF_MARKER[..HEADER_SIZE]);
+        let mut encoder = deflate::Encoder::new(&mut self.block);
+        encoder.write_all(&self.buf)?;
+        encoder.finish().into_result()?;
+        if self.block.len() + TRAILER_SIZE > MAX_BLOCK_SIZE {
+            // The data is incompressible
+            self.block.truncate(HEADER_SIZE);
+            let options = deflate::EncodeOptions::new().no_compression();
+            let mut encoder =
+                deflate::Encoder::with_options(&mut self.block, options).expect("Never fails");
+            encoder.write_all(&self.buf)?;
+            encoder.finish().into_result()?;
+        }
+        let mut crc32 = crc32::Crc32::new();
+        crc32.update(&self.buf);
+        self.block.extend_from_slice(&crc32.value().to_le_bytes());
+        self.block
+            .extend_from_slice(&(self.buf.len() as u32).to_le_bytes());
+
+        let bsize = (self.block.len() - 1) as u16;
+        self.block[16..HEADER_SIZE].copy_from_slice(&bsize.to_le_bytes());
+        self.inner.write_all(&self.block)?;
+        self.block_offset += self.block.len() as u64;
+        self.buf.clear();
+        Ok(())
+    }
+}
+impl<W> Write for Encoder<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buf.len() == MAX_BLOCK_DATA_SIZE {
+            self.write_block()?;
+        }
+        let size = cmp::min(buf.len(), MAX_BLOCK_DATA_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&buf[..size]);
+        Ok(size)
+    }
+
+    /// Writes the buffered data as a block and flushes the inner stream.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.inner.flush()
+    }
+}
+impl<W> Complete for Encoder<W>
+where
+    W: Write,
+{
+    fn complete(self) -> io::Result<()> {
+        self.finish().into_result().map(|_| ())
+    }
+}
+
+/// BGZF decoder.
+///
+/// The `BC` sub field of each block is validated against the actual size of the block.
+#[derive(Debug)]
+pub struct Decoder<R> {
+    inner: R,
+    block: Vec<u8>,
+    buf: Vec<u8>,
+    offset: usize,
+    block_offset: u64,
+    next_block_offset: u64,
+}
+impl<R> Decoder<R>
+where
+    R: Read,
+{
+    /// Makes a new decoder instance.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::bgzf::{Decoder, EOF_MARKER};
+    ///
+    /// let mut encoded_data = vec![31, 139, 8, 4, 0, 0, 0, 0, 0, 255, 6, 0, 66, 67, 2, 0, 39, 0,
+    ///                             243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0,
+    ///                             163, 28, 41, 28, 12, 0, 0, 0];
+    /// encoded_data.extend_from_slice(&EOF_MARKER);
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn new(inner: R) -> Self {
+        Decoder {
+            inner,
+            block: Vec::with_capacity(MAX_BLOCK_SIZE),
+            buf: Vec::with_capacity(MAX_BLOCK_SIZE),
+            offset: 0,
+            block_offset: 0,
+            next_block_offset: 0,
+        }
+    }
+
+    /// Returns the virtual file offset of the next byte to be read.
+    pub fn virtual_offset(&self) -> u64 {
+        if self.offset == self.buf.len() {
+            self.next_block_offset << 16
+        } else {
+            (self.block_offset << 16) | self.offset as u64
+        }
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the `Decoder`, returning the inner stream.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads the next block, and returns `false` if the stream reached EOF.
+    fn read_block(&mut self) -> io::Result<bool> {
+        self.block.resize(HEADER_SIZE, 0);
+        let mut filled = 0;
+        while filled < HEADER_SIZE {
+            match self.inner.read(&mut self.block[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(size) => filled += size,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if self.block[..3] != EOF_MARKER[..3] || self.block[3] & FLG_EXTRA == 0 {
+            return Err(invalid_data_error!("Not a BGZF block header"));
+        }
+        let xlen = u16::from_le_bytes([self.block[10], self.block[11]]) as usize;
+        if xlen < BC_SUBFIELD_SIZE {
+            return Err(invalid_data_error!(
+                "Too short extra field in BGZF block header: xlen={}",
+                xlen
+            ));
+        }
+        self.block.resize(12 + xlen, 0);
+        self.inner.read_exact(&mut self.block[HEADER_SIZE..])?;
+        let extra = gzip::ExtraField::read_from(&self.block[10..])
+            .map_err(|_| invalid_data_error!("Malformed extra field in BGZF block header"))?;
+        let bsize = match extra.get(*b"BC") {
+            Some(f) if f.data.len() == 2 => u16::from_le_bytes([f.data[0], f.data[1]]) as usize,
+            _ => return Err(invalid_data_error!("No BC sub field in BGZF block header")),
+        };
+        let block_size = bsize + 1;
+        if block_size < self.block.len() + TRAILER_SIZE {
+            return Err(invalid_data_error!(
+                "Too small BGZF block size: {}",
+                block_size
+            ));
+        }
+        let header_size = self.block.len();
+        self.block.resize(block_size, 0);
+        self.inner.read_exact(&mut self.block[header_size..])?;
+
+        self.buf.clear();
+        self.offset = 0;
+        let mut decoder = gzip::Decoder::new(&self.block[..])?;
+        decoder.read_to_end(&mut self.buf)?;
+        if !decoder.into_inner().is_empty() {
+            return Err(invalid_data_error!(
+                "The BC sub field does not match the block size: bsize={}",
+                bsize
+            ));
+        }
+        self.block_offset = self.next_block_offset;
+        self.next_block_offset += block_size as u64;
+        Ok(true)
+    }
+}
+impl<R> Decoder<R>
+where
+    R: Read + io::Seek,
+{
+    /// Seeks to the position specified by the virtual file offset `voffset`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Cursor, Read, Write};
+    /// use libflate::bgzf::{Encoder, Decoder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello").unwrap();
+    /// encoder.flush().unwrap();
+    /// encoder.write_all(b" World!").unwrap();
+    /// let voffset = encoder.virtual_offset() - 6;
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(encoded_data));
+    /// decoder.seek_to_virtual_offset(voffset).unwrap();
+    /// let mut buf = String::new();
+    /// decoder.read_to_string(&mut buf).unwrap();
+    /// assert_eq!(buf, "World!");
+    /// ```
+    pub fn seek_to_virtual_offset(&mut self, voffset: u64) -> io::Result<()> {
+        let block_offset = voffset >> 16;
+        let offset = (voffset & 0xFFFF) as usize;
+        self.inner.seek(io::SeekFrom::Start(block_offset))?;
+        self.next_block_offset = block_offset;
+        self.buf.clear();
+        self.offset = 0;
+        if !self.read_block()? && offset == 0 {
+            return Ok(());
+        }
+        if offset > self.buf.len() {
+            return Err(invalid_input_error!(
+                "Out of range virtual offset: block_size={}, offset={}",
+                self.buf.len(),
+                offset
+            ));
+        }
+        self.offset = offset;
+        Ok(())
+    }
+}
+impl<R> Read for Decoder<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.buf.len() {
+            if !self.read_block()? {
+                return Ok(0);
+            }
+        }
+        let size = cmp::min(buf.len(), self.buf.len() - self.offset);
+        buf[..size].copy_from_slice(&self.buf[self.offset..][..size]);
+        self.offset += size;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn encode_and_decode_works() {
+        let plain = (0..200_000)
+            .map(|i: u64| (i * i % 251) as u8)
+            .collect::<Vec<_>>();
+        let mut encoder = Encoder::new(Vec::new());
+        let mut voffsets = Vec::new();
+        for chunk in plain.chunks(10_000) {
+            voffsets.push(encoder.virtual_offset());
+            encoder.write_all(chunk).unwrap();
+        }
+        let encoded = encoder.finish().into_result().unwrap();
+        assert!(encoded.ends_with(&EOF_MARKER));
+
+        // Every block is a valid GZIP member
+        let mut decoded = Vec::new();
+        gzip::MultiDecoder::new(&encoded[..])
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain);
+
+        let mut decoded = Vec::new();
+        Decoder::new(&encoded[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain);
+
+        let mut decoder = Decoder::new(Cursor::new(&encoded));
+        for (i, voffset) in voffsets.into_iter().enumerate().rev() {
+            decoder.seek_to_virtual_offset(voffset).unwrap();
+            assert_eq!(decoder.virtual_offset(), voffset);
+            let mut buf = [0; 100];
+            decoder.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf[..], &plain[i * 10_000..][..100]);
+        }
+    }
+
+    #[test]
+    fn invalid_block_size_is_rejected() {
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(b"Hello World!").unwrap();
+        let mut encoded = encoder.finish().into_result().unwrap();
+        encoded[16] += 1;
+
+        let mut decoder = Decoder::new(&encoded[..]);
+        let e = decoder.read_to_end(&mut Vec::new()).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn short_extra_field_is_rejected() {
+        for xlen in 0..6 {
+            let mut header = vec![0x1F, 0x8B, 8, 4, 0, 0, 0, 0, 0, 0xFF, xlen, 0];
+            header.extend_from_slice(&[0; 32]);
+            let mut decoder = Decoder::new(&header[..]);
+            let e = decoder.read_to_end(&mut Vec::new()).err().unwrap();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        }
+
+        // An extra field without the BC sub field
+        let mut header = vec![0x1F, 0x8B, 8, 4, 0, 0, 0, 0, 0, 0xFF, 6, 0];
+        header.extend_from_slice(&[b'A', b'B', 2, 0, 0x1B, 0]);
+        header.extend_from_slice(&[0; 32]);
+        let mut decoder = Decoder::new(&header[..]);
+        let e = decoder.read_to_end(&mut Vec::new()).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,22 +1,19 @@
-use std::io;
+use core::cmp;
 
-#[derive(Debug)]
+use error::Error;
+use io;
+
+#[derive(Debug, Clone)]
 pub struct BitWriter<W> {
     inner: W,
     buf: u32,
     end: u8,
+    written_bytes: u64,
 }
 impl<W> BitWriter<W>
 where
     W: io::Write,
 {
-    pub fn new(inner: W) -> Self {
-        BitWriter {
-            inner,
-            buf: 0,
-            end: 0,
-        }
-    }
     #[inline(always)]
     pub fn write_bit(&mut self, bit: bool) -> io::Result<()> {
         self.write_bits(1, bit as u16)
@@ -29,9 +26,16 @@ where
         self.end += bitwidth;
         self.flush_if_needed()
     }
+    pub fn write_bytes(&mut self, buf: &[u8]) -> io::Result<()> {
+        debug_assert_eq!(self.end, 0, "The writer must be aligned to a byte boundary");
+        self.inner.write_all(buf)?;
+        self.written_bytes += buf.len() as u64;
+        Ok(())
+    }
     pub fn flush(&mut self) -> io::Result<()> {
         while self.end > 0 {
             self.inner.write_all(&[self.buf as u8])?;
+            self.written_bytes += 1;
             self.buf >>= 8;
             self.end = self.end.saturating_sub(8);
         }
@@ -42,6 +46,7 @@ where
     fn flush_if_needed(&mut self) -> io::Result<()> {
         if self.end >= 16 {
             self.inner.write_all(&(self.buf as u16).to_le_bytes())?;
+            self.written_bytes += 2;
             self.end -= 16;
             self.buf >>= 16;
         }
@@ -49,6 +54,31 @@ where
     }
 }
 impl<W> BitWriter<W> {
+    pub fn new(inner: W) -> Self {
+        BitWriter {
+            inner,
+            buf: 0,
+            end: 0,
+            written_bytes: 0,
+        }
+    }
+    /// Replaces the inner stream while keeping the bits which have not been flushed yet.
+    pub fn replace_inner<T>(self, inner: T) -> (BitWriter<T>, W) {
+        let writer = BitWriter {
+            inner,
+            buf: self.buf,
+            end: self.end,
+            written_bytes: self.written_bytes,
+        };
+        (writer, self.inner)
+    }
+    pub fn written_bytes(&self) -> u64 {
+        self.written_bytes
+    }
+    /// Returns the number of the written bits (including the ones not flushed yet).
+    pub fn written_bits(&self) -> u64 {
+        self.written_bytes * 8 + u64::from(self.end)
+    }
     pub fn as_inner_ref(&self) -> &W {
         &self.inner
     }
@@ -66,6 +96,7 @@ pub struct BitReader<R> {
     last_read: u32,
     offset: u8,
     last_error: Option<io::Error>,
+    read_bytes: u64,
 }
 impl<R> BitReader<R>
 where
@@ -77,6 +108,7 @@ where
             last_read: 0,
             offset: 32,
             last_error: None,
+            read_bytes: 0,
         }
     }
     #[inline(always)]
@@ -97,7 +129,16 @@ where
     }
     #[inline(always)]
     pub fn read_bits(&mut self, bitwidth: u8) -> io::Result<u16> {
-        let v = self.read_bits_unchecked(bitwidth);
+        let v = self.peek_bits(bitwidth)?;
+        self.skip_bits(bitwidth);
+        Ok(v)
+    }
+    /// Returns the next `bitwidth` bits without consuming them.
+    ///
+    /// The bits are consumed by `skip_bits`.
+    #[inline(always)]
+    pub fn peek_bits(&mut self, bitwidth: u8) -> io::Result<u16> {
+        let v = self.peek_bits_unchecked(bitwidth);
         self.check_last_error().map(|_| v)
     }
     #[inline(always)]
@@ -125,19 +166,59 @@ where
     #[inline(always)]
     pub fn skip_bits(&mut self, bitwidth: u8) {
         debug_assert!(self.last_error.is_some() || 32 - self.offset >= bitwidth);
-        self.offset += bitwidth;
+        // The offset is saturated so that the counters stay consistent after an EOF
+        self.offset = cmp::min(self.offset + bitwidth, 32);
     }
+    /// Reads the next byte from the inner stream.
+    ///
+    /// If the inner stream has reached EOF, `Error::UnexpectedEof` is returned
+    /// and the state of the reader is left unchanged.
     #[inline(always)]
     fn fill_next_u8(&mut self) -> io::Result<()> {
+        let mut buf = [0; 1];
+        if let Err(e) = self.inner.read_exact(&mut buf) {
+            return Err(if e.kind() == io::ErrorKind::UnexpectedEof {
+                Error::UnexpectedEof.into()
+            } else {
+                e
+            });
+        }
         self.offset -= 8;
         self.last_read >>= 8;
-
-        let mut buf = [0; 1];
-        self.inner.read_exact(&mut buf)?;
+        self.read_bytes += 1;
         let next = u32::from(buf[0]);
         self.last_read |= next << (32 - 8);
         Ok(())
     }
+    /// Makes a new reader that starts in the middle of a byte.
+    ///
+    /// `value` holds the `bits` unconsumed bits of the byte preceding `inner`
+    /// (see `BitReader::partial_byte`).
+    #[cfg(feature = "std")]
+    pub(crate) fn with_partial_byte(inner: R, bits: u8, value: u8) -> Self {
+        let mut this = Self::new(inner);
+        this.set_partial_byte(bits, value);
+        this
+    }
+    /// Discards the state of the reader and restarts it in the middle of a byte.
+    ///
+    /// See `BitReader::with_partial_byte` for `bits` and `value`.
+    #[cfg(feature = "std")]
+    pub(crate) fn set_partial_byte(&mut self, bits: u8, value: u8) {
+        debug_assert!(bits < 8);
+        self.offset = 32 - bits;
+        self.last_read = (u64::from(value) << self.offset) as u32;
+        self.last_error = None;
+        self.read_bytes = 0;
+    }
+    /// Returns the number of the unconsumed bits of the partially consumed byte and their value.
+    #[cfg(feature = "std")]
+    pub(crate) fn partial_byte(&self) -> (u8, u8) {
+        let bits = (32 - self.offset) % 8;
+        let value = (u64::from(self.last_read) >> self.offset) & ((1 << bits) - 1);
+        (bits, value as u8)
+    }
+    #[cfg(feature = "std")]
     #[inline]
     pub(crate) fn state(&self) -> BitReaderState {
         BitReaderState {
@@ -145,6 +226,7 @@ where
             offset: self.offset,
         }
     }
+    #[cfg(feature = "std")]
     #[inline]
     pub(crate) fn restore_state(&mut self, state: BitReaderState) {
         self.last_read = state.last_read;
@@ -155,6 +237,21 @@ impl<R> BitReader<R> {
     pub fn reset(&mut self) {
         self.offset = 32;
     }
+    /// Returns the number of bytes read from the inner stream via this reader,
+    /// excluding the bytes whose bits have not been consumed yet.
+    pub fn consumed_bytes(&self) -> u64 {
+        self.read_bytes - u64::from((32 - self.offset) / 8)
+    }
+    /// Returns the number of bits consumed from the inner stream via this reader.
+    pub fn consumed_bits(&self) -> u64 {
+        self.read_bytes * 8 - u64::from(32 - self.offset)
+    }
+    /// Returns the number of bytes read from the inner stream via this reader,
+    /// including the bytes whose bits have not been consumed yet.
+    #[cfg(feature = "std")]
+    pub(crate) fn read_bytes(&self) -> u64 {
+        self.read_bytes
+    }
     pub fn as_inner_ref(&self) -> &R {
         &self.inner
     }
@@ -166,6 +263,7 @@ impl<R> BitReader<R> {
     }
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct BitReaderState {
     last_read: u32,
@@ -191,20 +289,52 @@ mod test {
         assert_eq!(buf, [0b10100101, 0b01010101, 0b00000001]);
     }
 
+    #[test]
+    fn writer_counts_bits() {
+        let mut writer = BitWriter::new(Vec::new());
+        assert_eq!(writer.written_bits(), 0);
+        writer.write_bits(3, 0b010).unwrap();
+        assert_eq!(writer.written_bits(), 3);
+        writer.write_bits(15, 0).unwrap();
+        assert_eq!(writer.written_bits(), 18);
+        assert_eq!(writer.written_bytes(), 2);
+
+        // Flushing pads the pending bits to a byte boundary
+        writer.flush().unwrap();
+        assert_eq!(writer.written_bits(), 24);
+        writer.write_bytes(&[1, 2]).unwrap();
+        assert_eq!(writer.written_bits(), 40);
+        assert_eq!(writer.into_inner().len(), 5);
+    }
+
     #[test]
     fn reader_works() {
         let buf = [0b10100101, 0b11010101];
         let mut reader = BitReader::new(&buf[..]);
-        assert_eq!(reader.read_bit().unwrap(), true);
-        assert_eq!(reader.read_bit().unwrap(), false);
+        assert!(reader.read_bit().unwrap());
+        assert!(!reader.read_bit().unwrap());
         assert_eq!(reader.read_bits(8).unwrap(), 0b01101001);
         assert_eq!(reader.peek_bits_unchecked(3), 0b101);
         assert_eq!(reader.peek_bits_unchecked(3), 0b101);
         reader.skip_bits(1);
         assert_eq!(reader.peek_bits_unchecked(3), 0b010);
+        assert_eq!(reader.peek_bits(5).unwrap(), 0b11010);
+        assert_eq!(reader.consumed_bits(), 11);
         assert_eq!(
             reader.read_bits(8).map_err(|e| e.kind()),
             Err(io::ErrorKind::UnexpectedEof)
         );
     }
+
+    #[test]
+    fn partial_byte_works() {
+        let buf = [0b10100101, 0b11010101];
+        let mut reader = BitReader::new(&buf[..]);
+        assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+        assert_eq!(reader.partial_byte(), (5, 0b10100));
+
+        let mut reader = BitReader::with_partial_byte(&buf[1..], 5, 0b10100);
+        assert_eq!(reader.read_bits(7).unwrap(), 0b0110100);
+        assert_eq!(reader.read_bits(6).unwrap(), 0b110101);
+    }
 }
@@ -0,0 +1,132 @@
+//! The CRC-32 checksum used by the GZIP format.
+//!
+//! The computation is delegated to the [`crc32fast`](https://crates.io/crates/crc32fast) crate,
+//! which detects the CPU features at runtime and uses a carry-less multiplication
+//! (`PCLMULQDQ` on x86/x86-64 and `PMULL` on AArch64) based implementation if available.
+//!
+//! Note that the `crc32` instruction of SSE4.2 cannot be used here
+//! because it computes CRC-32C (the Castagnoli polynomial),
+//! whereas GZIP uses the CRC-32 of ISO 3309 (the polynomial `0x04C11DB7`).
+//!
+//! # Examples
+//! ```
+//! use std::io::Write;
+//! use libflate::crc32::{self, Crc32};
+//!
+//! let mut crc32 = Crc32::new();
+//! crc32.write_all(b"Hello World!").unwrap();
+//! assert_eq!(crc32.value(), 0x1C291CA3);
+//!
+//! assert_eq!(crc32::update(crc32::INITIAL_VALUE, b"Hello World!"), 0x1C291CA3);
+//! ```
+use core::fmt;
+use crc32fast;
+use io;
+
+/// The CRC-32 value of empty data.
+pub const INITIAL_VALUE: u32 = 0;
+
+/// Returns the CRC-32 value of the concatenation of the data whose value is `state` and `buf`.
+///
+/// Use `INITIAL_VALUE` as `state` to compute the value of `buf` alone.
+pub fn update(state: u32, buf: &[u8]) -> u32 {
+    let mut crc32 = crc32fast::Hasher::new_with_initial(state);
+    crc32.update(buf);
+    crc32.finalize()
+}
+
+/// Returns the CRC-32 value of the concatenation of two data
+/// from their values (`crc1` and `crc2`) and the length of the second data (`len2`).
+///
+/// This is the same as zlib's `crc32_combine`.
+///
+/// # Examples
+/// ```
+/// use libflate::crc32;
+///
+/// let crc1 = crc32::update(crc32::INITIAL_VALUE, b"Hello ");
+/// let crc2 = crc32::update(crc32::INITIAL_VALUE, b"World!");
+/// assert_eq!(crc32::combine(crc1, crc2, 6),
+///            crc32::update(crc32::INITIAL_VALUE, b"Hello World!"));
+/// ```
+pub fn combine(crc1: u32, crc2: u32, len2: u64) -> u32 {
+    let mut crc32 = crc32fast::Hasher::new_with_initial(crc1);
+    crc32.combine(&crc32fast::Hasher::new_with_initial_len(crc2, len2));
+    crc32.finalize()
+}
+
+/// A hasher that computes the CRC-32 value of the written bytes.
+#[derive(Clone)]
+pub struct Crc32(crc32fast::Hasher);
+impl Crc32 {
+    /// Makes a new hasher instance.
+    pub fn new() -> Self {
+        Crc32(crc32fast::Hasher::new())
+    }
+
+    /// Returns the CRC-32 value of the bytes written so far.
+    pub fn value(&self) -> u32 {
+        self.0.clone().finalize()
+    }
+
+    /// Updates the value with `buf`.
+    pub fn update(&mut self, buf: &[u8]) {
+        self.0.update(buf);
+    }
+
+    /// Resets the hasher to its initial state.
+    pub fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl fmt::Debug for Crc32 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Crc32(_)")
+    }
+}
+impl io::Write for Crc32 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc32_works() {
+        let mut crc32 = Crc32::new();
+        crc32.update(b"abcde");
+        assert_eq!(crc32.value(), 0x8587D865);
+        assert_eq!(update(update(INITIAL_VALUE, b"ab"), b"cde"), 0x8587D865);
+
+        crc32.reset();
+        assert_eq!(crc32.value(), INITIAL_VALUE);
+    }
+
+    #[test]
+    fn combine_works() {
+        let data = (0..100_000)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect::<Vec<_>>();
+        for &i in &[0, 1, 5_000, 99_999, 100_000] {
+            let (a, b) = data.split_at(i);
+            let crc1 = update(INITIAL_VALUE, a);
+            let crc2 = update(INITIAL_VALUE, b);
+            assert_eq!(
+                combine(crc1, crc2, b.len() as u64),
+                update(INITIAL_VALUE, &data)
+            );
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use core::fmt;
+use io;
+
+use super::Decoder;
+
+/// A DEFLATE decoder which decodes a sequence of independent DEFLATE streams
+/// as if they were a single stream.
+///
+/// Each reader yielded by the iterator must contain a complete DEFLATE stream.
+/// When the decoder reaches the end of a stream, it continues with the next reader,
+/// reusing the allocated window buffer.
+/// Any bytes following the final block of a stream in the same reader are ignored.
+///
+/// Unlike `gzip::MultiDecoder` (which decodes the members concatenated in a single reader),
+/// this takes the streams from separate readers.
+///
+/// # Examples
+/// ```
+/// use std::io::Read;
+/// use libflate::deflate::{self, ConcatDecoder};
+///
+/// let chunks = vec![deflate::compress(b"Hello "), deflate::compress(b"World!")];
+/// let mut decoder = ConcatDecoder::new(chunks.iter().map(|c| &c[..]));
+/// let mut decoded_data = Vec::new();
+/// decoder.read_to_end(&mut decoded_data).unwrap();
+///
+/// assert_eq!(decoded_data, b"Hello World!");
+/// ```
+pub struct ConcatDecoder<I>
+where
+    I: Iterator,
+{
+    readers: I,
+    decoder: Option<Decoder<I::Item>>,
+    eos: bool,
+}
+impl<I> ConcatDecoder<I>
+where
+    I: Iterator,
+    I::Item: io::Read,
+{
+    /// Makes a new decoder instance which decodes the streams yielded by `readers`.
+    pub fn new<T>(readers: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I, Item = I::Item>,
+    {
+        ConcatDecoder {
+            readers: readers.into_iter(),
+            decoder: None,
+            eos: false,
+        }
+    }
+
+    /// Returns the decoder of the current stream (if any).
+    pub fn as_decoder_ref(&self) -> Option<&Decoder<I::Item>> {
+        self.decoder.as_ref()
+    }
+
+    /// Unwraps this `ConcatDecoder`, returning the iterator of the remaining readers.
+    pub fn into_inner(self) -> I {
+        self.readers
+    }
+
+    fn next_stream(&mut self) -> bool {
+        match self.readers.next() {
+            None => {
+                self.eos = true;
+                false
+            }
+            Some(reader) => {
+                match self.decoder {
+                    Some(ref mut decoder) => {
+                        decoder.reset(reader);
+                    }
+                    None => self.decoder = Some(Decoder::new(reader)),
+                }
+                true
+            }
+        }
+    }
+}
+impl<I> fmt::Debug for ConcatDecoder<I>
+where
+    I: Iterator,
+    I::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConcatDecoder")
+            .field("decoder", &self.decoder)
+            .field("eos", &self.eos)
+            .finish()
+    }
+}
+impl<I> io::Read for ConcatDecoder<I>
+where
+    I: Iterator,
+    I::Item: io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.decoder.is_none() && !self.next_stream() {
+            return Ok(0);
+        }
+        while !self.eos {
+            let size = self.decoder.as_mut().expect("Never fails").read(buf)?;
+            if size != 0 {
+                return Ok(size);
+            }
+            self.next_stream();
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use deflate::{compress, EncodeOptions, Encoder};
+    use std::io::{Read, Write};
+
+    #[test]
+    fn concat_decoder_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i))
+            .collect::<String>()
+            .into_bytes();
+        let mut chunks = plain.chunks(3000).map(compress).collect::<Vec<_>>();
+        chunks.insert(1, compress(b""));
+        let mut encoder =
+            Encoder::with_options(Vec::new(), EncodeOptions::new().no_compression()).unwrap();
+        encoder.write_all(b"stored").unwrap();
+        chunks.push(encoder.finish().into_result().unwrap());
+
+        let mut decoder = ConcatDecoder::new(chunks.iter().map(|c| &c[..]));
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        let mut expected = plain.clone();
+        expected.extend_from_slice(b"stored");
+        assert_eq!(decoded, expected);
+        assert_eq!(decoder.read(&mut [0; 10]).unwrap(), 0);
+
+        let mut decoder = ConcatDecoder::new(Vec::<&[u8]>::new());
+        assert_eq!(decoder.read(&mut [0; 10]).unwrap(), 0);
+
+        chunks[2].truncate(10);
+        let mut decoder = ConcatDecoder::new(chunks.iter().map(|c| &c[..]));
+        assert!(decoder.read_to_end(&mut Vec::new()).is_err());
+    }
+}
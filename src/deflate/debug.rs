@@ -0,0 +1,84 @@
+//! Utilities for inspecting the intermediate state of the encoder.
+//!
+//! This module is available only if the `serde_json` feature is enabled.
+use std::io;
+
+use super::symbol::{Frequencies, Symbol};
+use super::Encoder;
+use lz77;
+
+#[derive(::serde::Serialize)]
+struct SymbolDump<'a> {
+    symbols: &'a [Symbol],
+    frequencies: Frequencies,
+}
+
+/// Writes a JSON representation of the symbols in the current block buffer of `encoder` to `writer`.
+///
+/// The JSON object has the following fields:
+/// - `symbols`: the LZ77 encoded symbols which have not been Huffman encoded yet
+/// - `frequencies`: the frequencies of the literal/length codes (`literal`) and
+///   the distance codes (`distance`) of the symbols
+///
+/// The symbols still held by the LZ77 encoder are not included.
+/// If the encoder does not compress the data, `symbols` is empty.
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::deflate::{debug, Encoder};
+///
+/// let mut encoder = Encoder::new(Vec::new());
+/// encoder.write_all(b"Hello World!").unwrap();
+///
+/// let mut json = Vec::new();
+/// debug::dump_symbols(&encoder, &mut json).unwrap();
+/// assert!(json.starts_with(b"{\"symbols\":["));
+/// ```
+pub fn dump_symbols<W, E, O>(encoder: &Encoder<W, E>, writer: O) -> io::Result<()>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode,
+    O: io::Write,
+{
+    let symbols = encoder.buffered_symbols();
+    let dump = SymbolDump {
+        symbols,
+        frequencies: Frequencies::from_symbols(symbols),
+    };
+    serde_json::to_writer(writer, &dump).map_err(io::Error::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use deflate::symbol::{DynamicHuffmanCodec, HuffmanCodec};
+    use std::io::Write;
+
+    #[derive(::serde::Deserialize)]
+    struct OwnedSymbolDump {
+        symbols: Vec<Symbol>,
+        frequencies: Frequencies,
+    }
+
+    #[test]
+    fn dump_symbols_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("Hello {} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(&plain).unwrap();
+
+        let mut json = Vec::new();
+        dump_symbols(&encoder, &mut json).unwrap();
+        let dump: OwnedSymbolDump = serde_json::from_slice(&json).unwrap();
+        assert!(dump.symbols.contains(&Symbol::Literal(b'H')));
+        assert_eq!(dump.frequencies, Frequencies::from_symbols(&dump.symbols));
+
+        // The Huffman step can be replayed from the dumped symbols
+        let mut symbols = dump.symbols;
+        symbols.push(Symbol::EndOfBlock);
+        assert!(DynamicHuffmanCodec.build(&symbols).is_ok());
+    }
+}
@@ -1,11 +1,134 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp;
+use core::mem;
+use io;
+use io::Read;
 use rle_decode_fast::rle_decode;
-use std::cmp;
-use std::io;
-use std::io::Read;
 
 use super::symbol;
+#[cfg(feature = "tokio")]
+use async_io::{self, PollReader};
 use bit;
 use lz77;
+#[cfg(feature = "tokio")]
+use non_blocking;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, ReadBuf};
+use {Error, ErrorContext};
+
+/// Options for a DEFLATE decoder.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DecodeOptions {
+    window_bits: u8,
+    max_output: Option<u64>,
+}
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl DecodeOptions {
+    /// Makes a default instance.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::{Decoder, DecodeOptions};
+    ///
+    /// let options = DecodeOptions::new();
+    /// let decoder = Decoder::with_options(&[][..], options);
+    /// ```
+    pub fn new() -> Self {
+        DecodeOptions {
+            window_bits: lz77::MAX_WINDOW_BITS,
+            max_output: None,
+        }
+    }
+
+    /// Specifies the base-2 logarithm of the maximum window size accepted by the decoder.
+    ///
+    /// `window_bits` is clamped to the range `lz77::MIN_WINDOW_BITS..=lz77::MAX_WINDOW_BITS`.
+    /// If a stream refers to data farther than the window size, the decoder returns an error.
+    /// For ZLIB streams, the window size declared in the header is also checked before decoding
+    /// (see `zlib::DecodeOptions::deflate_options`).
+    ///
+    /// Note that this limits the distance of back-references, not the memory usage as a whole:
+    /// the decoder keeps the decoded data of the current block in addition to the window,
+    /// so the peak memory usage also depends on the block sizes chosen by the encoder.
+    ///
+    /// The default value is `lz77::MAX_WINDOW_BITS`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::{Decoder, DecodeOptions};
+    ///
+    /// let options = DecodeOptions::new().window_bits(10);
+    /// assert_eq!(options.get_window_bits(), 10);
+    /// ```
+    pub fn window_bits(mut self, window_bits: u8) -> Self {
+        self.window_bits = window_bits.clamp(lz77::MIN_WINDOW_BITS, lz77::MAX_WINDOW_BITS);
+        self
+    }
+
+    /// Returns the base-2 logarithm of the maximum window size accepted by the decoder.
+    pub fn get_window_bits(&self) -> u8 {
+        self.window_bits
+    }
+
+    /// Specifies the maximum number of bytes the decoder is allowed to output.
+    ///
+    /// Once the decoded data exceeds `limit` bytes, the decoder returns an error of
+    /// `io::ErrorKind::Other` instead of decoding further.
+    /// This is useful to protect against decompression bombs.
+    ///
+    /// By default, the output size is not limited.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::DecodeOptions;
+    ///
+    /// let options = DecodeOptions::new().max_output(1024);
+    /// assert_eq!(options.get_max_output(), Some(1024));
+    /// ```
+    pub fn max_output(mut self, limit: u64) -> Self {
+        self.max_output = Some(limit);
+        self
+    }
+
+    /// Returns the maximum number of bytes the decoder is allowed to output.
+    pub fn get_max_output(&self) -> Option<u64> {
+        self.max_output
+    }
+
+    fn get_window_size(&self) -> usize {
+        1 << self.window_bits
+    }
+}
+
+/// Statistics of a DEFLATE decoding.
+///
+/// This is returned by `Decoder::stats`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct DecoderStats {
+    /// The number of the compressed bytes consumed from the inner stream.
+    pub compressed_bytes_consumed: u64,
+
+    /// The number of the decompressed bytes produced by the decoder.
+    pub decompressed_bytes_produced: u64,
+
+    /// The number of the decoded DEFLATE blocks.
+    pub block_count: usize,
+
+    /// The number of the decoded literal symbols.
+    pub literal_symbol_count: u64,
+
+    /// The number of the decoded back-reference symbols.
+    pub back_reference_symbol_count: u64,
+}
 
 /// DEFLATE decoder.
 #[derive(Debug)]
@@ -14,6 +137,11 @@ pub struct Decoder<R> {
     buffer: Vec<u8>,
     offset: usize,
     eos: bool,
+    window_size: usize,
+    max_output: Option<u64>,
+    stats: DecoderStats,
+    non_compressed_bytes: u64,
+    buffer_start: u64,
 }
 impl<R> Decoder<R>
 where
@@ -36,14 +164,151 @@ where
     /// assert_eq!(buf, b"Hello World!");
     /// ```
     pub fn new(inner: R) -> Self {
+        Self::with_options(inner, DecodeOptions::new())
+    }
+
+    /// Makes a new decoder instance with specified options.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::deflate::{Decoder, DecodeOptions};
+    ///
+    /// // "Hello World! Hello World!" has a back-reference of distance 13
+    /// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 84, 240, 64, 226, 0, 0];
+    ///
+    /// let options = DecodeOptions::new().window_bits(8);
+    /// let mut decoder = Decoder::with_options(&encoded_data[..], options);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World! Hello World!");
+    /// ```
+    pub fn with_options(inner: R, options: DecodeOptions) -> Self {
         Decoder {
             bit_reader: bit::BitReader::new(inner),
             buffer: Vec::new(),
             offset: 0,
             eos: false,
+            window_size: options.get_window_size(),
+            max_output: options.get_max_output(),
+            stats: DecoderStats::default(),
+            non_compressed_bytes: 0,
+            buffer_start: 0,
         }
     }
 
+    /// Makes a new decoder instance which outputs at most `limit` bytes.
+    ///
+    /// This is equivalent to `Decoder::with_options(inner, DecodeOptions::new().max_output(limit))`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{ErrorKind, Read};
+    /// use libflate::deflate::{self, Decoder};
+    ///
+    /// let encoded_data = deflate::compress(&[0; 100_000][..]);
+    ///
+    /// let mut decoder = Decoder::with_max_output(&encoded_data[..], 1000);
+    /// let error = decoder.read_to_end(&mut Vec::new()).err().unwrap();
+    /// assert_eq!(error.kind(), ErrorKind::Other);
+    /// assert_eq!(error.to_string(), "decompression bomb: output limit exceeded");
+    /// ```
+    pub fn with_max_output(inner: R, limit: u64) -> Self {
+        Self::with_options(inner, DecodeOptions::new().max_output(limit))
+    }
+
+    /// Makes a new decoder instance with the preset dictionary.
+    ///
+    /// The last `lz77::MAX_WINDOW_SIZE` bytes of `dict` are loaded into the sliding window
+    /// before decoding, so the stream can refer to them as if they preceded the stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Decoder, Encoder, EncodeOptions};
+    ///
+    /// let dict = b"Hello World!";
+    /// let options = EncodeOptions::new().dictionary(dict);
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::with_dictionary(&encoded_data[..], dict);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn with_dictionary(inner: R, dict: &[u8]) -> Self {
+        let mut this = Self::new(inner);
+        this.set_dictionary(dict);
+        this
+    }
+
+    pub(crate) fn set_dictionary(&mut self, dict: &[u8]) {
+        let dict = &dict[dict.len().saturating_sub(self.window_size)..];
+        self.buffer.clear();
+        self.buffer.extend_from_slice(dict);
+        self.offset = self.buffer.len();
+        self.buffer_start = 0u64.wrapping_sub(dict.len() as u64);
+    }
+
+    /// Returns the statistics of the decoding so far.
+    ///
+    /// The statistics are updated each time a block is decoded,
+    /// so they can be used to report the progress of the decoding.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::deflate::Decoder;
+    ///
+    /// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// decoder.read_to_end(&mut Vec::new()).unwrap();
+    ///
+    /// let stats = decoder.stats();
+    /// assert_eq!(stats.compressed_bytes_consumed, 14);
+    /// assert_eq!(stats.decompressed_bytes_produced, 12);
+    /// assert_eq!(stats.block_count, 1);
+    /// assert_eq!(stats.literal_symbol_count, 12);
+    /// assert_eq!(stats.back_reference_symbol_count, 0);
+    /// ```
+    pub fn stats(&self) -> &DecoderStats {
+        &self.stats
+    }
+
+    /// Resets the state of the decoder and replaces the inner stream with `new_reader`.
+    ///
+    /// The options and the allocated window buffer are kept,
+    /// so this is cheaper than making a new decoder for each stream.
+    ///
+    /// Returns the previous inner stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::deflate::Decoder;
+    ///
+    /// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// let _ = decoder.reset(&encoded_data[..]);
+    /// buf.clear();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn reset(&mut self, new_reader: R) -> R {
+        self.buffer.clear();
+        self.offset = 0;
+        self.eos = false;
+        self.stats = DecoderStats::default();
+        self.non_compressed_bytes = 0;
+        self.buffer_start = 0;
+        mem::replace(&mut self.bit_reader, bit::BitReader::new(new_reader)).into_inner()
+    }
+
     /// Returns the immutable reference to the inner stream.
     pub fn as_inner_ref(&self) -> &R {
         self.bit_reader.as_inner_ref()
@@ -69,6 +334,61 @@ where
         self.bit_reader.into_inner()
     }
 
+    /// Makes a new decoder that starts decoding at a block boundary in the middle of a stream.
+    ///
+    /// See `Decoder::sync_point` for `bits` and `value`.
+    #[cfg(feature = "std")]
+    pub(crate) fn with_sync_point(inner: R, bits: u8, value: u8, window: &[u8]) -> Self {
+        let mut this = Self::new(inner);
+        this.bit_reader =
+            bit::BitReader::with_partial_byte(this.bit_reader.into_inner(), bits, value);
+        this.set_dictionary(window);
+        this
+    }
+
+    /// Discards the state of the decoder and restarts it at a block boundary of the inner stream.
+    ///
+    /// The inner stream must already be positioned at the boundary.
+    /// See `Decoder::sync_point` for `bits` and `value`.
+    #[cfg(feature = "std")]
+    pub(crate) fn reset_to_sync_point(&mut self, bits: u8, value: u8, window: &[u8]) {
+        self.eos = false;
+        self.stats = DecoderStats::default();
+        self.non_compressed_bytes = 0;
+        self.bit_reader.set_partial_byte(bits, value);
+        self.set_dictionary(window);
+    }
+
+    /// Returns the position of the next block if all the decoded data have been read
+    /// and the next read will start decoding a new block.
+    ///
+    /// The position is a tuple of the number of the consumed compressed bytes,
+    /// the number of the decoded bytes, and the number and value of the unconsumed bits
+    /// of the last consumed byte.
+    #[cfg(feature = "std")]
+    pub(crate) fn sync_point(&self) -> Option<(u64, u64, u8, u8)> {
+        if self.offset < self.buffer.len() || self.eos {
+            return None;
+        }
+        let (bits, value) = self.bit_reader.partial_byte();
+        Some((self.compressed_size(), self.output_size(), bits, value))
+    }
+
+    /// Returns the number of the bytes read from the inner stream since the start of decoding
+    /// (or the last `reset_to_sync_point`).
+    ///
+    /// Unlike `DecoderStats::compressed_bytes_consumed`, this includes the bytes whose bits have not been consumed yet.
+    #[cfg(feature = "std")]
+    pub(crate) fn input_bytes_read(&self) -> u64 {
+        self.bit_reader.read_bytes() + self.non_compressed_bytes
+    }
+
+    /// Returns the last decoded data (up to the window size).
+    #[cfg(feature = "std")]
+    pub(crate) fn window(&self) -> &[u8] {
+        &self.buffer[self.buffer.len().saturating_sub(self.window_size)..]
+    }
+
     fn read_non_compressed_block(&mut self) -> io::Result<()> {
         self.bit_reader.reset();
         let mut buf = [0; 2];
@@ -76,6 +396,7 @@ where
         let len = u16::from_le_bytes(buf);
         self.bit_reader.as_inner_mut().read_exact(&mut buf)?;
         let nlen = u16::from_le_bytes(buf);
+        self.non_compressed_bytes += 4 + u64::from(len);
         if !len != nlen {
             Err(invalid_data_error!(
                 "LEN={} is not the one's complement of NLEN={}",
@@ -83,23 +404,21 @@ where
                 nlen
             ))
         } else {
-            self.bit_reader
+            let used = self
+                .bit_reader
                 .as_inner_mut()
                 .take(len.into())
-                .read_to_end(&mut self.buffer)
-                .and_then(|used| {
-                    if used != len.into() {
-                        Err(io::Error::new(
-                            io::ErrorKind::UnexpectedEof,
-                            format!(
-                                "The reader has incorrect length: expected {}, read {}",
-                                len, used
-                            ),
-                        ))
-                    } else {
-                        Ok(())
-                    }
-                })
+                .read_to_end(&mut self.buffer)?;
+            if used != usize::from(len) {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "The reader has incorrect length: expected {}, read {}",
+                        len, used
+                    ),
+                ));
+            }
+            self.check_output_limit()
         }
     }
     fn read_compressed_block<H>(&mut self, huffman: &H) -> io::Result<()>
@@ -113,8 +432,17 @@ where
             match s {
                 symbol::Symbol::Literal(b) => {
                     self.buffer.push(b);
+                    self.stats.literal_symbol_count += 1;
+                    self.check_output_limit()?;
                 }
                 symbol::Symbol::Share { length, distance } => {
+                    if self.window_size < distance as usize {
+                        return Err(invalid_data_error!(
+                            "Too long backword reference for the window: window_size={}, distance={}",
+                            self.window_size,
+                            distance
+                        ));
+                    }
                     if self.buffer.len() < distance as usize {
                         return Err(invalid_data_error!(
                             "Too long backword reference: buffer.len={}, distance={}",
@@ -123,6 +451,8 @@ where
                         ));
                     }
                     rle_decode(&mut self.buffer, usize::from(distance), usize::from(length));
+                    self.stats.back_reference_symbol_count += 1;
+                    self.check_output_limit()?;
                 }
                 symbol::Symbol::EndOfBlock => {
                     break;
@@ -131,10 +461,45 @@ where
         }
         Ok(())
     }
+    fn output_size(&self) -> u64 {
+        self.buffer_start.wrapping_add(self.buffer.len() as u64)
+    }
+    /// Returns the decoded data which have not been read yet.
+    #[cfg(feature = "std")]
+    pub(crate) fn buffered_data(&self) -> &[u8] {
+        &self.buffer[self.offset..]
+    }
+    // Reads blocks until some decoded data are available or the end of the stream is reached.
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        while self.offset == self.buffer.len() && !self.eos {
+            let result = self.read_block();
+            self.update_stats();
+            if let Err(e) = result {
+                self.check_output_limit()?;
+                let context = self.error_context();
+                return Err(Error::WithContext(Box::new(Error::from(e)), context).into());
+            }
+            self.stats.block_count += 1;
+            self.check_output_limit()?;
+        }
+        Ok(())
+    }
+    fn check_output_limit(&self) -> io::Result<()> {
+        if self
+            .max_output
+            .is_some_and(|limit| self.output_size() > limit)
+        {
+            Err(io::Error::other(
+                "decompression bomb: output limit exceeded",
+            ))
+        } else {
+            Ok(())
+        }
+    }
     fn truncate_old_buffer(&mut self) {
-        if self.buffer.len() > lz77::MAX_DISTANCE as usize * 4 {
+        if self.buffer.len() > self.window_size * 4 {
             let old_len = self.buffer.len();
-            let new_len = lz77::MAX_DISTANCE as usize;
+            let new_len = self.window_size;
             {
                 // isolation to please borrow checker
                 let (dst, src) = self.buffer.split_at_mut(old_len - new_len);
@@ -142,6 +507,35 @@ where
             }
             self.buffer.truncate(new_len);
             self.offset = new_len;
+            self.buffer_start = self.buffer_start.wrapping_add((old_len - new_len) as u64);
+        }
+    }
+    fn read_block(&mut self) -> io::Result<()> {
+        let bfinal = self.bit_reader.read_bit()?;
+        let btype = self.bit_reader.read_bits(2)?;
+        self.eos = bfinal;
+        self.truncate_old_buffer();
+        match btype {
+            0b00 => self.read_non_compressed_block(),
+            0b01 => self.read_compressed_block(&symbol::FixedHuffmanCodec),
+            0b10 => self.read_compressed_block(&symbol::DynamicHuffmanCodec),
+            0b11 => Err(Error::InvalidBlockType.into()),
+            _ => unreachable!(),
+        }
+    }
+    fn compressed_size(&self) -> u64 {
+        self.bit_reader.consumed_bytes() + self.non_compressed_bytes
+    }
+    fn update_stats(&mut self) {
+        self.stats.compressed_bytes_consumed = self.compressed_size();
+        self.stats.decompressed_bytes_produced = self.output_size();
+    }
+    fn error_context(&self) -> ErrorContext {
+        ErrorContext {
+            block_index: self.stats.block_count,
+            compressed_offset: self.compressed_size(),
+            compressed_bit_offset: self.bit_reader.consumed_bits() + self.non_compressed_bytes * 8,
+            uncompressed_offset: self.output_size(),
         }
     }
 }
@@ -150,38 +544,107 @@ where
     R: Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.offset < self.buffer.len() {
-            let copy_size = cmp::min(buf.len(), self.buffer.len() - self.offset);
-            buf[..copy_size].copy_from_slice(&self.buffer[self.offset..][..copy_size]);
-            self.offset += copy_size;
-            Ok(copy_size)
-        } else if self.eos {
-            Ok(0)
-        } else {
-            let bfinal = self.bit_reader.read_bit()?;
-            let btype = self.bit_reader.read_bits(2)?;
-            self.eos = bfinal;
-            self.truncate_old_buffer();
-            match btype {
-                0b00 => {
-                    self.read_non_compressed_block()?;
-                    self.read(buf)
-                }
-                0b01 => {
-                    self.read_compressed_block(&symbol::FixedHuffmanCodec)?;
-                    self.read(buf)
-                }
-                0b10 => {
-                    self.read_compressed_block(&symbol::DynamicHuffmanCodec)?;
-                    self.read(buf)
-                }
-                0b11 => Err(invalid_data_error!(
-                    "btype 0x11 of DEFLATE is reserved(error) value"
-                )),
-                _ => unreachable!(),
-            }
+        self.check_output_limit()?;
+        self.fill_buffer()?;
+        let copy_size = cmp::min(buf.len(), self.buffer.len() - self.offset);
+        buf[..copy_size].copy_from_slice(&self.buffer[self.offset..][..copy_size]);
+        self.offset += copy_size;
+        Ok(copy_size)
+    }
+}
+/// The decoded data are returned directly from the internal window buffer (without copying).
+///
+/// # Examples
+/// ```
+/// use std::io::BufRead;
+/// use libflate::deflate::{self, Decoder};
+///
+/// let encoded_data = deflate::compress(b"Hello\nWorld!\n");
+/// let lines = Decoder::new(&encoded_data[..]).lines().collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(lines, ["Hello", "World!"]);
+/// ```
+#[cfg(feature = "std")]
+impl<R> io::BufRead for Decoder<R>
+where
+    R: Read,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.check_output_limit()?;
+        self.fill_buffer()?;
+        Ok(&self.buffer[self.offset..])
+    }
+    fn consume(&mut self, amt: usize) {
+        self.offset = cmp::min(self.offset + amt, self.buffer.len());
+    }
+}
+
+/// Decodes a DEFLATE stream `input`.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate;
+///
+/// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+/// assert_eq!(deflate::decompress(&encoded_data).unwrap(), b"Hello World!");
+/// ```
+pub fn decompress(input: &[u8]) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(input.len().saturating_mul(2));
+    Decoder::new(input).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// DEFLATE decoder which implements `tokio::io::AsyncRead`.
+///
+/// This drives `non_blocking::deflate::Decoder` on top of `inner`,
+/// so the partial state of the decoder is kept while `inner` is not ready.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncDecoder<R> {
+    decoder: non_blocking::deflate::Decoder<PollReader<R>>,
+}
+#[cfg(feature = "tokio")]
+impl<R> AsyncDecoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Makes a new decoder instance.
+    ///
+    /// `inner` is to be decoded DEFLATE stream.
+    pub fn new(inner: R) -> Self {
+        AsyncDecoder {
+            decoder: non_blocking::deflate::Decoder::new(PollReader::new(inner)),
         }
     }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        self.decoder.as_inner_ref().as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        self.decoder.as_inner_mut().as_inner_mut()
+    }
+
+    /// Unwraps this `AsyncDecoder`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.decoder.into_inner().into_inner()
+    }
+}
+#[cfg(feature = "tokio")]
+impl<R> AsyncRead for AsyncDecoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.decoder.as_inner_mut().set_waker(cx.waker());
+        async_io::poll_read(&mut this.decoder, buf)
+    }
 }
 
 #[cfg(test)]
@@ -202,7 +665,7 @@ mod test {
             119, 142, 170, 169, 138, 202, 112, 228, 140, 38,
         ];
         let mut bit_reader = ::bit::BitReader::new(&input[..]);
-        assert_eq!(bit_reader.read_bit().unwrap(), false); // not final block
+        assert!(!bit_reader.read_bit().unwrap()); // not final block
         assert_eq!(bit_reader.read_bits(2).unwrap(), 0b10); // DynamicHuffmanCodec
         DynamicHuffmanCodec.load(&mut bit_reader).unwrap();
     }
@@ -226,5 +689,162 @@ mod test {
         let error = result.err().unwrap();
         assert_eq!(error.kind(), io::ErrorKind::InvalidData);
         assert!(error.to_string().starts_with("Too long backword reference"));
+
+        let error = Error::from(error);
+        let context = error.context().unwrap();
+        assert_eq!(context.block_index, 0);
+        assert_eq!(context.compressed_offset, 109);
+        assert_eq!(context.compressed_bit_offset.div_ceil(8), 109);
+        assert!(context.uncompressed_offset > 0);
+    }
+
+    #[test]
+    fn truncated_stream_is_unexpected_eof() {
+        let plain = (0..2000)
+            .map(|i| format!("{} ", i * 7 % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let options = ::deflate::EncodeOptions::new();
+        for options in [
+            options.clone(),
+            options.clone().fixed_huffman_codes(),
+            options.no_compression(),
+        ] {
+            let mut encoder = ::deflate::Encoder::with_options(Vec::new(), options).unwrap();
+            io::Write::write_all(&mut encoder, &plain).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+            for size in 0..encoded.len() {
+                let mut decoder = Decoder::new(&encoded[..size]);
+                let error = io::copy(&mut decoder, &mut io::sink()).unwrap_err();
+                assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "size={}", size);
+            }
+            assert_eq!(decompress(&encoded).unwrap(), plain);
+        }
+    }
+
+    #[test]
+    fn max_output_works() {
+        let plain = vec![0; 1_000_000];
+        let encoded = ::deflate::compress(&plain);
+
+        let mut buf = Vec::new();
+        let mut decoder = Decoder::with_max_output(&encoded[..], plain.len() as u64);
+        decoder.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, plain);
+
+        // The limit is checked within a block, not only at block boundaries
+        let mut decoder = Decoder::with_max_output(&encoded[..], 1000);
+        let error = decoder.read(&mut [0; 10]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::Other);
+        assert_eq!(
+            error.to_string(),
+            "decompression bomb: output limit exceeded"
+        );
+        assert!(decoder.buffer.len() < 1000 + 258);
+
+        // Subsequent reads fail too
+        assert!(decoder.read(&mut [0; 10]).is_err());
+    }
+
+    #[test]
+    fn reset_works() {
+        let plain0 = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let plain1 = b"Hello World!";
+        let encoded0 = ::deflate::compress(&plain0);
+        let encoded1 = ::deflate::compress(plain1);
+
+        let mut decoder = Decoder::new(&encoded0[..]);
+        let mut buf = Vec::new();
+        decoder.read_exact(&mut [0; 10]).unwrap(); // Leaves some data unread
+        let capacity = decoder.buffer.capacity();
+
+        decoder.reset(&encoded1[..]);
+        assert_eq!(decoder.buffer.capacity(), capacity);
+        decoder.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, plain1);
+    }
+
+    #[test]
+    fn buf_read_works() {
+        use std::io::BufRead;
+
+        let plain = (0..10_000)
+            .map(|i| format!("{}\n", i))
+            .collect::<String>()
+            .into_bytes();
+        let encoded = ::deflate::compress(&plain);
+
+        let lines = Decoder::new(&encoded[..])
+            .lines()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines.len(), 10_000);
+        assert_eq!(lines[1234], "1234");
+
+        // `fill_buf` and `read` can be mixed
+        let mut decoder = Decoder::new(&encoded[..]);
+        let mut decoded = Vec::new();
+        loop {
+            let size = {
+                let buf = decoder.fill_buf().unwrap();
+                decoded.extend_from_slice(&buf[..cmp::min(buf.len(), 7)]);
+                cmp::min(buf.len(), 7)
+            };
+            if size == 0 {
+                break;
+            }
+            decoder.consume(size);
+            let mut buf = [0; 5];
+            let size = decoder.read(&mut buf).unwrap();
+            decoded.extend_from_slice(&buf[..size]);
+        }
+        assert_eq!(decoded, plain);
+    }
+
+    #[test]
+    fn stats_works() {
+        use deflate::Encoder;
+        use std::io::Write;
+
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(&plain[..1000]).unwrap();
+        encoder.full_flush().unwrap();
+        encoder.write_all(&plain[1000..]).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let mut decoder = Decoder::new(&encoded[..]);
+        decoder.read_exact(&mut [0; 10]).unwrap();
+        let stats = decoder.stats().clone();
+        assert_eq!(stats.block_count, 1);
+        assert!(stats.compressed_bytes_consumed < encoded.len() as u64);
+        assert!(stats.decompressed_bytes_produced < plain.len() as u64);
+
+        decoder.read_to_end(&mut Vec::new()).unwrap();
+        let stats = decoder.stats();
+        assert!(stats.block_count > 1);
+        assert_eq!(stats.compressed_bytes_consumed, encoded.len() as u64);
+        assert_eq!(stats.decompressed_bytes_produced, plain.len() as u64);
+        assert!(stats.literal_symbol_count > 0);
+        assert!(stats.back_reference_symbol_count > 0);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_decoder_works() {
+        use deflate::Encoder;
+        use std::io::Write;
+        use util::{poll_read_to_end, PendingReader};
+
+        let plain = include_bytes!("../../README.md");
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let decoder = AsyncDecoder::new(PendingReader::new(&encoded[..]));
+        assert_eq!(poll_read_to_end(decoder).unwrap(), &plain[..]);
     }
 }
@@ -1,8 +1,19 @@
-use std::cmp;
-use std::io;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp;
+use core::error;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem;
+use io;
 
+#[cfg(feature = "std")]
+use super::entropy;
 use super::symbol;
+use super::symbol::HuffmanCodec;
 use super::BlockType;
+use super::ProgressEncoder;
 use bit;
 use finish::{Complete, Finish};
 use lz77;
@@ -12,11 +23,70 @@ pub const DEFAULT_BLOCK_SIZE: usize = 1024 * 1024;
 
 const MAX_NON_COMPRESSED_BLOCK_SIZE: usize = 0xFFFF;
 
+// The upper limit of `EncodeOptions::block_size` (to avoid overflows in the size calculations).
+const MAX_BLOCK_SIZE: usize = 1 << 31;
+
+// The range of the block sizes chosen by `EncodeOptions::block_size_auto`.
+const MIN_AUTO_BLOCK_SIZE: usize = 4096;
+const MAX_AUTO_BLOCK_SIZE: usize = 8 * 1024 * 1024;
+
+/// The maximum value accepted by `EncodeOptions::compression_level`.
+pub const MAX_COMPRESSION_LEVEL: u8 = 9;
+
+// Like zlib, the compression levels greater than or equal to this value use lazy matching.
+const LAZY_MATCH_MIN_LEVEL: u8 = 4;
+
+/// Commonly used compression levels.
+///
+/// Use `CompressionLevel::to_u8` to pass the level to `EncodeOptions::compression_level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionLevel {
+    /// No compression (level 0).
+    Store,
+
+    /// Best speed (level 1).
+    Fastest,
+
+    /// Balanced between speed and size (level 6).
+    Default,
+
+    /// Best compression (level 9).
+    Best,
+}
+impl CompressionLevel {
+    /// Converts from `CompressionLevel` to the numeric level.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::CompressionLevel;
+    ///
+    /// assert_eq!(CompressionLevel::Store.to_u8(), 0);
+    /// assert_eq!(CompressionLevel::Fastest.to_u8(), 1);
+    /// assert_eq!(CompressionLevel::Default.to_u8(), 6);
+    /// assert_eq!(CompressionLevel::Best.to_u8(), 9);
+    /// ```
+    pub fn to_u8(self) -> u8 {
+        match self {
+            CompressionLevel::Store => 0,
+            CompressionLevel::Fastest => 1,
+            CompressionLevel::Default => 6,
+            CompressionLevel::Best => 9,
+        }
+    }
+}
+
 /// Options for a DEFLATE encoder.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct EncodeOptions<E = lz77::DefaultLz77Encoder> {
     block_size: usize,
+    block_size_auto: bool,
     dynamic_huffman: bool,
+    store_if_larger: bool,
+    #[cfg(feature = "std")]
+    entropy_threshold: EntropyThreshold,
+    block_split: Option<BlockSplit>,
+    block_boundary_alignment: Option<usize>,
+    dictionary: Vec<u8>,
     lz77: Option<E>,
 }
 impl Default for EncodeOptions<lz77::DefaultLz77Encoder> {
@@ -27,20 +97,115 @@ impl Default for EncodeOptions<lz77::DefaultLz77Encoder> {
 impl EncodeOptions<lz77::DefaultLz77Encoder> {
     /// Makes a default instance.
     ///
+    /// The LZ77 encoder of this instance is the same as that of `compression_level(6)` (`CompressionLevel::Default`).
+    ///
     /// # Examples
     /// ```
     /// use libflate::deflate::{Encoder, EncodeOptions};
     ///
     /// let options = EncodeOptions::new();
-    /// let encoder = Encoder::with_options(Vec::new(), options);
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
     /// ```
     pub fn new() -> Self {
         EncodeOptions {
             block_size: DEFAULT_BLOCK_SIZE,
+            block_size_auto: false,
             dynamic_huffman: true,
+            store_if_larger: false,
+            #[cfg(feature = "std")]
+            entropy_threshold: EntropyThreshold(entropy::DEFAULT_ENTROPY_THRESHOLD),
+            block_split: None,
+            block_boundary_alignment: None,
+            dictionary: Vec::new(),
             lz77: Some(lz77::DefaultLz77Encoder::new()),
         }
     }
+
+    /// Specifies the compression level.
+    ///
+    /// `level` must be in the range `0..=MAX_COMPRESSION_LEVEL`.
+    /// Level 0 is the same as `no_compression()`, and larger values
+    /// produce smaller output at the cost of speed.
+    /// Each level is mapped to the same LZ77 parameters as zlib uses
+    /// (including lazy matching for level 4 and above),
+    /// and the type of the blocks is chosen accordingly
+    /// (non-compressed blocks for level 0 and dynamic huffman codes otherwise).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is greater than `MAX_COMPRESSION_LEVEL`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::{CompressionLevel, Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().compression_level(9);
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    ///
+    /// let options = EncodeOptions::new().compression_level(CompressionLevel::Fastest.to_u8());
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn compression_level(mut self, level: u8) -> Self {
+        assert!(
+            level <= MAX_COMPRESSION_LEVEL,
+            "Compression level must be between 0 and {}: level={}",
+            MAX_COMPRESSION_LEVEL,
+            level
+        );
+        if level == 0 {
+            return self.no_compression();
+        }
+
+        let window_size = self.lz77.as_ref().map_or(lz77::MAX_WINDOW_SIZE, |lz77| {
+            lz77::Lz77Encode::window_size(lz77)
+        });
+        let lz77 = lz77::DefaultLz77EncoderBuilder::new()
+            .window_size(window_size)
+            .params(lz77::Lz77Params::from(level))
+            .lazy_match(level >= LAZY_MATCH_MIN_LEVEL)
+            .build();
+        self.lz77 = Some(lz77);
+        self.dynamic_huffman = true;
+        self
+    }
+}
+impl EncodeOptions<lz77::ZopfliLz77Encoder> {
+    /// Makes an instance which uses `lz77::ZopfliLz77Encoder` (the optimal LZ77 parser).
+    ///
+    /// This produces smaller output than the other options, but is much slower.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::zopfli_lz77();
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    /// ```
+    pub fn zopfli_lz77() -> Self {
+        Self::with_lz77(lz77::ZopfliLz77Encoder::new())
+    }
+}
+impl EncodeOptions<lz77::SuffixArrayLz77Encoder> {
+    /// Makes an instance which uses `lz77::SuffixArrayLz77Encoder` (the exhaustive longest match search).
+    ///
+    /// This usually produces smaller output than the default options at the cost of speed.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::suffix_array_lz77();
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    /// ```
+    pub fn suffix_array_lz77() -> Self {
+        Self::with_lz77(lz77::SuffixArrayLz77Encoder::new())
+    }
 }
 impl<E> EncodeOptions<E>
 where
@@ -54,12 +219,19 @@ where
     /// use libflate::deflate::{Encoder, EncodeOptions};
     ///
     /// let options = EncodeOptions::with_lz77(DefaultLz77Encoder::new());
-    /// let encoder = Encoder::with_options(Vec::new(), options);
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
     /// ```
     pub fn with_lz77(lz77: E) -> Self {
         EncodeOptions {
             block_size: DEFAULT_BLOCK_SIZE,
+            block_size_auto: false,
             dynamic_huffman: true,
+            store_if_larger: false,
+            #[cfg(feature = "std")]
+            entropy_threshold: EntropyThreshold(entropy::DEFAULT_ENTROPY_THRESHOLD),
+            block_split: None,
+            block_boundary_alignment: None,
+            dictionary: Vec::new(),
             lz77: Some(lz77),
         }
     }
@@ -71,75 +243,885 @@ where
     /// use libflate::lz77::DefaultLz77Encoder;
     /// use libflate::deflate::{Encoder, EncodeOptions};
     ///
-    /// let options = EncodeOptions::new().no_compression();
-    /// let encoder = Encoder::with_options(Vec::new(), options);
+    /// let options = EncodeOptions::new().no_compression();
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn no_compression(mut self) -> Self {
+        self.lz77 = None;
+        self
+    }
+
+    /// Specifies the hint of the size of a DEFLATE block.
+    ///
+    /// The default value is `DEFAULT_BLOCK_SIZE`.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().block_size(512 * 1024);
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn block_size(mut self, size: usize) -> Self {
+        self.block_size = size;
+        self
+    }
+
+    /// Specifies to tune the block size from the characteristics of the input.
+    ///
+    /// `block_size` is used as the initial size, and the size is adjusted after each block
+    /// according to its compression ratio (the compressed size divided by the uncompressed size):
+    /// the size is halved (down to 4 KB) if the ratio is greater than 0.95,
+    /// and doubled (up to 8 MB) if the ratio is less than 0.5.
+    /// So poorly compressible data is split into small blocks,
+    /// while highly compressible data is encoded in large blocks.
+    ///
+    /// This has no effect if `block_split` is specified.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().block_size(64 * 1024).block_size_auto();
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// for _ in 0..256 {
+    ///     encoder.write_all(&[0; 4096]).unwrap();
+    /// }
+    /// let (_, stats) = encoder.finish_with_stats().into_result().unwrap();
+    ///
+    /// // 64 KB + 128 KB + 256 KB + 512 KB, and the final block
+    /// assert_eq!(stats.block_count, 5);
+    /// ```
+    pub fn block_size_auto(mut self) -> Self {
+        self.block_size_auto = true;
+        self
+    }
+
+    /// Specifies the callback that decides the boundaries of DEFLATE blocks.
+    ///
+    /// The callback is called after every write with the uncompressed data accumulated in the current block,
+    /// and a block boundary is emitted if it returns `true`.
+    /// If this is specified, the callback is used instead of `block_size`:
+    /// a boundary can be emitted before `block_size` bytes are accumulated (e.g., at a delimiter of the content),
+    /// and the block keeps growing beyond `block_size` while the callback returns `false`.
+    /// The accumulated data is kept in the buffer of the block (it is not copied for each call).
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// // Emits a block boundary at the end of each JSON object
+    /// let options = EncodeOptions::new().block_split(|buf: &[u8]| buf.ends_with(b"}\n"));
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"{\"id\":1}\n").unwrap();
+    /// encoder.write_all(b"{\"id\":2}\n").unwrap();
+    /// let (_, stats) = encoder.finish_with_stats().into_result().unwrap();
+    /// assert_eq!(stats.block_count, 3);
+    /// ```
+    pub fn block_split<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.block_split = Some(BlockSplit(Arc::new(f)));
+        self
+    }
+
+    /// Specifies to align block boundaries to every `align` bytes of the uncompressed data.
+    ///
+    /// Whenever the number of the written bytes reaches a multiple of `align`,
+    /// the current block is flushed and followed by an empty non-compressed block
+    /// (as `Encoder::sync_flush` does), regardless of `block_size`.
+    /// So decoding can be started at these byte-aligned points
+    /// (given the preceding 32 KB of the uncompressed data as the window).
+    ///
+    /// `align` must be a power of two (see `EncodeOptions::validate`).
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().block_boundary_alignment(4096);
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(&[0; 10_000]).unwrap();
+    /// let (_, stats) = encoder.finish_with_stats().into_result().unwrap();
+    ///
+    /// // Two aligned blocks (followed by the empty blocks) and the final block
+    /// assert_eq!(stats.block_count, 5);
+    /// ```
+    pub fn block_boundary_alignment(mut self, align: usize) -> Self {
+        self.block_boundary_alignment = Some(align);
+        self
+    }
+
+    /// Specifies to compress with fixed huffman codes.
+    ///
+    /// By default, the encoder estimates the size of each block encoded with
+    /// the fixed and the dynamic huffman codes, and uses the smaller one.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().fixed_huffman_codes();
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn fixed_huffman_codes(mut self) -> Self {
+        self.dynamic_huffman = false;
+        self
+    }
+
+    /// Specifies to emit non-compressed blocks instead of compressed ones that would be larger.
+    ///
+    /// Before writing a compressed block, the encoder estimates its size from the symbols of the block,
+    /// and if it exceeds the size of the non-compressed blocks for the same data, the latter are written.
+    /// This avoids expanding incompressible data (e.g., random or already compressed data),
+    /// at the cost of keeping a copy of the uncompressed data of the current block.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// // Pseudo-random (incompressible) data
+    /// let mut x = 1u32;
+    /// let data = (0..10_000).map(|_| { x ^= x << 13; x ^= x >> 17; x ^= x << 5; x as u8 }).collect::<Vec<_>>();
+    ///
+    /// let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::new().store_if_larger()).unwrap();
+    /// encoder.write_all(&data).unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    /// assert!(encoded_data.len() <= data.len() + 10);
+    /// ```
+    pub fn store_if_larger(mut self) -> Self {
+        self.store_if_larger = true;
+        self
+    }
+
+    /// Specifies the entropy threshold (in bits per byte) above which LZ77 encoding is skipped.
+    ///
+    /// If the byte-level entropy (see `entropy::estimate_entropy`) of the data passed to a `write` call
+    /// exceeds `threshold`, the data is written as non-compressed blocks without running
+    /// the LZ77 encoder and the huffman coding.
+    /// This saves time on already compressed or encrypted data.
+    /// Since the entropy is at most `8.0`, specifying a larger value disables this behavior.
+    ///
+    /// The default value is `entropy::DEFAULT_ENTROPY_THRESHOLD`.
+    /// This option is available only if the `std` feature is enabled.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().entropy_threshold(f64::INFINITY);
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn entropy_threshold(mut self, threshold: f64) -> Self {
+        self.entropy_threshold = EntropyThreshold(threshold);
+        self
+    }
+
+    /// Specifies the preset dictionary.
+    ///
+    /// The LZ77 encoder is initialized with `dict` (see `lz77::Lz77Encode::set_dictionary`),
+    /// so the encoded data may refer to the dictionary.
+    /// Such a stream must be decoded by `Decoder::with_dictionary` with the same dictionary.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().dictionary(b"Hello World!");
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn dictionary(mut self, dict: &[u8]) -> Self {
+        self.dictionary = dict.to_vec();
+        self
+    }
+    fn get_block_size(&self) -> usize {
+        if self.lz77.is_none() {
+            cmp::min(self.block_size, MAX_NON_COMPRESSED_BLOCK_SIZE)
+        } else {
+            self.block_size
+        }
+    }
+    #[cfg(feature = "std")]
+    pub(crate) fn get_lz77(&self) -> Option<&E> {
+        self.lz77.as_ref()
+    }
+    #[cfg(feature = "std")]
+    pub(crate) fn get_dictionary(&self) -> &[u8] {
+        &self.dictionary
+    }
+}
+impl<E> EncodeOptions<E>
+where
+    E: lz77::Lz77Encode,
+{
+    /// Checks whether the options are valid.
+    ///
+    /// The following constraints are checked:
+    /// - `block_size` is in the range `1..=2^31`
+    /// - The window size of the LZ77 encoder is in the range `256..=32768` (i.e., the window bits are `8..=15`)
+    /// - `block_boundary_alignment` (if specified) is a power of two
+    ///
+    /// `Encoder::with_options` calls this internally, so this is useful to check the options
+    /// built from user input (e.g., configuration files) before making an encoder.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::{EncodeOptions, OptionsError};
+    /// use libflate::lz77::DefaultLz77EncoderBuilder;
+    ///
+    /// assert!(EncodeOptions::new().validate().is_ok());
+    /// assert_eq!(EncodeOptions::new().block_size(0).validate(), Err(OptionsError::ZeroBlockSize));
+    ///
+    /// let lz77 = DefaultLz77EncoderBuilder::new().window_size(128).build();
+    /// assert_eq!(EncodeOptions::with_lz77(lz77).validate(), Err(OptionsError::InvalidWindowSize(128)));
+    /// ```
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        if self.block_size == 0 {
+            return Err(OptionsError::ZeroBlockSize);
+        }
+        if self.block_size > MAX_BLOCK_SIZE {
+            return Err(OptionsError::BlockSizeTooLarge(self.block_size));
+        }
+        if let Some(ref lz77) = self.lz77 {
+            let window_size = lz77.window_size();
+            if !(1 << lz77::MIN_WINDOW_BITS..=lz77::MAX_WINDOW_SIZE).contains(&window_size) {
+                return Err(OptionsError::InvalidWindowSize(window_size));
+            }
+        }
+        if let Some(align) = self.block_boundary_alignment {
+            if !align.is_power_of_two() {
+                return Err(OptionsError::InvalidBlockBoundaryAlignment(align));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error returned for invalid options (see `EncodeOptions::validate`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OptionsError {
+    /// `block_size` is zero.
+    ZeroBlockSize,
+
+    /// `block_size` is greater than `2^31`.
+    BlockSizeTooLarge(usize),
+
+    /// The window size of the LZ77 encoder is out of the range `256..=32768`.
+    InvalidWindowSize(u16),
+
+    /// `block_boundary_alignment` is not a power of two.
+    InvalidBlockBoundaryAlignment(usize),
+
+    /// The segment size of `SplitStreamEncoder` is zero.
+    ZeroSegmentSize,
+}
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OptionsError::ZeroBlockSize => write!(f, "Block size must not be zero"),
+            OptionsError::BlockSizeTooLarge(size) => write!(
+                f,
+                "Too large block size: {} (must be at most {})",
+                size, MAX_BLOCK_SIZE
+            ),
+            OptionsError::InvalidWindowSize(size) => write!(
+                f,
+                "LZ77 window size must be in the range {}..={}: {}",
+                1 << lz77::MIN_WINDOW_BITS,
+                lz77::MAX_WINDOW_SIZE,
+                size
+            ),
+            OptionsError::InvalidBlockBoundaryAlignment(align) => write!(
+                f,
+                "Block boundary alignment must be a power of two: {}",
+                align
+            ),
+            OptionsError::ZeroSegmentSize => write!(f, "Segment size must not be zero"),
+        }
+    }
+}
+impl error::Error for OptionsError {}
+impl From<OptionsError> for io::Error {
+    fn from(f: OptionsError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, f)
+    }
+}
+impl<E> EncodeOptions<E>
+where
+    E: lz77::Lz77Encode + 'static,
+{
+    /// Erases the type of the LZ77 encoder by boxing it.
+    ///
+    /// This is useful to store options (or encoders) which use different LZ77 encoders
+    /// in the same variable.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    /// use libflate::lz77::{Lz77Encode, NoCompressionLz77Encoder};
+    ///
+    /// let options: Vec<EncodeOptions<Box<dyn Lz77Encode>>> = vec![
+    ///     EncodeOptions::new().type_erased(),
+    ///     EncodeOptions::with_lz77(NoCompressionLz77Encoder::new()).type_erased(),
+    /// ];
+    /// for options in options {
+    ///     let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    ///     encoder.write_all(b"Hello World!").unwrap();
+    ///     encoder.finish().into_result().unwrap();
+    /// }
+    /// ```
+    pub fn type_erased(self) -> EncodeOptions<Box<dyn lz77::Lz77Encode>> {
+        EncodeOptions {
+            block_size: self.block_size,
+            block_size_auto: self.block_size_auto,
+            dynamic_huffman: self.dynamic_huffman,
+            store_if_larger: self.store_if_larger,
+            #[cfg(feature = "std")]
+            entropy_threshold: self.entropy_threshold,
+            block_split: self.block_split,
+            block_boundary_alignment: self.block_boundary_alignment,
+            dictionary: self.dictionary,
+            lz77: self
+                .lz77
+                .map(|lz77| Box::new(lz77) as Box<dyn lz77::Lz77Encode>),
+        }
+    }
+}
+
+/// A block splitting callback specified by `EncodeOptions::block_split`.
+///
+/// Two callbacks are regarded as equal only if they are the same instance.
+#[derive(Clone)]
+struct BlockSplit(Arc<BlockSplitFn>);
+type BlockSplitFn = dyn Fn(&[u8]) -> bool + Send + Sync;
+impl BlockSplit {
+    fn addr(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const u8 as usize
+    }
+}
+impl fmt::Debug for BlockSplit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BlockSplit({:#x})", self.addr())
+    }
+}
+impl PartialEq for BlockSplit {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr() == other.addr()
+    }
+}
+impl Eq for BlockSplit {}
+impl Hash for BlockSplit {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state);
+    }
+}
+
+/// An entropy threshold specified by `EncodeOptions::entropy_threshold`.
+///
+/// Two thresholds are regarded as equal only if they have the same bit pattern.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+struct EntropyThreshold(f64);
+#[cfg(feature = "std")]
+impl PartialEq for EntropyThreshold {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+#[cfg(feature = "std")]
+impl Eq for EntropyThreshold {}
+#[cfg(feature = "std")]
+impl Hash for EntropyThreshold {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+/// Returns the upper bound of the size of DEFLATE encoded data of `uncompressed_len` bytes.
+///
+/// Like zlib's `deflateBound`, the bound allows for the expansion of incompressible data:
+/// a compressed block never costs more than 9 bits per byte (the longest literal code
+/// of the fixed Huffman codes), and each block has a few bytes of overhead.
+/// It holds for any `EncodeOptions` with a `block_size` of at least 4096 bytes
+/// (including `block_size_auto`), unless `block_split`, `block_boundary_alignment`,
+/// `sync_flush` or `full_flush` add extra blocks.
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::deflate::{compressed_size_bound, Encoder};
+///
+/// let data = (0..100_000u64).map(|i| (i * i % 251) as u8).collect::<Vec<_>>();
+/// let mut encoder = Encoder::new(Vec::new());
+/// encoder.write_all(&data).unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+/// assert!(encoded_data.len() <= compressed_size_bound(data.len()));
+/// ```
+pub fn compressed_size_bound(uncompressed_len: usize) -> usize {
+    // 9 bits per byte, 10 bits of the block header and the end-of-block code per 4096 bytes,
+    // and the padding and the overhead of the last block.
+    uncompressed_len
+        .saturating_add(uncompressed_len >> 3)
+        .saturating_add(uncompressed_len >> 10)
+        .saturating_add(8)
+}
+
+/// Encodes `input` into a DEFLATE stream with the default options.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate;
+///
+/// let encoded_data = deflate::compress(b"Hello World!");
+/// assert_eq!(deflate::decompress(&encoded_data).unwrap(), b"Hello World!");
+/// ```
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    use io::Write;
+    let buf = Vec::with_capacity(compressed_size_bound(input.len()));
+    let mut encoder = Encoder::new(buf);
+    encoder.write_all(input).expect("Never fails");
+    encoder.finish().into_result().expect("Never fails")
+}
+
+/// Encodes `input` into a DEFLATE stream using the rayon thread pool.
+///
+/// `input` is split into chunks of the block size of `options`, and the chunks are compressed in parallel.
+/// Each chunk is compressed with the preceding window of `input` as a preset dictionary,
+/// and all chunks except the last are terminated by a sync flush (an empty non-compressed block)
+/// so that the results can be concatenated.
+/// The output is a single DEFLATE stream which can be decoded by any decoder.
+///
+/// # Panics
+///
+/// Panics if `options` are invalid (see `EncodeOptions::validate`).
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::{self, EncodeOptions};
+///
+/// let data = b"Hello World!".repeat(10_000);
+/// let encoded_data = deflate::par_compress(&data, EncodeOptions::new().block_size(16 * 1024));
+/// assert_eq!(deflate::decompress(&encoded_data).unwrap(), data);
+/// ```
+#[cfg(feature = "rayon")]
+pub fn par_compress<E>(input: &[u8], options: EncodeOptions<E>) -> Vec<u8>
+where
+    E: lz77::Lz77Encode + Clone + Send + Sync,
+{
+    use rayon::prelude::*;
+    use std::io::Write;
+
+    let chunk_size = cmp::max(options.get_block_size(), 1);
+    let window_size = options
+        .lz77
+        .as_ref()
+        .map_or(0, |lz77| lz77.window_size() as usize);
+    let chunks = cmp::max(input.len().div_ceil(chunk_size), 1);
+    let encoded = (0..chunks)
+        .into_par_iter()
+        .map(|i| {
+            let start = i * chunk_size;
+            let chunk = &input[start..cmp::min(start + chunk_size, input.len())];
+            let mut options = options.clone();
+            if i > 0 {
+                options = options.dictionary(&input[start.saturating_sub(window_size)..start]);
+            }
+            let buf = Vec::with_capacity(compressed_size_bound(chunk.len()));
+            let mut encoder = Encoder::with_options(buf, options).expect("Invalid options");
+            encoder.write_all(chunk).expect("Never fails");
+            if i + 1 == chunks {
+                encoder.finish().into_result().expect("Never fails")
+            } else {
+                encoder.sync_flush().expect("Never fails");
+                encoder.into_inner()
+            }
+        })
+        .collect::<Vec<_>>();
+    encoded.concat()
+}
+
+/// Statistics of a DEFLATE encoding.
+///
+/// This is returned by `Encoder::finish_with_stats`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct EncoderStats {
+    /// The number of the uncompressed bytes written to the encoder.
+    pub input_bytes: u64,
+
+    /// The number of the compressed bytes written to the inner stream.
+    pub output_bytes: u64,
+
+    /// The number of the literal symbols emitted by the LZ77 encoder.
+    pub literal_count: u64,
+
+    /// The number of the back-references emitted by the LZ77 encoder.
+    pub back_reference_count: u64,
+
+    /// The number of the DEFLATE blocks.
+    pub block_count: u64,
+
+    /// The statistics reported by the LZ77 encoder (see `lz77::Lz77Encode::stats`).
+    pub lz77: lz77::Lz77Stats,
+}
+impl EncoderStats {
+    /// Returns the ratio of the compressed size to the uncompressed size.
+    ///
+    /// If no byte has been written to the encoder, `0.0` will be returned.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.input_bytes == 0 {
+            0.0
+        } else {
+            self.output_bytes as f64 / self.input_bytes as f64
+        }
+    }
+}
+
+/// The internal state of a DEFLATE encoder detached from its inner stream.
+///
+/// This is returned by `Encoder::into_raw_parts` and holds the buffered data,
+/// the bits which have not been written to the inner stream yet, and the LZ77 encoder
+/// (including its allocated hash tables).
+/// It can be used to pool the allocations of encoders (see `Encoder::from_raw_parts`).
+#[derive(Debug, Clone)]
+pub struct EncoderState<E = lz77::DefaultLz77Encoder> {
+    bits: bit::BitWriter<()>,
+    block: Block<E>,
+}
+impl<E> EncoderState<E>
+where
+    E: lz77::Lz77Encode,
+{
+    /// Resets the state so that it can be used to start a new stream.
+    ///
+    /// The buffered data and bits are discarded, but the allocations are retained.
+    pub fn reset(&mut self) {
+        self.bits = bit::BitWriter::new(());
+        self.block.reset();
+    }
+}
+
+/// DEFLATE encoder.
+///
+/// The encoder can be cloned (if `W` and `E` can be cloned) to fork the stream.
+/// The clone is fully independent of the original, but the LZ77 history at the clone point is identical,
+/// so the subsequent writes to both encoders use the same back-reference opportunities.
+#[derive(Debug, Clone)]
+pub struct Encoder<W, E = lz77::DefaultLz77Encoder> {
+    writer: bit::BitWriter<W>,
+    block: Block<E>,
+}
+impl<W> Encoder<W, lz77::DefaultLz77Encoder>
+where
+    W: io::Write,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded DEFLATE stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    ///
+    /// assert_eq!(encoder.finish().into_result().unwrap(),
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
+    /// ```
+    pub fn new(inner: W) -> Self {
+        Self::with_options(inner, EncodeOptions::default()).expect("Never fails")
+    }
+}
+impl<W, E> Encoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Encoded DEFLATE stream is written to `inner`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `options` are invalid (see `EncodeOptions::validate`).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{Encoder, EncodeOptions, OptionsError};
+    ///
+    /// let options = EncodeOptions::new().no_compression();
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    ///
+    /// assert_eq!(encoder.finish().into_result().unwrap(),
+    ///            [1, 12, 0, 243, 255, 72, 101, 108, 108, 111, 32, 87, 111,
+    ///             114, 108, 100, 33]);
+    ///
+    /// let options = EncodeOptions::new().block_size(0);
+    /// assert_eq!(Encoder::with_options(Vec::new(), options).err(), Some(OptionsError::ZeroBlockSize));
+    /// ```
+    pub fn with_options(inner: W, options: EncodeOptions<E>) -> Result<Self, OptionsError> {
+        options.validate()?;
+        Ok(Encoder {
+            writer: bit::BitWriter::new(inner),
+            block: Block::new(options),
+        })
+    }
+
+    /// Makes a new encoder instance which reports the progress of the encoding to `callback`.
+    ///
+    /// This is equivalent to `Encoder::with_options(inner, options).map(|e| ProgressEncoder::new(e, callback))`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{EncodeOptions, Encoder};
+    ///
+    /// let mut encoder = Encoder::with_progress(Vec::new(), EncodeOptions::new(), |n| {
+    ///     eprintln!("{} bytes written", n);
+    /// })
+    /// .unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// assert_eq!(encoder.written_bytes(), 12);
+    /// ```
+    pub fn with_progress<F>(
+        inner: W,
+        options: EncodeOptions<E>,
+        callback: F,
+    ) -> Result<ProgressEncoder<W, E, F>, OptionsError>
+    where
+        F: FnMut(u64),
+    {
+        Self::with_options(inner, options).map(|encoder| ProgressEncoder::new(encoder, callback))
+    }
+
+    /// Flushes internal buffer and returns the inner stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    ///
+    /// assert_eq!(encoder.finish().into_result().unwrap(),
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
+    /// ```
+    pub fn finish(self) -> Finish<W, io::Error> {
+        let ((inner, _), error) = self.finish_with_stats().unwrap();
+        Finish::new(inner, error)
+    }
+
+    /// Flushes internal buffer and returns the inner stream with the statistics of the encoding.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World! Hello World!").unwrap();
+    ///
+    /// let (encoded_data, stats) = encoder.finish_with_stats().into_result().unwrap();
+    /// assert_eq!(stats.input_bytes, 25);
+    /// assert_eq!(stats.output_bytes, encoded_data.len() as u64);
+    /// assert_eq!(stats.literal_count, 13);
+    /// assert_eq!(stats.back_reference_count, 1);
+    /// assert_eq!(stats.block_count, 1);
+    /// assert_eq!(stats.lz77.longest_match, 12);
+    /// ```
+    pub fn finish_with_stats(mut self) -> Finish<(W, EncoderStats), io::Error> {
+        let result = self.block.finish(&mut self.writer);
+        let stats = EncoderStats {
+            output_bytes: self.writer.written_bytes(),
+            ..self.block.stats()
+        };
+        Finish::new((self.writer.into_inner(), stats), result.err())
+    }
+
+    /// Flushes the buffered data and aligns the output to a byte boundary without finishing the stream.
+    ///
+    /// The data written so far is encoded as non-final blocks and then
+    /// an empty non-compressed block is written (equivalent to zlib's `Z_SYNC_FLUSH`).
+    /// After that, a decoder can decode all of the data written so far.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Decoder, Encoder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello").unwrap();
+    /// encoder.sync_flush().unwrap();
+    /// assert!(encoder.as_inner_ref().ends_with(&[0, 0, 0xFF, 0xFF]));
+    ///
+    /// let mut decoder = Decoder::new(&encoder.as_inner_ref()[..]);
+    /// let mut buf = [0; 5];
+    /// decoder.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"Hello");
+    /// ```
+    pub fn sync_flush(&mut self) -> io::Result<()> {
+        self.block.sync_flush(&mut self.writer)
+    }
+
+    /// Flushes the buffered data like `sync_flush` and resets the state of the encoder.
+    ///
+    /// The data written after this call can be decoded independently of the preceding data
+    /// (equivalent to zlib's `Z_FULL_FLUSH`).
+    /// The LZ77 encoder is reset (see `lz77::Lz77Encode::reset`), so the preset dictionary
+    /// and the window are discarded and the following back-references never cross this point.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Decoder, Encoder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello").unwrap();
+    /// encoder.full_flush().unwrap();
+    /// let offset = encoder.as_inner_ref().len();
+    ///
+    /// encoder.write_all(b"World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[offset..]);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"World!");
+    /// ```
+    pub fn full_flush(&mut self) -> io::Result<()> {
+        self.block.full_flush(&mut self.writer)
+    }
+
+    /// Writes `data` as non-compressed (stored) blocks.
+    ///
+    /// The buffered data is flushed as non-final blocks first, and then `data` is written
+    /// bypassing the LZ77 and Huffman encoding.
+    /// If `data` is longer than 65535 bytes, it is split into multiple blocks.
+    /// If `is_final` is `true`, the last block is marked as the final block of the stream,
+    /// and the encoder no longer accepts data.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Decoder, Encoder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello ").unwrap();
+    /// encoder.write_stored_block(b"World!", true).unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    /// assert!(encoded_data.ends_with(&[6, 0, 249, 255, 87, 111, 114, 108, 100, 33]));
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn write_stored_block(&mut self, data: &[u8], is_final: bool) -> io::Result<()> {
+        self.block
+            .write_forced_block(&mut self.writer, data, is_final, BlockType::Raw)
+    }
+
+    /// Writes `data` as a block compressed with the fixed Huffman codes.
+    ///
+    /// The buffered data is flushed as non-final blocks first.
+    /// `data` is encoded by the LZ77 encoder of the encoder (if any) into a single block.
+    /// If `is_final` is `true`, the block is marked as the final block of the stream,
+    /// and the encoder no longer accepts data.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::deflate::{BlockType, Decoder, Encoder, Inspector};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_fixed_block(b"Hello World!", true).unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let blocks = Inspector::new(&encoded_data[..]).collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(blocks.len(), 1);
+    /// assert_eq!(blocks[0].kind, BlockType::Fixed);
     /// ```
-    pub fn no_compression(mut self) -> Self {
-        self.lz77 = None;
-        self
+    pub fn write_fixed_block(&mut self, data: &[u8], is_final: bool) -> io::Result<()> {
+        self.block
+            .write_forced_block(&mut self.writer, data, is_final, BlockType::Fixed)
     }
 
-    /// Specifies the hint of the size of a DEFLATE block.
+    /// Writes `data` as a block compressed with dynamic Huffman codes.
     ///
-    /// The default value is `DEFAULT_BLOCK_SIZE`.
+    /// This is the same as `write_fixed_block` except for the block type.
     ///
-    /// # Example
+    /// # Examples
     /// ```
-    /// use libflate::deflate::{Encoder, EncodeOptions};
+    /// use libflate::deflate::{BlockType, Encoder, Inspector};
     ///
-    /// let options = EncodeOptions::new().block_size(512 * 1024);
-    /// let encoder = Encoder::with_options(Vec::new(), options);
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_dynamic_block(b"Hello World!", false).unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let blocks = Inspector::new(&encoded_data[..]).collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(blocks[0].kind, BlockType::Dynamic);
+    /// assert_eq!(blocks[0].uncompressed_bytes, 12);
     /// ```
-    pub fn block_size(mut self, size: usize) -> Self {
-        self.block_size = size;
-        self
+    pub fn write_dynamic_block(&mut self, data: &[u8], is_final: bool) -> io::Result<()> {
+        self.block
+            .write_forced_block(&mut self.writer, data, is_final, BlockType::Dynamic)
     }
 
-    /// Specifies to compress with fixed huffman codes.
+    /// Resets the state of the encoder and replaces the inner stream with `new_writer`.
     ///
-    /// # Example
+    /// The internal buffers are kept allocated, so this is cheaper than making a new encoder.
+    /// The buffered data that has not been flushed yet is discarded.
+    ///
+    /// Returns the previous inner stream.
+    ///
+    /// # Examples
     /// ```
-    /// use libflate::deflate::{Encoder, EncodeOptions};
+    /// use std::io::Write;
+    /// use libflate::deflate::Encoder;
     ///
-    /// let options = EncodeOptions::new().fixed_huffman_codes();
-    /// let encoder = Encoder::with_options(Vec::new(), options);
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let _ = encoder.reset(Vec::new());
+    ///
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// assert_eq!(encoder.finish().into_result().unwrap(),
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
     /// ```
-    pub fn fixed_huffman_codes(mut self) -> Self {
-        self.dynamic_huffman = false;
-        self
+    pub fn reset(&mut self, new_writer: W) -> W {
+        self.block.reset();
+        mem::replace(&mut self.writer, bit::BitWriter::new(new_writer)).into_inner()
     }
 
-    fn get_block_type(&self) -> BlockType {
-        if self.lz77.is_none() {
-            BlockType::Raw
-        } else if self.dynamic_huffman {
-            BlockType::Dynamic
-        } else {
-            BlockType::Fixed
-        }
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.writer.as_inner_ref()
     }
-    fn get_block_size(&self) -> usize {
-        if self.lz77.is_none() {
-            cmp::min(self.block_size, MAX_NON_COMPRESSED_BLOCK_SIZE)
-        } else {
-            self.block_size
-        }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.writer.as_inner_mut()
     }
-}
 
-/// DEFLATE encoder.
-#[derive(Debug)]
-pub struct Encoder<W, E = lz77::DefaultLz77Encoder> {
-    writer: bit::BitWriter<W>,
-    block: Block<E>,
-}
-impl<W> Encoder<W, lz77::DefaultLz77Encoder>
-where
-    W: io::Write,
-{
-    /// Makes a new encoder instance.
+    /// Unwraps the `Encoder`, returning the inner stream.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+
+    /// Splits the encoder into the inner stream and the internal state without flushing anything.
     ///
-    /// Encoded DEFLATE stream is written to `inner`.
+    /// The encoder can be reconstructed by `Encoder::from_raw_parts`.
     ///
     /// # Examples
     /// ```
@@ -147,46 +1129,55 @@ where
     /// use libflate::deflate::Encoder;
     ///
     /// let mut encoder = Encoder::new(Vec::new());
-    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.write_all(b"Hello ").unwrap();
+    /// let (inner, state) = encoder.into_raw_parts();
     ///
+    /// let mut encoder = Encoder::from_raw_parts(inner, state);
+    /// encoder.write_all(b"World!").unwrap();
     /// assert_eq!(encoder.finish().into_result().unwrap(),
-    ///            [5, 192, 49, 13, 0, 0, 8, 3, 65, 43, 224, 6, 7, 24, 128, 237,
-    ///            147, 38, 245, 63, 244, 230, 65, 181, 50, 215, 1]);
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
     /// ```
-    pub fn new(inner: W) -> Self {
-        Self::with_options(inner, EncodeOptions::default())
+    pub fn into_raw_parts(self) -> (W, EncoderState<E>) {
+        let (bits, inner) = self.writer.replace_inner(());
+        let state = EncoderState {
+            bits,
+            block: self.block,
+        };
+        (inner, state)
     }
-}
-impl<W, E> Encoder<W, E>
-where
-    W: io::Write,
-    E: lz77::Lz77Encode,
-{
-    /// Makes a new encoder instance with specified options.
+
+    /// Makes an encoder instance from the inner stream and the state returned by `Encoder::into_raw_parts`.
     ///
-    /// Encoded DEFLATE stream is written to `inner`.
+    /// To start a new stream with the allocations of a previous encoder,
+    /// call `EncoderState::reset` before this.
     ///
     /// # Examples
     /// ```
     /// use std::io::Write;
-    /// use libflate::deflate::{Encoder, EncodeOptions};
+    /// use libflate::deflate::Encoder;
     ///
-    /// let options = EncodeOptions::new().no_compression();
-    /// let mut encoder = Encoder::with_options(Vec::new(), options);
+    /// let mut encoder = Encoder::new(Vec::new());
     /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.write_fixed_block(&[], true).unwrap();
+    /// let (encoded_data, mut state) = encoder.into_raw_parts();
     ///
+    /// // Reuses the state for another stream
+    /// state.reset();
+    /// let mut encoder = Encoder::from_raw_parts(Vec::new(), state);
+    /// encoder.write_all(b"Hello World!").unwrap();
     /// assert_eq!(encoder.finish().into_result().unwrap(),
-    ///            [1, 12, 0, 243, 255, 72, 101, 108, 108, 111, 32, 87, 111,
-    ///             114, 108, 100, 33]);
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
     /// ```
-    pub fn with_options(inner: W, options: EncodeOptions<E>) -> Self {
+    pub fn from_raw_parts(inner: W, state: EncoderState<E>) -> Self {
+        let (writer, ()) = state.bits.replace_inner(inner);
         Encoder {
-            writer: bit::BitWriter::new(inner),
-            block: Block::new(options),
+            writer,
+            block: state.block,
         }
     }
 
-    /// Flushes internal buffer and returns the inner stream.
+    /// Returns the number of the uncompressed bytes written to the encoder
+    /// since it was created (or last reset).
     ///
     /// # Examples
     /// ```
@@ -195,31 +1186,38 @@ where
     ///
     /// let mut encoder = Encoder::new(Vec::new());
     /// encoder.write_all(b"Hello World!").unwrap();
+    /// assert_eq!(encoder.uncompressed_position(), 12);
     ///
-    /// assert_eq!(encoder.finish().into_result().unwrap(),
-    ///            [5, 192, 49, 13, 0, 0, 8, 3, 65, 43, 224, 6, 7, 24, 128, 237,
-    ///            147, 38, 245, 63, 244, 230, 65, 181, 50, 215, 1]);
+    /// let _ = encoder.reset(Vec::new());
+    /// assert_eq!(encoder.uncompressed_position(), 0);
     /// ```
-    pub fn finish(mut self) -> Finish<W, io::Error> {
-        match self.block.finish(&mut self.writer) {
-            Ok(_) => Finish::new(self.writer.into_inner(), None),
-            Err(e) => Finish::new(self.writer.into_inner(), Some(e)),
-        }
+    pub fn uncompressed_position(&self) -> u64 {
+        self.block.input_bytes
     }
 
-    /// Returns the immutable reference to the inner stream.
-    pub fn as_inner_ref(&self) -> &W {
-        self.writer.as_inner_ref()
-    }
-
-    /// Returns the mutable reference to the inner stream.
-    pub fn as_inner_mut(&mut self) -> &mut W {
-        self.writer.as_inner_mut()
+    /// Returns the number of the compressed bytes written to the inner stream
+    /// since the encoder was created (or last reset).
+    ///
+    /// The data buffered in the encoder (including the bits of an incomplete byte) are not counted.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.sync_flush().unwrap();
+    /// assert_eq!(encoder.compressed_position(), encoder.as_inner_ref().len() as u64);
+    /// ```
+    pub fn compressed_position(&self) -> u64 {
+        self.writer.written_bytes()
     }
 
-    /// Unwraps the `Encoder`, returning the inner stream.
-    pub fn into_inner(self) -> W {
-        self.writer.into_inner()
+    /// Returns the symbols of the current block buffer.
+    #[cfg(feature = "serde_json")]
+    pub(crate) fn buffered_symbols(&self) -> &[symbol::Symbol] {
+        self.block.block_buf.symbols()
     }
 }
 impl<W, E> io::Write for Encoder<W, E>
@@ -245,48 +1243,293 @@ where
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Block<E> {
-    block_type: BlockType,
     block_size: usize,
+    block_size_auto: bool,
     block_buf: BlockBuf<E>,
+    block_split: Option<BlockSplit>,
+    alignment: Option<usize>,
+    #[cfg(feature = "std")]
+    entropy_threshold: f64,
+    // The statistics of the LZ77 encoder before it was reset by `write_high_entropy_data`
+    reset_lz77_stats: lz77::Lz77Stats,
+    input_bytes: u64,
+    block_count: u64,
+    forced_symbol_counts: (u64, u64),
+    finished: bool,
 }
 impl<E> Block<E>
 where
     E: lz77::Lz77Encode,
 {
     fn new(options: EncodeOptions<E>) -> Self {
+        let block_size = options.get_block_size();
+        let mut lz77 = options.lz77;
+        if let Some(ref mut lz77) = lz77 {
+            if !options.dictionary.is_empty() {
+                lz77.set_dictionary(&options.dictionary);
+            }
+        }
         Block {
-            block_type: options.get_block_type(),
-            block_size: options.get_block_size(),
-            block_buf: BlockBuf::new(options.lz77, options.dynamic_huffman),
+            block_size,
+            block_size_auto: options.block_size_auto,
+            block_buf: BlockBuf::new(
+                lz77,
+                options.dynamic_huffman,
+                options.store_if_larger,
+                options.block_split.is_some(),
+            ),
+            block_split: options.block_split,
+            alignment: options.block_boundary_alignment,
+            #[cfg(feature = "std")]
+            entropy_threshold: options.entropy_threshold.0,
+            reset_lz77_stats: lz77::Lz77Stats::default(),
+            input_bytes: 0,
+            block_count: 0,
+            forced_symbol_counts: (0, 0),
+            finished: false,
+        }
+    }
+    fn check_not_finished(&self) -> io::Result<()> {
+        if self.finished {
+            Err(invalid_input_error!(
+                "The final block of the stream has already been written"
+            ))
+        } else {
+            Ok(())
+        }
+    }
+    fn flush_buffered<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        while self.block_buf.len() > 0 {
+            self.block_count += self.block_buf.flush(writer, false)?;
+        }
+        Ok(())
+    }
+    fn write_forced_block<W>(
+        &mut self,
+        writer: &mut bit::BitWriter<W>,
+        data: &[u8],
+        is_final: bool,
+        block_type: BlockType,
+    ) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.check_not_finished()?;
+        self.flush_buffered(writer)?;
+        match block_type {
+            BlockType::Raw => {
+                self.block_count += RawBuf::write_blocks(writer, data, is_final)?;
+            }
+            BlockType::Fixed => {
+                writer.write_bit(is_final)?;
+                writer.write_bits(2, BlockType::Fixed as u16)?;
+                self.write_compressed_block(writer, data, &symbol::FixedHuffmanCodec)?;
+            }
+            BlockType::Dynamic => {
+                writer.write_bit(is_final)?;
+                writer.write_bits(2, BlockType::Dynamic as u16)?;
+                self.write_compressed_block(writer, data, &symbol::DynamicHuffmanCodec)?;
+            }
         }
+        self.input_bytes += data.len() as u64;
+        if is_final {
+            self.finished = true;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+    fn write_compressed_block<W, H>(
+        &mut self,
+        writer: &mut bit::BitWriter<W>,
+        data: &[u8],
+        huffman: &H,
+    ) -> io::Result<()>
+    where
+        W: io::Write,
+        H: HuffmanCodec,
+    {
+        let mut symbols = Vec::new();
+        if let Some(lz77) = self.block_buf.lz77_mut() {
+            lz77.encode(data, &mut symbols);
+            lz77.flush(&mut symbols);
+        } else {
+            symbols.extend(data.iter().map(|&b| symbol::Symbol::Literal(b)));
+        }
+        symbols.push(symbol::Symbol::EndOfBlock);
+
+        let symbol_encoder = huffman.build(&symbols)?;
+        huffman.save(writer, &symbol_encoder)?;
+        for s in &symbols {
+            match *s {
+                symbol::Symbol::Literal(_) => self.forced_symbol_counts.0 += 1,
+                symbol::Symbol::Share { .. } => self.forced_symbol_counts.1 += 1,
+                symbol::Symbol::EndOfBlock => {}
+            }
+            symbol_encoder.encode(writer, s)?;
+        }
+        self.block_count += 1;
+        Ok(())
+    }
+    fn write<W>(&mut self, writer: &mut bit::BitWriter<W>, mut buf: &[u8]) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.check_not_finished()?;
+        let align = match self.alignment {
+            None => return self.write_unaligned(writer, buf),
+            Some(align) => align as u64,
+        };
+        while !buf.is_empty() {
+            let until_boundary = align - self.input_bytes % align;
+            let size = cmp::min(buf.len() as u64, until_boundary) as usize;
+            self.write_unaligned(writer, &buf[..size])?;
+            if size as u64 == until_boundary {
+                self.sync_flush(writer)?;
+            }
+            buf = &buf[size..];
+        }
+        Ok(())
     }
-    fn write<W>(&mut self, writer: &mut bit::BitWriter<W>, buf: &[u8]) -> io::Result<()>
+    fn write_unaligned<W>(&mut self, writer: &mut bit::BitWriter<W>, buf: &[u8]) -> io::Result<()>
     where
         W: io::Write,
     {
+        #[cfg(feature = "std")]
+        {
+            if self.block_buf.lz77_mut().is_some()
+                && entropy::estimate_entropy(buf) > self.entropy_threshold
+            {
+                return self.write_high_entropy_data(writer, buf);
+            }
+        }
+        self.input_bytes += buf.len() as u64;
         self.block_buf.append(buf);
+        if let Some(ref block_split) = self.block_split {
+            if (block_split.0)(self.block_buf.data()) {
+                self.flush_buffered(writer)?;
+            }
+            return Ok(());
+        }
         while self.block_buf.len() >= self.block_size {
-            writer.write_bit(false)?;
-            writer.write_bits(2, self.block_type as u16)?;
-            self.block_buf.flush(writer)?;
+            let (uncompressed_size, start) = (self.block_buf.len(), writer.written_bits());
+            self.block_count += self.block_buf.flush(writer, false)?;
+            if self.block_size_auto {
+                let uncompressed_size = (uncompressed_size - self.block_buf.len()) as u64;
+                self.tune_block_size(uncompressed_size, writer.written_bits() - start);
+            }
+        }
+        Ok(())
+    }
+    fn tune_block_size(&mut self, uncompressed_size: u64, compressed_bits: u64) {
+        let ratio = compressed_bits as f64 / (uncompressed_size * 8) as f64;
+        if ratio > 0.95 && self.block_size > MIN_AUTO_BLOCK_SIZE {
+            self.block_size = cmp::max(self.block_size / 2, MIN_AUTO_BLOCK_SIZE);
+        } else if ratio < 0.5 && self.block_size < MAX_AUTO_BLOCK_SIZE {
+            self.block_size = cmp::min(self.block_size * 2, MAX_AUTO_BLOCK_SIZE);
+        }
+    }
+    #[cfg(feature = "std")]
+    fn write_high_entropy_data<W>(
+        &mut self,
+        writer: &mut bit::BitWriter<W>,
+        buf: &[u8],
+    ) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.flush_buffered(writer)?;
+        self.block_count += RawBuf::write_blocks(writer, buf, false)?;
+        self.input_bytes += buf.len() as u64;
+
+        // The LZ77 encoder has not seen `buf`, so its window is replaced with the tail of `buf`
+        // to keep the distances of the following back-references valid.
+        let lz77 = self.block_buf.lz77_mut().expect("Never fails");
+        merge_lz77_stats(&mut self.reset_lz77_stats, &lz77.stats());
+        lz77.reset();
+        lz77.set_dictionary(&buf[buf.len().saturating_sub(lz77::MAX_DISTANCE as usize)..]);
+        Ok(())
+    }
+    fn sync_flush<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.check_not_finished()?;
+        self.flush_buffered(writer)?;
+
+        // An empty non-compressed block (see RFC-1951 3.2.4).
+        writer.write_bit(false)?;
+        writer.write_bits(2, BlockType::Raw as u16)?;
+        writer.flush()?;
+        writer.write_bytes(&[0, 0, 0xFF, 0xFF])?;
+        self.block_count += 1;
+        writer.flush()
+    }
+    fn full_flush<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.sync_flush(writer)?;
+        if let Some(lz77) = self.block_buf.lz77_mut() {
+            merge_lz77_stats(&mut self.reset_lz77_stats, &lz77.stats());
+            lz77.reset();
         }
         Ok(())
     }
-    fn finish<W>(mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    fn finish<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
     where
         W: io::Write,
     {
-        writer.write_bit(true)?;
-        writer.write_bits(2, self.block_type as u16)?;
-        self.block_buf.flush(writer)?;
+        if self.finished {
+            return writer.flush();
+        }
+        self.block_count += self.block_buf.flush(writer, true)?;
+        self.finished = true;
         writer.flush()?;
         Ok(())
     }
+    fn reset(&mut self) {
+        self.block_buf.reset();
+        self.reset_lz77_stats = lz77::Lz77Stats::default();
+        self.input_bytes = 0;
+        self.block_count = 0;
+        self.forced_symbol_counts = (0, 0);
+        self.finished = false;
+    }
+    fn stats(&self) -> EncoderStats {
+        let (literal_count, back_reference_count) = self.block_buf.symbol_counts();
+        let mut lz77 = self.reset_lz77_stats.clone();
+        merge_lz77_stats(&mut lz77, &self.block_buf.lz77_stats());
+        EncoderStats {
+            input_bytes: self.input_bytes,
+            output_bytes: 0,
+            literal_count: literal_count + self.forced_symbol_counts.0,
+            back_reference_count: back_reference_count + self.forced_symbol_counts.1,
+            block_count: self.block_count,
+            lz77,
+        }
+    }
+}
+
+fn merge_lz77_stats(stats: &mut lz77::Lz77Stats, other: &lz77::Lz77Stats) {
+    if other.match_count > 0 {
+        if stats.match_count == 0 {
+            stats.shortest_match = other.shortest_match;
+        } else {
+            stats.shortest_match = cmp::min(stats.shortest_match, other.shortest_match);
+        }
+        stats.longest_match = cmp::max(stats.longest_match, other.longest_match);
+    }
+    stats.literal_count += other.literal_count;
+    stats.match_count += other.match_count;
+    stats.total_match_length += other.total_match_length;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum BlockBuf<E> {
     Raw(RawBuf),
     Fixed(CompressBuf<symbol::FixedHuffmanCodec, E>),
@@ -296,12 +1539,27 @@ impl<E> BlockBuf<E>
 where
     E: lz77::Lz77Encode,
 {
-    fn new(lz77: Option<E>, dynamic: bool) -> Self {
+    /// Makes a new buffer.
+    ///
+    /// If `keep_original` is `true`, the uncompressed data of the current block is kept (see `BlockBuf::data`).
+    fn new(lz77: Option<E>, dynamic: bool, store_if_larger: bool, keep_original: bool) -> Self {
         if let Some(lz77) = lz77 {
             if dynamic {
-                BlockBuf::Dynamic(CompressBuf::new(symbol::DynamicHuffmanCodec, lz77))
+                let huffman = symbol::DynamicHuffmanCodec;
+                BlockBuf::Dynamic(CompressBuf::new(
+                    huffman,
+                    lz77,
+                    store_if_larger,
+                    keep_original,
+                ))
             } else {
-                BlockBuf::Fixed(CompressBuf::new(symbol::FixedHuffmanCodec, lz77))
+                let huffman = symbol::FixedHuffmanCodec;
+                BlockBuf::Fixed(CompressBuf::new(
+                    huffman,
+                    lz77,
+                    store_if_larger,
+                    keep_original,
+                ))
             }
         } else {
             BlockBuf::Raw(RawBuf::new())
@@ -321,19 +1579,72 @@ where
             BlockBuf::Dynamic(ref b) => b.len(),
         }
     }
-    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    /// Returns the uncompressed data of the current block.
+    ///
+    /// An empty slice is returned if the data is not kept by the buffer.
+    fn data(&self) -> &[u8] {
+        match *self {
+            BlockBuf::Raw(ref b) => &b.buf,
+            BlockBuf::Fixed(ref b) => b.original.as_deref().unwrap_or(&[]),
+            BlockBuf::Dynamic(ref b) => b.original.as_deref().unwrap_or(&[]),
+        }
+    }
+    fn reset(&mut self) {
+        match *self {
+            BlockBuf::Raw(ref mut b) => b.reset(),
+            BlockBuf::Fixed(ref mut b) => b.reset(),
+            BlockBuf::Dynamic(ref mut b) => b.reset(),
+        }
+    }
+    fn lz77_mut(&mut self) -> Option<&mut E> {
+        match *self {
+            BlockBuf::Raw(_) => None,
+            BlockBuf::Fixed(ref mut b) => Some(&mut b.lz77),
+            BlockBuf::Dynamic(ref mut b) => Some(&mut b.lz77),
+        }
+    }
+    fn lz77_stats(&self) -> lz77::Lz77Stats {
+        match *self {
+            BlockBuf::Raw(_) => lz77::Lz77Stats::default(),
+            BlockBuf::Fixed(ref b) => b.lz77.stats(),
+            BlockBuf::Dynamic(ref b) => b.lz77.stats(),
+        }
+    }
+    #[cfg(feature = "serde_json")]
+    fn symbols(&self) -> &[symbol::Symbol] {
+        match *self {
+            BlockBuf::Raw(_) => &[],
+            BlockBuf::Fixed(ref b) => &b.buf,
+            BlockBuf::Dynamic(ref b) => &b.buf,
+        }
+    }
+    fn symbol_counts(&self) -> (u64, u64) {
+        match *self {
+            BlockBuf::Raw(_) => (0, 0),
+            BlockBuf::Fixed(ref b) => (b.literal_count, b.back_reference_count),
+            BlockBuf::Dynamic(ref b) => (b.literal_count, b.back_reference_count),
+        }
+    }
+    /// Writes the buffered data as blocks and returns the number of the written blocks.
+    ///
+    /// The `BFINAL` bit of the last written block is set to `is_final`.
+    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>, is_final: bool) -> io::Result<u64>
     where
         W: io::Write,
     {
         match *self {
-            BlockBuf::Raw(ref mut b) => b.flush(writer),
-            BlockBuf::Fixed(ref mut b) => b.flush(writer),
-            BlockBuf::Dynamic(ref mut b) => b.flush(writer),
+            BlockBuf::Raw(ref mut b) => {
+                let blocks = RawBuf::write_blocks(writer, &b.buf, is_final)?;
+                b.buf.clear();
+                Ok(blocks)
+            }
+            BlockBuf::Fixed(ref mut b) => b.flush(writer, is_final, BlockType::Fixed),
+            BlockBuf::Dynamic(ref mut b) => b.flush_with_cheaper_codes(writer, is_final),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct RawBuf {
     buf: Vec<u8>,
 }
@@ -347,63 +1658,236 @@ impl RawBuf {
     fn len(&self) -> usize {
         self.buf.len()
     }
-    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    fn reset(&mut self) {
+        self.buf.clear();
+    }
+    /// Writes `data` as non-compressed blocks (including their headers) and returns the number of them.
+    ///
+    /// At least one block is written even if `data` is empty.
+    fn write_blocks<W>(
+        writer: &mut bit::BitWriter<W>,
+        data: &[u8],
+        is_final: bool,
+    ) -> io::Result<u64>
+    where
+        W: io::Write,
+    {
+        let mut blocks = 0;
+        let mut chunks = data.chunks(MAX_NON_COMPRESSED_BLOCK_SIZE).peekable();
+        loop {
+            let chunk = chunks.next().unwrap_or(&[]);
+            let is_last = chunks.peek().is_none();
+            writer.write_bit(is_final && is_last)?;
+            writer.write_bits(2, BlockType::Raw as u16)?;
+            RawBuf::write_block(writer, chunk)?;
+            blocks += 1;
+            if is_last {
+                return Ok(blocks);
+            }
+        }
+    }
+    fn write_block<W>(writer: &mut bit::BitWriter<W>, data: &[u8]) -> io::Result<()>
     where
         W: io::Write,
     {
-        let size = cmp::min(self.buf.len(), MAX_NON_COMPRESSED_BLOCK_SIZE);
+        debug_assert!(data.len() <= MAX_NON_COMPRESSED_BLOCK_SIZE);
+        let size = data.len() as u16;
         writer.flush()?;
-        writer
-            .as_inner_mut()
-            .write_all(&(size as u16).to_le_bytes())?;
-        writer
-            .as_inner_mut()
-            .write_all(&(!size as u16).to_le_bytes())?;
-        writer.as_inner_mut().write_all(&self.buf[..size])?;
-        self.buf.drain(0..size);
-        Ok(())
+        writer.write_bytes(&size.to_le_bytes())?;
+        writer.write_bytes(&(!size).to_le_bytes())?;
+        writer.write_bytes(data)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct CompressBuf<H, E> {
     huffman: H,
     lz77: E,
     buf: Vec<symbol::Symbol>,
+    store_if_larger: bool,
+    original: Option<Vec<u8>>,
     original_size: usize,
+    literal_count: u64,
+    back_reference_count: u64,
 }
 impl<H, E> CompressBuf<H, E>
 where
     H: symbol::HuffmanCodec,
     E: lz77::Lz77Encode,
 {
-    fn new(huffman: H, lz77: E) -> Self {
+    fn new(huffman: H, lz77: E, store_if_larger: bool, keep_original: bool) -> Self {
         CompressBuf {
             huffman,
             lz77,
             buf: Vec::new(),
+            store_if_larger,
+            original: if store_if_larger || keep_original {
+                Some(Vec::new())
+            } else {
+                None
+            },
             original_size: 0,
+            literal_count: 0,
+            back_reference_count: 0,
         }
     }
     fn append(&mut self, buf: &[u8]) {
         self.original_size += buf.len();
+        if let Some(ref mut original) = self.original {
+            original.extend_from_slice(buf);
+        }
         self.lz77.encode(buf, &mut self.buf);
     }
     fn len(&self) -> usize {
         self.original_size
     }
-    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    fn reset(&mut self) {
+        self.lz77.reset();
+        self.buf.clear();
+        if let Some(ref mut original) = self.original {
+            original.clear();
+        }
+        self.original_size = 0;
+        self.literal_count = 0;
+        self.back_reference_count = 0;
+    }
+    fn flush<W>(
+        &mut self,
+        writer: &mut bit::BitWriter<W>,
+        is_final: bool,
+        block_type: BlockType,
+    ) -> io::Result<u64>
     where
         W: io::Write,
     {
-        self.lz77.flush(&mut self.buf);
-        self.buf.push(symbol::Symbol::EndOfBlock);
+        self.flush_lz77();
         let symbol_encoder = self.huffman.build(&self.buf)?;
+        if self.store_if_larger {
+            let bits = symbol::header_bits(&self.huffman, &symbol_encoder)
+                .saturating_add(symbol::symbol_bits(&self.buf, &symbol_encoder));
+            if self.is_larger_than_stored(bits) {
+                return self.write_stored_blocks(writer, is_final);
+            }
+        }
+        writer.write_bit(is_final)?;
+        writer.write_bits(2, block_type as u16)?;
         self.huffman.save(writer, &symbol_encoder)?;
+        self.write_symbols(writer, &symbol_encoder)?;
+        Ok(1)
+    }
+    /// Returns `true` if a compressed block of `bits` bits (excluding the block header)
+    /// is larger than the non-compressed blocks for the same data.
+    fn is_larger_than_stored(&self, bits: usize) -> bool {
+        // `BFINAL`, `BTYPE`, (at most 7 bits of) padding, `LEN` and `NLEN` per block
+        let blocks = cmp::max(
+            1,
+            self.original_size.div_ceil(MAX_NON_COMPRESSED_BLOCK_SIZE),
+        );
+        let stored_bits = self.original_size * 8 + blocks * (3 + 7 + 32);
+        bits.saturating_add(3) > stored_bits
+    }
+    fn write_stored_blocks<W>(
+        &mut self,
+        writer: &mut bit::BitWriter<W>,
+        is_final: bool,
+    ) -> io::Result<u64>
+    where
+        W: io::Write,
+    {
+        let original = self.original.as_mut().expect("Never fails");
+        let blocks = RawBuf::write_blocks(writer, original, is_final)?;
+        for s in self.buf.drain(..) {
+            match s {
+                symbol::Symbol::Literal(_) => self.literal_count += 1,
+                symbol::Symbol::Share { .. } => self.back_reference_count += 1,
+                symbol::Symbol::EndOfBlock => {}
+            }
+        }
+        original.clear();
+        self.original_size = 0;
+        Ok(blocks)
+    }
+    fn flush_lz77(&mut self) {
+        self.lz77.flush(&mut self.buf);
+        self.buf.push(symbol::Symbol::EndOfBlock);
+    }
+    fn write_symbols<W>(
+        &mut self,
+        writer: &mut bit::BitWriter<W>,
+        symbol_encoder: &symbol::Encoder,
+    ) -> io::Result<()>
+    where
+        W: io::Write,
+    {
         for s in self.buf.drain(..) {
+            match s {
+                symbol::Symbol::Literal(_) => self.literal_count += 1,
+                symbol::Symbol::Share { .. } => self.back_reference_count += 1,
+                symbol::Symbol::EndOfBlock => {}
+            }
             symbol_encoder.encode(writer, &s)?;
         }
+        if let Some(ref mut original) = self.original {
+            original.clear();
+        }
         self.original_size = 0;
         Ok(())
     }
 }
+
+impl<E> CompressBuf<symbol::DynamicHuffmanCodec, E>
+where
+    E: lz77::Lz77Encode,
+{
+    /// Writes the buffered data as a block with the cheaper of fixed and dynamic huffman codes.
+    ///
+    /// All the symbols of the block are collected before the block is written,
+    /// so the bit costs of both codes can be computed from them.
+    fn flush_with_cheaper_codes<W>(
+        &mut self,
+        writer: &mut bit::BitWriter<W>,
+        is_final: bool,
+    ) -> io::Result<u64>
+    where
+        W: io::Write,
+    {
+        self.flush_lz77();
+        let fixed = symbol::FixedHuffmanCodec.build(&self.buf)?;
+        let dynamic = symbol::DynamicHuffmanCodec.build(&self.buf)?;
+        let fixed_bits = symbol::symbol_bits(&self.buf, &fixed);
+        let dynamic_bits = symbol::header_bits(&symbol::DynamicHuffmanCodec, &dynamic)
+            .saturating_add(symbol::symbol_bits(&self.buf, &dynamic));
+        if self.store_if_larger && self.is_larger_than_stored(cmp::min(fixed_bits, dynamic_bits)) {
+            self.write_stored_blocks(writer, is_final)
+        } else if fixed_bits < dynamic_bits {
+            self.write_block(
+                writer,
+                is_final,
+                BlockType::Fixed,
+                &symbol::FixedHuffmanCodec,
+                &fixed,
+            )
+        } else {
+            let huffman = symbol::DynamicHuffmanCodec;
+            self.write_block(writer, is_final, BlockType::Dynamic, &huffman, &dynamic)
+        }
+    }
+    fn write_block<W, C>(
+        &mut self,
+        writer: &mut bit::BitWriter<W>,
+        is_final: bool,
+        block_type: BlockType,
+        huffman: &C,
+        symbol_encoder: &symbol::Encoder,
+    ) -> io::Result<u64>
+    where
+        W: io::Write,
+        C: HuffmanCodec,
+    {
+        writer.write_bit(is_final)?;
+        writer.write_bits(2, block_type as u16)?;
+        huffman.save(writer, symbol_encoder)?;
+        self.write_symbols(writer, symbol_encoder)?;
+        Ok(1)
+    }
+}
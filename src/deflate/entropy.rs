@@ -0,0 +1,43 @@
+//! Estimation of the byte-level entropy of data.
+//!
+//! This module is available only if the `std` feature is enabled.
+
+/// The default value of `EncodeOptions::entropy_threshold` (in bits per byte).
+pub const DEFAULT_ENTROPY_THRESHOLD: f64 = 7.9;
+
+/// Estimates the byte-level (order-0) Shannon entropy of `buf` in bits per byte.
+///
+/// The result is in the range `0.0..=8.0`, and `0.0` is returned for an empty buffer.
+/// Data which is already compressed or encrypted has an entropy close to `8.0`.
+///
+/// Note that the entropy does not take repeated strings into account,
+/// so data with a high entropy may still be compressible by LZ77
+/// (e.g., a sequence of all the byte values repeated many times).
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::entropy::estimate_entropy;
+///
+/// assert_eq!(estimate_entropy(b""), 0.0);
+/// assert_eq!(estimate_entropy(b"aaaa"), 0.0);
+/// assert_eq!(estimate_entropy(b"abab"), 1.0);
+///
+/// let all_bytes = (0..=255).collect::<Vec<u8>>();
+/// assert_eq!(estimate_entropy(&all_bytes), 8.0);
+/// ```
+pub fn estimate_entropy(buf: &[u8]) -> f64 {
+    let mut histogram = [0usize; 256];
+    for &b in buf {
+        histogram[b as usize] += 1;
+    }
+    let total = buf.len() as f64;
+    histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum::<f64>()
+        .max(0.0)
+}
@@ -0,0 +1,459 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+use io::{self, Read, Write};
+
+use super::symbol::{self, HuffmanCodec};
+use super::BlockType;
+use bit;
+use lz77;
+use Error;
+
+/// Information about a block of a DEFLATE stream.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlockInfo {
+    /// The zero-based index of the block.
+    pub index: usize,
+
+    /// The type of the block.
+    pub kind: BlockType,
+
+    /// The size of the encoded block (including the block header) in bytes.
+    ///
+    /// Because blocks are not byte aligned, this is the number of bits rounded up to bytes.
+    pub compressed_bytes: usize,
+
+    /// The size of the decoded block in bytes.
+    pub uncompressed_bytes: usize,
+
+    /// The number of literals in the block.
+    ///
+    /// The bytes of a raw block are counted as literals.
+    pub literal_count: usize,
+
+    /// The number of back-references in the block.
+    pub back_ref_count: usize,
+}
+
+/// An iterator which reads a DEFLATE stream and yields information about each block.
+///
+/// The decompressed data is not materialized.
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::deflate::{BlockType, Encoder, Inspector};
+///
+/// let mut encoder = Encoder::new(Vec::new());
+/// encoder.write_all(b"Hello World! Hello World!").unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let blocks = Inspector::new(&encoded_data[..]).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(blocks.len(), 1);
+/// assert_eq!(blocks[0].kind, BlockType::Fixed); // The data is too small to amortize a dynamic header
+/// assert_eq!(blocks[0].uncompressed_bytes, 25);
+/// assert_eq!(blocks[0].back_ref_count, 1);
+/// ```
+#[derive(Debug)]
+pub struct Inspector<R> {
+    bit_reader: bit::BitReader<R>,
+    non_compressed_bytes: u64,
+    uncompressed_total: u64,
+    block_index: usize,
+    eos: bool,
+}
+impl<R> Inspector<R>
+where
+    R: Read,
+{
+    /// Makes a new inspector instance.
+    pub fn new(inner: R) -> Self {
+        Inspector {
+            bit_reader: bit::BitReader::new(inner),
+            non_compressed_bytes: 0,
+            uncompressed_total: 0,
+            block_index: 0,
+            eos: false,
+        }
+    }
+
+    pub(crate) fn consumed_bits(&self) -> u64 {
+        self.bit_reader.consumed_bits() + self.non_compressed_bytes * 8
+    }
+    fn read_block(&mut self) -> io::Result<BlockInfo> {
+        self.read_block_with(None, None)
+    }
+    // Reads a block, storing the bytes of a non-compressed block to `data`
+    // and the codes of a compressed block to `codes` (if given).
+    fn read_block_with(
+        &mut self,
+        data: Option<&mut Vec<u8>>,
+        codes: Option<&mut Vec<lz77::Code>>,
+    ) -> io::Result<BlockInfo> {
+        let start = self.consumed_bits();
+        self.eos = self.bit_reader.read_bit()?;
+        let kind = match self.bit_reader.read_bits(2)? {
+            0b00 => BlockType::Raw,
+            0b01 => BlockType::Fixed,
+            0b10 => BlockType::Dynamic,
+            _ => return Err(Error::InvalidBlockType.into()),
+        };
+        let mut info = BlockInfo {
+            index: self.block_index,
+            kind,
+            compressed_bytes: 0,
+            uncompressed_bytes: 0,
+            literal_count: 0,
+            back_ref_count: 0,
+        };
+        match kind {
+            BlockType::Raw => self.read_non_compressed_block(&mut info, data)?,
+            BlockType::Fixed => {
+                self.read_compressed_block(&symbol::FixedHuffmanCodec, &mut info, codes)?
+            }
+            BlockType::Dynamic => {
+                self.read_compressed_block(&symbol::DynamicHuffmanCodec, &mut info, codes)?
+            }
+        }
+        info.compressed_bytes = (self.consumed_bits() - start).div_ceil(8) as usize;
+        self.block_index += 1;
+        Ok(info)
+    }
+    fn read_non_compressed_block(
+        &mut self,
+        info: &mut BlockInfo,
+        data: Option<&mut Vec<u8>>,
+    ) -> io::Result<()> {
+        self.bit_reader.reset();
+        let mut buf = [0; 2];
+        self.bit_reader.as_inner_mut().read_exact(&mut buf)?;
+        let len = u16::from_le_bytes(buf);
+        self.bit_reader.as_inner_mut().read_exact(&mut buf)?;
+        let nlen = u16::from_le_bytes(buf);
+        if !len != nlen {
+            return Err(invalid_data_error!(
+                "LEN={} is not the one's complement of NLEN={}",
+                len,
+                nlen
+            ));
+        }
+        let mut block_data = self.bit_reader.as_inner_mut().take(len.into());
+        let used = match data {
+            Some(data) => io::copy(&mut block_data, data)?,
+            None => io::copy(&mut block_data, &mut io::sink())?,
+        };
+        if used != u64::from(len) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "The reader has incorrect length: expected {}, read {}",
+                    len, used
+                ),
+            ));
+        }
+        self.non_compressed_bytes += 4 + used;
+        self.uncompressed_total += used;
+        info.uncompressed_bytes = len as usize;
+        info.literal_count = len as usize;
+        Ok(())
+    }
+    fn read_compressed_block<H>(
+        &mut self,
+        huffman: &H,
+        info: &mut BlockInfo,
+        mut codes: Option<&mut Vec<lz77::Code>>,
+    ) -> io::Result<()>
+    where
+        H: HuffmanCodec,
+    {
+        let symbol_decoder = huffman.load(&mut self.bit_reader)?;
+        loop {
+            let s = symbol_decoder.decode_unchecked(&mut self.bit_reader);
+            self.bit_reader.check_last_error()?;
+            match s {
+                symbol::Symbol::Literal(b) => {
+                    if let Some(ref mut codes) = codes {
+                        codes.push(lz77::Code::Literal(b));
+                    }
+                    info.literal_count += 1;
+                    info.uncompressed_bytes += 1;
+                    self.uncompressed_total += 1;
+                }
+                symbol::Symbol::Share { length, distance } => {
+                    let window_size = u64::from(lz77::MAX_DISTANCE);
+                    if window_size.min(self.uncompressed_total) < u64::from(distance) {
+                        return Err(invalid_data_error!(
+                            "Too long backword reference: decoded={}, distance={}",
+                            self.uncompressed_total,
+                            distance
+                        ));
+                    }
+                    if let Some(ref mut codes) = codes {
+                        codes.push(lz77::Code::Pointer {
+                            length,
+                            backward_distance: distance,
+                        });
+                    }
+                    info.back_ref_count += 1;
+                    info.uncompressed_bytes += length as usize;
+                    self.uncompressed_total += u64::from(length);
+                }
+                symbol::Symbol::EndOfBlock => {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+impl<R> Iterator for Inspector<R>
+where
+    R: Read,
+{
+    type Item = io::Result<BlockInfo>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.eos {
+            return None;
+        }
+        let result = self.read_block();
+        if result.is_err() {
+            self.eos = true;
+        }
+        Some(result)
+    }
+}
+
+/// A block of a DEFLATE stream yielded by `BlockIterator`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawBlock {
+    /// The information about the block.
+    pub info: BlockInfo,
+
+    /// Whether the block is the final block of the stream (i.e., the `BFINAL` bit is set).
+    pub is_final: bool,
+
+    /// The position of the encoded block (including the block header) in the stream in bits.
+    ///
+    /// Blocks are not byte aligned, so this is given in bits from the start of the stream.
+    pub bit_range: Range<u64>,
+
+    /// The bytes of a non-compressed block (`None` for compressed blocks).
+    pub stored_data: Option<Vec<u8>>,
+
+    /// The LZ77 codes of a compressed block, excluding the end-of-block code (`None` for non-compressed blocks).
+    pub codes: Option<Vec<lz77::Code>>,
+}
+
+/// An iterator which reads a DEFLATE stream and yields the contents of each block.
+///
+/// Unlike `Inspector`, this yields the stored bytes of the non-compressed blocks
+/// and the LZ77 codes of the compressed blocks, so the blocks can be re-encoded
+/// without decompressing the stream.
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::deflate::{BlockIterator, BlockType, Encoder, EncodeOptions};
+/// use libflate::lz77::Code;
+///
+/// let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::new().fixed_huffman_codes()).unwrap();
+/// encoder.write_all(b"Hello Hello").unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let blocks = BlockIterator::new(&encoded_data[..]).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(blocks.len(), 1);
+/// assert_eq!(blocks[0].info.kind, BlockType::Fixed);
+/// assert!(blocks[0].is_final);
+/// let codes = blocks[0].codes.as_ref().unwrap();
+/// assert_eq!(codes.last(), Some(&Code::Pointer { length: 5, backward_distance: 6 }));
+/// ```
+#[derive(Debug)]
+pub struct BlockIterator<R> {
+    inspector: Inspector<R>,
+}
+impl<R> BlockIterator<R>
+where
+    R: Read,
+{
+    /// Makes a new iterator instance.
+    pub fn new(inner: R) -> Self {
+        BlockIterator {
+            inspector: Inspector::new(inner),
+        }
+    }
+
+    fn read_block(&mut self) -> io::Result<RawBlock> {
+        let start = self.inspector.consumed_bits();
+        let mut data = Vec::new();
+        let mut codes = Vec::new();
+        let info = self
+            .inspector
+            .read_block_with(Some(&mut data), Some(&mut codes))?;
+        let (stored_data, codes) = match info.kind {
+            BlockType::Raw => (Some(data), None),
+            BlockType::Fixed | BlockType::Dynamic => (None, Some(codes)),
+        };
+        Ok(RawBlock {
+            info,
+            is_final: self.inspector.eos,
+            bit_range: start..self.inspector.consumed_bits(),
+            stored_data,
+            codes,
+        })
+    }
+}
+impl<R> Iterator for BlockIterator<R>
+where
+    R: Read,
+{
+    type Item = io::Result<RawBlock>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.inspector.eos {
+            return None;
+        }
+        let result = self.read_block();
+        if result.is_err() {
+            self.inspector.eos = true;
+        }
+        Some(result)
+    }
+}
+
+/// Reads a DEFLATE stream from `reader` and writes the structure of its blocks to `writer`
+/// as a human-readable table.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate;
+///
+/// let encoded_data = deflate::compress(b"Hello World!");
+/// let mut table = Vec::new();
+/// deflate::inspect(&encoded_data[..], &mut table).unwrap();
+///
+/// let table = String::from_utf8(table).unwrap();
+/// assert_eq!(table.lines().count(), 2); // The header line and a block
+/// ```
+pub fn inspect<R, W>(reader: R, mut writer: W) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    writeln!(
+        writer,
+        "{:>5}  {:<7}  {:>10}  {:>12}  {:>10}  {:>10}",
+        "index", "kind", "compressed", "uncompressed", "literals", "back-refs"
+    )?;
+    for info in Inspector::new(reader) {
+        let info = info?;
+        writeln!(
+            writer,
+            "{:>5}  {:<7}  {:>10}  {:>12}  {:>10}  {:>10}",
+            info.index,
+            format!("{:?}", info.kind),
+            info.compressed_bytes,
+            info.uncompressed_bytes,
+            info.literal_count,
+            info.back_ref_count
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use deflate::{EncodeOptions, Encoder};
+
+    #[test]
+    fn inspector_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+
+        for (options, kind) in [
+            (EncodeOptions::new().no_compression(), BlockType::Raw),
+            (EncodeOptions::new().fixed_huffman_codes(), BlockType::Fixed),
+            (EncodeOptions::new(), BlockType::Dynamic),
+        ] {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(&plain).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+
+            let blocks = Inspector::new(&encoded[..])
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+            assert!(blocks.iter().any(|b| b.kind == kind));
+            assert_eq!(
+                blocks.iter().map(|b| b.uncompressed_bytes).sum::<usize>(),
+                plain.len()
+            );
+            let compressed = blocks.iter().map(|b| b.compressed_bytes).sum::<usize>();
+            assert!(compressed >= encoded.len() && compressed <= encoded.len() + blocks.len());
+        }
+    }
+
+    #[test]
+    fn block_iterator_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder =
+            Encoder::with_options(Vec::new(), EncodeOptions::new().block_size(100_000)).unwrap();
+        encoder.write_all(&plain[..200_000]).unwrap();
+        encoder
+            .write_stored_block(&plain[200_000..210_000], false)
+            .unwrap();
+        encoder.write_all(&plain[210_000..]).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let blocks = BlockIterator::new(&encoded[..])
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let infos = Inspector::new(&encoded[..])
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            blocks.iter().map(|b| &b.info).collect::<Vec<_>>(),
+            infos.iter().collect::<Vec<_>>()
+        );
+        assert!(blocks.iter().any(|b| b.info.kind == BlockType::Raw));
+        assert_eq!(blocks.iter().filter(|b| b.is_final).count(), 1);
+        assert!(blocks.last().unwrap().is_final);
+        assert_eq!(blocks[0].bit_range.start, 0);
+        for pair in blocks.windows(2) {
+            assert_eq!(pair[0].bit_range.end, pair[1].bit_range.start);
+        }
+        assert_eq!(
+            blocks.last().unwrap().bit_range.end.div_ceil(8),
+            encoded.len() as u64
+        );
+
+        // Reconstructs the data from the blocks
+        let mut decoded: Vec<u8> = Vec::new();
+        for block in &blocks {
+            match (&block.stored_data, &block.codes) {
+                (Some(data), None) => decoded.extend_from_slice(data),
+                (None, Some(codes)) => {
+                    for code in codes {
+                        match *code {
+                            lz77::Code::Literal(b) => decoded.push(b),
+                            lz77::Code::Pointer {
+                                length,
+                                backward_distance,
+                            } => {
+                                let start = decoded.len() - usize::from(backward_distance);
+                                for i in 0..usize::from(length) {
+                                    let b = decoded[start + i];
+                                    decoded.push(b);
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => panic!(),
+            }
+        }
+        assert_eq!(decoded, plain);
+    }
+}
@@ -19,24 +19,69 @@
 //!
 //! assert_eq!(decoded_data, b"Hello World!");
 //! ```
+pub use self::concat::ConcatDecoder;
+pub use self::decode::decompress;
+#[cfg(feature = "tokio")]
+pub use self::decode::AsyncDecoder;
+pub use self::decode::DecodeOptions;
 pub use self::decode::Decoder;
+pub use self::decode::DecoderStats;
+pub use self::encode::compress;
+pub use self::encode::compressed_size_bound;
+#[cfg(feature = "rayon")]
+pub use self::encode::par_compress;
+pub use self::encode::CompressionLevel;
 pub use self::encode::EncodeOptions;
 pub use self::encode::Encoder;
+pub use self::encode::EncoderState;
+pub use self::encode::EncoderStats;
+pub use self::encode::OptionsError;
 pub use self::encode::DEFAULT_BLOCK_SIZE;
+pub use self::encode::MAX_COMPRESSION_LEVEL;
+pub use self::inspect::inspect;
+pub use self::inspect::BlockInfo;
+pub use self::inspect::BlockIterator;
+pub use self::inspect::Inspector;
+pub use self::inspect::RawBlock;
+pub use self::progress::ProgressDecoder;
+pub use self::progress::ProgressEncoder;
+pub use self::split::SplitPoint;
+#[cfg(feature = "std")]
+pub use self::split::SplitStreamDecoder;
+pub use self::split::SplitStreamEncoder;
+pub use self::symbol::build_length_limited_codes;
+pub use self::validate::ValidationReport;
+pub use self::validate::Validator;
 
+mod concat;
+#[cfg(feature = "serde_json")]
+pub mod debug;
 mod decode;
 mod encode;
+#[cfg(feature = "std")]
+pub mod entropy;
+mod inspect;
+mod progress;
+mod split;
 pub(crate) mod symbol;
+mod validate;
 
-#[derive(Debug, Clone, Copy)]
-enum BlockType {
+/// The type of a DEFLATE block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockType {
+    /// A block which stores the data without compression.
     Raw = 0b00,
+
+    /// A block which is compressed with the fixed huffman codes.
     Fixed = 0b01,
+
+    /// A block which is compressed with the dynamic huffman codes.
     Dynamic = 0b10,
 }
 
 #[cfg(test)]
 mod test {
+    use std::cmp;
     use std::io::{Read, Write};
 
     use super::*;
@@ -59,4 +104,822 @@ mod test {
 
         assert_eq!(buffer, plain);
     }
+
+    #[test]
+    fn compression_levels_work() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+
+        let mut sizes = Vec::new();
+        for level in 0..=MAX_COMPRESSION_LEVEL {
+            let options = EncodeOptions::new().compression_level(level);
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(&plain[..]).expect("encode");
+            let encoded = encoder.finish().into_result().unwrap();
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&encoded[..]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert_eq!(buffer, plain);
+            sizes.push(encoded.len());
+        }
+        assert!(sizes[0] > plain.len());
+        assert!(sizes[1] < sizes[0]);
+        assert!(sizes[9] <= sizes[1]);
+    }
+
+    #[test]
+    fn default_options_equal_level_6() {
+        let zeros = vec![0; 1 << 20];
+        let text = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        for plain in [&zeros[..], &text[..]] {
+            let mut encoder = Encoder::new(Vec::new());
+            encoder.write_all(plain).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+
+            let options = EncodeOptions::new().compression_level(CompressionLevel::Default.to_u8());
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(plain).unwrap();
+            assert_eq!(encoder.finish().into_result().unwrap(), encoded);
+        }
+
+        // A long run must be encoded by the matches of the maximum length
+        // (1 MiB of zeros was encoded into 1,044 bytes before the compression levels were introduced).
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(&zeros).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert!(encoded.len() <= 1_050, "{}", encoded.len());
+        assert_eq!(decompress(&encoded).unwrap(), zeros);
+    }
+
+    #[test]
+    fn forced_blocks_work() {
+        let plain = (0..100_000).map(|i| (i % 13) as u8).collect::<Vec<_>>();
+        for options in [EncodeOptions::new(), EncodeOptions::new().no_compression()] {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(&plain[..10]).unwrap();
+            encoder
+                .write_stored_block(&plain[10..80_000], false)
+                .unwrap();
+            encoder
+                .write_fixed_block(&plain[80_000..90_000], false)
+                .unwrap();
+            encoder
+                .write_dynamic_block(&plain[90_000..99_000], false)
+                .unwrap();
+            encoder.write_stored_block(&plain[99_000..], true).unwrap();
+            assert!(encoder.write_all(b"foo").is_err());
+            let (encoded, stats) = encoder.finish_with_stats().into_result().unwrap();
+            assert_eq!(stats.input_bytes, plain.len() as u64);
+
+            let kinds = Inspector::new(&encoded[..])
+                .map(|b| b.unwrap().kind)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                &kinds[1..],
+                [
+                    BlockType::Raw,
+                    BlockType::Raw,
+                    BlockType::Fixed,
+                    BlockType::Dynamic,
+                    BlockType::Raw
+                ]
+            );
+            assert_eq!(stats.block_count, kinds.len() as u64);
+
+            let mut decoded = Vec::new();
+            Decoder::new(&encoded[..])
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, plain);
+        }
+    }
+
+    #[test]
+    fn type_erased_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i % 100))
+            .collect::<String>()
+            .into_bytes();
+
+        let typed = EncodeOptions::new();
+        let mut encoder = Encoder::with_options(Vec::new(), typed).unwrap();
+        encoder.write_all(&plain[..]).expect("encode");
+        let expected = encoder.finish().into_result().unwrap();
+
+        let erased = EncodeOptions::new().type_erased();
+        let mut encoder = Encoder::with_options(Vec::new(), erased).unwrap();
+        encoder.write_all(&plain[..]).expect("encode");
+        let encoded = encoder.finish().into_result().unwrap();
+        assert_eq!(encoded, expected);
+
+        let mut buffer = Vec::new();
+        let mut decoder = Decoder::new(&encoded[..]);
+        decoder.read_to_end(&mut buffer).expect("decode");
+        assert_eq!(buffer, plain);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_compress_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        for options in [
+            EncodeOptions::new(),
+            EncodeOptions::new().fixed_huffman_codes(),
+            EncodeOptions::new().no_compression(),
+        ] {
+            let encoded = par_compress(&plain, options.block_size(10_000));
+            assert_eq!(decompress(&encoded).unwrap(), plain);
+        }
+        assert_eq!(
+            decompress(&par_compress(&[], EncodeOptions::new())).unwrap(),
+            b""
+        );
+    }
+
+    #[test]
+    fn lazy_match_works() {
+        let plain = include_bytes!("../../README.md")
+            .iter()
+            .chain(include_bytes!("encode.rs"))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut sizes = Vec::new();
+        for lazy_match in [false, true] {
+            let lz77 = lz77::DefaultLz77EncoderBuilder::new()
+                .lazy_match(lazy_match)
+                .build();
+            let mut encoder =
+                Encoder::with_options(Vec::new(), EncodeOptions::with_lz77(lz77)).unwrap();
+            encoder.write_all(&plain[..]).expect("encode");
+            let encoded = encoder.finish().into_result().unwrap();
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&encoded[..]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert_eq!(buffer, plain);
+            sizes.push(encoded.len());
+        }
+        assert!(sizes[1] < sizes[0]);
+    }
+
+    #[test]
+    fn sync_flush_works() {
+        for options in [
+            EncodeOptions::new(),
+            EncodeOptions::new().fixed_huffman_codes(),
+            EncodeOptions::new().no_compression(),
+        ] {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(b"Hello World!").expect("encode");
+            encoder.sync_flush().expect("sync flush");
+            let flushed = encoder.as_inner_ref().clone();
+            assert!(flushed.ends_with(&[0, 0, 0xFF, 0xFF]));
+
+            let mut buffer = [0; 12];
+            let mut decoder = Decoder::new(&flushed[..]);
+            decoder.read_exact(&mut buffer).expect("decode");
+            assert_eq!(&buffer, b"Hello World!");
+
+            encoder.write_all(b" Hello!").expect("encode");
+            encoder.full_flush().expect("full flush");
+            encoder.write_all(b" World!").expect("encode");
+            let encoded = encoder.finish().into_result().unwrap();
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&encoded[..]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert_eq!(buffer, b"Hello World! Hello! World!");
+        }
+    }
+
+    #[test]
+    fn full_flush_resets_lz77_works() {
+        let text = (0..4000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut x: u32 = 1;
+        let random = (0..64 * 1024)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        let cases = [
+            (EncodeOptions::new().type_erased(), &text, &text[..]),
+            (EncodeOptions::zopfli_lz77().type_erased(), &text, &text[..]),
+            (
+                EncodeOptions::suffix_array_lz77().type_erased(),
+                &text,
+                &text[..],
+            ),
+            (
+                EncodeOptions::new().type_erased(),
+                &random,
+                &random[random.len() - 200..],
+            ),
+            (
+                EncodeOptions::new().dictionary(&text).type_erased(),
+                &text,
+                &text[..],
+            ),
+        ];
+        for (options, before, after) in cases {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(before).unwrap();
+            encoder.full_flush().unwrap();
+            let offset = encoder.as_inner_ref().len();
+            encoder.write_all(after).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+
+            let mut decoded = Vec::new();
+            let mut decoder = Decoder::new(&encoded[offset..]);
+            decoder.read_to_end(&mut decoded).expect("decode");
+            assert_eq!(decoded, after);
+        }
+    }
+
+    #[test]
+    fn position_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder =
+            Encoder::with_options(Vec::new(), EncodeOptions::new().block_size(4096)).unwrap();
+        let mut index = Vec::new();
+        for chunk in plain.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+            encoder.full_flush().unwrap();
+            assert_eq!(
+                encoder.compressed_position(),
+                encoder.as_inner_ref().len() as u64
+            );
+            index.push((
+                encoder.uncompressed_position(),
+                encoder.compressed_position(),
+            ));
+        }
+        assert_eq!(index.last().unwrap().0, plain.len() as u64);
+        let encoded = encoder.finish().into_result().unwrap();
+
+        // Each flush point can be decoded independently of the preceding data
+        let (start, offset) = index[4];
+        let mut decoder = Decoder::new(&encoded[offset as usize..]);
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, &plain[start as usize..]);
+    }
+
+    #[test]
+    fn finish_with_stats_works() {
+        let plain = (0..50_000).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        for (options, no_compression) in [
+            (EncodeOptions::new(), false),
+            (EncodeOptions::new().fixed_huffman_codes(), false),
+            (EncodeOptions::new().no_compression(), true),
+        ] {
+            let mut encoder = Encoder::with_options(Vec::new(), options.block_size(8192)).unwrap();
+            encoder.write_all(&plain).expect("write");
+            let (encoded, stats) = encoder.finish_with_stats().into_result().expect("finish");
+            assert_eq!(stats.input_bytes, plain.len() as u64);
+            assert_eq!(stats.output_bytes, encoded.len() as u64);
+            assert!(stats.block_count > 1);
+            if no_compression {
+                assert_eq!(stats.literal_count, 0);
+                assert_eq!(stats.back_reference_count, 0);
+            } else {
+                assert!(stats.literal_count > 0);
+                assert!(stats.back_reference_count > 0);
+                assert_eq!(stats.lz77.literal_count, stats.literal_count);
+                assert_eq!(stats.lz77.match_count, stats.back_reference_count);
+                assert!(stats.lz77.shortest_match >= 3);
+                assert!(stats.lz77.longest_match <= lz77::MAX_LENGTH);
+            }
+        }
+    }
+
+    #[test]
+    fn reset_works() {
+        let plain = (0..50_000).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        for options in [
+            EncodeOptions::new(),
+            EncodeOptions::new().fixed_huffman_codes(),
+            EncodeOptions::new().no_compression(),
+        ] {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(b"Hello World!").expect("write");
+            encoder.reset(Vec::new());
+            encoder.write_all(&plain).expect("write");
+            let encoded = encoder.finish().into_result().expect("finish");
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&encoded[..]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert_eq!(buffer, plain);
+        }
+    }
+
+    #[test]
+    fn raw_parts_works() {
+        let plain = (0..50_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        for options in [
+            EncodeOptions::new().block_size(4096),
+            EncodeOptions::new().fixed_huffman_codes(),
+            EncodeOptions::new().no_compression(),
+        ] {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            for chunk in plain.chunks(1000) {
+                encoder.write_all(chunk).expect("write");
+                let (inner, state) = encoder.into_raw_parts();
+                encoder = Encoder::from_raw_parts(inner, state);
+            }
+            encoder.write_stored_block(&[], true).expect("write");
+            let (encoded, mut state) = encoder.into_raw_parts();
+            assert_eq!(decompress(&encoded).expect("decode"), plain);
+
+            state.reset();
+            let mut encoder = Encoder::from_raw_parts(Vec::new(), state);
+            encoder.write_all(&plain[..1000]).expect("write");
+            let encoded = encoder.finish().into_result().expect("finish");
+            assert_eq!(decompress(&encoded).expect("decode"), &plain[..1000]);
+        }
+    }
+
+    #[test]
+    fn validate_options_works() {
+        use gzip;
+        use std::io;
+        use zlib;
+
+        assert_eq!(EncodeOptions::new().block_size(1).validate(), Ok(()));
+        assert_eq!(EncodeOptions::new().block_size(1 << 31).validate(), Ok(()));
+        assert_eq!(
+            EncodeOptions::new().block_size(0).validate(),
+            Err(OptionsError::ZeroBlockSize)
+        );
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(
+            EncodeOptions::new().block_size((1 << 31) + 1).validate(),
+            Err(OptionsError::BlockSizeTooLarge((1 << 31) + 1))
+        );
+        for (window_size, valid) in [(255, false), (256, true), (1000, true), (32_768, true)] {
+            let lz77 = lz77::DefaultLz77EncoderBuilder::new()
+                .window_size(window_size)
+                .build();
+            let result = EncodeOptions::with_lz77(lz77).validate();
+            if valid {
+                assert_eq!(result, Ok(()));
+            } else {
+                assert_eq!(result, Err(OptionsError::InvalidWindowSize(window_size)));
+            }
+        }
+        assert_eq!(
+            EncodeOptions::new()
+                .no_compression()
+                .block_size(0)
+                .validate(),
+            Err(OptionsError::ZeroBlockSize)
+        );
+
+        let options = EncodeOptions::new().block_size(0);
+        assert_eq!(
+            Encoder::with_options(Vec::new(), options).err(),
+            Some(OptionsError::ZeroBlockSize)
+        );
+        let options = gzip::EncodeOptions::new().block_size(0);
+        let e = gzip::Encoder::with_options(Vec::new(), options).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        let options = zlib::EncodeOptions::new().block_size(0);
+        let e = zlib::Encoder::with_options(Vec::new(), options).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn clone_works() {
+        let plain = (0..50_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let (head, tail) = plain.split_at(20_000);
+        for options in [
+            EncodeOptions::new(),
+            EncodeOptions::new().fixed_huffman_codes(),
+            EncodeOptions::new().no_compression(),
+        ] {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            for chunk in head.chunks(1000) {
+                encoder.write_all(chunk).expect("write");
+            }
+
+            let mut forked = encoder.clone();
+            forked.write_all(b"forked").expect("write");
+            let forked = forked.finish().into_result().expect("finish");
+            let mut expected = head.to_vec();
+            expected.extend_from_slice(b"forked");
+            assert_eq!(decompress(&forked).expect("decode"), expected);
+
+            let mut cloned = encoder.clone();
+            for chunk in tail.chunks(1000) {
+                encoder.write_all(chunk).expect("write");
+                cloned.write_all(chunk).expect("write");
+            }
+            let encoded = encoder.finish().into_result().expect("finish");
+            assert_eq!(cloned.finish().into_result().expect("finish"), encoded);
+            assert_eq!(decompress(&encoded).expect("decode"), plain);
+        }
+    }
+
+    #[test]
+    fn encode_options_eq_works() {
+        use std::collections::HashMap;
+
+        assert_eq!(EncodeOptions::new(), EncodeOptions::new());
+        assert_ne!(
+            EncodeOptions::new(),
+            EncodeOptions::new().fixed_huffman_codes()
+        );
+        assert_ne!(
+            EncodeOptions::new(),
+            EncodeOptions::new().compression_level(1)
+        );
+        assert_eq!(
+            EncodeOptions::zopfli_lz77(),
+            EncodeOptions::zopfli_lz77().clone()
+        );
+
+        let mut cache = HashMap::new();
+        cache.insert(EncodeOptions::new(), "default");
+        cache.insert(EncodeOptions::new().block_size(4096), "small");
+        assert_eq!(cache.get(&EncodeOptions::new()), Some(&"default"));
+        assert_eq!(
+            cache.get(&EncodeOptions::new().block_size(4096)),
+            Some(&"small")
+        );
+        assert_eq!(cache.get(&EncodeOptions::new().no_compression()), None);
+    }
+
+    #[test]
+    fn progress_works() {
+        let plain = (0..50_000).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        let mut encoded = Vec::new();
+        let mut progress = Vec::new();
+        {
+            let mut encoder =
+                Encoder::with_progress(&mut encoded, EncodeOptions::new(), |n| progress.push(n))
+                    .unwrap();
+            for chunk in plain.chunks(1000) {
+                encoder.write_all(chunk).expect("write");
+            }
+            assert_eq!(encoder.written_bytes(), plain.len() as u64);
+            encoder.finish().into_result().expect("finish");
+        }
+        assert_eq!(progress.len(), 50);
+        assert_eq!(progress.last(), Some(&(plain.len() as u64)));
+
+        let mut last = 0;
+        let mut decoded = Vec::new();
+        let mut decoder = ProgressDecoder::new(Decoder::new(&encoded[..]), |n| {
+            assert!(n >= last);
+            last = n;
+        });
+        decoder.read_to_end(&mut decoded).expect("decode");
+        assert_eq!(decoder.read_bytes(), plain.len() as u64);
+        drop(decoder);
+        assert_eq!(last, plain.len() as u64);
+        assert_eq!(decoded, plain);
+    }
+
+    #[test]
+    fn block_boundary_alignment_works() {
+        let plain = (0..50_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        for options in [
+            EncodeOptions::new(),
+            EncodeOptions::new().fixed_huffman_codes(),
+            EncodeOptions::new().no_compression(),
+        ] {
+            let options = options.block_boundary_alignment(4096);
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            for chunk in plain.chunks(1000) {
+                encoder.write_all(chunk).expect("write");
+            }
+            let encoded = encoder.finish().into_result().expect("finish");
+            assert_eq!(decompress(&encoded).expect("decode"), plain);
+
+            let mut offset = 0;
+            let mut sync_offsets = Vec::new();
+            for block in Inspector::new(&encoded[..]) {
+                let block = block.expect("inspect");
+                if block.kind == BlockType::Raw && block.uncompressed_bytes == 0 {
+                    sync_offsets.push(offset);
+                }
+                offset += block.uncompressed_bytes;
+            }
+            let expected = (1..=plain.len() / 4096)
+                .map(|i| i * 4096)
+                .collect::<Vec<_>>();
+            assert_eq!(sync_offsets, expected);
+        }
+    }
+
+    #[test]
+    fn block_boundary_alignment_must_be_power_of_two() {
+        for align in [0, 1000] {
+            let options = EncodeOptions::new().block_boundary_alignment(align);
+            assert_eq!(
+                Encoder::with_options(Vec::new(), options).err(),
+                Some(OptionsError::InvalidBlockBoundaryAlignment(align))
+            );
+        }
+        let options = EncodeOptions::new().block_boundary_alignment(1024);
+        assert!(Encoder::with_options(Vec::new(), options).is_ok());
+    }
+
+    #[test]
+    fn compressed_size_bound_works() {
+        assert_eq!(compressed_size_bound(0), 8);
+        assert_eq!(compressed_size_bound(usize::MAX), usize::MAX);
+
+        let mut x: u32 = 1;
+        let random = (0..0x30000)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        // Bytes greater than 143 have the 9-bit literal codes in the fixed Huffman codes
+        let high = random.iter().map(|b| b | 0x80).collect::<Vec<_>>();
+        for size in [0, 1, 100, 1000, 4095, 0xFFFF, 0x10000, 0x30000] {
+            for plain in [&random[..size], &high[..size], &vec![0; size][..]] {
+                for options in [
+                    EncodeOptions::new(),
+                    EncodeOptions::new().fixed_huffman_codes(),
+                    EncodeOptions::new().no_compression(),
+                    EncodeOptions::new().entropy_threshold(f64::INFINITY),
+                    EncodeOptions::new().block_size(4096),
+                    EncodeOptions::new().block_size(4096).block_size_auto(),
+                ] {
+                    for chunk_size in [size, 300] {
+                        let mut encoder =
+                            Encoder::with_options(Vec::new(), options.clone()).unwrap();
+                        for chunk in plain.chunks(cmp::max(chunk_size, 1)) {
+                            encoder.write_all(chunk).expect("write");
+                        }
+                        let encoded = encoder.finish().into_result().expect("finish");
+                        assert!(encoded.len() <= compressed_size_bound(size));
+                    }
+                }
+            }
+            assert!(::zlib::compress(&random[..size]).len() <= ::zlib::compressed_size_bound(size));
+            assert!(::gzip::compress(&random[..size]).len() <= ::gzip::compressed_size_bound(size));
+        }
+    }
+
+    #[test]
+    fn cheaper_huffman_codes_are_chosen() {
+        let encode = |options: EncodeOptions, plain: &[u8]| {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(plain).expect("write");
+            encoder.finish().into_result().expect("finish")
+        };
+        let text = include_bytes!("../../README.md");
+        for plain in [&b"Hello World!"[..], &text[..]] {
+            let default = encode(EncodeOptions::new(), plain);
+            let fixed = encode(EncodeOptions::new().fixed_huffman_codes(), plain);
+            assert!(default.len() <= fixed.len());
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&default[..]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert_eq!(buffer, plain);
+        }
+
+        // Fixed huffman codes are chosen because the data is too small to amortize a dynamic header
+        assert_eq!(
+            encode(EncodeOptions::new(), b"Hello World!"),
+            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]
+        );
+        assert!(
+            encode(EncodeOptions::new(), text).len()
+                < encode(EncodeOptions::new().fixed_huffman_codes(), text).len()
+        );
+    }
+
+    #[test]
+    fn block_split_works() {
+        let lines = (0..10)
+            .map(|i| format!("{}\n", i.to_string().repeat(500)))
+            .collect::<Vec<_>>();
+        let plain = lines.concat().into_bytes();
+        for options in [EncodeOptions::new(), EncodeOptions::new().no_compression()] {
+            let options = options.block_split(|buf: &[u8]| buf.ends_with(b"\n"));
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            for line in &lines {
+                let (head, tail) = line.as_bytes().split_at(line.len() / 2);
+                encoder.write_all(head).expect("write");
+                encoder.write_all(tail).expect("write");
+            }
+            let (encoded, stats) = encoder.finish_with_stats().into_result().expect("finish");
+            assert_eq!(stats.block_count, lines.len() as u64 + 1);
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&encoded[..]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert_eq!(buffer, plain);
+        }
+
+        // The callback receives the accumulated block and can veto `block_size`
+        let plain = (0..100_000).map(|i| (i % 11) as u8).collect::<Vec<_>>();
+        for (options, split_size, block_count) in [
+            // A block per 30000 bytes and the final block
+            (EncodeOptions::new(), 30_000, 4),
+            (EncodeOptions::new().no_compression(), 30_000, 4),
+            // A single block, written as two non-compressed blocks (at most 65535 bytes each)
+            (EncodeOptions::new().no_compression(), usize::MAX, 2),
+        ] {
+            let options = options
+                .block_size(4096)
+                .block_split(move |buf: &[u8]| buf.len() >= split_size);
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            for chunk in plain.chunks(1000) {
+                encoder.write_all(chunk).expect("write");
+            }
+            let (encoded, stats) = encoder.finish_with_stats().into_result().expect("finish");
+            assert_eq!(stats.block_count, block_count);
+            assert_eq!(decompress(&encoded).unwrap(), plain);
+        }
+    }
+
+    #[test]
+    fn block_size_auto_works() {
+        let mut x: u32 = 1;
+        let random = (0..200_000)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        let repetitive = (0..1_000_000).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        for (plain, grows) in [(random, false), (repetitive, true)] {
+            let encode = |options: EncodeOptions| {
+                let options = options
+                    .block_size(64 * 1024)
+                    .entropy_threshold(f64::INFINITY);
+                let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+                for chunk in plain.chunks(1000) {
+                    encoder.write_all(chunk).expect("write");
+                }
+                encoder.finish_with_stats().into_result().expect("finish")
+            };
+            let (_, fixed_stats) = encode(EncodeOptions::new());
+            let (encoded, stats) = encode(EncodeOptions::new().block_size_auto());
+            if grows {
+                assert!(stats.block_count < fixed_stats.block_count);
+            } else {
+                assert!(stats.block_count > fixed_stats.block_count);
+            }
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&encoded[..]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert_eq!(buffer, plain);
+        }
+    }
+
+    #[test]
+    fn window_bits_works() {
+        let plain = (0..10_000)
+            .map(|i| (i % 1500 / 8) as u8)
+            .collect::<Vec<_>>();
+        let lz77 = lz77::DefaultLz77EncoderBuilder::new()
+            .window_bits(11)
+            .build();
+        let mut encoder =
+            Encoder::with_options(Vec::new(), EncodeOptions::with_lz77(lz77)).unwrap();
+        encoder.write_all(&plain).expect("write");
+        let encoded = encoder.finish().into_result().expect("finish");
+
+        let mut buffer = Vec::new();
+        let options = DecodeOptions::new().window_bits(11);
+        let mut decoder = Decoder::with_options(&encoded[..], options);
+        decoder.read_to_end(&mut buffer).expect("decode");
+        assert_eq!(buffer, plain);
+
+        let options = DecodeOptions::new().window_bits(10);
+        let mut decoder = Decoder::with_options(&encoded[..], options);
+        assert!(decoder.read_to_end(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn huffman_only_works() {
+        // Skewed distribution without repeated strings
+        let mut x: u32 = 1;
+        let plain = (0..10_000)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                ((x >> 16) % 256).leading_zeros() as u8
+            })
+            .collect::<Vec<_>>();
+        let options = EncodeOptions::with_lz77(lz77::HuffmanOnlyLz77Encoder::new());
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        encoder.write_all(&plain).expect("write");
+        let (encoded, stats) = encoder.finish_with_stats().into_result().expect("finish");
+        assert_eq!(stats.back_reference_count, 0);
+        assert!(encoded.len() < plain.len() / 2);
+
+        let mut buffer = Vec::new();
+        let mut decoder = Decoder::new(&encoded[..]);
+        decoder.read_to_end(&mut buffer).expect("decode");
+        assert_eq!(buffer, plain);
+    }
+
+    #[test]
+    fn store_if_larger_works() {
+        let mut x: u32 = 1;
+        let random = (0..100_000)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        let compressible = (0..100_000).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        for options in [
+            EncodeOptions::new().store_if_larger(),
+            EncodeOptions::new().fixed_huffman_codes().store_if_larger(),
+        ] {
+            for plain in &[&random, &compressible] {
+                let mut encoder = Encoder::with_options(Vec::new(), options.clone()).unwrap();
+                for chunk in plain.chunks(1000) {
+                    encoder.write_all(chunk).expect("write");
+                }
+                let encoded = encoder.finish().into_result().expect("finish");
+                // Two non-compressed blocks (5 bytes of overhead for each)
+                assert!(encoded.len() <= plain.len() + 10);
+                if *plain == &compressible {
+                    assert!(encoded.len() < plain.len() / 10);
+                }
+
+                let mut buffer = Vec::new();
+                let mut decoder = Decoder::new(&encoded[..]);
+                decoder.read_to_end(&mut buffer).expect("decode");
+                assert_eq!(&buffer, *plain);
+            }
+        }
+    }
+
+    #[test]
+    fn entropy_threshold_works() {
+        let mut x: u32 = 1;
+        let random = (0..50_000)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        // Refers to the tail of the random data
+        let mut compressible = random[40_000..].to_vec();
+        compressible.extend_from_slice(&[0; 30_000]);
+
+        let mut block_counts = Vec::new();
+        for threshold in &[entropy::DEFAULT_ENTROPY_THRESHOLD, f64::INFINITY] {
+            let options = EncodeOptions::new().entropy_threshold(*threshold);
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(&random).expect("write");
+            encoder.write_all(&compressible).expect("write");
+            let (encoded, stats) = encoder.finish_with_stats().into_result().expect("finish");
+            assert!(encoded.len() < random.len() + 1000);
+            // Both the zeros and the copy of the random data are compressed
+            assert!(stats.lz77.total_match_length > 35_000);
+            block_counts.push(stats.block_count);
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&encoded[..]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert_eq!(buffer, [&random[..], &compressible[..]].concat());
+        }
+        // The random data is written as non-compressed blocks only if the threshold is exceeded
+        assert!(block_counts[0] > block_counts[1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_large_compression_level_panics() {
+        let _ = EncodeOptions::new().compression_level(MAX_COMPRESSION_LEVEL + 1);
+    }
 }
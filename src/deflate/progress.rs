@@ -0,0 +1,191 @@
+use core::fmt;
+use io;
+
+use super::{Decoder, Encoder};
+use finish::{Complete, Finish};
+use lz77;
+
+/// A DEFLATE encoder which reports the progress of the encoding.
+///
+/// The callback is called after each `write` call with the total number of
+/// the uncompressed bytes written so far.
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::deflate::{EncodeOptions, Encoder};
+///
+/// let mut progress = Vec::new();
+/// let mut encoder =
+///     Encoder::with_progress(Vec::new(), EncodeOptions::new(), |n| progress.push(n)).unwrap();
+/// encoder.write_all(b"Hello ").unwrap();
+/// encoder.write_all(b"World!").unwrap();
+/// encoder.finish().into_result().unwrap();
+/// assert_eq!(progress, [6, 12]);
+/// ```
+pub struct ProgressEncoder<W, E, F> {
+    encoder: Encoder<W, E>,
+    callback: F,
+    written: u64,
+}
+impl<W, E, F> ProgressEncoder<W, E, F>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode,
+    F: FnMut(u64),
+{
+    /// Makes a new encoder instance which wraps `encoder`.
+    pub fn new(encoder: Encoder<W, E>, callback: F) -> Self {
+        ProgressEncoder {
+            encoder,
+            callback,
+            written: 0,
+        }
+    }
+
+    /// Returns the total number of the uncompressed bytes written so far.
+    pub fn written_bytes(&self) -> u64 {
+        self.written
+    }
+
+    /// Flushes internal buffer and returns the inner stream.
+    ///
+    /// See `Encoder::finish` for more details.
+    pub fn finish(self) -> Finish<W, io::Error> {
+        self.encoder.finish()
+    }
+
+    /// Returns the immutable reference to the wrapped encoder.
+    pub fn as_encoder_ref(&self) -> &Encoder<W, E> {
+        &self.encoder
+    }
+
+    /// Returns the mutable reference to the wrapped encoder.
+    pub fn as_encoder_mut(&mut self) -> &mut Encoder<W, E> {
+        &mut self.encoder
+    }
+
+    /// Unwraps this `ProgressEncoder`, returning the wrapped encoder.
+    pub fn into_encoder(self) -> Encoder<W, E> {
+        self.encoder
+    }
+}
+impl<W, E, F> fmt::Debug for ProgressEncoder<W, E, F>
+where
+    W: fmt::Debug,
+    E: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProgressEncoder")
+            .field("encoder", &self.encoder)
+            .field("written", &self.written)
+            .finish()
+    }
+}
+impl<W, E, F> io::Write for ProgressEncoder<W, E, F>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode,
+    F: FnMut(u64),
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let size = self.encoder.write(buf)?;
+        self.written += size as u64;
+        (self.callback)(self.written);
+        Ok(size)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+impl<W, E, F> Complete for ProgressEncoder<W, E, F>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode,
+    F: FnMut(u64),
+{
+    fn complete(self) -> io::Result<()> {
+        self.finish().into_result().map(|_| ())
+    }
+}
+
+/// A DEFLATE decoder which reports the progress of the decoding.
+///
+/// The callback is called after each `read` call with the total number of
+/// the decompressed bytes read so far.
+///
+/// # Examples
+/// ```
+/// use std::io::Read;
+/// use libflate::deflate::{self, Decoder, ProgressDecoder};
+///
+/// let encoded_data = deflate::compress(b"Hello World!");
+/// let mut last = 0;
+/// let mut decoder = ProgressDecoder::new(Decoder::new(&encoded_data[..]), |n| last = n);
+/// let mut buf = Vec::new();
+/// decoder.read_to_end(&mut buf).unwrap();
+/// drop(decoder);
+/// assert_eq!(last, 12);
+/// ```
+pub struct ProgressDecoder<R, F> {
+    decoder: Decoder<R>,
+    callback: F,
+    read: u64,
+}
+impl<R, F> ProgressDecoder<R, F>
+where
+    R: io::Read,
+    F: FnMut(u64),
+{
+    /// Makes a new decoder instance which wraps `decoder`.
+    pub fn new(decoder: Decoder<R>, callback: F) -> Self {
+        ProgressDecoder {
+            decoder,
+            callback,
+            read: 0,
+        }
+    }
+
+    /// Returns the total number of the decompressed bytes read so far.
+    pub fn read_bytes(&self) -> u64 {
+        self.read
+    }
+
+    /// Returns the immutable reference to the wrapped decoder.
+    pub fn as_decoder_ref(&self) -> &Decoder<R> {
+        &self.decoder
+    }
+
+    /// Returns the mutable reference to the wrapped decoder.
+    pub fn as_decoder_mut(&mut self) -> &mut Decoder<R> {
+        &mut self.decoder
+    }
+
+    /// Unwraps this `ProgressDecoder`, returning the wrapped decoder.
+    pub fn into_decoder(self) -> Decoder<R> {
+        self.decoder
+    }
+}
+impl<R, F> fmt::Debug for ProgressDecoder<R, F>
+where
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProgressDecoder")
+            .field("decoder", &self.decoder)
+            .field("read", &self.read)
+            .finish()
+    }
+}
+impl<R, F> io::Read for ProgressDecoder<R, F>
+where
+    R: io::Read,
+    F: FnMut(u64),
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.decoder.read(buf)?;
+        self.read += size as u64;
+        (self.callback)(self.read);
+        Ok(size)
+    }
+}
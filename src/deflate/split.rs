@@ -0,0 +1,443 @@
+use alloc::vec::Vec;
+use core::cmp;
+use io;
+
+#[cfg(feature = "std")]
+use super::Decoder;
+use super::{EncodeOptions, Encoder, OptionsError};
+use finish::{Complete, Finish};
+use lz77;
+
+/// An empty final block with fixed Huffman codes (`BFINAL=1`, `BTYPE=01`, end-of-block).
+#[cfg(feature = "std")]
+const EMPTY_FINAL_BLOCK: [u8; 2] = [0x03, 0x00];
+
+/// The start position of a segment of a stream encoded by `SplitStreamEncoder`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SplitPoint {
+    /// The offset of the first uncompressed byte of the segment.
+    pub uncompressed_offset: u64,
+
+    /// The offset of the first compressed byte of the segment in the DEFLATE stream.
+    pub compressed_offset: u64,
+}
+
+/// A DEFLATE encoder which splits the stream into independently decodable segments.
+///
+/// Whenever `segment_size` uncompressed bytes have been written to the current segment,
+/// the next write starts a new segment by `Encoder::full_flush`,
+/// so no block of a segment refers to the data of the preceding segments.
+/// The start positions of the segments are recorded as `SplitPoint`s,
+/// which allow `SplitStreamDecoder` to decode the segments in parallel.
+///
+/// The output is a single DEFLATE stream which can also be decoded by any decoder.
+/// The compression ratio is lower than that of `Encoder` because the LZ77 history is discarded at each split point.
+/// Note that a preset dictionary (`EncodeOptions::dictionary`) makes the first segment
+/// depend on the dictionary.
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::deflate::{self, SplitStreamEncoder};
+///
+/// let mut encoder = SplitStreamEncoder::new(Vec::new(), 6);
+/// encoder.write_all(b"Hello World!").unwrap();
+/// let (encoded_data, points) = encoder.finish().into_result().unwrap();
+///
+/// assert_eq!(points.len(), 2);
+/// assert_eq!(points[1].uncompressed_offset, 6);
+/// let second = &encoded_data[points[1].compressed_offset as usize..];
+/// assert_eq!(deflate::decompress(second).unwrap(), b"World!");
+/// ```
+#[derive(Debug)]
+pub struct SplitStreamEncoder<W, E = lz77::DefaultLz77Encoder> {
+    encoder: Encoder<W, E>,
+    segment_size: u64,
+    segment_written: u64,
+    points: Vec<SplitPoint>,
+}
+impl<W> SplitStreamEncoder<W, lz77::DefaultLz77Encoder>
+where
+    W: io::Write,
+{
+    /// Makes a new encoder instance which starts a new segment every `segment_size` uncompressed bytes.
+    ///
+    /// Encoded DEFLATE stream is written to `inner`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `segment_size` is `0`.
+    pub fn new(inner: W, segment_size: u64) -> Self {
+        assert_ne!(segment_size, 0, "The segment size must be positive");
+        Self::with_options(inner, segment_size, EncodeOptions::default()).expect("Never fails")
+    }
+}
+impl<W, E> SplitStreamEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `segment_size` is `0` or `options` are invalid (see `EncodeOptions::validate`).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{EncodeOptions, SplitStreamEncoder};
+    ///
+    /// let options = EncodeOptions::new().fixed_huffman_codes();
+    /// let mut encoder = SplitStreamEncoder::with_options(Vec::new(), 4096, options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// assert_eq!(encoder.split_points().len(), 1);
+    /// ```
+    pub fn with_options(
+        inner: W,
+        segment_size: u64,
+        options: EncodeOptions<E>,
+    ) -> Result<Self, OptionsError> {
+        if segment_size == 0 {
+            return Err(OptionsError::ZeroSegmentSize);
+        }
+        Ok(SplitStreamEncoder {
+            encoder: Encoder::with_options(inner, options)?,
+            segment_size,
+            segment_written: 0,
+            points: vec![SplitPoint::default()],
+        })
+    }
+
+    /// Returns the start positions of the segments started so far (including the current one).
+    pub fn split_points(&self) -> &[SplitPoint] {
+        &self.points
+    }
+
+    /// Flushes internal buffer and returns the inner stream with the start positions of the segments.
+    pub fn finish(self) -> Finish<(W, Vec<SplitPoint>), io::Error> {
+        let (inner, error) = self.encoder.finish().unwrap();
+        Finish::new((inner, self.points), error)
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.encoder.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.encoder.as_inner_mut()
+    }
+
+    /// Unwraps this `SplitStreamEncoder`, returning the inner stream.
+    pub fn into_inner(self) -> W {
+        self.encoder.into_inner()
+    }
+
+    fn start_next_segment(&mut self) -> io::Result<()> {
+        self.encoder.full_flush()?;
+        self.points.push(SplitPoint {
+            uncompressed_offset: self.encoder.uncompressed_position(),
+            compressed_offset: self.encoder.compressed_position(),
+        });
+        self.segment_written = 0;
+        Ok(())
+    }
+}
+impl<W, E> io::Write for SplitStreamEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.segment_written == self.segment_size {
+            self.start_next_segment()?;
+        }
+        let size = cmp::min(buf.len() as u64, self.segment_size - self.segment_written) as usize;
+        let written_size = self.encoder.write(&buf[..size])?;
+        self.segment_written += written_size as u64;
+        Ok(written_size)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+impl<W, E> Complete for SplitStreamEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode,
+{
+    fn complete(self) -> io::Result<()> {
+        self.finish().into_result().map(|_| ())
+    }
+}
+
+/// A decoder of the streams encoded by `SplitStreamEncoder`.
+///
+/// Each segment is decoded independently, so the segments can be decoded in parallel
+/// (see `SplitStreamDecoder::par_decode_to`).
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::deflate::{SplitStreamDecoder, SplitStreamEncoder};
+///
+/// let mut encoder = SplitStreamEncoder::new(Vec::new(), 6);
+/// encoder.write_all(b"Hello World!").unwrap();
+/// let (encoded_data, points) = encoder.finish().into_result().unwrap();
+///
+/// let decoder = SplitStreamDecoder::new(&encoded_data, &points);
+/// assert_eq!(decoder.decode_segment(1).unwrap(), b"World!");
+///
+/// let mut decoded_data = Vec::new();
+/// decoder.decode_to(&mut decoded_data).unwrap();
+/// assert_eq!(decoded_data, b"Hello World!");
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct SplitStreamDecoder<'a> {
+    input: &'a [u8],
+    points: &'a [SplitPoint],
+}
+#[cfg(feature = "std")]
+impl<'a> SplitStreamDecoder<'a> {
+    /// Makes a new decoder instance.
+    ///
+    /// `input` is the whole DEFLATE stream and `points` are the start positions of its segments
+    /// (the ones returned by `SplitStreamEncoder::finish`).
+    pub fn new(input: &'a [u8], points: &'a [SplitPoint]) -> Self {
+        SplitStreamDecoder { input, points }
+    }
+
+    /// Returns the number of the segments.
+    pub fn segment_count(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Decodes the `i`-th segment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error if `i` is out of range or the split points are inconsistent with `input`,
+    /// and an `InvalidData` error if the segment is broken or does not have the expected length.
+    pub fn decode_segment(&self, i: usize) -> io::Result<Vec<u8>> {
+        use std::io::Read;
+
+        let point = self
+            .points
+            .get(i)
+            .ok_or_else(|| invalid_input_error!("No such segment: {}", i))?;
+        let next = self.points.get(i + 1);
+        let start = point.compressed_offset as usize;
+        let end = next.map_or(self.input.len(), |p| p.compressed_offset as usize);
+        if start > end || end > self.input.len() {
+            return Err(invalid_input_error!(
+                "Segment {} is out of the input range: {}..{}",
+                i,
+                start,
+                end
+            ));
+        }
+
+        // Non-final segments end with an empty non-compressed block, so an empty final block is appended.
+        let segment = (&self.input[start..end]).chain(&EMPTY_FINAL_BLOCK[..]);
+        let mut decoded = Vec::new();
+        Decoder::new(segment).read_to_end(&mut decoded)?;
+        if let Some(next) = next {
+            let expected = next
+                .uncompressed_offset
+                .saturating_sub(point.uncompressed_offset);
+            if decoded.len() as u64 != expected {
+                return Err(invalid_data_error!(
+                    "Segment {} has {} bytes (expected {} bytes)",
+                    i,
+                    decoded.len(),
+                    expected
+                ));
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Decodes all the segments one by one and writes the results to `writer`.
+    ///
+    /// Returns the number of the decoded bytes.
+    pub fn decode_to<W>(&self, mut writer: W) -> io::Result<u64>
+    where
+        W: io::Write,
+    {
+        let mut size = 0;
+        for i in 0..self.points.len() {
+            let decoded = self.decode_segment(i)?;
+            writer.write_all(&decoded)?;
+            size += decoded.len() as u64;
+        }
+        Ok(size)
+    }
+
+    /// Decodes the segments in parallel using the rayon thread pool and writes the results to `writer` in order.
+    ///
+    /// The segments are decoded in batches of the number of the threads of the pool,
+    /// so at most that many decoded segments are held in memory at once.
+    /// Returns the number of the decoded bytes.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{SplitStreamDecoder, SplitStreamEncoder};
+    ///
+    /// let data = b"Hello World!".repeat(10_000);
+    /// let mut encoder = SplitStreamEncoder::new(Vec::new(), 16 * 1024);
+    /// encoder.write_all(&data).unwrap();
+    /// let (encoded_data, points) = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoded_data = Vec::new();
+    /// SplitStreamDecoder::new(&encoded_data, &points).par_decode_to(&mut decoded_data).unwrap();
+    /// assert_eq!(decoded_data, data);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_decode_to<W>(&self, mut writer: W) -> io::Result<u64>
+    where
+        W: io::Write,
+    {
+        use rayon::prelude::*;
+
+        let batch_size = cmp::max(rayon::current_num_threads(), 1);
+        let mut size = 0;
+        let mut start = 0;
+        while start < self.points.len() {
+            let end = cmp::min(start + batch_size, self.points.len());
+            let decoded = (start..end)
+                .into_par_iter()
+                .map(|i| self.decode_segment(i))
+                .collect::<Vec<_>>();
+            for segment in decoded {
+                let segment = segment?;
+                writer.write_all(&segment)?;
+                size += segment.len() as u64;
+            }
+            start = end;
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use deflate::decompress;
+    use std::io::Write;
+
+    #[test]
+    fn split_stream_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder = SplitStreamEncoder::new(Vec::new(), 10_000);
+        for chunk in plain.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let (encoded, points) = encoder.finish().into_result().unwrap();
+        assert_eq!(points.len(), plain.len().div_ceil(10_000));
+        assert_eq!(decompress(&encoded).unwrap(), plain);
+        check_segments(&encoded, &points, &plain, 10_000);
+
+        assert!(SplitStreamDecoder::new(&encoded, &points)
+            .decode_segment(points.len())
+            .is_err());
+        let mut broken = points.clone();
+        broken[1].uncompressed_offset += 1;
+        let e = SplitStreamDecoder::new(&encoded, &broken)
+            .decode_segment(0)
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let result = SplitStreamEncoder::with_options(Vec::new(), 0, EncodeOptions::new());
+        assert_eq!(result.err(), Some(OptionsError::ZeroSegmentSize));
+    }
+
+    #[test]
+    fn split_stream_with_various_lz77_works() {
+        let text = (0..5000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut x: u32 = 1;
+        let mut random_prefixed = (0..64 * 1024)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        let tail = random_prefixed[random_prefixed.len() - 200..].to_vec();
+        random_prefixed.extend_from_slice(&tail);
+        random_prefixed.extend_from_slice(&text);
+
+        let cases = [
+            (EncodeOptions::zopfli_lz77().type_erased(), &text, 15_000),
+            (
+                EncodeOptions::suffix_array_lz77().type_erased(),
+                &text,
+                15_000,
+            ),
+            (
+                EncodeOptions::new().type_erased(),
+                &random_prefixed,
+                64 * 1024,
+            ),
+        ];
+        for (options, plain, head) in cases {
+            let mut encoder = SplitStreamEncoder::with_options(Vec::new(), 8192, options).unwrap();
+            // A large first write (the random prefix is encoded as high-entropy data)
+            encoder.write_all(&plain[..head]).unwrap();
+            for chunk in plain[head..].chunks(1000) {
+                encoder.write_all(chunk).unwrap();
+            }
+            let (encoded, points) = encoder.finish().into_result().unwrap();
+            assert_eq!(decompress(&encoded).unwrap(), *plain);
+            check_segments(&encoded, &points, plain, 8192);
+        }
+    }
+
+    fn check_segments(encoded: &[u8], points: &[SplitPoint], plain: &[u8], segment_size: usize) {
+        let decoder = SplitStreamDecoder::new(encoded, points);
+        for i in 0..points.len() {
+            let start = points[i].uncompressed_offset as usize;
+            let end = points
+                .get(i + 1)
+                .map_or(plain.len(), |p| p.uncompressed_offset as usize);
+            assert!(end - start >= cmp::min(segment_size, plain.len() - start));
+            assert_eq!(decoder.decode_segment(i).unwrap(), &plain[start..end]);
+        }
+        let mut decoded = Vec::new();
+        assert_eq!(decoder.decode_to(&mut decoded).unwrap(), plain.len() as u64);
+        assert_eq!(decoded, plain);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn par_decode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder = SplitStreamEncoder::new(Vec::new(), 4096);
+        for chunk in plain.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let (encoded, points) = encoder.finish().into_result().unwrap();
+
+        let mut decoded = Vec::new();
+        let decoder = SplitStreamDecoder::new(&encoded, &points);
+        assert_eq!(
+            decoder.par_decode_to(&mut decoded).unwrap(),
+            plain.len() as u64
+        );
+        assert_eq!(decoded, plain);
+    }
+}
@@ -1,7 +1,10 @@
-use std::cmp;
-use std::io;
-use std::iter;
-use std::ops::Range;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp;
+use core::convert::TryFrom;
+use core::iter;
+use core::ops::Range;
+use io;
 
 use bit;
 use huffman;
@@ -15,6 +18,36 @@ const FIXED_LITERAL_OR_LENGTH_CODE_TABLE: [(u8, Range<u16>, u16); 4] = [
     (8, 280..288, 0b0_1100_0000),
 ];
 
+// The decoding tables of the fixed huffman codes (see `huffman::Decoder` for the layout).
+const FIXED_LITERAL_OR_LENGTH_DECODE_TABLE: [u16; 1 << 9] =
+    build_fixed_decode_table(&FIXED_LITERAL_OR_LENGTH_CODE_TABLE);
+const FIXED_DISTANCE_DECODE_TABLE: [u16; 1 << 5] = build_fixed_decode_table(&[(5, 0..30, 0)]);
+
+/// Builds the decoding table of `codes` whose entries are `(bitwidth, symbols, code_base)`.
+///
+/// This is equivalent to `huffman::DecoderBuilder` but can be evaluated at compile time.
+const fn build_fixed_decode_table<const N: usize>(codes: &[(u8, Range<u16>, u16)]) -> [u16; N] {
+    let max_bitwidth = N.trailing_zeros() as u8;
+    let mut table = [huffman::UNMAPPED; N];
+    let mut i = 0;
+    while i < codes.len() {
+        let (bitwidth, ref symbols, code_base) = codes[i];
+        let mut symbol = symbols.start;
+        while symbol < symbols.end {
+            let code = code_base + (symbol - symbols.start);
+            let code_le = code.reverse_bits() >> (16 - bitwidth as u32);
+            let mut padding = 0;
+            while padding < 1 << (max_bitwidth - bitwidth) {
+                table[(padding << bitwidth) | code_le as usize] = (symbol << 5) | bitwidth as u16;
+                padding += 1;
+            }
+            symbol += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
 const BITWIDTH_CODE_ORDER: [usize; 19] = [
     16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
 ];
@@ -55,6 +88,9 @@ const LENGTH_TABLE: [(u16, u8); 29] = [
 
 const MAX_DISTANCE_CODE_COUNT: usize = 30;
 
+/// The maximum length of the literal/length and distance codes (RFC-1951 3.2.7).
+const MAX_CODE_BITWIDTH: u8 = 15;
+
 const DISTANCE_TABLE: [(u16, u8); 30] = [
     (1, 0),
     (2, 0),
@@ -88,7 +124,8 @@ const DISTANCE_TABLE: [(u16, u8); 30] = [
     (24_577, 13),
 ];
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum Symbol {
     EndOfBlock,
     Literal(u8),
@@ -166,6 +203,62 @@ impl From<lz77::Code> for Symbol {
         }
     }
 }
+impl TryFrom<Symbol> for lz77::Code {
+    type Error = Symbol;
+
+    /// Fails (returning the symbol as is) if `symbol` is `Symbol::EndOfBlock`.
+    fn try_from(symbol: Symbol) -> Result<Self, Symbol> {
+        match symbol {
+            Symbol::Literal(b) => Ok(lz77::Code::Literal(b)),
+            Symbol::Share { length, distance } => Ok(lz77::Code::Pointer {
+                length,
+                backward_distance: distance,
+            }),
+            Symbol::EndOfBlock => Err(symbol),
+        }
+    }
+}
+
+/// The frequencies of the literal/length codes and the distance codes of symbols.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Frequencies {
+    pub literal: Vec<usize>,
+    pub distance: Vec<usize>,
+}
+impl Frequencies {
+    pub fn from_symbols(symbols: &[Symbol]) -> Self {
+        let mut literal = vec![0; 286];
+        let mut distance = vec![0; 30];
+        for s in symbols {
+            literal[s.code() as usize] += 1;
+            if let Some((d, _, _)) = s.distance() {
+                distance[d as usize] += 1;
+            }
+        }
+        Frequencies { literal, distance }
+    }
+}
+
+/// Builds the code lengths of the optimal Huffman codes for `frequencies`
+/// whose lengths do not exceed `max_bits`.
+///
+/// This uses the package-merge algorithm by Larmore and Hirschberg.
+/// The lengths of the symbols whose frequencies are zero are zero.
+/// `max_bits` must be large enough to give a code to every used symbol
+/// (i.e., `2^max_bits` must not be less than the number of the used symbols).
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::build_length_limited_codes;
+///
+/// let frequencies = [1, 1, 2, 3, 5, 8, 13, 0];
+/// assert_eq!(build_length_limited_codes(&frequencies, 7), [6, 6, 5, 4, 3, 2, 1, 0]);
+/// assert_eq!(build_length_limited_codes(&frequencies, 3), [3, 3, 3, 3, 3, 3, 2, 0]);
+/// ```
+pub fn build_length_limited_codes(frequencies: &[usize], max_bits: u8) -> Vec<u8> {
+    huffman::calc_length_limited_bitwidthes(frequencies, max_bits)
+}
 
 #[derive(Debug)]
 pub struct Encoder {
@@ -189,6 +282,37 @@ impl Encoder {
         }
         Ok(())
     }
+
+    /// Returns the number of the bits required to encode `symbol`.
+    pub fn bit_width(&self, symbol: &Symbol) -> usize {
+        let mut width = self.literal.lookup(symbol.code()).width as usize;
+        if let Some((bits, _)) = symbol.extra_lengh() {
+            width += bits as usize;
+        }
+        if let Some((code, bits, _)) = symbol.distance() {
+            width += self.distance.lookup(u16::from(code)).width as usize + bits as usize;
+        }
+        width
+    }
+}
+
+/// Returns the number of the bits of the header which describes `encoder` built by `codec`.
+pub fn header_bits<H>(codec: &H, encoder: &Encoder) -> usize
+where
+    H: HuffmanCodec,
+{
+    let mut header = bit::BitWriter::new(io::sink());
+    match codec.save(&mut header, encoder) {
+        Ok(()) => header.written_bits() as usize,
+        Err(_) => usize::MAX,
+    }
+}
+
+/// Returns the number of the bits required to encode `symbols` with `encoder`.
+///
+/// The header of the codes is not included (see `header_bits`).
+pub fn symbol_bits(symbols: &[Symbol], encoder: &Encoder) -> usize {
+    symbols.iter().map(|s| encoder.bit_width(s)).sum()
 }
 
 #[derive(Debug)]
@@ -247,6 +371,10 @@ impl Decoder {
     }
 }
 
+/// Builds, saves and loads the Huffman codes of a compressed block.
+///
+/// The codec is chosen statically per block type (`FixedHuffmanCodec` or `DynamicHuffmanCodec`),
+/// so `save` and `load` are generic over the underlying stream rather than object-safe.
 pub trait HuffmanCodec {
     fn build(&self, symbols: &[Symbol]) -> io::Result<Encoder>;
     fn save<W>(&self, writer: &mut bit::BitWriter<W>, codec: &Encoder) -> io::Result<()>
@@ -257,7 +385,7 @@ pub trait HuffmanCodec {
         R: io::Read;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FixedHuffmanCodec;
 impl HuffmanCodec for FixedHuffmanCodec {
     #[allow(unused_variables)]
@@ -295,52 +423,34 @@ impl HuffmanCodec for FixedHuffmanCodec {
     where
         R: io::Read,
     {
-        let mut literal_builder = huffman::DecoderBuilder::new(9, Some(END_OF_BLOCK));
-        for &(bitwidth, ref symbols, code_base) in &FIXED_LITERAL_OR_LENGTH_CODE_TABLE {
-            for (code, symbol) in symbols
-                .clone()
-                .enumerate()
-                .map(|(i, s)| (code_base + i as u16, s))
-            {
-                literal_builder.set_mapping(symbol, huffman::Code::new(bitwidth, code))?;
-            }
-        }
-
-        let mut distance_builder = huffman::DecoderBuilder::new(5, None);
-        for i in 0..30 {
-            distance_builder.set_mapping(i, huffman::Code::new(5, i))?;
-        }
-
+        // The end-of-block code is 7 bits
         Ok(Decoder {
-            literal: literal_builder.finish(),
-            distance: distance_builder.finish(),
+            literal: huffman::Decoder::from_static_table(
+                &FIXED_LITERAL_OR_LENGTH_DECODE_TABLE,
+                7,
+                9,
+            ),
+            distance: huffman::Decoder::from_static_table(&FIXED_DISTANCE_DECODE_TABLE, 5, 5),
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DynamicHuffmanCodec;
 impl HuffmanCodec for DynamicHuffmanCodec {
     fn build(&self, symbols: &[Symbol]) -> io::Result<Encoder> {
-        let mut literal_counts = [0; 286];
-        let mut distance_counts = [0; 30];
-        let mut empty_distance_table = true;
-        for s in symbols {
-            literal_counts[s.code() as usize] += 1;
-            if let Some((d, _, _)) = s.distance() {
-                empty_distance_table = false;
-                distance_counts[d as usize] += 1;
-            }
-        }
-        if empty_distance_table {
+        let mut frequencies = Frequencies::from_symbols(symbols);
+        if frequencies.distance.iter().all(|&c| c == 0) {
             // Sets a dummy value because an empty distance table causes decoding error on Windows.
             //
             // See https://github.com/sile/libflate/issues/23 for more details.
-            distance_counts[0] = 1;
+            frequencies.distance[0] = 1;
         }
+        let literal = build_length_limited_codes(&frequencies.literal, MAX_CODE_BITWIDTH);
+        let distance = build_length_limited_codes(&frequencies.distance, MAX_CODE_BITWIDTH);
         Ok(Encoder {
-            literal: huffman::EncoderBuilder::from_frequencies(&literal_counts, 15)?,
-            distance: huffman::EncoderBuilder::from_frequencies(&distance_counts, 15)?,
+            literal: huffman::EncoderBuilder::from_bitwidthes(&literal)?,
+            distance: huffman::EncoderBuilder::from_bitwidthes(&distance)?,
         })
     }
     fn save<W>(&self, writer: &mut bit::BitWriter<W>, codec: &Encoder) -> io::Result<()>
@@ -463,15 +573,15 @@ where
         16 => {
             let count = reader.read_bits(2)? + 3;
             let last = last.ok_or_else(|| invalid_data_error!("No preceding value"))?;
-            Box::new(iter::repeat(last).take(count as usize))
+            Box::new(iter::repeat_n(last, count as usize))
         }
         17 => {
             let zeros = reader.read_bits(3)? + 3;
-            Box::new(iter::repeat(0).take(zeros as usize))
+            Box::new(iter::repeat_n(0, zeros as usize))
         }
         18 => {
             let zeros = reader.read_bits(7)? + 11;
-            Box::new(iter::repeat(0).take(zeros as usize))
+            Box::new(iter::repeat_n(0, zeros as usize))
         }
         _ => unreachable!(),
     })
@@ -492,8 +602,8 @@ fn build_bitwidth_codes(
         (&codec.literal, literal_code_count),
         (&codec.distance, distance_code_count),
     ] {
-        for (i, c) in (0..size).map(|x| e.lookup(x as u16).width).enumerate() {
-            if i > 0 && run_lens.last().map_or(false, |s| s.value == c) {
+        for (i, c) in (0..size).map(|x| e.lookup(x).width).enumerate() {
+            if i > 0 && run_lens.last().is_some_and(|s| s.value == c) {
                 run_lens.last_mut().unwrap().count += 1;
             } else {
                 run_lens.push(RunLength { value: c, count: 1 })
@@ -532,3 +642,128 @@ fn build_bitwidth_codes(
     }
     codes
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn symbol_code_conversion_works() {
+        let codes = [
+            lz77::Code::Literal(b'a'),
+            lz77::Code::Pointer {
+                length: 258,
+                backward_distance: 32_768,
+            },
+        ];
+        for code in codes.iter().cloned() {
+            let symbol = Symbol::from(code.clone());
+            assert_eq!(lz77::Code::try_from(symbol), Ok(code));
+        }
+        assert_eq!(
+            lz77::Code::try_from(Symbol::EndOfBlock),
+            Err(Symbol::EndOfBlock)
+        );
+    }
+
+    #[test]
+    fn length_limited_codes_work() {
+        // Fibonacci-distributed frequencies make the unlimited Huffman codes as deep as possible
+        let mut frequencies = vec![1; 288];
+        let (mut a, mut b) = (1, 1);
+        for f in frequencies.iter_mut().take(40) {
+            *f = a;
+            let next = a + b;
+            a = b;
+            b = next;
+        }
+        assert!(huffman::calc_optimal_max_bitwidth(&frequencies) > MAX_CODE_BITWIDTH);
+
+        let lengths = build_length_limited_codes(&frequencies, MAX_CODE_BITWIDTH);
+        assert_eq!(lengths.len(), frequencies.len());
+        assert!(lengths.iter().all(|&l| 0 < l && l <= MAX_CODE_BITWIDTH));
+
+        // The code is complete (i.e., the Kraft sum is one)
+        let kraft = lengths
+            .iter()
+            .map(|&l| 1u64 << (MAX_CODE_BITWIDTH - l))
+            .sum::<u64>();
+        assert_eq!(kraft, 1 << MAX_CODE_BITWIDTH);
+
+        // More frequent symbols never have longer codes
+        for i in 0..frequencies.len() {
+            for j in 0..frequencies.len() {
+                if frequencies[i] > frequencies[j] {
+                    assert!(lengths[i] <= lengths[j]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_decode_tables_work() {
+        use huffman::Builder;
+
+        let mut literal_builder = huffman::DecoderBuilder::new(9, Some(END_OF_BLOCK));
+        for &(bitwidth, ref symbols, code_base) in &FIXED_LITERAL_OR_LENGTH_CODE_TABLE {
+            for symbol in symbols.clone() {
+                let code = code_base + (symbol - symbols.start);
+                literal_builder
+                    .set_mapping(symbol, huffman::Code::new(bitwidth, code))
+                    .unwrap();
+            }
+        }
+        let mut distance_builder = huffman::DecoderBuilder::new(5, None);
+        for i in 0..30 {
+            distance_builder
+                .set_mapping(i, huffman::Code::new(5, i))
+                .unwrap();
+        }
+
+        let decoder = FixedHuffmanCodec
+            .load(&mut bit::BitReader::new(&[][..]))
+            .unwrap();
+        assert_eq!(decoder.literal, literal_builder.finish());
+        assert_eq!(decoder.distance, distance_builder.finish());
+    }
+
+    #[test]
+    fn costs_work() {
+        let mut symbols = (0..1000)
+            .map(|i| {
+                if i % 10 == 0 {
+                    Symbol::Share {
+                        length: 3 + i % 200,
+                        distance: 1 + i * 7,
+                    }
+                } else {
+                    Symbol::Literal((i % 50) as u8)
+                }
+            })
+            .collect::<Vec<_>>();
+        symbols.push(Symbol::EndOfBlock);
+
+        // The costs are the same as the numbers of the actually written bits
+        let encoder = FixedHuffmanCodec.build(&symbols).unwrap();
+        let mut writer = bit::BitWriter::new(Vec::new());
+        for s in &symbols {
+            encoder.encode(&mut writer, s).unwrap();
+        }
+        let fixed_cost = symbol_bits(&symbols, &encoder);
+        assert_eq!(fixed_cost, writer.written_bits() as usize);
+
+        let encoder = DynamicHuffmanCodec.build(&symbols).unwrap();
+        let mut writer = bit::BitWriter::new(Vec::new());
+        DynamicHuffmanCodec.save(&mut writer, &encoder).unwrap();
+        let header_cost = header_bits(&DynamicHuffmanCodec, &encoder);
+        assert_eq!(header_cost, writer.written_bits() as usize);
+        for s in &symbols {
+            encoder.encode(&mut writer, s).unwrap();
+        }
+        let dynamic_cost = header_cost + symbol_bits(&symbols, &encoder);
+        assert_eq!(dynamic_cost, writer.written_bits() as usize);
+
+        // Skewed literals are cheaper with dynamic codes
+        assert!(dynamic_cost < fixed_cost);
+    }
+}
@@ -0,0 +1,104 @@
+use io::{self, Read};
+
+use super::Inspector;
+
+/// A summary of a validated DEFLATE stream.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct ValidationReport {
+    /// The number of the blocks in the stream.
+    pub block_count: usize,
+
+    /// The size of the stream in bytes.
+    pub compressed_size: u64,
+
+    /// The size of the decoded data in bytes.
+    pub uncompressed_size: u64,
+}
+
+/// A validator which checks that a DEFLATE stream is well-formed without producing the decoded data.
+///
+/// The block headers, the Huffman codes, and the distances of the back-references are checked.
+/// Because the validity of a back-reference only depends on the amount of the preceding data
+/// (not on its contents), the decoded bytes are neither written nor kept in a window.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::{self, Validator};
+///
+/// let encoded_data = deflate::compress(b"Hello World! Hello World!");
+/// let report = Validator::new(&encoded_data[..]).validate().unwrap();
+/// assert_eq!(report.block_count, 1);
+/// assert_eq!(report.compressed_size, encoded_data.len() as u64);
+/// assert_eq!(report.uncompressed_size, 25);
+///
+/// assert!(Validator::new(&encoded_data[..4]).validate().is_err());
+/// ```
+#[derive(Debug)]
+pub struct Validator<R> {
+    inspector: Inspector<R>,
+}
+impl<R> Validator<R>
+where
+    R: Read,
+{
+    /// Makes a new validator instance.
+    ///
+    /// `inner` is to be validated DEFLATE stream.
+    pub fn new(inner: R) -> Self {
+        Validator {
+            inspector: Inspector::new(inner),
+        }
+    }
+
+    /// Reads the whole stream and returns the summary of it if the stream is well-formed.
+    pub fn validate(mut self) -> io::Result<ValidationReport> {
+        let mut report = ValidationReport::default();
+        for block in &mut self.inspector {
+            let block = block?;
+            report.block_count += 1;
+            report.uncompressed_size += block.uncompressed_bytes as u64;
+        }
+        report.compressed_size = self.inspector.consumed_bits().div_ceil(8);
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use deflate::{EncodeOptions, Encoder};
+    use io::Write;
+
+    #[test]
+    fn validator_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder =
+            Encoder::with_options(Vec::new(), EncodeOptions::new().block_size(4096)).unwrap();
+        for chunk in plain.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let mut encoded = encoder.finish().into_result().unwrap();
+        encoded.extend_from_slice(b"trailing data");
+
+        let report = Validator::new(&encoded[..]).validate().unwrap();
+        assert!(report.block_count > 1);
+        assert_eq!(report.compressed_size, encoded.len() as u64 - 13);
+        assert_eq!(report.uncompressed_size, plain.len() as u64);
+
+        // A back-reference beyond the start of the stream
+        let invalid = [
+            180, 253, 73, 143, 28, 201, 150, 46, 8, 254, 150, 184, 139, 75, 18, 69, 247, 32, 157,
+            51, 27, 141, 132, 207, 78, 210, 167, 116, 243, 160, 223, 136, 141, 66, 205, 76, 221,
+            76, 195, 213, 84, 236, 234, 224, 78, 227, 34, 145, 221, 139, 126, 232, 69, 173, 170,
+            208, 192, 219, 245, 67, 3, 15, 149, 120, 171, 70, 53, 106, 213, 175, 23, 21, 153, 139,
+            254, 27, 249, 75, 234, 124, 71, 116, 56, 71, 68, 212, 204, 121, 115, 64, 222, 160, 203,
+            119, 142, 170, 169, 138, 202, 112, 228, 140, 38, 171, 162, 88, 212, 235, 56, 136, 231,
+            233, 239, 113, 249, 163, 252, 16, 42, 138, 49, 226, 108, 73, 28, 153,
+        ];
+        let error = Validator::new(&invalid[..]).validate().err().unwrap();
+        assert!(error.to_string().starts_with("Too long backword reference"));
+    }
+}
@@ -0,0 +1,258 @@
+//! The error type of this crate.
+use alloc::boxed::Box;
+use core::error;
+use core::fmt;
+use io;
+
+/// An error that occurs while decoding a compressed stream.
+///
+/// Since the decoders implement `io::Read`, their methods return `io::Error`.
+/// The errors detected by this crate are stored in the `io::Error` as a payload,
+/// and can be retrieved by `Error::from(io_error)`.
+///
+/// # Examples
+/// ```
+/// use std::io::Read;
+/// use libflate::{gzip, Error};
+///
+/// let mut encoded_data = gzip::compress(b"Hello World!");
+/// let len = encoded_data.len();
+/// encoded_data[len - 8] ^= 1; // Corrupts the CRC-32 in the trailer
+///
+/// let mut decoder = gzip::Decoder::new(&encoded_data[..]).unwrap();
+/// let e = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+/// assert!(matches!(Error::from(e), Error::ChecksumMismatch { .. }));
+///
+/// let e = gzip::decompress(&encoded_data[..len - 1]).unwrap_err();
+/// assert!(matches!(Error::from(e), Error::UnexpectedEof));
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The magic number (e.g., the ID of GZIP) of the stream is invalid.
+    InvalidMagic,
+
+    /// The stream is compressed with a method other than DEFLATE.
+    UnsupportedCompressionMethod(u8),
+
+    /// The type of a DEFLATE block is the reserved value (`0b11`).
+    InvalidBlockType,
+
+    /// The bit lengths of a huffman code describe more codes than possible.
+    HuffmanCodeOversubscribed,
+
+    /// The stream contains a code which is not assigned by the incomplete huffman code.
+    HuffmanCodeUndersubscribed,
+
+    /// The checksum stored in the stream differs from the one of the decoded data.
+    ChecksumMismatch {
+        /// The checksum stored in the stream.
+        expected: u32,
+
+        /// The checksum of the decoded data.
+        got: u32,
+    },
+
+    /// The size stored in the stream (e.g., the `ISIZE` field of GZIP) differs from
+    /// the one of the decoded data.
+    SizeMismatch {
+        /// The size stored in the stream.
+        expected: u32,
+
+        /// The size of the decoded data (modulo `2^32`).
+        got: u32,
+    },
+
+    /// The stream ended unexpectedly.
+    UnexpectedEof,
+
+    /// Other I/O error.
+    Io(io::Error),
+
+    /// An error with the position in the stream where it occurred.
+    WithContext(Box<Error>, ErrorContext),
+}
+impl Error {
+    /// Returns the context of the error if it is available.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        if let Error::WithContext(_, ref context) = *self {
+            Some(context)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the underlying error without the context.
+    pub fn without_context(&self) -> &Error {
+        if let Error::WithContext(ref e, _) = *self {
+            e.without_context()
+        } else {
+            self
+        }
+    }
+
+    fn kind(&self) -> io::ErrorKind {
+        match *self {
+            Error::Io(ref e) => e.kind(),
+            Error::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            Error::WithContext(ref e, _) => e.kind(),
+            _ => io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+/// The position in a DEFLATE stream where an error occurred.
+///
+/// The offsets are relative to the start of the DEFLATE stream
+/// (i.e., the ZLIB and GZIP headers are not counted).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorContext {
+    /// The zero-based index of the block.
+    pub block_index: usize,
+
+    /// The number of the compressed bytes consumed before the error.
+    pub compressed_offset: u64,
+
+    /// The number of the compressed bits consumed before the error.
+    ///
+    /// Unlike `compressed_offset` (which counts a partially consumed byte as consumed),
+    /// this points to the exact bit position.
+    pub compressed_bit_offset: u64,
+
+    /// The number of the bytes decoded before the error.
+    pub uncompressed_offset: u64,
+}
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "block {} at compressed offset 0x{:X} (uncompressed offset 0x{:X})",
+            self.block_index, self.compressed_offset, self.uncompressed_offset
+        )
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidMagic => write!(f, "Invalid magic number"),
+            Error::UnsupportedCompressionMethod(method) => write!(
+                f,
+                "Compression methods other than DEFLATE(8) are unsupported: method={}",
+                method
+            ),
+            Error::InvalidBlockType => write!(f, "btype 0x11 of DEFLATE is reserved(error) value"),
+            Error::HuffmanCodeOversubscribed => write!(f, "Oversubscribed huffman code"),
+            Error::HuffmanCodeUndersubscribed => write!(f, "Invalid huffman coded stream"),
+            Error::ChecksumMismatch { expected, got } => write!(
+                f,
+                "Checksum mismatched: value=0x{:08X}, expected=0x{:08X}",
+                got, expected
+            ),
+            Error::SizeMismatch { expected, got } => {
+                write!(f, "Size mismatched: value={}, expected={}", got, expected)
+            }
+            Error::UnexpectedEof => write!(f, "Unexpected end of stream"),
+            Error::Io(ref e) => e.fmt(f),
+            Error::WithContext(ref e, ref context) => write!(f, "{} in {}", e, context),
+        }
+    }
+}
+/// A recoverable problem detected by a decoder in lenient mode.
+///
+/// See `gzip::DecodeOptions::lenient` and `zlib::DecodeOptions::lenient`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeWarning {
+    /// The checksum stored in the trailer differs from the one of the decoded data.
+    ChecksumMismatch {
+        /// The checksum stored in the stream.
+        expected: u32,
+
+        /// The checksum of the decoded data.
+        got: u32,
+    },
+
+    /// The size stored in the trailer differs from the one of the decoded data.
+    SizeMismatch {
+        /// The size stored in the stream.
+        expected: u32,
+
+        /// The size of the decoded data (modulo `2^32`).
+        got: u32,
+    },
+}
+impl DecodeWarning {
+    pub(crate) fn into_error(self) -> Error {
+        match self {
+            DecodeWarning::ChecksumMismatch { expected, got } => {
+                Error::ChecksumMismatch { expected, got }
+            }
+            DecodeWarning::SizeMismatch { expected, got } => Error::SizeMismatch { expected, got },
+        }
+    }
+}
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.clone().into_error().fmt(f)
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::WithContext(ref e, _) => e.source(),
+            _ => None,
+        }
+    }
+}
+impl From<Error> for io::Error {
+    fn from(f: Error) -> Self {
+        match f {
+            Error::Io(e) => e,
+            _ => io::Error::new(f.kind(), f),
+        }
+    }
+}
+impl From<io::Error> for Error {
+    /// Extracts the `Error` stored in `f`.
+    ///
+    /// If `f` does not have it, `Error::UnexpectedEof` is returned for `io::ErrorKind::UnexpectedEof`
+    /// and `Error::Io(f)` is returned otherwise.
+    fn from(f: io::Error) -> Self {
+        if f.get_ref().is_some_and(|e| e.is::<Error>()) {
+            let inner = f.into_inner().expect("Never fails");
+            *inner.downcast::<Error>().expect("Never fails")
+        } else if f.kind() == io::ErrorKind::UnexpectedEof {
+            Error::UnexpectedEof
+        } else {
+            Error::Io(f)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversion_works() {
+        let e = io::Error::from(Error::InvalidBlockType);
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(Error::from(e), Error::InvalidBlockType));
+
+        let e = io::Error::from(Error::UnexpectedEof);
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(matches!(Error::from(e), Error::UnexpectedEof));
+
+        let e = io::Error::new(io::ErrorKind::InvalidData, "foo");
+        assert!(matches!(Error::from(e), Error::Io(_)));
+
+        let context = ErrorContext::default();
+        let e = io::Error::from(Error::WithContext(Box::new(Error::UnexpectedEof), context));
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        let e = Error::from(e);
+        assert_eq!(e.context(), Some(&context));
+        assert!(matches!(e.without_context(), Error::UnexpectedEof));
+    }
+}
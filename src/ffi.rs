@@ -0,0 +1,345 @@
+//! C API of the GZIP encoder and decoder.
+//!
+//! This module is available only if the `ffi` feature is enabled.
+//! The C header is generated from this module by `cbindgen` into `$OUT_DIR/libflate.h`
+//! when the crate is built, and a copy of it is committed as `include/libflate.h`.
+//!
+//! All the functions return one of the `LIBFLATE_*` codes (except for the `*_free` functions),
+//! and never unwind across the FFI boundary: a panic is reported as `LIBFLATE_ERROR_PANIC`.
+use std::io::{self, Read, Write};
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use gzip;
+
+/// The operation succeeded.
+pub const LIBFLATE_OK: c_int = 0;
+
+/// The operation succeeded, but more output is available (call the function again).
+pub const LIBFLATE_MORE_OUTPUT: c_int = 1;
+
+/// A required pointer argument was null.
+pub const LIBFLATE_ERROR_NULL_POINTER: c_int = -1;
+
+/// An I/O error occurred (e.g., the compressed data are broken).
+pub const LIBFLATE_ERROR_IO: c_int = -2;
+
+/// The encoder has already been finished.
+pub const LIBFLATE_ERROR_FINISHED: c_int = -3;
+
+/// A panic occurred in the library.
+pub const LIBFLATE_ERROR_PANIC: c_int = -4;
+
+/// GZIP encoder which accumulates the encoded data in memory.
+#[derive(Debug)]
+pub struct GzipEncoder {
+    state: EncoderState,
+}
+
+#[derive(Debug)]
+enum EncoderState {
+    Encoding(Box<gzip::Encoder<Vec<u8>>>),
+    Finished { encoded: Vec<u8>, offset: usize },
+    Poisoned,
+}
+
+/// GZIP decoder which decodes the data given at the creation.
+#[derive(Debug)]
+pub struct GzipDecoder {
+    decoder: gzip::Decoder<io::Cursor<Vec<u8>>>,
+}
+
+fn catch_panic<F>(f: F) -> c_int
+where
+    F: FnOnce() -> c_int,
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(LIBFLATE_ERROR_PANIC)
+}
+
+unsafe fn as_slice<'a>(buf: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        Some(&[])
+    } else if buf.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(buf, len))
+    }
+}
+
+unsafe fn as_slice_mut<'a>(buf: *mut u8, len: usize) -> Option<&'a mut [u8]> {
+    if len == 0 {
+        Some(&mut [])
+    } else if buf.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts_mut(buf, len))
+    }
+}
+
+/// Makes a new GZIP encoder and stores it to `*encoder`.
+///
+/// The encoder must be released by `libflate_gzip_encoder_free`.
+///
+/// # Safety
+///
+/// `encoder` must be null or a valid pointer to write a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn libflate_gzip_encoder_new(encoder: *mut *mut GzipEncoder) -> c_int {
+    catch_panic(|| {
+        if encoder.is_null() {
+            return LIBFLATE_ERROR_NULL_POINTER;
+        }
+        match gzip::Encoder::new(Vec::new()) {
+            Ok(inner) => {
+                let state = EncoderState::Encoding(Box::new(inner));
+                *encoder = Box::into_raw(Box::new(GzipEncoder { state }));
+                LIBFLATE_OK
+            }
+            Err(_) => LIBFLATE_ERROR_IO,
+        }
+    })
+}
+
+/// Encodes `len` bytes of `buf`.
+///
+/// # Safety
+///
+/// `encoder` must be null or a pointer made by `libflate_gzip_encoder_new`,
+/// and `buf` must be null or valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn libflate_gzip_encoder_write(
+    encoder: *mut GzipEncoder,
+    buf: *const u8,
+    len: usize,
+) -> c_int {
+    catch_panic(|| {
+        let (encoder, buf) = match (encoder.as_mut(), as_slice(buf, len)) {
+            (Some(encoder), Some(buf)) => (encoder, buf),
+            _ => return LIBFLATE_ERROR_NULL_POINTER,
+        };
+        match encoder.state {
+            EncoderState::Encoding(ref mut inner) => match inner.write_all(buf) {
+                Ok(()) => LIBFLATE_OK,
+                Err(_) => LIBFLATE_ERROR_IO,
+            },
+            _ => LIBFLATE_ERROR_FINISHED,
+        }
+    })
+}
+
+/// Finishes the encoding and copies the encoded data to `out` (up to `out_len` bytes).
+///
+/// The number of the copied bytes is stored to `*written`.
+/// If the encoded data do not fit in `out`, `LIBFLATE_MORE_OUTPUT` is returned
+/// and the rest of the data can be taken by calling this function again.
+///
+/// # Safety
+///
+/// `encoder` must be null or a pointer made by `libflate_gzip_encoder_new`,
+/// `out` must be null or valid for writes of `out_len` bytes,
+/// and `written` must be null or a valid pointer to write a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn libflate_gzip_encoder_finish(
+    encoder: *mut GzipEncoder,
+    out: *mut u8,
+    out_len: usize,
+    written: *mut usize,
+) -> c_int {
+    catch_panic(|| {
+        let (encoder, out) = match (encoder.as_mut(), as_slice_mut(out, out_len)) {
+            (Some(encoder), Some(out)) if !written.is_null() => (encoder, out),
+            _ => return LIBFLATE_ERROR_NULL_POINTER,
+        };
+        *written = 0;
+        if let EncoderState::Encoding(_) = encoder.state {
+            let inner = match std::mem::replace(&mut encoder.state, EncoderState::Poisoned) {
+                EncoderState::Encoding(inner) => inner,
+                _ => unreachable!(),
+            };
+            match inner.finish().into_result() {
+                Ok(encoded) => encoder.state = EncoderState::Finished { encoded, offset: 0 },
+                Err(_) => return LIBFLATE_ERROR_IO,
+            }
+        }
+        match encoder.state {
+            EncoderState::Finished {
+                ref encoded,
+                ref mut offset,
+            } => {
+                let size = (&encoded[*offset..]).read(out).expect("Never fails");
+                *offset += size;
+                *written = size;
+                if *offset < encoded.len() {
+                    LIBFLATE_MORE_OUTPUT
+                } else {
+                    LIBFLATE_OK
+                }
+            }
+            _ => LIBFLATE_ERROR_IO,
+        }
+    })
+}
+
+/// Releases the encoder made by `libflate_gzip_encoder_new`.
+///
+/// # Safety
+///
+/// `encoder` must be null or a pointer made by `libflate_gzip_encoder_new` which has not been released.
+#[no_mangle]
+pub unsafe extern "C" fn libflate_gzip_encoder_free(encoder: *mut GzipEncoder) {
+    if !encoder.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(encoder))));
+    }
+}
+
+/// Makes a new GZIP decoder which decodes `len` bytes of `buf`, and stores it to `*decoder`.
+///
+/// The data are copied, so `buf` can be released after this call.
+/// The decoder must be released by `libflate_gzip_decoder_free`.
+///
+/// # Safety
+///
+/// `buf` must be null or valid for reads of `len` bytes,
+/// and `decoder` must be null or a valid pointer to write a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn libflate_gzip_decoder_new(
+    buf: *const u8,
+    len: usize,
+    decoder: *mut *mut GzipDecoder,
+) -> c_int {
+    catch_panic(|| {
+        let buf = match as_slice(buf, len) {
+            Some(buf) if !decoder.is_null() => buf,
+            _ => return LIBFLATE_ERROR_NULL_POINTER,
+        };
+        match gzip::Decoder::new(io::Cursor::new(buf.to_vec())) {
+            Ok(inner) => {
+                *decoder = Box::into_raw(Box::new(GzipDecoder { decoder: inner }));
+                LIBFLATE_OK
+            }
+            Err(_) => {
+                *decoder = ptr::null_mut();
+                LIBFLATE_ERROR_IO
+            }
+        }
+    })
+}
+
+/// Decodes up to `out_len` bytes to `out`.
+///
+/// The number of the decoded bytes is stored to `*read`.
+/// Zero means that the end of the stream has been reached.
+///
+/// # Safety
+///
+/// `decoder` must be null or a pointer made by `libflate_gzip_decoder_new`,
+/// `out` must be null or valid for writes of `out_len` bytes,
+/// and `read` must be null or a valid pointer to write a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn libflate_gzip_decoder_read(
+    decoder: *mut GzipDecoder,
+    out: *mut u8,
+    out_len: usize,
+    read: *mut usize,
+) -> c_int {
+    catch_panic(|| {
+        let (decoder, out) = match (decoder.as_mut(), as_slice_mut(out, out_len)) {
+            (Some(decoder), Some(out)) if !read.is_null() => (decoder, out),
+            _ => return LIBFLATE_ERROR_NULL_POINTER,
+        };
+        *read = 0;
+        match decoder.decoder.read(out) {
+            Ok(size) => {
+                *read = size;
+                LIBFLATE_OK
+            }
+            Err(_) => LIBFLATE_ERROR_IO,
+        }
+    })
+}
+
+/// Releases the decoder made by `libflate_gzip_decoder_new`.
+///
+/// # Safety
+///
+/// `decoder` must be null or a pointer made by `libflate_gzip_decoder_new` which has not been released.
+#[no_mangle]
+pub unsafe extern "C" fn libflate_gzip_decoder_free(decoder: *mut GzipDecoder) {
+    if !decoder.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(decoder))));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn header_is_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/libflate.h"));
+        let committed = include_str!("../include/libflate.h");
+        assert!(
+            generated == committed,
+            "include/libflate.h is outdated: copy {}/libflate.h to it",
+            env!("OUT_DIR")
+        );
+    }
+
+    #[test]
+    fn ffi_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i))
+            .collect::<String>()
+            .into_bytes();
+        unsafe {
+            let mut encoder = ptr::null_mut();
+            assert_eq!(libflate_gzip_encoder_new(&mut encoder), LIBFLATE_OK);
+            for chunk in plain.chunks(1000) {
+                let result = libflate_gzip_encoder_write(encoder, chunk.as_ptr(), chunk.len());
+                assert_eq!(result, LIBFLATE_OK);
+            }
+
+            let mut encoded = Vec::new();
+            let mut buf = [0; 100];
+            let mut size = 0;
+            loop {
+                let result =
+                    libflate_gzip_encoder_finish(encoder, buf.as_mut_ptr(), buf.len(), &mut size);
+                encoded.extend_from_slice(&buf[..size]);
+                if result == LIBFLATE_OK {
+                    break;
+                }
+                assert_eq!(result, LIBFLATE_MORE_OUTPUT);
+            }
+            let result = libflate_gzip_encoder_write(encoder, plain.as_ptr(), 1);
+            assert_eq!(result, LIBFLATE_ERROR_FINISHED);
+            libflate_gzip_encoder_free(encoder);
+
+            let mut decoder = ptr::null_mut();
+            let result = libflate_gzip_decoder_new(encoded.as_ptr(), encoded.len(), &mut decoder);
+            assert_eq!(result, LIBFLATE_OK);
+            let mut decoded = Vec::new();
+            loop {
+                let result =
+                    libflate_gzip_decoder_read(decoder, buf.as_mut_ptr(), buf.len(), &mut size);
+                assert_eq!(result, LIBFLATE_OK);
+                if size == 0 {
+                    break;
+                }
+                decoded.extend_from_slice(&buf[..size]);
+            }
+            libflate_gzip_decoder_free(decoder);
+            assert_eq!(decoded, plain);
+
+            let result = libflate_gzip_decoder_new(encoded.as_ptr(), 3, &mut decoder);
+            assert_eq!(result, LIBFLATE_ERROR_IO);
+            assert!(decoder.is_null());
+            assert_eq!(
+                libflate_gzip_encoder_new(ptr::null_mut()),
+                LIBFLATE_ERROR_NULL_POINTER
+            );
+        }
+    }
+}
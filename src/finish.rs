@@ -1,6 +1,6 @@
 //! `Finish` and related types.
-use std::io::{self, Write};
-use std::ops::{Deref, DerefMut};
+use core::ops::{Deref, DerefMut};
+use io::{self, Write};
 
 /// `Finish` is a type that represents a value which
 /// may have an error occurred during the computation.
@@ -85,6 +85,67 @@ impl<T, E> Finish<T, E> {
             Ok(&self.value)
         }
     }
+
+    /// Splits the instance into the value and the result of the computation.
+    ///
+    /// Unlike `into_result`, the value is returned even if an error occurred.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::Finish;
+    ///
+    /// let succeeded = Finish::new("value", None as Option<()>);
+    /// assert_eq!(succeeded.split(), ("value", Ok(())));
+    ///
+    /// let failed = Finish::new("value", Some("error"));
+    /// assert_eq!(failed.split(), ("value", Err("error")));
+    /// ```
+    pub fn split(self) -> (T, Result<(), E>) {
+        let result = self.error.map_or(Ok(()), Err);
+        (self.value, result)
+    }
+
+    /// Maps a `Finish<T, E>` to `Finish<U, E>` by applying a function to the contained value.
+    ///
+    /// The error (if any) is left untouched.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::Finish;
+    ///
+    /// let failed = Finish::new("value", Some("error"));
+    /// assert_eq!(failed.map(str::len).unwrap(), (5, Some("error")));
+    /// ```
+    pub fn map<F, U>(self, f: F) -> Finish<U, E>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Finish::new(f(self.value), self.error)
+    }
+
+    /// Returns the contained value if no error occurred, otherwise computes it from the error.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::Finish;
+    ///
+    /// let succeeded = Finish::new("value", None as Option<()>);
+    /// assert_eq!(succeeded.unwrap_or_else(|_| "default"), "value");
+    ///
+    /// let failed = Finish::new("value", Some("error"));
+    /// assert_eq!(failed.unwrap_or_else(|_| "default"), "default");
+    /// ```
+    pub fn unwrap_or_else<F>(self, f: F) -> T
+    where
+        F: FnOnce(E) -> T,
+    {
+        self.into_result().unwrap_or_else(f)
+    }
+}
+impl<T, E> From<Finish<T, E>> for Result<T, E> {
+    fn from(f: Finish<T, E>) -> Self {
+        f.into_result()
+    }
 }
 
 /// A wrapper struct that completes the processing of the underlying instance when drops.
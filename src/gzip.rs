@@ -19,14 +19,37 @@
 //!
 //! assert_eq!(decoded_data, b"Hello World!");
 //! ```
-use std::{ffi::CString, io, time};
+use std::io::{Seek, SeekFrom};
+use std::{cmp, error, ffi::CString, fmt, io, mem, slice, time};
 
-use checksum;
+use adler32;
+#[cfg(feature = "tokio")]
+use async_io::{self, PollReader};
+use crc32;
 use deflate;
 use finish::{Complete, Finish};
 use lz77;
+#[cfg(feature = "tokio")]
+use non_blocking;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use util::CountingReader;
+use {DecodeWarning, Error};
 
 const GZIP_ID: [u8; 2] = [31, 139];
+
+const INDEX_MAGIC: [u8; 4] = *b"GZIX";
+const INDEX_VERSION: u8 = 1;
+
+/// The size of the window stored in an `IndexPoint`.
+pub const INDEX_WINDOW_SIZE: usize = 32768;
+
+/// The default interval (in uncompressed bytes) of the points of an `Index`.
+pub const DEFAULT_INDEX_INTERVAL: u64 = 1024 * 1024;
 const COMPRESSION_METHOD_DEFLATE: u8 = 8;
 
 const OS_FAT: u8 = 0;
@@ -51,8 +74,12 @@ const F_EXTRA: u8 = 0b00_0100;
 const F_NAME: u8 = 0b00_1000;
 const F_COMMENT: u8 = 0b01_0000;
 
+/// The number of the leading bytes of the input inspected by `EncodeOptions::auto_text_flag`.
+const TEXT_PROBE_SIZE: usize = 512;
+
 /// Compression levels defined by the GZIP format.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum CompressionLevel {
     /// Compressor used fastest algorithm.
     Fastest,
@@ -78,6 +105,13 @@ impl CompressionLevel {
             _ => CompressionLevel::Unknown,
         }
     }
+    fn from_level(level: u8) -> Self {
+        match level {
+            1 => CompressionLevel::Fastest,
+            9 => CompressionLevel::Slowest,
+            _ => CompressionLevel::Unknown,
+        }
+    }
 }
 impl From<lz77::CompressionLevel> for CompressionLevel {
     fn from(f: lz77::CompressionLevel) -> Self {
@@ -89,16 +123,24 @@ impl From<lz77::CompressionLevel> for CompressionLevel {
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct Trailer {
+/// GZIP trailer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Trailer {
     crc32: u32,
     input_size: u32,
 }
 impl Trailer {
+    /// Returns the CRC-32 of the uncompressed data.
     pub fn crc32(&self) -> u32 {
         self.crc32
     }
-    pub fn read_from<R>(mut reader: R) -> io::Result<Self>
+
+    /// Returns the size of the uncompressed data modulo `2^32` (i.e., the `ISIZE` field).
+    pub fn input_size(&self) -> u32 {
+        self.input_size
+    }
+
+    pub(crate) fn read_from<R>(mut reader: R) -> io::Result<Self>
     where
         R: io::Read,
     {
@@ -109,7 +151,10 @@ impl Trailer {
         let input_size = u32::from_le_bytes(buf);
         Ok(Trailer { crc32, input_size })
     }
-    fn write_to<W>(&self, mut writer: W) -> io::Result<()>
+    pub(crate) fn new(crc32: u32, input_size: u32) -> Self {
+        Trailer { crc32, input_size }
+    }
+    pub(crate) fn write_to<W>(&self, mut writer: W) -> io::Result<()>
     where
         W: io::Write,
     {
@@ -177,16 +222,62 @@ impl HeaderBuilder {
         self
     }
 
+    /// Sets the modification time (UNIX timestamp).
+    ///
+    /// This is an alias of `modification_time`.
+    pub fn mtime(&mut self, modification_time: u32) -> &mut Self {
+        self.modification_time(modification_time)
+    }
+
+    /// Sets the modification time from a `SystemTime`.
+    ///
+    /// The fractional part of the seconds is truncated.
+    /// Returns an error if `t` is before the UNIX epoch or does not fit in the `u32` field
+    /// (i.e., after 2106-02-07).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use libflate::gzip::HeaderBuilder;
+    ///
+    /// let t = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    /// let header = HeaderBuilder::new().mtime_from_system_time(t).unwrap().finish();
+    /// assert_eq!(header.modification_time(), 1_000_000_000);
+    ///
+    /// assert!(HeaderBuilder::new().mtime_from_system_time(UNIX_EPOCH - Duration::from_secs(1)).is_err());
+    /// ```
+    pub fn mtime_from_system_time(&mut self, t: time::SystemTime) -> io::Result<&mut Self> {
+        let secs = t
+            .duration_since(time::UNIX_EPOCH)
+            .map_err(|_| invalid_input_error!("modification time before UNIX epoch: {:?}", t))?
+            .as_secs();
+        if secs > u64::from(u32::MAX) {
+            return Err(invalid_input_error!(
+                "modification time too large: {} seconds since UNIX epoch",
+                secs
+            ));
+        }
+        Ok(self.modification_time(secs as u32))
+    }
+
     /// Sets the OS type.
     ///
+    /// Both of `Os` and the raw `u8` value of the OS field are accepted.
+    ///
     /// ```
     /// use libflate::gzip::{HeaderBuilder, Os};
     ///
     /// let header = HeaderBuilder::new().os(Os::Ntfs).finish();
     /// assert_eq!(header.os(), Os::Ntfs);
+    ///
+    /// let header = HeaderBuilder::new().os(0).finish();
+    /// assert_eq!(header.os(), Os::Fat);
     /// ```
-    pub fn os(&mut self, os: Os) -> &mut Self {
-        self.header.os = os;
+    pub fn os<T>(&mut self, os: T) -> &mut Self
+    where
+        T: Into<Os>,
+    {
+        self.header.os = os.into();
         self
     }
 
@@ -204,6 +295,20 @@ impl HeaderBuilder {
         self
     }
 
+    /// Sets whether the encoding data is a ASCII text (i.e., the FTEXT flag).
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::HeaderBuilder;
+    ///
+    /// let header = HeaderBuilder::new().text_flag(true).finish();
+    /// assert_eq!(header.is_text(), true);
+    /// ```
+    pub fn text_flag(&mut self, is_text: bool) -> &mut Self {
+        self.header.is_text = is_text;
+        self
+    }
+
     /// Specifies toe verify header bytes using CRC-16.
     ///
     /// # Examples
@@ -218,22 +323,74 @@ impl HeaderBuilder {
         self
     }
 
+    /// Sets whether the CRC-16 of the header bytes is written (i.e., the FHCRC flag).
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::HeaderBuilder;
+    ///
+    /// let header = HeaderBuilder::new().header_crc(true).finish();
+    /// assert_eq!(header.is_verified(), true);
+    /// ```
+    pub fn header_crc(&mut self, enabled: bool) -> &mut Self {
+        self.header.is_verified = enabled;
+        self
+    }
+
     /// Sets the extra field.
     ///
+    /// `extra` is an `ExtraField` or an `ExtraFieldBuilder`.
+    ///
     /// # Examples
     /// ```
-    /// use libflate::gzip::{HeaderBuilder, ExtraField, ExtraSubField};
+    /// use libflate::gzip::{HeaderBuilder, ExtraField, ExtraFieldBuilder, ExtraSubField};
     ///
     /// let subfield = ExtraSubField{id: [0, 1], data: vec![2, 3, 4]};
     /// let extra = ExtraField{subfields: vec![subfield]};
     /// let header = HeaderBuilder::new().extra_field(extra.clone()).finish();
     /// assert_eq!(header.extra_field(), Some(&extra));
+    ///
+    /// let mut builder = ExtraFieldBuilder::new();
+    /// builder.subfield([0, 1], &[2, 3, 4]).unwrap();
+    /// let header = HeaderBuilder::new().extra_field(builder).finish();
+    /// assert_eq!(header.extra_field(), Some(&extra));
     /// ```
-    pub fn extra_field(&mut self, extra: ExtraField) -> &mut Self {
-        self.header.extra_field = Some(extra);
+    pub fn extra_field<T>(&mut self, extra: T) -> &mut Self
+    where
+        T: Into<ExtraField>,
+    {
+        self.header.extra_field = Some(extra.into());
         self
     }
 
+    /// Sets the extra field from the raw bytes (i.e., the sequence of the sub fields).
+    ///
+    /// If the length of `data` exceeds 65535 bytes or
+    /// `data` is not a valid sequence of sub fields, an error will be returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::{HeaderBuilder, ExtraField, ExtraSubField};
+    ///
+    /// let header = HeaderBuilder::new().extra(&[0, 1, 3, 0, 2, 3, 4]).unwrap().finish();
+    /// let subfield = ExtraSubField{id: [0, 1], data: vec![2, 3, 4]};
+    /// assert_eq!(header.extra_field(), Some(&ExtraField{subfields: vec![subfield]}));
+    ///
+    /// assert!(HeaderBuilder::new().extra(&[0, 1, 3, 0]).is_err());
+    /// assert!(HeaderBuilder::new().extra(&[0; 0x10000]).is_err());
+    /// ```
+    pub fn extra(&mut self, data: &[u8]) -> io::Result<&mut Self> {
+        if data.len() > 0xFFFF {
+            return Err(invalid_input_error!("extra field too long: {}", data.len()));
+        }
+        let mut buf = Vec::with_capacity(2 + data.len());
+        buf.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        buf.extend_from_slice(data);
+        let extra = ExtraField::read_from(&buf[..])
+            .map_err(|e| invalid_input_error!("malformed extra field: {}", e))?;
+        Ok(self.extra_field(extra))
+    }
+
     /// Sets the file name.
     ///
     /// # Examples
@@ -249,6 +406,26 @@ impl HeaderBuilder {
         self
     }
 
+    /// Sets the file name from a string.
+    ///
+    /// If `name` contains a NUL byte, an error will be returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::ffi::CString;
+    /// use libflate::gzip::HeaderBuilder;
+    ///
+    /// let header = HeaderBuilder::new().name("foo").unwrap().finish();
+    /// assert_eq!(header.filename(), Some(&CString::new("foo").unwrap()));
+    ///
+    /// assert!(HeaderBuilder::new().name("foo\0").is_err());
+    /// ```
+    pub fn name(&mut self, name: &str) -> io::Result<&mut Self> {
+        let name = CString::new(name)
+            .map_err(|e| invalid_input_error!("file name contains a NUL byte: {}", e))?;
+        Ok(self.filename(name))
+    }
+
     /// Sets the comment.
     ///
     /// # Examples
@@ -264,10 +441,47 @@ impl HeaderBuilder {
         self
     }
 
+    /// Sets the comment from a string.
+    ///
+    /// If `comment` contains a NUL byte, an error will be returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::ffi::CString;
+    /// use libflate::gzip::HeaderBuilder;
+    ///
+    /// let header = HeaderBuilder::new().comment_str("foo").unwrap().finish();
+    /// assert_eq!(header.comment(), Some(&CString::new("foo").unwrap()));
+    ///
+    /// assert!(HeaderBuilder::new().comment_str("foo\0").is_err());
+    /// ```
+    pub fn comment_str(&mut self, comment: &str) -> io::Result<&mut Self> {
+        let comment = CString::new(comment)
+            .map_err(|e| invalid_input_error!("comment contains a NUL byte: {}", e))?;
+        Ok(self.comment(comment))
+    }
+
     /// Returns the result header.
     pub fn finish(&self) -> Header {
         self.header.clone()
     }
+
+    /// Returns the result header.
+    ///
+    /// This is an alias of `finish`.
+    pub fn build(&self) -> Header {
+        self.finish()
+    }
+}
+impl From<HeaderBuilder> for Header {
+    fn from(builder: HeaderBuilder) -> Self {
+        builder.header
+    }
+}
+impl<'a> From<&'a mut HeaderBuilder> for Header {
+    fn from(builder: &'a mut HeaderBuilder) -> Self {
+        builder.finish()
+    }
 }
 impl Default for HeaderBuilder {
     fn default() -> Self {
@@ -276,7 +490,8 @@ impl Default for HeaderBuilder {
 }
 
 /// GZIP Header.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Header {
     modification_time: u32,
     compression_level: CompressionLevel,
@@ -293,6 +508,30 @@ impl Header {
         self.modification_time
     }
 
+    /// Returns the modification time as a `SystemTime`.
+    ///
+    /// Returns `None` if the modification time is zero (i.e., no time stamp is available).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use libflate::gzip::HeaderBuilder;
+    ///
+    /// let header = HeaderBuilder::new().modification_time(10).finish();
+    /// assert_eq!(header.modification_time_as_system_time(), Some(UNIX_EPOCH + Duration::from_secs(10)));
+    ///
+    /// let header = HeaderBuilder::new().modification_time(0).finish();
+    /// assert_eq!(header.modification_time_as_system_time(), None);
+    /// ```
+    pub fn modification_time_as_system_time(&self) -> Option<time::SystemTime> {
+        if self.modification_time == 0 {
+            None
+        } else {
+            let elapsed = time::Duration::from_secs(u64::from(self.modification_time));
+            Some(time::UNIX_EPOCH + elapsed)
+        }
+    }
+
     /// Returns the compression level.
     pub fn compression_level(&self) -> CompressionLevel {
         self.compression_level.clone()
@@ -308,6 +547,29 @@ impl Header {
         self.is_text
     }
 
+    /// Returns `true` if the FTEXT flag is set, `false` otherwise.
+    ///
+    /// This is the same as `is_text`. The flag is only a hint given by the encoder
+    /// (e.g., GNU gzip uses it to choose the name of the decompressed file),
+    /// and is not verified by the decoder.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::{Decoder, Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().auto_text_flag(true);
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let decoder = Decoder::new(&encoded_data[..]).unwrap();
+    /// assert!(decoder.header().is_text_hint());
+    /// ```
+    pub fn is_text_hint(&self) -> bool {
+        self.is_text
+    }
+
     /// Returns `true` if the header bytes is verified by CRC-16, `false` otherwise.
     pub fn is_verified(&self) -> bool {
         self.is_verified
@@ -318,6 +580,23 @@ impl Header {
         self.extra_field.as_ref()
     }
 
+    /// Sets the extra field built by `extra`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::{ExtraFieldBuilder, HeaderBuilder};
+    ///
+    /// let mut builder = ExtraFieldBuilder::new();
+    /// builder.subfield(*b"BC", &[0, 1]).unwrap();
+    ///
+    /// let mut header = HeaderBuilder::new().finish();
+    /// header.set_extra(builder);
+    /// assert_eq!(header.extra_field().and_then(|e| e.get(*b"BC")).map(|f| &f.data[..]), Some(&[0, 1][..]));
+    /// ```
+    pub fn set_extra(&mut self, extra: ExtraFieldBuilder) {
+        self.extra_field = Some(extra.into());
+    }
+
     /// Returns the file name.
     pub fn filename(&self) -> Option<&CString> {
         self.filename.as_ref()
@@ -341,68 +620,67 @@ impl Header {
         .map(|e| e.0)
         .sum()
     }
-    fn crc16(&self) -> u16 {
-        let mut crc = checksum::Crc32::new();
-        let mut buf = Vec::new();
-        Header {
-            is_verified: false,
-            ..self.clone()
-        }
-        .write_to(&mut buf)
-        .unwrap();
-        crc.update(&buf);
-        crc.value() as u16
-    }
-    fn write_to<W>(&self, mut writer: W) -> io::Result<()>
+    pub(crate) fn write_to<W>(&self, mut writer: W) -> io::Result<()>
     where
         W: io::Write,
     {
-        writer.write_all(&GZIP_ID)?;
-        writer.write_all(&[COMPRESSION_METHOD_DEFLATE, self.flags()])?;
-        writer.write_all(&self.modification_time.to_le_bytes())?;
-        writer.write_all(&[self.compression_level.to_u8(), self.os.to_u8()])?;
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&GZIP_ID);
+        buf.extend_from_slice(&[COMPRESSION_METHOD_DEFLATE, self.flags()]);
+        buf.extend_from_slice(&self.modification_time.to_le_bytes());
+        buf.extend_from_slice(&[self.compression_level.to_u8(), self.os.to_u8()]);
         if let Some(ref x) = self.extra_field {
-            x.write_to(&mut writer)?;
+            x.write_to(&mut buf)?;
         }
         if let Some(ref x) = self.filename {
-            writer.write_all(x.as_bytes_with_nul())?;
+            buf.extend_from_slice(x.as_bytes_with_nul());
         }
         if let Some(ref x) = self.comment {
-            writer.write_all(x.as_bytes_with_nul())?;
+            buf.extend_from_slice(x.as_bytes_with_nul());
         }
         if self.is_verified {
-            writer.write_all(&self.crc16().to_le_bytes())?;
+            // The CRC-16 covers all the preceding header bytes (see RFC-1952 2.3.1)
+            let mut crc = crc32::Crc32::new();
+            crc.update(&buf);
+            buf.extend_from_slice(&(crc.value() as u16).to_le_bytes());
         }
-        Ok(())
+        writer.write_all(&buf)
     }
-    pub(crate) fn read_from<R>(mut reader: R) -> io::Result<Self>
+    /// Reads a header from `reader` and returns it with the number of the bytes read.
+    fn read_with_size<R>(reader: R) -> io::Result<(Self, u64)>
     where
         R: io::Read,
     {
+        let mut reader = CountingReader::new(reader);
+        let header = Self::read_from(&mut reader)?;
+        Ok((header, reader.bytes_read()))
+    }
+    pub(crate) fn read_from<R>(reader: R) -> io::Result<Self>
+    where
+        R: io::Read,
+    {
+        use std::io::Read;
+
+        let mut reader = Crc32Reader {
+            inner: reader,
+            crc32: crc32::Crc32::new(),
+        };
         let mut this = HeaderBuilder::new().finish();
         let mut buf = [0; 2 + 1 + 1 + 4 + 1 + 1];
         reader.read_exact(&mut buf)?;
         let id = &buf[0..2];
         if id != GZIP_ID {
-            return Err(invalid_data_error!(
-                "Unexpected GZIP ID: value={:?}, \
-                 expected={:?}",
-                id,
-                GZIP_ID
-            ));
+            return Err(Error::InvalidMagic.into());
         }
         let compression_method = buf[2];
         if compression_method != COMPRESSION_METHOD_DEFLATE {
-            return Err(invalid_data_error!(
-                "Compression methods other than DEFLATE(8) are \
-                 unsupported: method={}",
-                compression_method
-            ));
+            return Err(Error::UnsupportedCompressionMethod(compression_method).into());
         }
         let flags = buf[3];
         this.modification_time = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
         this.compression_level = CompressionLevel::from_u8(buf[8]);
         this.os = Os::from_u8(buf[9]);
+        this.is_text = flags & F_TEXT != 0;
         if flags & F_EXTRA != 0 {
             this.extra_field = Some(ExtraField::read_from(&mut reader)?);
         }
@@ -415,17 +693,15 @@ impl Header {
         // Checksum verification is skipped during fuzzing
         // so that random data from fuzzer can reach actually interesting code.
         // Compilation flag 'fuzzing' is automatically set by all 3 Rust fuzzers.
-        if flags & F_HCRC != 0 && cfg!(not(fuzzing)) {
+        if flags & F_HCRC != 0 {
+            let expected = reader.crc32.value() as u16;
             let mut buf = [0; 2];
-            reader.read_exact(&mut buf)?;
-            let crc = u16::from_le_bytes(buf);
-            let expected = this.crc16();
-            if crc != expected {
-                return Err(invalid_data_error!(
-                    "CRC16 of GZIP header mismatched: value={}, \
-                     expected={}",
-                    crc,
-                    expected
+            reader.inner.read_exact(&mut buf)?;
+            let got = u16::from_le_bytes(buf);
+            if cfg!(not(fuzzing)) && got != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    HeaderError::CrcMismatch { expected, got },
                 ));
             }
             this.is_verified = true;
@@ -433,6 +709,130 @@ impl Header {
         Ok(this)
     }
 }
+impl fmt::Display for Header {
+    /// Formats the header in a human-readable form similar to `gzip -lv`.
+    ///
+    /// The output consists of the following lines (absent fields are printed as `-`),
+    /// and this format is stable across releases:
+    ///
+    /// ```text
+    /// modification time: <UNIX timestamp>
+    /// compression level: <fastest|slowest|unknown>
+    /// os: <OS name> (<OS byte>)
+    /// text: <yes|no>
+    /// header crc: <yes|no>
+    /// name: <original file name>
+    /// comment: <comment>
+    /// extra field: <length of the extra field> bytes
+    /// ```
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::{HeaderBuilder, Os};
+    ///
+    /// let header = HeaderBuilder::new()
+    ///     .modification_time(123)
+    ///     .os(Os::Ntfs)
+    ///     .name("foo.txt").unwrap()
+    ///     .finish();
+    /// assert_eq!(header.to_string(),
+    ///            "modification time: 123\n\
+    ///             compression level: unknown\n\
+    ///             os: NTFS (11)\n\
+    ///             text: no\n\
+    ///             header crc: no\n\
+    ///             name: foo.txt\n\
+    ///             comment: -\n\
+    ///             extra field: -");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn yes_or_no(b: bool) -> &'static str {
+            if b {
+                "yes"
+            } else {
+                "no"
+            }
+        }
+
+        writeln!(f, "modification time: {}", self.modification_time)?;
+        let level = match self.compression_level {
+            CompressionLevel::Fastest => "fastest",
+            CompressionLevel::Slowest => "slowest",
+            CompressionLevel::Unknown => "unknown",
+        };
+        writeln!(f, "compression level: {}", level)?;
+        writeln!(f, "os: {} ({})", self.os.name(), self.os.to_u8())?;
+        writeln!(f, "text: {}", yes_or_no(self.is_text))?;
+        writeln!(f, "header crc: {}", yes_or_no(self.is_verified))?;
+        match self.filename {
+            Some(ref x) => writeln!(f, "name: {}", x.to_string_lossy())?,
+            None => writeln!(f, "name: -")?,
+        }
+        match self.comment {
+            Some(ref x) => writeln!(f, "comment: {}", x.to_string_lossy())?,
+            None => writeln!(f, "comment: -")?,
+        }
+        match self.extra_field {
+            Some(ref x) => write!(f, "extra field: {} bytes", x.len()),
+            None => write!(f, "extra field: -"),
+        }
+    }
+}
+
+/// A reader that computes the CRC-32 of the read bytes.
+struct Crc32Reader<R> {
+    inner: R,
+    crc32: crc32::Crc32,
+}
+impl<R> io::Read for Crc32Reader<R>
+where
+    R: io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.crc32.update(&buf[..size]);
+        Ok(size)
+    }
+}
+
+/// An error that occurs while reading a GZIP header.
+///
+/// This is wrapped in an `io::Error` of `io::ErrorKind::InvalidData`,
+/// and can be retrieved via `io::Error::get_ref` and `downcast_ref`.
+///
+/// # Examples
+/// ```
+/// use libflate::gzip::{Decoder, HeaderError};
+///
+/// // A header with the FHCRC flag and a wrong CRC-16
+/// let encoded_data = [31, 139, 8, 2, 0, 0, 0, 0, 0, 3, 0, 0];
+/// let error = Decoder::new(&encoded_data[..]).err().unwrap();
+/// let error = error.get_ref().and_then(|e| e.downcast_ref::<HeaderError>());
+/// assert_eq!(error, Some(&HeaderError::CrcMismatch { expected: 0x77A7, got: 0 }));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HeaderError {
+    /// The CRC-16 (FHCRC) of the header bytes mismatched.
+    CrcMismatch {
+        /// The CRC-16 computed from the header bytes.
+        expected: u16,
+
+        /// The CRC-16 stored in the header.
+        got: u16,
+    },
+}
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HeaderError::CrcMismatch { expected, got } => write!(
+                f,
+                "CRC16 of GZIP header mismatched: value={}, expected={}",
+                got, expected
+            ),
+        }
+    }
+}
+impl error::Error for HeaderError {}
 
 fn read_cstring<R>(mut reader: R) -> io::Result<CString>
 where
@@ -450,13 +850,49 @@ where
 }
 
 /// Extra field of a GZIP header.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct ExtraField {
     /// Data of the extra field.
     pub subfields: Vec<ExtraSubField>,
 }
 impl ExtraField {
-    fn read_from<R>(mut reader: R) -> io::Result<Self>
+    /// Returns an iterator over the sub fields.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::ExtraFieldBuilder;
+    ///
+    /// let extra = ExtraFieldBuilder::new()
+    ///     .subfield(*b"BC", &[0, 1]).unwrap()
+    ///     .subfield(*b"SI", &[2]).unwrap()
+    ///     .finish();
+    /// let ids = extra.iter().map(|f| f.id).collect::<Vec<_>>();
+    /// assert_eq!(ids, [*b"BC", *b"SI"]);
+    /// ```
+    pub fn iter(&self) -> slice::Iter<'_, ExtraSubField> {
+        self.subfields.iter()
+    }
+
+    /// Returns the first sub field which has the ID `id`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::ExtraFieldBuilder;
+    ///
+    /// let extra = ExtraFieldBuilder::new().subfield(*b"BC", &[0, 1]).unwrap().finish();
+    /// assert_eq!(extra.get(*b"BC").map(|f| &f.data[..]), Some(&[0, 1][..]));
+    /// assert_eq!(extra.get(*b"SI"), None);
+    /// ```
+    pub fn get(&self, id: [u8; 2]) -> Option<&ExtraSubField> {
+        self.subfields.iter().find(|f| f.id == id)
+    }
+
+    fn len(&self) -> usize {
+        self.subfields.iter().map(|f| f.write_len()).sum()
+    }
+
+    pub(crate) fn read_from<R>(mut reader: R) -> io::Result<Self>
     where
         R: io::Read,
     {
@@ -474,7 +910,7 @@ impl ExtraField {
     where
         W: io::Write,
     {
-        let len = self.subfields.iter().map(|f| f.write_len()).sum::<usize>();
+        let len = self.len();
         if len > 0xFFFF {
             return Err(invalid_data_error!("extra field too long: {}", len));
         }
@@ -486,8 +922,68 @@ impl ExtraField {
     }
 }
 
+impl<'a> IntoIterator for &'a ExtraField {
+    type Item = &'a ExtraSubField;
+    type IntoIter = slice::Iter<'a, ExtraSubField>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Builder of `ExtraField`.
+///
+/// The total length of the extra field is checked when each sub field is appended.
+#[derive(Debug, Default, Clone)]
+pub struct ExtraFieldBuilder {
+    field: ExtraField,
+    len: usize,
+}
+impl ExtraFieldBuilder {
+    /// Makes a new builder instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a sub field.
+    ///
+    /// If the total length of the extra field exceeds 65535 bytes, an error will be returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::ExtraFieldBuilder;
+    ///
+    /// let mut builder = ExtraFieldBuilder::new();
+    /// assert!(builder.subfield(*b"AB", &[0; 0xFFFF - 4]).is_ok());
+    /// assert!(builder.subfield(*b"CD", &[]).is_err());
+    /// ```
+    pub fn subfield(&mut self, id: [u8; 2], data: &[u8]) -> io::Result<&mut Self> {
+        let field = ExtraSubField {
+            id,
+            data: data.to_owned(),
+        };
+        let len = self.len + field.write_len();
+        if len > 0xFFFF {
+            return Err(invalid_input_error!("extra field too long: {}", len));
+        }
+        self.len = len;
+        self.field.subfields.push(field);
+        Ok(self)
+    }
+
+    /// Returns the result extra field.
+    pub fn finish(&self) -> ExtraField {
+        self.field.clone()
+    }
+}
+impl From<ExtraFieldBuilder> for ExtraField {
+    fn from(f: ExtraFieldBuilder) -> Self {
+        f.field
+    }
+}
+
 /// A sub field in the extra field of a GZIP header.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct ExtraSubField {
     /// ID of the field.
     pub id: [u8; 2],
@@ -530,6 +1026,7 @@ impl ExtraSubField {
 
 /// OS type.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub enum Os {
     /// FAT filesystem (MS-DOS, OS/2, NT/Win32)
     Fat,
@@ -579,8 +1076,57 @@ pub enum Os {
     /// Undefined value in RFC-1952
     Undefined(u8),
 }
+impl From<u8> for Os {
+    fn from(x: u8) -> Self {
+        Os::from_u8(x)
+    }
+}
 impl Os {
-    fn to_u8(&self) -> u8 {
+    /// Returns the OS type of the target platform.
+    ///
+    /// This is `Os::Fat` on Windows (as GNU gzip does), `Os::Unix` on Unix-like platforms
+    /// (including macOS, since `Os::Macintosh` denotes the classic Mac OS),
+    /// and `Os::Unknown` on the other platforms.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::Os;
+    ///
+    /// if cfg!(unix) {
+    ///     assert_eq!(Os::current(), Os::Unix);
+    /// }
+    /// ```
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            Os::Fat
+        } else if cfg!(unix) {
+            Os::Unix
+        } else {
+            Os::Unknown
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Os::Fat => "FAT",
+            Os::Amiga => "Amiga",
+            Os::Vms => "VMS",
+            Os::Unix => "Unix",
+            Os::VmCms => "VM/CMS",
+            Os::AtariTos => "Atari TOS",
+            Os::Hpfs => "HPFS",
+            Os::Macintosh => "Macintosh",
+            Os::ZSystem => "Z-System",
+            Os::CpM => "CP/M",
+            Os::Tops20 => "TOPS-20",
+            Os::Ntfs => "NTFS",
+            Os::Qdos => "QDOS",
+            Os::AcornRiscos => "Acorn RISCOS",
+            Os::Unknown => "unknown",
+            Os::Undefined(_) => "undefined",
+        }
+    }
+    fn to_u8(&self) -> u8 {
         match *self {
             Os::Fat => OS_FAT,
             Os::Amiga => OS_AMIGA,
@@ -623,19 +1169,21 @@ impl Os {
 }
 
 /// Options for a GZIP encoder.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct EncodeOptions<E>
 where
     E: lz77::Lz77Encode,
 {
     header: Header,
     options: deflate::EncodeOptions<E>,
+    auto_text_flag: bool,
 }
 impl Default for EncodeOptions<lz77::DefaultLz77Encoder> {
     fn default() -> Self {
         EncodeOptions {
             header: HeaderBuilder::new().finish(),
             options: Default::default(),
+            auto_text_flag: false,
         }
     }
 }
@@ -652,6 +1200,30 @@ impl EncodeOptions<lz77::DefaultLz77Encoder> {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Specifies the compression level.
+    ///
+    /// See `deflate::EncodeOptions::compression_level` for the details.
+    /// The `XFL` field of the header is set to `CompressionLevel::Slowest` for level 9,
+    /// `CompressionLevel::Fastest` for level 1, and `CompressionLevel::Unknown` otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is greater than `deflate::MAX_COMPRESSION_LEVEL`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::{CompressionLevel, Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().compression_level(9);
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// assert_eq!(encoder.header().compression_level(), CompressionLevel::Slowest);
+    /// ```
+    pub fn compression_level(mut self, level: u8) -> Self {
+        self.options = self.options.compression_level(level);
+        self.header.compression_level = CompressionLevel::from_level(level);
+        self
+    }
 }
 impl<E> EncodeOptions<E>
 where
@@ -673,6 +1245,7 @@ where
         EncodeOptions {
             header,
             options: deflate::EncodeOptions::with_lz77(lz77),
+            auto_text_flag: false,
         }
     }
 
@@ -694,6 +1267,8 @@ where
 
     /// Sets the GZIP header which will be written to the output stream.
     ///
+    /// Both of `Header` and `HeaderBuilder` are accepted.
+    ///
     /// # Example
     /// ```
     /// use libflate::gzip::{Encoder, EncodeOptions, HeaderBuilder};
@@ -701,9 +1276,58 @@ where
     /// let header = HeaderBuilder::new().text().modification_time(100).finish();
     /// let options = EncodeOptions::new().header(header);
     /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    ///
+    /// let options = EncodeOptions::new().header(HeaderBuilder::new().name("foo.txt").unwrap());
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
     /// ```
-    pub fn header(mut self, header: Header) -> Self {
-        self.header = header;
+    pub fn header<H>(mut self, header: H) -> Self
+    where
+        H: Into<Header>,
+    {
+        self.header = header.into();
+        self
+    }
+
+    /// Sets the OS field of the header to the OS type of the target platform.
+    ///
+    /// See `Os::current` for the detected values.
+    /// Note that this overwrites the OS field of the header given by `header`.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::gzip::{Encoder, EncodeOptions, Os};
+    ///
+    /// let options = EncodeOptions::new().auto_os();
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// assert_eq!(encoder.header().os(), Os::current());
+    /// ```
+    pub fn auto_os(mut self) -> Self {
+        self.header.os = Os::current();
+        self
+    }
+
+    /// Specifies whether to set the FTEXT flag of the header automatically.
+    ///
+    /// If enabled, the flag is set if the first 512 bytes of the input are valid UTF-8
+    /// (the flag given by `header` is overwritten).
+    /// Because of that, the header is not written until 512 bytes are written
+    /// or the encoder is flushed or finished.
+    ///
+    /// The default value is `false`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().auto_text_flag(true);
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.flush().unwrap();
+    /// assert!(encoder.header().is_text());
+    /// ```
+    pub fn auto_text_flag(mut self, enabled: bool) -> Self {
+        self.auto_text_flag = enabled;
         self
     }
 
@@ -737,13 +1361,243 @@ where
         self
     }
 }
+impl<E> EncodeOptions<E>
+where
+    E: lz77::Lz77Encode + 'static,
+{
+    /// Erases the type of the LZ77 encoder by boxing it.
+    ///
+    /// See `deflate::EncodeOptions::type_erased` for more details.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::gzip::{Encoder, EncodeOptions};
+    /// use libflate::lz77::Lz77Encode;
+    ///
+    /// let options: EncodeOptions<Box<dyn Lz77Encode>> = EncodeOptions::new().type_erased();
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn type_erased(self) -> EncodeOptions<Box<dyn lz77::Lz77Encode>> {
+        EncodeOptions {
+            header: self.header,
+            options: self.options.type_erased(),
+            auto_text_flag: self.auto_text_flag,
+        }
+    }
+}
+impl<E> From<deflate::EncodeOptions<E>> for EncodeOptions<E>
+where
+    E: lz77::Lz77Encode,
+{
+    /// Makes GZIP options which encode the payload with `options`.
+    ///
+    /// The header is the default one of `HeaderBuilder::new` except for the following fields:
+    /// the `XFL` field is derived from the compression level of the LZ77 encoder
+    /// (`CompressionLevel::Unknown` if LZ77 compression is disabled),
+    /// and if a preset dictionary is specified, its ID is stored in
+    /// the `DICTIONARY_ID_SUBFIELD` extra subfield (see `Decoder::with_dictionary_id_check`).
+    /// `auto_text_flag` is disabled.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::{deflate, gzip};
+    ///
+    /// let options = deflate::EncodeOptions::new().block_size(4096).compression_level(9);
+    /// let encoder = gzip::Encoder::with_options(Vec::new(), options.into()).unwrap();
+    /// assert_eq!(encoder.header().compression_level(), gzip::CompressionLevel::Slowest);
+    /// ```
+    fn from(options: deflate::EncodeOptions<E>) -> Self {
+        let mut builder = HeaderBuilder::new();
+        if !options.get_dictionary().is_empty() {
+            let id = adler32::update(adler32::INITIAL_VALUE, options.get_dictionary());
+            let extra = ExtraFieldBuilder::new()
+                .subfield(DICTIONARY_ID_SUBFIELD, &id.to_be_bytes())
+                .expect("Never fails")
+                .finish();
+            builder.extra_field(extra);
+        }
+        let mut header = builder.finish();
+        header.compression_level = options
+            .get_lz77()
+            .map_or(CompressionLevel::Unknown, |lz77| {
+                From::from(lz77.compression_level())
+            });
+        EncodeOptions {
+            header,
+            options,
+            auto_text_flag: false,
+        }
+    }
+}
+
+/// Reads the header and the trailer of a GZIP stream without decompressing the payload.
+///
+/// The payload is walked block by block without producing the decompressed data (see `deflate::Validator`),
+/// so the returned trailer is the one of the first member even if the stream has multiple members
+/// or trailing bytes.
+/// An error is returned if the `ISIZE` field of the trailer does not match the size of the payload.
+/// The `CRC32` field is not verified.
+///
+/// # Examples
+/// ```
+/// use std::io::{Cursor, Write};
+/// use libflate::gzip::{self, Encoder, EncodeOptions, HeaderBuilder};
+///
+/// let header = HeaderBuilder::new().name("hello.txt").unwrap().mtime(123).finish();
+/// let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::new().header(header)).unwrap();
+/// encoder.write_all(b"Hello World!").unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let (header, trailer) = gzip::inspect(Cursor::new(encoded_data)).unwrap();
+/// assert_eq!(header.filename().unwrap().to_str(), Ok("hello.txt"));
+/// assert_eq!(header.modification_time(), 123);
+/// assert_eq!(trailer.input_size(), 12);
+/// ```
+pub fn inspect<R>(reader: R) -> io::Result<(Header, Trailer)>
+where
+    R: io::Read + io::Seek,
+{
+    inspect_stream(reader)
+}
+
+/// Reads the header and the trailer of a GZIP stream without decompressing the payload.
+///
+/// This is the same as `inspect` except that `reader` is not required to be seekable.
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::gzip::{self, Encoder};
+///
+/// let mut encoder = Encoder::new(Vec::new()).unwrap();
+/// encoder.write_all(b"Hello World!").unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let (_, trailer) = gzip::inspect_stream(&encoded_data[..]).unwrap();
+/// assert_eq!(trailer.input_size(), 12);
+/// ```
+pub fn inspect_stream<R>(mut reader: R) -> io::Result<(Header, Trailer)>
+where
+    R: io::Read,
+{
+    let header = Header::read_from(&mut reader)?;
+    let report = deflate::Validator::new(&mut reader).validate()?;
+    let trailer = Trailer::read_from(&mut reader)?;
+    let got = report.uncompressed_size as u32;
+    if trailer.input_size != got {
+        return Err(Error::SizeMismatch {
+            expected: trailer.input_size,
+            got,
+        }
+        .into());
+    }
+    Ok((header, trailer))
+}
+
+/// Returns the upper bound of the size of GZIP encoded data of `uncompressed_len` bytes.
+///
+/// This is `deflate::compressed_size_bound` plus the size of a header without optional fields
+/// (i.e., `FEXTRA`, `FNAME`, `FCOMMENT` and `FHCRC`) and the trailer.
+///
+/// # Examples
+/// ```
+/// use libflate::{deflate, gzip};
+///
+/// assert_eq!(gzip::compressed_size_bound(100), deflate::compressed_size_bound(100) + 18);
+/// ```
+pub fn compressed_size_bound(uncompressed_len: usize) -> usize {
+    // 10 bytes header and 8 bytes trailer (CRC32 and ISIZE)
+    deflate::compressed_size_bound(uncompressed_len).saturating_add(10 + 8)
+}
+
+// The maximum compression ratio of DEFLATE is about 1032:1.
+const MAX_COMPRESSION_RATIO: usize = 1032;
+
+/// Encodes `input` into a GZIP stream with the default options.
+///
+/// # Examples
+/// ```
+/// use libflate::gzip;
+///
+/// let encoded_data = gzip::compress(b"Hello World!");
+/// assert_eq!(gzip::decompress(&encoded_data).unwrap(), b"Hello World!");
+/// ```
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+    let buf = Vec::with_capacity(compressed_size_bound(input.len()));
+    let mut encoder = Encoder::new(buf).expect("Never fails");
+    encoder.write_all(input).expect("Never fails");
+    encoder.finish().into_result().expect("Never fails")
+}
+
+/// Encodes `input` into a GZIP stream (a single member) using the rayon thread pool.
+///
+/// See `deflate::par_compress` for details.
+///
+/// # Examples
+/// ```
+/// use libflate::gzip::{self, EncodeOptions};
+///
+/// let data = b"Hello World!".repeat(10_000);
+/// let encoded_data = gzip::par_compress(&data, EncodeOptions::new().block_size(16 * 1024));
+/// assert_eq!(gzip::decompress(&encoded_data).unwrap(), data);
+/// ```
+#[cfg(feature = "rayon")]
+pub fn par_compress<E>(input: &[u8], options: EncodeOptions<E>) -> Vec<u8>
+where
+    E: lz77::Lz77Encode + Clone + Send + Sync,
+{
+    let mut buf = Vec::with_capacity(compressed_size_bound(input.len()));
+    options.header.write_to(&mut buf).expect("Never fails");
+    buf.extend_from_slice(&deflate::par_compress(input, options.options));
+    let mut crc32 = crc32::Crc32::new();
+    crc32.update(input);
+    let trailer = Trailer {
+        crc32: crc32.value(),
+        input_size: input.len() as u32,
+    };
+    trailer.write_to(&mut buf).expect("Never fails");
+    buf
+}
+
+/// Decodes a GZIP stream `input`.
+///
+/// If `input` consists of multiple members, they are decoded and concatenated.
+/// The output buffer is pre-allocated based on the `ISIZE` field of the last member.
+///
+/// # Examples
+/// ```
+/// use libflate::gzip;
+///
+/// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+///                     163, 28, 41, 28, 12, 0, 0, 0];
+/// assert_eq!(gzip::decompress(&encoded_data).unwrap(), b"Hello World!");
+/// ```
+pub fn decompress(input: &[u8]) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+    let size_hint = if input.len() >= 4 {
+        let mut isize = [0; 4];
+        isize.copy_from_slice(&input[input.len() - 4..]);
+        u32::from_le_bytes(isize) as usize
+    } else {
+        0
+    };
+    let capacity = cmp::min(size_hint, input.len().saturating_mul(MAX_COMPRESSION_RATIO));
+    let mut buf = Vec::with_capacity(capacity);
+    MultiDecoder::new(input)?.read_to_end(&mut buf)?;
+    Ok(buf)
+}
 
 /// GZIP encoder.
+#[derive(Debug)]
 pub struct Encoder<W, E = lz77::DefaultLz77Encoder> {
     header: Header,
-    crc32: checksum::Crc32,
+    header_size: u64,
+    crc32: crc32::Crc32,
     input_size: u32,
     writer: deflate::Encoder<W, E>,
+    text_probe: Option<Vec<u8>>,
 }
 impl<W> Encoder<W, lz77::DefaultLz77Encoder>
 where
@@ -765,6 +1619,30 @@ where
     pub fn new(inner: W) -> io::Result<Self> {
         Self::with_options(inner, EncodeOptions::new())
     }
+
+    /// Makes a new encoder instance with the specified compression level and header.
+    ///
+    /// This is equivalent to
+    /// `Encoder::with_options(inner, EncodeOptions::new().header(header).compression_level(level))`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is greater than `deflate::MAX_COMPRESSION_LEVEL`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::{Encoder, HeaderBuilder};
+    ///
+    /// let header = HeaderBuilder::new().name("hello.txt").unwrap().finish();
+    /// let mut encoder = Encoder::with_compression_level(Vec::new(), 6, header).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().into_result().unwrap();
+    /// ```
+    pub fn with_compression_level(inner: W, level: u8, header: Header) -> io::Result<Self> {
+        let options = EncodeOptions::new().header(header).compression_level(level);
+        Self::with_options(inner, options)
+    }
 }
 impl<W, E> Encoder<W, E>
 where
@@ -774,6 +1652,8 @@ where
     /// Makes a new encoder instance with specified options.
     ///
     /// Encoded GZIP stream is written to `inner`.
+    /// If `inner` is positioned at the end of an existing GZIP stream (e.g., a file opened in append mode),
+    /// the encoded data is appended to it as a new member, which can be decoded by `MultiDecoder`.
     ///
     /// # Examples
     /// ```
@@ -789,18 +1669,85 @@ where
     ///            &[31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255, 72, 101, 108, 108,
     ///              111, 32, 87, 111, 114, 108, 100, 33, 163, 28, 41, 28, 12, 0, 0, 0][..]);
     /// ```
-    pub fn with_options(mut inner: W, options: EncodeOptions<E>) -> io::Result<Self> {
-        options.header.write_to(&mut inner)?;
-        Ok(Encoder {
-            header: options.header.clone(),
-            crc32: checksum::Crc32::new(),
+    pub fn with_options(inner: W, options: EncodeOptions<E>) -> io::Result<Self> {
+        let writer = deflate::Encoder::with_options(inner, options.options)?;
+        if options.auto_text_flag {
+            Ok(Encoder {
+                header: options.header,
+                header_size: 0,
+                crc32: crc32::Crc32::new(),
+                input_size: 0,
+                writer,
+                text_probe: Some(Vec::with_capacity(TEXT_PROBE_SIZE)),
+            })
+        } else {
+            Self::from_deflate_encoder(options.header, writer)
+        }
+    }
+
+    /// Makes a new encoder instance which wraps an already configured DEFLATE encoder.
+    ///
+    /// `header` is written to the inner stream of `inner` immediately,
+    /// so no data must have been written to `inner` yet.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate;
+    /// use libflate::gzip::{Decoder, Encoder, HeaderBuilder};
+    ///
+    /// let options = deflate::EncodeOptions::zopfli_lz77().block_size(4096);
+    /// let deflate_encoder = deflate::Encoder::with_options(Vec::new(), options).unwrap();
+    /// let header = HeaderBuilder::new().finish();
+    /// let mut encoder = Encoder::from_deflate_encoder(header, deflate_encoder).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut buf = Vec::new();
+    /// Decoder::new(&encoded_data[..]).unwrap().read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn from_deflate_encoder(header: Header, inner: deflate::Encoder<W, E>) -> io::Result<Self> {
+        let mut encoder = Encoder {
+            header,
+            header_size: 0,
+            crc32: crc32::Crc32::new(),
             input_size: 0,
-            writer: deflate::Encoder::with_options(inner, options.options),
-        })
+            writer: inner,
+            text_probe: None,
+        };
+        encoder.write_header()?;
+        Ok(encoder)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut header_bytes = Vec::new();
+        self.header.write_to(&mut header_bytes)?;
+        self.writer.as_inner_mut().write_all(&header_bytes)?;
+        self.header_size = header_bytes.len() as u64;
+        Ok(())
+    }
+
+    fn flush_text_probe(&mut self) -> io::Result<()> {
+        use std::io::Write;
+
+        if let Some(probe) = self.text_probe.take() {
+            self.header.is_text = match std::str::from_utf8(&probe) {
+                Ok(_) => !probe.is_empty(),
+                // A multi-byte character may be cut at the end of the probe
+                Err(e) => probe.len() == TEXT_PROBE_SIZE && e.error_len().is_none(),
+            };
+            self.write_header()?;
+            self.writer.write_all(&probe)?;
+        }
+        Ok(())
     }
 
     /// Returns the header of the GZIP stream.
     ///
+    /// If `EncodeOptions::auto_text_flag` is enabled,
+    /// the FTEXT flag is not determined until the header is written.
+    ///
     /// # Examples
     /// ```
     /// use libflate::gzip::{Encoder, Os};
@@ -812,6 +1759,47 @@ where
         &self.header
     }
 
+    /// Returns the CRC-32 value of the uncompressed data written so far.
+    ///
+    /// This is the value which will be stored in the trailer if no more data is written.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::crc32;
+    /// use libflate::gzip::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello ").unwrap();
+    /// encoder.write_all(b"World!").unwrap();
+    /// assert_eq!(encoder.uncompressed_crc32_so_far(),
+    ///            crc32::update(crc32::INITIAL_VALUE, b"Hello World!"));
+    /// ```
+    pub fn uncompressed_crc32_so_far(&self) -> u32 {
+        self.crc32.value()
+    }
+
+    /// Returns the number of the bytes written to the inner stream so far (including the header).
+    ///
+    /// Note that the encoder buffers the data of the current block (and a few bits of the last written byte),
+    /// so this value increases only when a block is flushed.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new()).unwrap();
+    /// assert_eq!(encoder.compressed_bytes_written_so_far(), 10);
+    ///
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// assert_eq!(encoder.compressed_bytes_written_so_far(),
+    ///            encoder.as_inner_ref().len() as u64);
+    /// ```
+    pub fn compressed_bytes_written_so_far(&self) -> u64 {
+        self.header_size + self.writer.compressed_position()
+    }
+
     /// Writes the GZIP trailer and returns the inner stream.
     ///
     /// # Examples
@@ -840,7 +1828,10 @@ where
     /// let mut encoder = AutoFinishUnchecked::new(Encoder::new(&mut buf).unwrap());
     /// io::copy(&mut &plain[..], &mut encoder).unwrap();
     /// ```
-    pub fn finish(self) -> Finish<W, io::Error> {
+    pub fn finish(mut self) -> Finish<W, io::Error> {
+        if let Err(e) = self.flush_text_probe() {
+            return Finish::new(self.writer.into_inner(), Some(e));
+        }
         let trailer = Trailer {
             crc32: self.crc32.value(),
             input_size: self.input_size,
@@ -863,7 +1854,26 @@ where
     }
 
     /// Unwraps the `Encoder`, returning the inner stream.
-    pub fn into_inner(self) -> W {
+    ///
+    /// If the header has not been written yet (see `EncodeOptions::auto_text_flag`),
+    /// it is written to the inner stream before returning.
+    /// Use `flush` beforehand to handle the error of the write.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::{Decoder, Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().auto_text_flag(true);
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello").unwrap();
+    ///
+    /// let inner = encoder.into_inner();
+    /// let decoder = Decoder::new(&inner[..]).unwrap();
+    /// assert!(decoder.header().is_text_hint());
+    /// ```
+    pub fn into_inner(mut self) -> W {
+        let _ = self.flush_text_probe();
         self.writer.into_inner()
     }
 }
@@ -873,12 +1883,22 @@ where
     E: lz77::Lz77Encode,
 {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let written_size = self.writer.write(buf)?;
+        let written_size = if let Some(ref mut probe) = self.text_probe {
+            let size = cmp::min(buf.len(), TEXT_PROBE_SIZE - probe.len());
+            probe.extend_from_slice(&buf[..size]);
+            if probe.len() == TEXT_PROBE_SIZE {
+                self.flush_text_probe()?;
+            }
+            size
+        } else {
+            self.writer.write(buf)?
+        };
         self.crc32.update(&buf[..written_size]);
         self.input_size = self.input_size.wrapping_add(written_size as u32);
         Ok(written_size)
     }
     fn flush(&mut self) -> io::Result<()> {
+        self.flush_text_probe()?;
         self.writer.flush()
     }
 }
@@ -892,135 +1912,288 @@ where
     }
 }
 
-/// GZIP decoder.
+/// GZIP encoder which splits the output into multiple members.
+///
+/// When the number of the uncompressed bytes in the current member reaches `member_size`,
+/// the member is closed and a new member with the same header is started by the next write.
+/// Because each member can be decoded independently, a reader can start decoding at
+/// the member which contains the desired position (see `MemberScanner`).
+///
+/// The encoded data of the current member are buffered in memory until they are
+/// written to the inner stream by each `write` call.
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+/// use libflate::gzip::{MemberScanner, MultiDecoder, MultiMemberEncoder};
+///
+/// let mut encoder = MultiMemberEncoder::new(Vec::new(), 6).unwrap();
+/// encoder.write_all(b"Hello World!").unwrap();
+/// assert_eq!(encoder.member_count(), 2);
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// assert_eq!(MemberScanner::new(&encoded_data[..]).count(), 2);
+///
+/// let mut decoded_data = Vec::new();
+/// MultiDecoder::new(&encoded_data[..]).unwrap().read_to_end(&mut decoded_data).unwrap();
+/// assert_eq!(decoded_data, b"Hello World!");
+/// ```
 #[derive(Debug)]
-pub struct Decoder<R> {
-    header: Header,
-    reader: deflate::Decoder<R>,
-    crc32: checksum::Crc32,
-    eos: bool,
+pub struct MultiMemberEncoder<W, E = lz77::DefaultLz77Encoder>
+where
+    E: lz77::Lz77Encode,
+{
+    inner: W,
+    member: Encoder<Vec<u8>, E>,
+    options: EncodeOptions<E>,
+    member_size: u64,
+    member_written: u64,
+    member_count: u64,
 }
-impl<R> Decoder<R>
+impl<W> MultiMemberEncoder<W, lz77::DefaultLz77Encoder>
 where
-    R: io::Read,
+    W: io::Write,
 {
-    /// Makes a new decoder instance.
+    /// Makes a new encoder instance which starts a new member every `member_size` uncompressed bytes.
     ///
-    /// `inner` is to be decoded GZIP stream.
+    /// Encoded GZIP stream is written to `inner`.
     ///
-    /// # Examples
-    /// ```
-    /// use std::io::Read;
-    /// use libflate::gzip::Decoder;
+    /// # Panics
     ///
-    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
-    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
-    ///                     163, 28, 41, 28, 12, 0, 0, 0];
+    /// Panics if `member_size` is `0`.
+    pub fn new(inner: W, member_size: u64) -> io::Result<Self> {
+        Self::with_options(inner, member_size, EncodeOptions::new())
+    }
+}
+impl<W, E> MultiMemberEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone,
+{
+    /// Makes a new encoder instance with specified options.
     ///
-    /// let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
-    /// let mut buf = Vec::new();
-    /// decoder.read_to_end(&mut buf).unwrap();
+    /// Every member is encoded with a copy of `options` (including the header).
     ///
-    /// assert_eq!(buf, b"Hello World!");
-    /// ```
-    pub fn new(mut inner: R) -> io::Result<Self> {
-        let header = Header::read_from(&mut inner)?;
-        Ok(Self::with_header(inner, header))
-    }
-
-    /// Returns the header of the GZIP stream.
+    /// # Panics
+    ///
+    /// Panics if `member_size` is `0`.
     ///
     /// # Examples
     /// ```
-    /// use libflate::gzip::{Decoder, Os};
+    /// use std::io::Write;
+    /// use libflate::gzip::{EncodeOptions, HeaderBuilder, MemberScanner, MultiMemberEncoder};
     ///
-    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
-    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
-    ///                     163, 28, 41, 28, 12, 0, 0, 0];
+    /// let header = HeaderBuilder::new().modification_time(123).finish();
+    /// let options = EncodeOptions::new().header(header);
+    /// let mut encoder = MultiMemberEncoder::with_options(Vec::new(), 4, options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
     ///
-    /// let decoder = Decoder::new(&encoded_data[..]).unwrap();
-    /// assert_eq!(decoder.header().os(), Os::Unix);
+    /// for member in MemberScanner::new(&encoded_data[..]) {
+    ///     assert_eq!(member.unwrap().header().modification_time(), 123);
+    /// }
     /// ```
-    pub fn header(&self) -> &Header {
-        &self.header
+    pub fn with_options(inner: W, member_size: u64, options: EncodeOptions<E>) -> io::Result<Self> {
+        assert_ne!(member_size, 0, "The member size must be positive");
+        let member = Encoder::with_options(Vec::new(), options.clone())?;
+        let mut encoder = MultiMemberEncoder {
+            inner,
+            member,
+            options,
+            member_size,
+            member_written: 0,
+            member_count: 1,
+        };
+        encoder.write_buffered()?;
+        Ok(encoder)
+    }
+
+    /// Returns the number of the members started so far (including the current one).
+    pub fn member_count(&self) -> u64 {
+        self.member_count
+    }
+
+    /// Closes the current member and writes the GZIP trailer of it, returning the inner stream.
+    pub fn finish(self) -> Finish<W, io::Error> {
+        let MultiMemberEncoder {
+            mut inner, member, ..
+        } = self;
+        let result = member
+            .finish()
+            .into_result()
+            .and_then(|buf| inner.write_all(&buf))
+            .and_then(|_| inner.flush());
+        Finish::new(inner, result.err())
     }
 
     /// Returns the immutable reference to the inner stream.
-    pub fn as_inner_ref(&self) -> &R {
-        self.reader.as_inner_ref()
+    pub fn as_inner_ref(&self) -> &W {
+        &self.inner
     }
 
     /// Returns the mutable reference to the inner stream.
-    pub fn as_inner_mut(&mut self) -> &mut R {
-        self.reader.as_inner_mut()
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.inner
     }
 
-    /// Unwraps this `Decoder`, returning the underlying reader.
-    ///
-    /// # Examples
-    /// ```
-    /// use std::io::Cursor;
-    /// use libflate::gzip::Decoder;
+    /// Unwraps the `MultiMemberEncoder`, returning the inner stream.
     ///
-    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
-    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
-    ///                     163, 28, 41, 28, 12, 0, 0, 0];
-    ///
-    /// let decoder = Decoder::new(Cursor::new(&encoded_data[..])).unwrap();
-    /// assert_eq!(decoder.into_inner().into_inner(), &encoded_data[..]);
-    /// ```
-    pub fn into_inner(self) -> R {
-        self.reader.into_inner()
+    /// The data of the current member which have not been written yet are discarded.
+    pub fn into_inner(self) -> W {
+        self.inner
     }
 
-    fn with_header(inner: R, header: Header) -> Self {
-        Decoder {
-            header,
-            reader: deflate::Decoder::new(inner),
-            crc32: checksum::Crc32::new(),
-            eos: false,
-        }
+    fn write_buffered(&mut self) -> io::Result<()> {
+        let buf = self.member.as_inner_mut();
+        self.inner.write_all(buf)?;
+        buf.clear();
+        Ok(())
+    }
+
+    fn start_next_member(&mut self) -> io::Result<()> {
+        let next = Encoder::with_options(Vec::new(), self.options.clone())?;
+        let buf = mem::replace(&mut self.member, next)
+            .finish()
+            .into_result()?;
+        self.inner.write_all(&buf)?;
+        self.member_written = 0;
+        self.member_count += 1;
+        self.write_buffered()
     }
 }
-impl<R> io::Read for Decoder<R>
+impl<W, E> io::Write for MultiMemberEncoder<W, E>
 where
-    R: io::Read,
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone,
 {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.eos {
-            Ok(0)
-        } else {
-            let read_size = self.reader.read(buf)?;
-            self.crc32.update(&buf[..read_size]);
-            if read_size == 0 {
-                self.eos = true;
-                let trailer = Trailer::read_from(self.reader.as_inner_mut())?;
-                // checksum verification is skipped during fuzzing
-                // so that random data from fuzzer can reach actually interesting code
-                // Compilation flag 'fuzzing' is automatically set by all 3 Rust fuzzers.
-                if cfg!(not(fuzzing)) && trailer.crc32 != self.crc32.value() {
-                    Err(invalid_data_error!(
-                        "CRC32 mismatched: value={}, expected={}",
-                        self.crc32.value(),
-                        trailer.crc32
-                    ))
-                } else {
-                    Ok(0)
-                }
-            } else {
-                Ok(read_size)
-            }
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
         }
+        if self.member_written == self.member_size {
+            self.start_next_member()?;
+        }
+        let size = cmp::min(buf.len() as u64, self.member_size - self.member_written) as usize;
+        let written_size = self.member.write(&buf[..size])?;
+        self.member_written += written_size as u64;
+        self.write_buffered()?;
+        Ok(written_size)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.member.flush()?;
+        self.write_buffered()?;
+        self.inner.flush()
+    }
+}
+impl<W, E> Complete for MultiMemberEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone,
+{
+    fn complete(self) -> io::Result<()> {
+        self.finish().into_result().map(|_| ())
     }
 }
 
-/// A decoder that decodes all members in a GZIP stream.
+/// Options for a GZIP decoder.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct DecodeOptions {
+    options: deflate::DecodeOptions,
+    lenient: bool,
+}
+impl DecodeOptions {
+    /// Makes a default instance.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::{Decoder, DecodeOptions};
+    ///
+    /// let options = DecodeOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Specifies the options of the underlying DEFLATE decoder.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate;
+    /// use libflate::gzip::DecodeOptions;
+    ///
+    /// let options = DecodeOptions::new().deflate_options(deflate::DecodeOptions::new().window_bits(10));
+    /// assert_eq!(options.get_deflate_options().get_window_bits(), 10);
+    /// ```
+    pub fn deflate_options(mut self, options: deflate::DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the options of the underlying DEFLATE decoder.
+    pub fn get_deflate_options(&self) -> &deflate::DecodeOptions {
+        &self.options
+    }
+
+    /// Enables the lenient mode.
+    ///
+    /// In the lenient mode, a mismatch of the CRC-32 or the `ISIZE` field in the trailer
+    /// is not an error but is recorded as a warning (see `Decoder::warnings`).
+    /// This is similar to `gunzip --force` and is useful for recovering data written by buggy encoders.
+    ///
+    /// Errors in the compressed data itself (e.g., an invalid huffman code or block type)
+    /// are reported as errors even in the lenient mode.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::gzip::{self, Decoder, DecodeOptions};
+    /// use libflate::DecodeWarning;
+    ///
+    /// let mut encoded_data = gzip::compress(b"Hello World!");
+    /// let len = encoded_data.len();
+    /// encoded_data[len - 8] ^= 1; // Corrupts the CRC-32 in the trailer
+    ///
+    /// let options = DecodeOptions::new().lenient();
+    /// let mut decoder = Decoder::with_options(&encoded_data[..], options).unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// assert!(matches!(decoder.warnings(), [DecodeWarning::ChecksumMismatch { .. }]));
+    /// ```
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Returns `true` if the lenient mode is enabled, otherwise `false`.
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+}
+impl From<deflate::DecodeOptions> for DecodeOptions {
+    fn from(options: deflate::DecodeOptions) -> Self {
+        DecodeOptions::new().deflate_options(options)
+    }
+}
+
+/// GZIP decoder.
 #[derive(Debug)]
-pub struct MultiDecoder<R> {
+pub struct Decoder<R> {
     header: Header,
-    decoder: Result<Decoder<R>, R>,
+    header_size: u64,
+    stream_start: Option<u64>,
+    reader: deflate::Decoder<R>,
+    crc32: crc32::Crc32,
+    input_size: u32,
+    eos: bool,
+    trailer: Option<Trailer>,
+    lenient: bool,
+    warnings: Vec<DecodeWarning>,
+    partial: bool,
+    position: u64,
+    index: Option<Index>,
 }
-impl<R> MultiDecoder<R>
+impl<R> Decoder<R>
 where
     R: io::Read,
 {
@@ -1031,132 +2204,1504 @@ where
     /// # Examples
     /// ```
     /// use std::io::Read;
-    /// use libflate::gzip::MultiDecoder;
+    /// use libflate::gzip::Decoder;
     ///
-    /// let mut encoded_data = Vec::new();
+    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+    ///                     163, 28, 41, 28, 12, 0, 0, 0];
     ///
-    /// // Add a member (a GZIP binary that represents "Hello ")
-    /// encoded_data.extend(&[31, 139, 8, 0, 51, 206, 75, 90, 0, 3, 5, 128, 49, 9, 0, 0, 0, 194, 170, 24,
-    ///                       199, 34, 126, 3, 251, 127, 163, 131, 71, 192, 252, 45, 234, 6, 0, 0, 0][..]);
+    /// let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
     ///
-    /// // Add another member (a GZIP binary that represents "World!")
-    /// encoded_data.extend(&[31, 139, 8, 0, 227, 207, 75, 90, 0, 3, 5, 128, 49, 9, 0, 0, 0, 194, 178, 152,
-    ///                       202, 2, 158, 130, 96, 255, 99, 120, 111, 4, 222, 157, 40, 118, 6, 0, 0, 0][..]);
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let (header, header_size) = Header::read_with_size(&mut inner)?;
+        Ok(Self::with_header(inner, header, header_size))
+    }
+
+    /// Makes a new decoder instance which outputs at most `limit` bytes.
     ///
-    /// let mut decoder = MultiDecoder::new(&encoded_data[..]).unwrap();
+    /// Once the decoded data exceeds `limit` bytes, reading returns an error of `io::ErrorKind::Other`.
+    /// This is useful to protect against decompression bombs.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{ErrorKind, Read};
+    /// use libflate::gzip::Decoder;
+    ///
+    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+    ///                     163, 28, 41, 28, 12, 0, 0, 0];
+    ///
+    /// let mut decoder = Decoder::with_max_output(&encoded_data[..], 5).unwrap();
+    /// let error = decoder.read_to_end(&mut Vec::new()).err().unwrap();
+    /// assert_eq!(error.kind(), ErrorKind::Other);
+    /// ```
+    pub fn with_max_output(inner: R, limit: u64) -> io::Result<Self> {
+        Self::with_options(inner, deflate::DecodeOptions::new().max_output(limit))
+    }
+
+    /// Makes a new decoder instance with specified options.
+    ///
+    /// `options` can be either `gzip::DecodeOptions` or `deflate::DecodeOptions`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::gzip::{Decoder, DecodeOptions};
+    ///
+    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+    ///                     163, 28, 41, 28, 12, 0, 0, 0];
+    ///
+    /// let options = DecodeOptions::new().lenient();
+    /// let mut decoder = Decoder::with_options(&encoded_data[..], options).unwrap();
     /// let mut buf = Vec::new();
     /// decoder.read_to_end(&mut buf).unwrap();
     ///
     /// assert_eq!(buf, b"Hello World!");
+    /// assert!(decoder.warnings().is_empty());
     /// ```
-    pub fn new(inner: R) -> io::Result<Self> {
-        let decoder = Decoder::new(inner)?;
-        Ok(MultiDecoder {
-            header: decoder.header().clone(),
-            decoder: Ok(decoder),
-        })
+    pub fn with_options<O>(mut inner: R, options: O) -> io::Result<Self>
+    where
+        O: Into<DecodeOptions>,
+    {
+        let (header, header_size) = Header::read_with_size(&mut inner)?;
+        Ok(Self::with_header_and_options(
+            inner,
+            header,
+            header_size,
+            options.into(),
+        ))
     }
 
-    /// Returns the header of the current member in the GZIP stream.
+    /// Makes a new decoder instance which pre-loads the dictionary specified in the header.
+    ///
+    /// GZIP has no preset dictionary (unlike ZLIB's `FDICT`), so the dictionary is identified
+    /// by the extra subfield with the ID `DICTIONARY_ID_SUBFIELD` (`"DI"`),
+    /// whose data is the Adler-32 checksum of the dictionary in big-endian (four bytes, like ZLIB's `DICTID`).
+    ///
+    /// If the subfield is present, `lookup` is called with the checksum and
+    /// the returned dictionary is loaded into the window of the DEFLATE decoder.
+    /// An error is returned if `lookup` returns `None`, the subfield is malformed,
+    /// or the checksum of the returned dictionary differs from the one in the subfield.
+    /// If the subfield is absent, this behaves the same as `Decoder::new` (and `lookup` is not called).
     ///
     /// # Examples
     /// ```
-    /// use libflate::gzip::{MultiDecoder, Os};
+    /// use std::io::{Read, Write};
+    /// use libflate::{adler32, deflate};
+    /// use libflate::gzip::{Decoder, Encoder, ExtraFieldBuilder, HeaderBuilder, DICTIONARY_ID_SUBFIELD};
+    ///
+    /// let dict: &'static [u8] = b"Content-Type: text/html";
+    /// let dict_id = adler32::update(1, dict);
+    /// let extra = ExtraFieldBuilder::new()
+    ///     .subfield(DICTIONARY_ID_SUBFIELD, &dict_id.to_be_bytes())
+    ///     .unwrap()
+    ///     .finish();
+    /// let header = HeaderBuilder::new().extra_field(extra).finish();
+    /// let options = deflate::EncodeOptions::new().dictionary(dict);
+    /// let deflate = deflate::Encoder::with_options(Vec::new(), options).unwrap();
+    /// let mut encoder = Encoder::from_deflate_encoder(header, deflate).unwrap();
+    /// encoder.write_all(b"Content-Type: text/plain").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let lookup = |id| if id == dict_id { Some(dict) } else { None };
+    /// let mut decoder = Decoder::with_dictionary_id_check(&encoded_data[..], lookup).unwrap();
+    /// let mut decoded_data = Vec::new();
+    /// decoder.read_to_end(&mut decoded_data).unwrap();
+    /// assert_eq!(decoded_data, b"Content-Type: text/plain");
+    /// ```
+    pub fn with_dictionary_id_check<F, D>(mut inner: R, lookup: F) -> io::Result<Self>
+    where
+        F: FnOnce(u32) -> Option<D>,
+        D: AsRef<[u8]>,
+    {
+        let (header, header_size) = Header::read_with_size(&mut inner)?;
+        let subfield = header
+            .extra_field()
+            .and_then(|f| f.get(DICTIONARY_ID_SUBFIELD));
+        let subfield = match subfield {
+            None => return Ok(Self::with_header(inner, header, header_size)),
+            Some(subfield) => subfield,
+        };
+        if subfield.data.len() != 4 {
+            return Err(invalid_data_error!(
+                "malformed dictionary ID subfield: {} bytes",
+                subfield.data.len()
+            ));
+        }
+        let id = u32::from_be_bytes([
+            subfield.data[0],
+            subfield.data[1],
+            subfield.data[2],
+            subfield.data[3],
+        ]);
+        let dict =
+            lookup(id).ok_or_else(|| invalid_data_error!("unknown dictionary: ID=0x{:08X}", id))?;
+        let dict = dict.as_ref();
+        let actual_id = adler32::update(1, dict);
+        if actual_id != id {
+            return Err(invalid_data_error!(
+                "dictionary ID mismatched: value=0x{:08X}, expected=0x{:08X}",
+                actual_id,
+                id
+            ));
+        }
+        let reader = deflate::Decoder::with_dictionary(inner, dict);
+        Ok(Self::with_header_and_reader(
+            header,
+            header_size,
+            reader,
+            false,
+        ))
+    }
+
+    /// Returns the header of the GZIP stream.
+    ///
+    /// The header is read when the decoder is made (e.g., by `Decoder::new`),
+    /// so it is available through a shared reference before decoding the body.
+    /// This makes it possible to decide whether to continue decoding by inspecting the header.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::ffi::CString;
+    /// use std::io::{Read, Write};
+    /// use libflate::gzip::{Decoder, Encoder, EncodeOptions, HeaderBuilder, Os};
     ///
     /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
     ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
     ///                     163, 28, 41, 28, 12, 0, 0, 0];
     ///
-    /// let decoder = MultiDecoder::new(&encoded_data[..]).unwrap();
+    /// let decoder = Decoder::new(&encoded_data[..]).unwrap();
     /// assert_eq!(decoder.header().os(), Os::Unix);
+    ///
+    /// // Inspects the file name and the modification time before decoding the body
+    /// let filename = CString::new("hello.txt").unwrap();
+    /// let header = HeaderBuilder::new().filename(filename.clone()).mtime(123).finish();
+    /// let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::new().header(header)).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
+    /// assert_eq!(decoder.header().filename(), Some(&filename));
+    /// assert_eq!(decoder.header().modification_time(), 123);
+    ///
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
     /// ```
     pub fn header(&self) -> &Header {
         &self.header
     }
 
+    /// Returns the trailer of the GZIP stream.
+    ///
+    /// `None` is returned until the end of the stream is reached (i.e., `read` returns `0`)
+    /// and the trailer is read.
+    /// The trailer is available even if its verification failed.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{self, Write};
+    /// use libflate::gzip::{Decoder, Encoder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
+    /// assert!(decoder.trailer().is_none());
+    ///
+    /// io::copy(&mut decoder, &mut io::sink()).unwrap();
+    /// assert_eq!(decoder.trailer().map(|t| t.input_size()), Some(12));
+    /// ```
+    pub fn trailer(&self) -> Option<&Trailer> {
+        self.trailer.as_ref()
+    }
+
+    /// Resets the state of the decoder and replaces the inner stream with `new_reader`.
+    ///
+    /// The header of the new stream is read from `new_reader`, and the CRC-32 and
+    /// the warnings of the previous stream are cleared.
+    /// The allocated buffers of the DEFLATE decoder are kept.
+    ///
+    /// Returns the previous inner stream.
+    /// If reading the header fails, an error is returned and the state of the decoder is not changed.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::gzip::{self, Decoder};
+    ///
+    /// let encoded0 = gzip::compress(b"Hello");
+    /// let encoded1 = gzip::compress(b"World!");
+    ///
+    /// let mut decoder = Decoder::new(&encoded0[..]).unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello");
+    ///
+    /// decoder.reset(&encoded1[..]).unwrap();
+    /// buf.clear();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"World!");
+    /// ```
+    pub fn reset(&mut self, mut new_reader: R) -> io::Result<R> {
+        let (header, header_size) = Header::read_with_size(&mut new_reader)?;
+        self.header = header;
+        self.header_size = header_size;
+        self.stream_start = None;
+        self.crc32 = crc32::Crc32::new();
+        self.input_size = 0;
+        self.eos = false;
+        self.trailer = None;
+        self.warnings.clear();
+        self.partial = false;
+        self.position = 0;
+        self.index = None;
+        Ok(self.reader.reset(new_reader))
+    }
+
+    /// Returns the warnings detected so far in the lenient mode.
+    ///
+    /// The trailer is verified after the end of the compressed data is reached,
+    /// so the warnings about it are available after the stream is consumed.
+    /// If the lenient mode is disabled, this always returns an empty slice.
+    pub fn warnings(&self) -> &[DecodeWarning] {
+        &self.warnings
+    }
+
+    /// Sets the index of the stream used to speed up seeking (see `io::Seek` implementation).
+    ///
+    /// The index must have been built from the same stream as the one being decoded.
+    pub fn set_index(&mut self, index: Option<Index>) {
+        self.index = index;
+    }
+
+    /// Returns the index of the stream set by `Decoder::set_index`.
+    pub fn index(&self) -> Option<&Index> {
+        self.index.as_ref()
+    }
+
     /// Returns the immutable reference to the inner stream.
     pub fn as_inner_ref(&self) -> &R {
-        match self.decoder {
-            Err(ref reader) => reader,
-            Ok(ref decoder) => decoder.as_inner_ref(),
-        }
+        self.reader.as_inner_ref()
     }
 
     /// Returns the mutable reference to the inner stream.
     pub fn as_inner_mut(&mut self) -> &mut R {
-        match self.decoder {
-            Err(ref mut reader) => reader,
-            Ok(ref mut decoder) => decoder.as_inner_mut(),
+        self.reader.as_inner_mut()
+    }
+
+    /// Unwraps this `Decoder`, returning the underlying reader.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Cursor;
+    /// use libflate::gzip::Decoder;
+    ///
+    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+    ///                     163, 28, 41, 28, 12, 0, 0, 0];
+    ///
+    /// let decoder = Decoder::new(Cursor::new(&encoded_data[..])).unwrap();
+    /// assert_eq!(decoder.into_inner().into_inner(), &encoded_data[..]);
+    /// ```
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+
+    fn with_header(inner: R, header: Header, header_size: u64) -> Self {
+        Self::with_header_and_options(inner, header, header_size, DecodeOptions::new())
+    }
+
+    fn with_header_and_options(
+        inner: R,
+        header: Header,
+        header_size: u64,
+        options: DecodeOptions,
+    ) -> Self {
+        let reader = deflate::Decoder::with_options(inner, options.options);
+        Self::with_header_and_reader(header, header_size, reader, options.lenient)
+    }
+
+    fn with_header_and_reader(
+        header: Header,
+        header_size: u64,
+        reader: deflate::Decoder<R>,
+        lenient: bool,
+    ) -> Self {
+        Decoder {
+            header,
+            header_size,
+            stream_start: None,
+            reader,
+            crc32: crc32::Crc32::new(),
+            input_size: 0,
+            eos: false,
+            trailer: None,
+            lenient,
+            warnings: Vec::new(),
+            partial: false,
+            position: 0,
+            index: None,
         }
     }
 
-    /// Unwraps this `MultiDecoder`, returning the underlying reader.
-    ///
-    /// # Examples
-    /// ```
-    /// use std::io::Cursor;
-    /// use libflate::gzip::MultiDecoder;
+    fn read_trailer(&mut self) -> io::Result<()> {
+        self.eos = true;
+        let trailer = Trailer::read_from(self.reader.as_inner_mut())?;
+        // checksum verification is skipped during fuzzing
+        // so that random data from fuzzer can reach actually interesting code
+        // Compilation flag 'fuzzing' is automatically set by all 3 Rust fuzzers.
+        //
+        // The trailer of a decoder started in the middle of the stream (see `Index::seek_to`)
+        // cannot be verified.
+        let result = if cfg!(not(fuzzing)) && !self.partial {
+            self.verify_trailer(&trailer)
+        } else {
+            Ok(())
+        };
+        self.trailer = Some(trailer);
+        result
+    }
+
+    fn verify_trailer(&mut self, trailer: &Trailer) -> io::Result<()> {
+        let mut problems = Vec::new();
+        if trailer.crc32 != self.crc32.value() {
+            problems.push(DecodeWarning::ChecksumMismatch {
+                expected: trailer.crc32,
+                got: self.crc32.value(),
+            });
+        }
+        if trailer.input_size != self.input_size {
+            problems.push(DecodeWarning::SizeMismatch {
+                expected: trailer.input_size,
+                got: self.input_size,
+            });
+        }
+        if self.lenient {
+            self.warnings.extend(problems);
+            Ok(())
+        } else if let Some(problem) = problems.into_iter().next() {
+            Err(problem.into_error().into())
+        } else {
+            Ok(())
+        }
+    }
+}
+impl<R> io::Read for Decoder<R>
+where
+    R: io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.eos {
+            Ok(0)
+        } else {
+            let read_size = self.reader.read(buf)?;
+            self.crc32.update(&buf[..read_size]);
+            self.input_size = self.input_size.wrapping_add(read_size as u32);
+            self.position += read_size as u64;
+            if read_size == 0 {
+                self.read_trailer().map(|()| 0)
+            } else {
+                Ok(read_size)
+            }
+        }
+    }
+}
+/// The decoded data are returned directly from the internal window buffer of the DEFLATE decoder
+/// (without copying), and the CRC-32 is updated when the data are consumed.
+///
+/// # Examples
+/// ```
+/// use std::io::BufRead;
+/// use libflate::gzip::{self, Decoder};
+///
+/// let encoded_data = gzip::compress(b"Hello\nWorld!\n");
+/// let decoder = Decoder::new(&encoded_data[..]).unwrap();
+/// let lines = decoder.lines().collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(lines, ["Hello", "World!"]);
+/// ```
+impl<R> io::BufRead for Decoder<R>
+where
+    R: io::Read,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if !self.eos && self.reader.fill_buf()?.is_empty() {
+            self.read_trailer()?;
+        }
+        if self.eos {
+            Ok(&[])
+        } else {
+            self.reader.fill_buf()
+        }
+    }
+    fn consume(&mut self, amt: usize) {
+        let data = self.reader.buffered_data();
+        let amt = cmp::min(amt, data.len());
+        self.crc32.update(&data[..amt]);
+        self.input_size = self.input_size.wrapping_add(amt as u32);
+        self.position += amt as u64;
+        self.reader.consume(amt);
+    }
+}
+impl<R> Seek for Decoder<R>
+where
+    R: io::Read + Seek,
+{
+    /// Seeks to an offset of the decoded data.
+    ///
+    /// Seeking forward decodes and discards the data up to the offset.
+    /// Seeking backward rewinds the inner stream to the start of the GZIP stream
+    /// (i.e., the position of the inner stream when the decoder was made) and decodes it again,
+    /// unless an index is set by `Decoder::set_index`, in which case decoding restarts
+    /// from the nearest preceding point of the index.
+    /// `SeekFrom::End` decodes the rest of the stream to know the size of the decoded data.
+    ///
+    /// Seeking beyond the end of the decoded data is an error.
+    /// If the decoding does not start at the beginning of the stream,
+    /// the CRC-32 and `ISIZE` in the trailer are not verified.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Cursor, Read, Seek, SeekFrom};
+    /// use libflate::gzip::{self, Decoder};
+    ///
+    /// let encoded_data = gzip::compress(b"Hello World!");
+    /// let mut decoder = Decoder::new(Cursor::new(encoded_data)).unwrap();
+    ///
+    /// let mut buf = [0; 5];
+    /// decoder.seek(SeekFrom::Start(6)).unwrap();
+    /// decoder.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"World");
+    ///
+    /// decoder.seek(SeekFrom::Current(-11)).unwrap();
+    /// decoder.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"Hello");
+    ///
+    /// assert_eq!(decoder.seek(SeekFrom::End(-1)).unwrap(), 11);
+    /// ```
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let start = self.stream_start()?;
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                io::copy(self, &mut io::sink())?;
+                self.position.checked_add_signed(offset)
+            }
+        }
+        .ok_or_else(|| {
+            invalid_input_error!("Invalid seek to a negative or overflowing position")
+        })?;
+
+        let index = self.index.take();
+        let point = index.as_ref().and_then(|index| {
+            index
+                .points
+                .iter()
+                .take_while(|p| p.uncompressed_offset <= target)
+                .last()
+        });
+        let result = match point {
+            Some(point) if target < self.position || self.position < point.uncompressed_offset => {
+                self.restart_at(start, Some(point))
+            }
+            None if target < self.position => self.restart_at(start, None),
+            _ => Ok(()),
+        };
+        self.index = index;
+        result?;
+
+        let skip = target - self.position;
+        io::copy(&mut io::Read::take(&mut *self, skip), &mut io::sink())?;
+        if self.position != target {
+            return Err(invalid_input_error!(
+                "Seek beyond the end of the decoded data: offset={}, size={}",
+                target,
+                self.position
+            ));
+        }
+        Ok(target)
+    }
+}
+impl<R> Decoder<R>
+where
+    R: io::Read + Seek,
+{
+    /// Returns the position of the start of the GZIP stream in the inner stream.
+    ///
+    /// This must be called before the decoding is restarted by `restart_at`,
+    /// because the position is derived from the number of the bytes read so far.
+    fn stream_start(&mut self) -> io::Result<u64> {
+        if let Some(start) = self.stream_start {
+            return Ok(start);
+        }
+        let trailer_size = if self.trailer.is_some() { 8 } else { 0 };
+        let read_bytes = self.header_size + self.reader.input_bytes_read() + trailer_size;
+        let position = self.reader.as_inner_mut().stream_position()?;
+        let start = position.checked_sub(read_bytes).ok_or_else(|| {
+            invalid_data_error!(
+                "The inner stream has been moved: position={}, read_bytes={}",
+                position,
+                read_bytes
+            )
+        })?;
+        self.stream_start = Some(start);
+        Ok(start)
+    }
+
+    fn restart_at(&mut self, start: u64, point: Option<&IndexPoint>) -> io::Result<()> {
+        match point {
+            None => {
+                let inner = self.reader.as_inner_mut();
+                inner.seek(SeekFrom::Start(start))?;
+                Header::read_from(inner)?;
+                self.reader.reset_to_sync_point(0, 0, &[]);
+                self.position = 0;
+            }
+            Some(point) => {
+                let inner = self.reader.as_inner_mut();
+                inner.seek(SeekFrom::Start(start + point.compressed_offset))?;
+                self.reader
+                    .reset_to_sync_point(point.bits, point.bit_buf, &point.window);
+                self.position = point.uncompressed_offset;
+            }
+        }
+        self.crc32 = crc32::Crc32::new();
+        self.input_size = 0;
+        self.eos = false;
+        self.trailer = None;
+        self.warnings.clear();
+        self.partial = self.position != 0;
+        Ok(())
+    }
+}
+
+/// A decoder that decodes all members in a GZIP stream.
+#[derive(Debug)]
+pub struct MultiDecoder<R> {
+    header: Header,
+    decoder: Result<Decoder<R>, R>,
+}
+impl<R> MultiDecoder<R>
+where
+    R: io::Read,
+{
+    /// Makes a new decoder instance.
+    ///
+    /// `inner` is to be decoded GZIP stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::gzip::MultiDecoder;
+    ///
+    /// let mut encoded_data = Vec::new();
+    ///
+    /// // Add a member (a GZIP binary that represents "Hello ")
+    /// encoded_data.extend(&[31, 139, 8, 0, 51, 206, 75, 90, 0, 3, 5, 128, 49, 9, 0, 0, 0, 194, 170, 24,
+    ///                       199, 34, 126, 3, 251, 127, 163, 131, 71, 192, 252, 45, 234, 6, 0, 0, 0][..]);
+    ///
+    /// // Add another member (a GZIP binary that represents "World!")
+    /// encoded_data.extend(&[31, 139, 8, 0, 227, 207, 75, 90, 0, 3, 5, 128, 49, 9, 0, 0, 0, 194, 178, 152,
+    ///                       202, 2, 158, 130, 96, 255, 99, 120, 111, 4, 222, 157, 40, 118, 6, 0, 0, 0][..]);
+    ///
+    /// let mut decoder = MultiDecoder::new(&encoded_data[..]).unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn new(inner: R) -> io::Result<Self> {
+        let decoder = Decoder::new(inner)?;
+        Ok(MultiDecoder {
+            header: decoder.header().clone(),
+            decoder: Ok(decoder),
+        })
+    }
+
+    /// Returns the header of the current member in the GZIP stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::{MultiDecoder, Os};
+    ///
+    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+    ///                     163, 28, 41, 28, 12, 0, 0, 0];
+    ///
+    /// let decoder = MultiDecoder::new(&encoded_data[..]).unwrap();
+    /// assert_eq!(decoder.header().os(), Os::Unix);
+    /// ```
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        match self.decoder {
+            Err(ref reader) => reader,
+            Ok(ref decoder) => decoder.as_inner_ref(),
+        }
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        match self.decoder {
+            Err(ref mut reader) => reader,
+            Ok(ref mut decoder) => decoder.as_inner_mut(),
+        }
+    }
+
+    /// Unwraps this `MultiDecoder`, returning the underlying reader.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Cursor;
+    /// use libflate::gzip::MultiDecoder;
+    ///
+    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+    ///                     163, 28, 41, 28, 12, 0, 0, 0];
+    ///
+    /// let decoder = MultiDecoder::new(Cursor::new(&encoded_data[..])).unwrap();
+    /// assert_eq!(decoder.into_inner().into_inner(), &encoded_data[..]);
+    /// ```
+    pub fn into_inner(self) -> R {
+        match self.decoder {
+            Err(reader) => reader,
+            Ok(decoder) => decoder.into_inner(),
+        }
+    }
+}
+impl<R> io::Read for MultiDecoder<R>
+where
+    R: io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read_size = match self.decoder {
+            Err(_) => return Ok(0),
+            Ok(ref mut decoder) => decoder.read(buf)?,
+        };
+        // take_mut closure must have the type it borrows as return type,
+        // so we put the function return result to this variable instead.
+        // If function logic is correct, these initial values will never be returned.
+        let mut result: io::Result<usize> = Err(io::Error::other(
+            "If you see this error, please report a bug in libflate",
+        ));
+        if read_size == 0 {
+            take_mut::take(self, |mut owned_self| {
+                let mut reader = owned_self.decoder.ok().expect("Never fails").into_inner();
+                match Header::read_with_size(&mut reader) {
+                    Err(e) => {
+                        if e.kind() == io::ErrorKind::UnexpectedEof {
+                            result = Ok(0);
+                        } else {
+                            result = Err(e);
+                        }
+                        owned_self.decoder = Err(reader);
+                        owned_self
+                    }
+                    Ok((header, header_size)) => {
+                        owned_self.header = header.clone();
+                        owned_self.decoder = Ok(Decoder::with_header(reader, header, header_size));
+                        result = owned_self.read(buf);
+                        owned_self
+                    }
+                }
+            })
+        } else {
+            result = Ok(read_size);
+        }
+        result
+    }
+}
+
+/// The ID of the extra subfield which holds the dictionary ID
+/// (see `Decoder::with_dictionary_id_check`).
+pub const DICTIONARY_ID_SUBFIELD: [u8; 2] = *b"DI";
+
+/// An alias of `MultiDecoder`.
+///
+/// It transparently reads through the member boundaries of a multi-member GZIP stream
+/// (e.g., the output of `cat file1.gz file2.gz`).
+pub type MultiMemberDecoder<R> = MultiDecoder<R>;
+
+/// An iterator-like reader that yields a decoder for each member in a GZIP stream.
+///
+/// Because each decoder borrows the underlying reader, this does not implement `Iterator`.
+/// Use `next_member` to get the decoders one by one.
+#[derive(Debug)]
+pub struct MemberIterator<R> {
+    reader: R,
+    eos: bool,
+}
+impl<R> MemberIterator<R>
+where
+    R: io::Read,
+{
+    /// Makes a new instance.
+    ///
+    /// `inner` is to be decoded GZIP stream.
+    pub fn new(inner: R) -> Self {
+        MemberIterator {
+            reader: inner,
+            eos: false,
+        }
+    }
+
+    /// Reads the header of the next member and returns the decoder of the member.
+    ///
+    /// The returned decoder must be read to the end before calling this method again,
+    /// otherwise the next header cannot be found.
+    /// If there are no more members, `None` will be returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::gzip::{Encoder, MemberIterator};
+    ///
+    /// let mut encoded_data = Vec::new();
+    /// for text in ["Hello ", "World!"] {
+    ///     let mut encoder = Encoder::new(Vec::new()).unwrap();
+    ///     encoder.write_all(text.as_bytes()).unwrap();
+    ///     encoded_data.extend(encoder.finish().into_result().unwrap());
+    /// }
+    ///
+    /// let mut members = MemberIterator::new(&encoded_data[..]);
+    /// let mut texts = Vec::new();
+    /// while let Some(decoder) = members.next_member() {
+    ///     let mut text = String::new();
+    ///     decoder.unwrap().read_to_string(&mut text).unwrap();
+    ///     texts.push(text);
+    /// }
+    /// assert_eq!(texts, ["Hello ", "World!"]);
+    /// ```
+    pub fn next_member(&mut self) -> Option<io::Result<Decoder<&mut R>>> {
+        if self.eos {
+            return None;
+        }
+        match Header::read_with_size(&mut self.reader) {
+            Err(e) => {
+                self.eos = true;
+                if e.kind() == io::ErrorKind::UnexpectedEof {
+                    None
+                } else {
+                    Some(Err(e))
+                }
+            }
+            Ok((header, header_size)) => Some(Ok(Decoder::with_header(
+                &mut self.reader,
+                header,
+                header_size,
+            ))),
+        }
+    }
+
+    /// Unwraps this `MemberIterator`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Information about a member in a GZIP stream.
+///
+/// This is yielded by `MemberScanner` and `MemberReader::members`.
+#[derive(Debug, Clone)]
+pub struct MemberInfo {
+    header: Header,
+    start: u64,
+    end: u64,
+}
+impl MemberInfo {
+    /// Returns the header of the member.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the offset of the first byte of the member (i.e., the header) in the source stream.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Returns the offset just past the last byte of the member (i.e., the trailer) in the source stream.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Returns the size of the member in bytes, including the header and the trailer.
+    pub fn compressed_size(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Seeks `source` to the start of the member and returns a decoder of the member.
+    ///
+    /// `source` must be the stream from which this information was obtained.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Cursor, Read, Write};
+    /// use libflate::gzip::{Encoder, MemberReader};
+    ///
+    /// let mut encoded_data = Vec::new();
+    /// for text in ["Hello ", "World!"] {
+    ///     let mut encoder = Encoder::new(Vec::new()).unwrap();
+    ///     encoder.write_all(text.as_bytes()).unwrap();
+    ///     encoded_data.extend(encoder.finish().into_result().unwrap());
+    /// }
+    ///
+    /// let mut reader = MemberReader::new(Cursor::new(encoded_data));
+    /// let members = reader.members().collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(members.len(), 2);
+    ///
+    /// let mut source = reader.into_inner();
+    /// let mut text = String::new();
+    /// members[1].decode(&mut source).unwrap().read_to_string(&mut text).unwrap();
+    /// assert_eq!(text, "World!");
+    /// ```
+    pub fn decode<'a, R>(&self, source: &'a mut R) -> io::Result<Decoder<io::Take<&'a mut R>>>
+    where
+        R: io::Read + io::Seek,
+    {
+        source.seek(io::SeekFrom::Start(self.start))?;
+        Decoder::new(io::Read::take(source, self.compressed_size()))
+    }
+}
+
+/// A forward-only scanner that yields the information of each member in a GZIP stream.
+///
+/// The compressed data of each member is decoded (and verified) but discarded,
+/// so that the position of the next member can be found.
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::gzip::{Encoder, EncodeOptions, HeaderBuilder, MemberScanner};
+///
+/// let mut encoded_data = Vec::new();
+/// for name in ["foo.txt", "bar.txt"] {
+///     let header = HeaderBuilder::new().name(name).unwrap().finish();
+///     let options = EncodeOptions::new().header(header);
+///     let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+///     encoder.write_all(b"Hello World!").unwrap();
+///     encoded_data.extend(encoder.finish().into_result().unwrap());
+/// }
+///
+/// let names = MemberScanner::new(&encoded_data[..])
+///     .map(|m| m.unwrap().header().filename().unwrap().clone().into_string().unwrap())
+///     .collect::<Vec<_>>();
+/// assert_eq!(names, ["foo.txt", "bar.txt"]);
+/// ```
+#[derive(Debug)]
+pub struct MemberScanner<R> {
+    reader: CountingReader<R>,
+    error: Option<io::Error>,
+    eos: bool,
+}
+impl<R> MemberScanner<R>
+where
+    R: io::Read,
+{
+    /// Makes a new instance.
+    ///
+    /// `inner` is to be scanned GZIP stream.
+    /// The offsets of the yielded members are relative to the current position of `inner`.
+    pub fn new(inner: R) -> Self {
+        Self::with_offset(inner, 0)
+    }
+
+    fn with_offset(inner: R, offset: u64) -> Self {
+        MemberScanner {
+            reader: CountingReader::with_count(inner, offset),
+            error: None,
+            eos: false,
+        }
+    }
+
+    /// Unwraps this `MemberScanner`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+
+    fn scan_member(&mut self) -> io::Result<MemberInfo> {
+        let start = self.reader.bytes_read();
+        let header = Header::read_from(&mut self.reader)?;
+        let header_size = self.reader.bytes_read() - start;
+        let mut decoder = Decoder::with_header(&mut self.reader, header, header_size);
+        io::copy(&mut decoder, &mut io::sink())?;
+        Ok(MemberInfo {
+            header: decoder.header,
+            start,
+            end: self.reader.bytes_read(),
+        })
+    }
+}
+impl<R> Iterator for MemberScanner<R>
+where
+    R: io::Read,
+{
+    type Item = io::Result<MemberInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.eos {
+            return None;
+        }
+        if let Some(e) = self.error.take() {
+            self.eos = true;
+            return Some(Err(e));
+        }
+        let start = self.reader.bytes_read();
+        match self.scan_member() {
+            Err(e) => {
+                self.eos = true;
+                if e.kind() == io::ErrorKind::UnexpectedEof && self.reader.bytes_read() == start {
+                    None
+                } else {
+                    Some(Err(e))
+                }
+            }
+            Ok(member) => Some(Ok(member)),
+        }
+    }
+}
+
+/// A reader that lists the members in a seekable GZIP stream.
+///
+/// See the documentation of `MemberInfo::decode` for an example.
+#[derive(Debug)]
+pub struct MemberReader<R> {
+    inner: R,
+}
+impl<R> MemberReader<R>
+where
+    R: io::Read + io::Seek,
+{
+    /// Makes a new instance.
+    ///
+    /// `inner` is to be read GZIP stream.
+    pub fn new(inner: R) -> Self {
+        MemberReader { inner }
+    }
+
+    /// Returns an iterator over the members in the stream.
+    ///
+    /// The scan always starts from the beginning of the stream,
+    /// and the offsets of the yielded members are absolute positions in the stream.
+    pub fn members(&mut self) -> MemberScanner<&mut R> {
+        match self.inner.seek(io::SeekFrom::Start(0)) {
+            Err(e) => {
+                let mut scanner = MemberScanner::with_offset(&mut self.inner, 0);
+                scanner.error = Some(e);
+                scanner
+            }
+            Ok(offset) => MemberScanner::with_offset(&mut self.inner, offset),
+        }
+    }
+
+    /// Unwraps this `MemberReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Splits a multi-member GZIP stream into the raw bytes of each member.
+///
+/// The members are located by `MemberReader::members`
+/// (i.e., the compressed data are decoded and verified but the decoded data are discarded).
+/// Then the bytes of each member (the header, the compressed data and the trailer) are copied as is
+/// to the writer returned by `output`, which is called with the zero-based index and the information of the member.
+///
+/// Returns the number of the members.
+///
+/// # Examples
+/// ```
+/// use std::fs::{self, File};
+/// use std::io::Cursor;
+/// use libflate::gzip;
+///
+/// let members = [gzip::compress(b"Hello "), gzip::compress(b"World!")];
+/// let encoded_data = members.concat();
+///
+/// let dir = std::env::temp_dir();
+/// let path = |i| dir.join(format!("libflate-split-members-{}-{}.gz", std::process::id(), i));
+/// let count = gzip::split_members(Cursor::new(encoded_data), |i, _| File::create(path(i))).unwrap();
+/// assert_eq!(count, 2);
+/// for (i, member) in members.iter().enumerate() {
+///     assert_eq!(&fs::read(path(i)).unwrap(), member);
+///     fs::remove_file(path(i)).unwrap();
+/// }
+/// ```
+pub fn split_members<R, W, F>(src: R, mut output: F) -> io::Result<usize>
+where
+    R: io::Read + io::Seek,
+    W: io::Write,
+    F: FnMut(usize, &MemberInfo) -> io::Result<W>,
+{
+    let mut reader = MemberReader::new(src);
+    let members = reader.members().collect::<io::Result<Vec<_>>>()?;
+    let mut src = reader.into_inner();
+    for (i, member) in members.iter().enumerate() {
+        let mut writer = output(i, member)?;
+        src.seek(io::SeekFrom::Start(member.start()))?;
+        let size = io::copy(
+            &mut io::Read::take(&mut src, member.compressed_size()),
+            &mut writer,
+        )?;
+        if size != member.compressed_size() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "GZIP member is truncated",
+            ));
+        }
+        writer.flush()?;
+    }
+    Ok(members.len())
+}
+
+/// A point in a GZIP stream where decoding can be resumed.
+///
+/// Each point is located at the boundary of a DEFLATE block,
+/// and holds the last `INDEX_WINDOW_SIZE` bytes of the data decoded before it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct IndexPoint {
+    /// The offset of the first byte of the block that has not been consumed yet,
+    /// relative to the start of the GZIP stream.
+    pub compressed_offset: u64,
+
+    /// The number of the bytes decoded before the point.
+    pub uncompressed_offset: u64,
+
+    /// The last decoded bytes before the point.
+    ///
+    /// If fewer than `INDEX_WINDOW_SIZE` bytes have been decoded, the window is padded by zeros at the front.
+    pub window: [u8; INDEX_WINDOW_SIZE],
+
+    /// The number of the bits of the byte preceding `compressed_offset` that belong to the block (`0..=7`).
+    pub bits: u8,
+
+    /// The value of the `bits` bits of the byte preceding `compressed_offset`
+    /// (i.e., the byte shifted right by `8 - bits`).
+    pub bit_buf: u8,
+}
+impl fmt::Debug for IndexPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IndexPoint")
+            .field("compressed_offset", &self.compressed_offset)
+            .field("uncompressed_offset", &self.uncompressed_offset)
+            .field("window", &format_args!("[_; {}]", self.window.len()))
+            .field("bits", &self.bits)
+            .field("bit_buf", &self.bit_buf)
+            .finish()
+    }
+}
+
+/// A random-access index of a GZIP stream.
+///
+/// This is the same approach as `zran.c` in the zlib distribution:
+/// the index records the state of the decoder at some block boundaries (see `IndexPoint`),
+/// so that decoding can be resumed from the nearest point instead of the start of the stream.
+///
+/// Only the first member of a multi-member GZIP stream is indexed.
+///
+/// # Examples
+/// ```
+/// use std::io::{Cursor, Read};
+/// use libflate::gzip::{self, EncodeOptions, Encoder, IndexBuilder};
+///
+/// let plain = (0..100_000).map(|i| format!("{} ", i)).collect::<String>().into_bytes();
+/// let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::new().block_size(4096)).unwrap();
+/// std::io::copy(&mut &plain[..], &mut encoder).unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let index = IndexBuilder::with_interval(&encoded_data[..], 64 * 1024).unwrap().build().unwrap();
+/// assert!(index.points().len() > 1);
+///
+/// let mut decoder = index.seek_to(Cursor::new(&encoded_data), 300_000).unwrap();
+/// let mut buf = Vec::new();
+/// decoder.read_to_end(&mut buf).unwrap();
+/// assert_eq!(buf, &plain[300_000..]);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Index {
+    points: Vec<IndexPoint>,
+}
+impl Index {
+    /// Returns the points of the index in ascending order of the offsets.
+    pub fn points(&self) -> &[IndexPoint] {
+        &self.points
+    }
+
+    /// Makes a decoder which starts decoding at `uncompressed_offset` of the stream `r`.
+    ///
+    /// The offsets of the index are relative to the start of `r`.
+    /// The decoder resumes from the nearest point preceding `uncompressed_offset`,
+    /// and skips the data between the point and `uncompressed_offset`.
+    ///
+    /// Because the decoder does not decode the whole stream, the CRC-32 and `ISIZE`
+    /// in the trailer are not verified unless the decoding starts at the beginning of the stream.
+    pub fn seek_to<R>(&self, mut r: R, uncompressed_offset: u64) -> io::Result<Decoder<R>>
+    where
+        R: io::Read + Seek,
+    {
+        let point = self
+            .points
+            .iter()
+            .take_while(|p| p.uncompressed_offset <= uncompressed_offset)
+            .last()
+            .ok_or_else(|| invalid_input_error!("The index has no points"))?;
+
+        r.seek(SeekFrom::Start(0))?;
+        let (header, header_size) = Header::read_with_size(&mut r)?;
+        r.seek(SeekFrom::Start(point.compressed_offset))?;
+        let reader = deflate::Decoder::with_sync_point(r, point.bits, point.bit_buf, &point.window);
+        let mut decoder = Decoder::with_header_and_reader(header, header_size, reader, false);
+        decoder.stream_start = Some(0);
+        decoder.partial = point.uncompressed_offset != 0;
+        decoder.position = point.uncompressed_offset;
+        io::copy(
+            &mut io::Read::take(
+                &mut decoder,
+                uncompressed_offset - point.uncompressed_offset,
+            ),
+            &mut io::sink(),
+        )?;
+        Ok(decoder)
+    }
+
+    /// Writes the index to `writer` in a binary format.
+    ///
+    /// The written index can be read by `Index::read_from`.
+    pub fn write_to<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        writer.write_all(&INDEX_MAGIC)?;
+        writer.write_all(&[INDEX_VERSION])?;
+        writer.write_all(&(self.points.len() as u64).to_le_bytes())?;
+        for point in &self.points {
+            writer.write_all(&point.compressed_offset.to_le_bytes())?;
+            writer.write_all(&point.uncompressed_offset.to_le_bytes())?;
+            writer.write_all(&[point.bits, point.bit_buf])?;
+            writer.write_all(&point.window)?;
+        }
+        Ok(())
+    }
+
+    /// Reads an index written by `Index::write_to` from `reader`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::{self, Index, IndexBuilder};
+    ///
+    /// let encoded_data = gzip::compress(b"Hello World!");
+    /// let index = IndexBuilder::new(&encoded_data[..]).unwrap().build().unwrap();
+    ///
+    /// let mut buf = Vec::new();
+    /// index.write_to(&mut buf).unwrap();
+    /// assert_eq!(Index::read_from(&buf[..]).unwrap(), index);
+    /// ```
+    pub fn read_from<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: io::Read,
+    {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != INDEX_MAGIC {
+            return Err(invalid_data_error!(
+                "Unexpected magic bytes of an index: {:?}",
+                magic
+            ));
+        }
+        let mut version = [0; 1];
+        reader.read_exact(&mut version)?;
+        if version[0] != INDEX_VERSION {
+            return Err(invalid_data_error!(
+                "Unsupported index version: {}",
+                version[0]
+            ));
+        }
+
+        let mut buf = [0; 8];
+        reader.read_exact(&mut buf)?;
+        let count = u64::from_le_bytes(buf);
+        let mut points = Vec::new();
+        for _ in 0..count {
+            reader.read_exact(&mut buf)?;
+            let compressed_offset = u64::from_le_bytes(buf);
+            reader.read_exact(&mut buf)?;
+            let uncompressed_offset = u64::from_le_bytes(buf);
+            let mut bits = [0; 2];
+            reader.read_exact(&mut bits)?;
+            if bits[0] > 7 || (bits[0] > 0 && compressed_offset == 0) {
+                return Err(invalid_data_error!(
+                    "Invalid bit position of an index point: bits={}",
+                    bits[0]
+                ));
+            }
+            let mut window = [0; INDEX_WINDOW_SIZE];
+            reader.read_exact(&mut window)?;
+            points.push(IndexPoint {
+                compressed_offset,
+                uncompressed_offset,
+                window,
+                bits: bits[0],
+                bit_buf: bits[1],
+            });
+        }
+        Ok(Index { points })
+    }
+}
+
+/// A builder of `Index`.
+///
+/// The builder decodes the GZIP stream and records an `IndexPoint` at the first block boundary
+/// after every interval of the decoded data.
+/// It implements `io::Read`, so the index can be built while consuming the decoded data.
+#[derive(Debug)]
+pub struct IndexBuilder<R> {
+    decoder: Decoder<R>,
+    header_size: u64,
+    interval: u64,
+    points: Vec<IndexPoint>,
+}
+impl<R> IndexBuilder<R>
+where
+    R: io::Read,
+{
+    /// Makes a new builder which records a point every `DEFAULT_INDEX_INTERVAL` bytes.
+    ///
+    /// `inner` is to be indexed GZIP stream.
+    pub fn new(inner: R) -> io::Result<Self> {
+        Self::with_interval(inner, DEFAULT_INDEX_INTERVAL)
+    }
+
+    /// Makes a new builder which records a point every `interval` bytes.
+    pub fn with_interval(inner: R, interval: u64) -> io::Result<Self> {
+        let mut reader = CountingReader::new(inner);
+        let header = Header::read_from(&mut reader)?;
+        let header_size = reader.bytes_read();
+        Ok(IndexBuilder {
+            header_size,
+            decoder: Decoder::with_header(reader.into_inner(), header, header_size),
+            interval,
+            points: Vec::new(),
+        })
+    }
+
+    /// Decodes the rest of the stream and returns the built index.
+    pub fn build(mut self) -> io::Result<Index> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(self.finish())
+    }
+
+    /// Returns the index built from the data decoded so far.
+    pub fn finish(self) -> Index {
+        Index {
+            points: self.points,
+        }
+    }
+
+    fn add_point_if_needed(&mut self) {
+        let (compressed_offset, uncompressed_offset, bits, bit_buf) =
+            match self.decoder.reader.sync_point() {
+                Some(point) => point,
+                None => return,
+            };
+        if let Some(last) = self.points.last() {
+            if uncompressed_offset - last.uncompressed_offset < self.interval {
+                return;
+            }
+        }
+
+        let mut window = [0; INDEX_WINDOW_SIZE];
+        let decoded = self.decoder.reader.window();
+        let decoded = &decoded[decoded.len().saturating_sub(INDEX_WINDOW_SIZE)..];
+        window[INDEX_WINDOW_SIZE - decoded.len()..].copy_from_slice(decoded);
+        self.points.push(IndexPoint {
+            compressed_offset: self.header_size + compressed_offset,
+            uncompressed_offset,
+            window,
+            bits,
+            bit_buf,
+        });
+    }
+}
+impl<R> io::Read for IndexBuilder<R>
+where
+    R: io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.add_point_if_needed();
+        self.decoder.read(buf)
+    }
+}
+
+/// GZIP decoder which implements `tokio::io::AsyncRead`.
+///
+/// See `deflate::AsyncDecoder` for details.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncDecoder<R> {
+    decoder: non_blocking::gzip::Decoder<PollReader<R>>,
+}
+#[cfg(feature = "tokio")]
+impl<R> AsyncDecoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Makes a new decoder instance.
+    ///
+    /// `inner` is to be decoded GZIP stream.
+    pub fn new(inner: R) -> Self {
+        AsyncDecoder {
+            decoder: non_blocking::gzip::Decoder::new(PollReader::new(inner)),
+        }
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        self.decoder.as_inner_ref().as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        self.decoder.as_inner_mut().as_inner_mut()
+    }
+
+    /// Unwraps this `AsyncDecoder`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.decoder.into_inner().into_inner()
+    }
+}
+#[cfg(feature = "tokio")]
+impl<R> AsyncRead for AsyncDecoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.decoder.as_inner_mut().set_waker(cx.waker());
+        async_io::poll_read(&mut this.decoder, buf)
+    }
+}
+
+/// GZIP encoder which implements `tokio::io::AsyncWrite`.
+///
+/// The encoded data is staged in an internal buffer and written to `inner` asynchronously.
+/// `poll_shutdown` writes the final block and the trailer (as `Encoder::finish` does)
+/// and then shuts down `inner`.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncEncoder<W, E = lz77::DefaultLz77Encoder> {
+    inner: W,
+    encoder: Option<Encoder<Vec<u8>, E>>,
+    buf: Vec<u8>,
+    offset: usize,
+}
+#[cfg(feature = "tokio")]
+impl<W> AsyncEncoder<W, lz77::DefaultLz77Encoder>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded GZIP stream is written to `inner`.
+    pub fn new(inner: W) -> io::Result<Self> {
+        Self::with_options(inner, EncodeOptions::new())
+    }
+}
+#[cfg(feature = "tokio")]
+impl<W, E> AsyncEncoder<W, E>
+where
+    W: AsyncWrite + Unpin,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Encoded GZIP stream is written to `inner`.
+    pub fn with_options(inner: W, options: EncodeOptions<E>) -> io::Result<Self> {
+        let mut encoder = Encoder::with_options(Vec::new(), options)?;
+        let buf = std::mem::take(encoder.as_inner_mut());
+        Ok(AsyncEncoder {
+            inner,
+            encoder: Some(encoder),
+            buf,
+            offset: 0,
+        })
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwraps the `AsyncEncoder`, returning the inner stream.
     ///
-    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
-    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
-    ///                     163, 28, 41, 28, 12, 0, 0, 0];
-    ///
-    /// let decoder = MultiDecoder::new(Cursor::new(&encoded_data[..])).unwrap();
-    /// assert_eq!(decoder.into_inner().into_inner(), &encoded_data[..]);
-    /// ```
-    pub fn into_inner(self) -> R {
-        match self.decoder {
-            Err(reader) => reader,
-            Ok(decoder) => decoder.into_inner(),
-        }
+    /// The data which have not been written to the inner stream are discarded.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn poll_drain(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        async_io::poll_drain(&mut self.inner, cx, &mut self.buf, &mut self.offset)
     }
 }
-impl<R> io::Read for MultiDecoder<R>
+#[cfg(feature = "tokio")]
+impl<W, E> AsyncWrite for AsyncEncoder<W, E>
 where
-    R: io::Read,
+    W: AsyncWrite + Unpin,
+    E: lz77::Lz77Encode + Unpin,
 {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let read_size = match self.decoder {
-            Err(_) => return Ok(0),
-            Ok(ref mut decoder) => decoder.read(buf)?,
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        use std::io::Write;
+
+        let this = self.get_mut();
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+        let encoder = match this.encoder {
+            Some(ref mut encoder) => encoder,
+            None => {
+                return Poll::Ready(Err(invalid_input_error!("The encoder has been shut down")))
+            }
         };
-        // take_mut closure must have the type it borrows as return type,
-        // so we put the function return result to this variable instead.
-        // If function logic is correct, these initial values will never be returned.
-        let mut result: io::Result<usize> = Err(io::Error::new(
-            io::ErrorKind::Other,
-            "If you see this error, please report a bug in libflate",
-        ));
-        if read_size == 0 {
-            take_mut::take(self, |mut owned_self| {
-                let mut reader = owned_self
-                    .decoder
-                    .ok()
-                    .take()
-                    .expect("Never fails")
-                    .into_inner();
-                match Header::read_from(&mut reader) {
-                    Err(e) => {
-                        if e.kind() == io::ErrorKind::UnexpectedEof {
-                            result = Ok(0);
-                        } else {
-                            result = Err(e);
-                        }
-                        owned_self.decoder = Err(reader);
-                        owned_self
-                    }
-                    Ok(header) => {
-                        owned_self.header = header.clone();
-                        owned_self.decoder = Ok(Decoder::with_header(reader, header));
-                        result = owned_self.read(buf);
-                        owned_self
-                    }
-                }
-            })
-        } else {
-            result = Ok(read_size);
+        encoder.write_all(buf)?;
+        this.buf.append(encoder.as_inner_mut());
+        Poll::Ready(Ok(buf.len()))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(encoder) = this.encoder.take() {
+            let mut tail = encoder.finish().into_result()?;
+            this.buf.append(&mut tail);
+        }
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_shutdown(cx),
+            other => other,
         }
-        result
     }
 }
 
@@ -1210,14 +3755,734 @@ mod test {
     fn multi_decode_works() {
         use std::iter;
         let text = b"Hello World!";
-        let encoded: Vec<u8> = iter::repeat(encode(text).unwrap())
-            .take(2)
-            .flat_map(|b| b)
-            .collect();
+        let encoded: Vec<u8> = iter::repeat_n(encode(text).unwrap(), 2).flatten().collect();
         assert_eq!(decode(&encoded).unwrap(), b"Hello World!");
         assert_eq!(decode_multi(&encoded).unwrap(), b"Hello World!Hello World!");
     }
 
+    #[test]
+    fn with_dictionary_id_check_works() {
+        let dict = b"Hello World! Hello GZIP!!".to_vec();
+        let dict_id = adler32::update(1, &dict);
+        let plain = b"Hello GZIP!! Hello World!";
+        let encode_with_subfield = |data: &[u8]| {
+            let extra = ExtraFieldBuilder::new()
+                .subfield(DICTIONARY_ID_SUBFIELD, data)
+                .unwrap()
+                .finish();
+            let header = HeaderBuilder::new().extra_field(extra).finish();
+            let options = deflate::EncodeOptions::new().dictionary(&dict);
+            let deflate = deflate::Encoder::with_options(Vec::new(), options).unwrap();
+            let mut encoder = Encoder::from_deflate_encoder(header, deflate).unwrap();
+            io::copy(&mut &plain[..], &mut encoder).unwrap();
+            encoder.finish().into_result().unwrap()
+        };
+
+        let encoded = encode_with_subfield(&dict_id.to_be_bytes());
+        let lookup = |id| if id == dict_id { Some(&dict) } else { None };
+        let mut decoder = Decoder::with_dictionary_id_check(&encoded[..], lookup).unwrap();
+        let mut decoded = Vec::new();
+        io::copy(&mut decoder, &mut decoded).unwrap();
+        assert_eq!(decoded, plain);
+
+        let e = Decoder::with_dictionary_id_check(&encoded[..], |_| None::<Vec<u8>>).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        let e = Decoder::with_dictionary_id_check(&encoded[..], |_| Some(b"other")).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let encoded = encode_with_subfield(&[1, 2, 3]);
+        let e = Decoder::with_dictionary_id_check(&encoded[..], lookup).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let encoded = encode(plain).unwrap();
+        let decoder = Decoder::with_dictionary_id_check(&encoded[..], |_| -> Option<&[u8]> {
+            panic!("never called")
+        });
+        assert_eq!(decode(&encoded).unwrap(), plain);
+        let mut decoded = Vec::new();
+        io::copy(&mut decoder.unwrap(), &mut decoded).unwrap();
+        assert_eq!(decoded, plain);
+    }
+
+    #[test]
+    fn buf_read_works() {
+        use std::io::{BufRead, Read};
+
+        let plain = (0..10_000)
+            .map(|i| format!("{}\n", i))
+            .collect::<String>()
+            .into_bytes();
+        let encoded = encode(&plain).unwrap();
+
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        let mut line = String::new();
+        decoder.read_line(&mut line).unwrap();
+        assert_eq!(line, "0\n");
+        let mut rest = Vec::new();
+        decoder.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &plain[2..]);
+        assert_eq!(decoder.fill_buf().unwrap(), b"");
+        assert_eq!(
+            decoder.trailer().map(|t| t.crc32()),
+            Some(crc32::update(crc32::INITIAL_VALUE, &plain))
+        );
+
+        let lines = Decoder::new(&encoded[..])
+            .unwrap()
+            .lines()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines.len(), 10_000);
+
+        // The CRC-32 of the data consumed via `BufRead` is verified
+        let mut corrupted = encoded.clone();
+        let len = corrupted.len();
+        corrupted[len - 8] ^= 1;
+        let decoder = Decoder::new(&corrupted[..]).unwrap();
+        let e = decoder.lines().find_map(|line| line.err()).unwrap();
+        assert!(matches!(Error::from(e), Error::ChecksumMismatch { .. }));
+    }
+
+    #[test]
+    fn trailer_crc32_works() {
+        let plain = (0..1000)
+            .map(|i| format!("{} ", i))
+            .collect::<String>()
+            .into_bytes();
+        let expected_crc32 = crc32::update(crc32::INITIAL_VALUE, &plain);
+        let mut hasher = crc32::Crc32::new();
+        for chunk in plain.chunks(100) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.value(), expected_crc32);
+
+        let mut encoder = Encoder::new(Vec::new()).unwrap();
+        let header_size = encoder.compressed_bytes_written_so_far() as usize;
+        for chunk in plain.chunks(100) {
+            encoder.write_all(chunk).unwrap();
+        }
+        assert_eq!(encoder.uncompressed_crc32_so_far(), expected_crc32);
+        let encoded = encoder.finish().into_result().unwrap();
+        let trailer_start = encoded.len() - 8;
+        assert_eq!(
+            encoded[trailer_start..trailer_start + 4],
+            expected_crc32.to_le_bytes()
+        );
+
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        let mut decoded = Vec::new();
+        io::copy(&mut decoder, &mut decoded).unwrap();
+        assert_eq!(decoded, plain);
+        let trailer = decoder.trailer().unwrap();
+        assert_eq!(trailer.crc32(), expected_crc32);
+        assert_eq!(trailer.input_size(), plain.len() as u32);
+
+        // Corrupting any byte after the header is detected
+        for i in header_size..encoded.len() {
+            let mut corrupted = encoded.clone();
+            corrupted[i] ^= 0xFF;
+            let e = decode(&corrupted).unwrap_err();
+            if i >= trailer_start + 4 {
+                assert!(matches!(Error::from(e), Error::SizeMismatch { .. }));
+            } else if i >= trailer_start {
+                assert!(matches!(Error::from(e), Error::ChecksumMismatch { .. }));
+            }
+        }
+    }
+
+    #[test]
+    fn from_deflate_options_works() {
+        let options = EncodeOptions::from(deflate::EncodeOptions::new().no_compression());
+        assert_eq!(
+            options.header.compression_level(),
+            CompressionLevel::Unknown
+        );
+        assert_eq!(options.header.extra_field(), None);
+        assert_eq!(
+            options.options,
+            deflate::EncodeOptions::new().no_compression()
+        );
+        assert!(!options.auto_text_flag);
+
+        let options = EncodeOptions::from(deflate::EncodeOptions::new().compression_level(1));
+        assert_eq!(
+            options.header.compression_level(),
+            CompressionLevel::Fastest
+        );
+
+        let dict = b"Hello World! Hello GZIP!!";
+        let options = deflate::EncodeOptions::new().dictionary(dict);
+        let mut encoder = Encoder::with_options(Vec::new(), options.into()).unwrap();
+        encoder.write_all(b"Hello GZIP!! Hello World!").unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        let mut decoder = Decoder::with_dictionary_id_check(&encoded[..], |id| {
+            assert_eq!(id, adler32::update(adler32::INITIAL_VALUE, dict));
+            Some(dict)
+        })
+        .unwrap();
+        let mut decoded = Vec::new();
+        io::copy(&mut decoder, &mut decoded).unwrap();
+        assert_eq!(decoded, b"Hello GZIP!! Hello World!");
+    }
+
+    #[test]
+    fn multi_member_encoder_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i))
+            .collect::<String>()
+            .into_bytes();
+        let header = HeaderBuilder::new()
+            .os(Os::Ntfs)
+            .modification_time(123)
+            .finish();
+        let options = EncodeOptions::new().header(header.clone());
+        let mut encoder = MultiMemberEncoder::with_options(Vec::new(), 10_000, options).unwrap();
+        for chunk in plain.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let member_count = encoder.member_count();
+        assert_eq!(member_count, (plain.len() as u64).div_ceil(10_000));
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let members = MemberScanner::new(&encoded[..])
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(members.len() as u64, member_count);
+        for (i, member) in members.iter().enumerate() {
+            assert_eq!(member.header(), &header);
+            let start = i * 10_000;
+            let end = cmp::min(start + 10_000, plain.len());
+            let compressed = &encoded[member.start() as usize..member.end() as usize];
+            assert_eq!(decode(compressed).unwrap(), &plain[start..end]);
+        }
+        assert_eq!(decode_multi(&encoded).unwrap(), plain);
+
+        // No empty member is written at the end
+        let mut encoder = MultiMemberEncoder::new(Vec::new(), 6).unwrap();
+        encoder.write_all(b"Hello World!").unwrap();
+        encoder.write_all(b"").unwrap();
+        assert_eq!(encoder.member_count(), 2);
+        let encoded = encoder.finish().into_result().unwrap();
+        assert_eq!(MemberScanner::new(&encoded[..]).count(), 2);
+    }
+
+    #[test]
+    fn split_members_works() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let members = (0..3)
+            .map(|i| {
+                let header = HeaderBuilder::new().mtime(i).finish();
+                let mut encoder = Encoder::with_compression_level(Vec::new(), 6, header).unwrap();
+                io::copy(&mut &format!("member {}", i).into_bytes()[..], &mut encoder).unwrap();
+                encoder.finish().into_result().unwrap()
+            })
+            .collect::<Vec<_>>();
+        let encoded = members.concat();
+
+        struct SharedWriter(Rc<RefCell<Vec<u8>>>);
+        impl io::Write for SharedWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut outputs = Vec::new();
+        let count = split_members(io::Cursor::new(&encoded), |i, member| {
+            assert_eq!(member.header().modification_time(), i as u32);
+            let output = Rc::new(RefCell::new(Vec::new()));
+            outputs.push(output.clone());
+            Ok(SharedWriter(output))
+        })
+        .unwrap();
+        assert_eq!(count, 3);
+        let outputs = outputs
+            .into_iter()
+            .map(|o| o.borrow().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(outputs, members);
+        assert_eq!(decode(&outputs[2]).unwrap(), b"member 2");
+
+        let mut truncated = encoded.clone();
+        truncated.pop();
+        let mut called = false;
+        let result = split_members(io::Cursor::new(truncated), |_, _| {
+            called = true;
+            Ok(io::sink())
+        });
+        assert!(result.is_err());
+        assert!(!called);
+    }
+
+    #[test]
+    fn append_member_works() {
+        let mut file = io::Cursor::new(encode(b"Hello ").unwrap());
+        file.seek(io::SeekFrom::End(0)).unwrap();
+        let header = HeaderBuilder::new().name("second").unwrap().finish();
+        let options = EncodeOptions::new().header(header.clone());
+        let mut encoder = Encoder::with_options(&mut file, options).unwrap();
+        io::copy(&mut &b"World!"[..], &mut encoder).unwrap();
+        encoder.finish().into_result().unwrap();
+
+        let encoded = file.into_inner();
+        assert_eq!(decode(&encoded).unwrap(), b"Hello ");
+        assert_eq!(decode_multi(&encoded).unwrap(), b"Hello World!");
+
+        let first_len = encode(b"Hello ").unwrap().len();
+        let decoder = Decoder::new(&encoded[first_len..]).unwrap();
+        assert_eq!(decoder.header(), &header);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn header_serde_works() {
+        let extra_field = ExtraFieldBuilder::new()
+            .subfield(*b"AB", b"foo")
+            .unwrap()
+            .finish();
+        let header = HeaderBuilder::new()
+            .modification_time(123)
+            .os(Os::Undefined(100))
+            .text()
+            .header_crc(true)
+            .extra_field(extra_field)
+            .filename(CString::new("foo.txt").unwrap())
+            .comment(CString::new("bar").unwrap())
+            .finish();
+        let json = ::serde_json::to_string(&header).unwrap();
+        let deserialized: Header = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, header);
+
+        let header = HeaderBuilder::new().finish();
+        let json = ::serde_json::to_string(&header).unwrap();
+        let deserialized: Header = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, header);
+    }
+
+    #[test]
+    fn mtime_system_time_works() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let max = UNIX_EPOCH + Duration::from_secs(u64::from(u32::MAX));
+        let header = HeaderBuilder::new()
+            .mtime_from_system_time(max + Duration::from_millis(999))
+            .unwrap()
+            .finish();
+        assert_eq!(header.modification_time(), u32::MAX);
+        assert_eq!(header.modification_time_as_system_time(), Some(max));
+
+        let e = HeaderBuilder::new()
+            .mtime_from_system_time(max + Duration::from_secs(1))
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        let e = HeaderBuilder::new()
+            .mtime_from_system_time(UNIX_EPOCH - Duration::from_secs(1))
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+        let header = HeaderBuilder::new()
+            .mtime_from_system_time(UNIX_EPOCH)
+            .unwrap()
+            .finish();
+        assert_eq!(header.modification_time_as_system_time(), None);
+    }
+
+    #[test]
+    fn header_display_works() {
+        let extra_field = ExtraFieldBuilder::new()
+            .subfield(*b"AB", b"foo")
+            .unwrap()
+            .finish();
+        let header = HeaderBuilder::new()
+            .modification_time(1_000_000)
+            .os(200)
+            .text()
+            .header_crc(true)
+            .extra_field(extra_field)
+            .comment(CString::new("bar").unwrap())
+            .finish();
+        let expected = [
+            "modification time: 1000000",
+            "compression level: unknown",
+            "os: undefined (200)",
+            "text: yes",
+            "header crc: yes",
+            "name: -",
+            "comment: bar",
+            "extra field: 7 bytes",
+        ];
+        assert_eq!(header.to_string(), expected.join("\n"));
+    }
+
+    #[test]
+    fn auto_os_works() {
+        let header = HeaderBuilder::new().os(Os::Amiga).finish();
+        let options = EncodeOptions::new().header(header).auto_os();
+        let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert_eq!(encoded[9], Os::current().to_u8());
+
+        let decoder = Decoder::new(&encoded[..]).unwrap();
+        assert_eq!(decoder.header().os(), Os::current());
+        if cfg!(windows) {
+            assert_eq!(encoded[9], 0);
+        } else if cfg!(unix) {
+            assert_eq!(encoded[9], 3);
+        }
+    }
+
+    #[test]
+    fn header_crc_works() {
+        let header = HeaderBuilder::new()
+            .name("foo.txt")
+            .unwrap()
+            .header_crc(true)
+            .finish();
+        let options = EncodeOptions::new().header(header);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        encoder.write_all(b"Hello World!").unwrap();
+        let mut encoded = encoder.finish().into_result().unwrap();
+
+        let decoder = Decoder::new(&encoded[..]).unwrap();
+        assert!(decoder.header().is_verified());
+        assert_eq!(decode(&encoded).unwrap(), b"Hello World!");
+
+        // Flips a bit in the file name
+        encoded[10] ^= 1;
+        let error = Decoder::new(&encoded[..]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = error.get_ref().unwrap().downcast_ref::<HeaderError>();
+        assert!(matches!(error, Some(HeaderError::CrcMismatch { .. })));
+    }
+
+    #[test]
+    fn member_iterator_works() {
+        use std::io::Read;
+        let mut encoded = encode(b"Hello").unwrap();
+        encoded.extend(encode(b"World!").unwrap());
+
+        let mut members = MemberIterator::new(&encoded[..]);
+        let mut texts = Vec::new();
+        while let Some(decoder) = members.next_member() {
+            let mut buf = Vec::new();
+            decoder.unwrap().read_to_end(&mut buf).unwrap();
+            texts.push(buf);
+        }
+        assert_eq!(texts, [&b"Hello"[..], &b"World!"[..]]);
+
+        // Trailing garbage is reported as an error
+        encoded.extend(b"garbage!!!");
+        let mut members = MemberIterator::new(&encoded[..]);
+        for _ in 0..2 {
+            let mut buf = Vec::new();
+            let mut decoder = members.next_member().unwrap().unwrap();
+            decoder.read_to_end(&mut buf).unwrap();
+        }
+        assert!(members.next_member().unwrap().is_err());
+        assert!(members.next_member().is_none());
+    }
+
+    #[test]
+    fn compression_level_works() {
+        let plain = include_bytes!("../README.md");
+        for (level, xfl) in [(1, 4), (6, 0), (9, 2)] {
+            let options = EncodeOptions::new().compression_level(level);
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(plain).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+            assert_eq!(encoded[8], xfl);
+            assert_eq!(decode(&encoded).unwrap(), &plain[..]);
+        }
+    }
+
+    #[test]
+    fn decompress_works() {
+        let plain = include_bytes!("../README.md");
+        let mut encoded = compress(&plain[..1000]);
+        encoded.extend(compress(&plain[1000..]));
+        assert_eq!(decompress(&encoded).unwrap(), &plain[..]);
+
+        assert!(decompress(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn trailer_works() {
+        use std::io::{Cursor, Read};
+
+        let plain = b"Hello World!";
+        let mut encoded = compress(plain);
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        let mut buf = [0; 12];
+        decoder.read_exact(&mut buf).unwrap();
+        assert_eq!(decoder.trailer(), None);
+        assert_eq!(decoder.read(&mut buf).unwrap(), 0);
+        let trailer = Trailer::new(crc32::update(crc32::INITIAL_VALUE, plain), 12);
+        assert_eq!(decoder.trailer(), Some(&trailer));
+
+        let mut decoder = Decoder::new(Cursor::new(&encoded[..])).unwrap();
+        io::copy(&mut decoder, &mut io::sink()).unwrap();
+        decoder.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(decoder.trailer(), None);
+
+        let len = encoded.len();
+        encoded[len - 4] ^= 1; // Corrupts the ISIZE in the trailer
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        assert!(io::copy(&mut decoder, &mut io::sink()).is_err());
+        assert_eq!(decoder.trailer().map(|t| t.input_size()), Some(13));
+    }
+
+    #[test]
+    fn lenient_decode_works() {
+        let plain = b"Hello World!";
+        let mut encoded = compress(plain);
+        let len = encoded.len();
+        encoded[len - 4] ^= 1; // Corrupts the ISIZE in the trailer
+
+        let e = decode(&encoded).err().unwrap();
+        assert!(matches!(
+            Error::from(e),
+            Error::SizeMismatch {
+                expected: 13,
+                got: 12
+            }
+        ));
+
+        let options = DecodeOptions::new().lenient();
+        let mut decoder = Decoder::with_options(&encoded[..], options).unwrap();
+        let mut buf = Vec::new();
+        io::copy(&mut decoder, &mut buf).unwrap();
+        assert_eq!(buf, plain);
+        assert_eq!(
+            decoder.warnings(),
+            [DecodeWarning::SizeMismatch {
+                expected: 13,
+                got: 12
+            }]
+        );
+
+        // Errors in the compressed data are not recoverable
+        let mut encoded = compress(plain);
+        encoded[10] |= 0b110; // BTYPE=0b11 (reserved)
+        let options = DecodeOptions::new().lenient();
+        let mut decoder = Decoder::with_options(&encoded[..], options).unwrap();
+        let e = io::copy(&mut decoder, &mut io::sink()).err().unwrap();
+        assert!(matches!(
+            Error::from(e).without_context(),
+            Error::InvalidBlockType
+        ));
+    }
+
+    #[test]
+    fn index_works() {
+        use std::io::{Cursor, Read};
+
+        let plain = (0..200_000)
+            .map(|i| format!("{} ", i * 7 % 10007))
+            .collect::<String>()
+            .into_bytes();
+        let options = EncodeOptions::new().block_size(4096);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        for chunk in plain.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let mut builder = IndexBuilder::with_interval(&encoded[..], 100_000).unwrap();
+        let mut decoded = Vec::new();
+        builder.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, plain);
+        let index = builder.finish();
+        assert!(index.points().len() > 5);
+        assert!(index.points().iter().any(|p| p.bits != 0));
+
+        let mut buf = Vec::new();
+        index.write_to(&mut buf).unwrap();
+        let index = Index::read_from(&buf[..]).unwrap();
+
+        for &offset in &[0, 1, 99_999, 100_000, 512_345, plain.len() as u64 - 1] {
+            let mut decoder = index.seek_to(Cursor::new(&encoded), offset).unwrap();
+            let mut buf = Vec::new();
+            decoder.read_to_end(&mut buf).unwrap();
+            assert_eq!(buf, &plain[offset as usize..]);
+        }
+
+        assert!(Index::read_from(&buf[1..]).is_err());
+    }
+
+    #[test]
+    fn seek_works() {
+        use std::io::{Cursor, Read, Seek, SeekFrom};
+
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i * 7 % 10007))
+            .collect::<String>()
+            .into_bytes();
+        let options = EncodeOptions::new().block_size(4096);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        for chunk in plain.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let encoded = encoder.finish().into_result().unwrap();
+        let index = IndexBuilder::with_interval(&encoded[..], 50_000)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        for index in [None, Some(index)] {
+            let mut decoder = Decoder::new(Cursor::new(&encoded)).unwrap();
+            decoder.set_index(index);
+            let mut buf = [0; 100];
+            for &offset in &[300_000, 10, 250_000, 0, 123_456] {
+                assert_eq!(decoder.seek(SeekFrom::Start(offset)).unwrap(), offset);
+                decoder.read_exact(&mut buf).unwrap();
+                let offset = offset as usize;
+                assert_eq!(&buf[..], &plain[offset..offset + buf.len()]);
+            }
+            assert_eq!(decoder.seek(SeekFrom::Current(-200)).unwrap(), 123_356);
+
+            let size = plain.len() as u64;
+            assert_eq!(decoder.seek(SeekFrom::End(-10)).unwrap(), size - 10);
+            let mut rest = Vec::new();
+            decoder.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, &plain[plain.len() - 10..]);
+
+            assert!(decoder.seek(SeekFrom::Start(size + 1)).is_err());
+            assert!(decoder.seek(SeekFrom::Current(-1)).is_ok());
+
+            decoder.seek(SeekFrom::Start(0)).unwrap();
+            let mut all = Vec::new();
+            decoder.read_to_end(&mut all).unwrap();
+            assert_eq!(all, plain);
+        }
+    }
+
+    #[test]
+    fn seek_with_stream_start_offset_works() {
+        use std::io::{Cursor, Read, Seek, SeekFrom};
+
+        let plain = (0..20_000)
+            .map(|i| format!("{} ", i * 7 % 10007))
+            .collect::<String>()
+            .into_bytes();
+        for options in [
+            EncodeOptions::new().block_size(4096),
+            EncodeOptions::new().no_compression(),
+        ] {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(&plain).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+            let index = IndexBuilder::with_interval(&encoded[..], 10_000)
+                .unwrap()
+                .build()
+                .unwrap();
+
+            // The GZIP stream follows some prefix bytes and another member
+            let mut file = b"prefix".to_vec();
+            file.extend_from_slice(&encode(b"first member").unwrap());
+            let start = file.len() as u64;
+            file.extend_from_slice(&encoded);
+
+            for index in [None, Some(index)] {
+                let mut inner = Cursor::new(&file);
+                inner.seek(SeekFrom::Start(start)).unwrap();
+                let mut decoder = Decoder::new(inner).unwrap();
+                decoder.set_index(index);
+                let mut buf = [0; 100];
+                decoder.read_exact(&mut buf).unwrap();
+                for &offset in &[50_000, 0, 30_000, 10] {
+                    assert_eq!(decoder.seek(SeekFrom::Start(offset)).unwrap(), offset);
+                    decoder.read_exact(&mut buf).unwrap();
+                    let offset = offset as usize;
+                    assert_eq!(&buf[..], &plain[offset..offset + buf.len()]);
+                }
+
+                // Rewinding after the trailer has been read
+                let mut rest = Vec::new();
+                decoder.read_to_end(&mut rest).unwrap();
+                decoder.seek(SeekFrom::Start(0)).unwrap();
+                let mut all = Vec::new();
+                decoder.read_to_end(&mut all).unwrap();
+                assert_eq!(all, plain);
+            }
+        }
+    }
+
+    #[test]
+    fn inspect_works() {
+        use std::io::Cursor;
+        let plain = include_bytes!("../README.md");
+        let header = HeaderBuilder::new().name("README.md").unwrap().finish();
+        let options = EncodeOptions::new().header(header.clone());
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        encoder.write_all(plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let (h0, t0) = inspect(Cursor::new(&encoded[..])).unwrap();
+        let (h1, t1) = inspect_stream(&encoded[..]).unwrap();
+        assert_eq!(h0.filename(), header.filename());
+        assert_eq!(h1.filename(), header.filename());
+        assert_eq!(t0, t1);
+        assert_eq!(t0.input_size(), plain.len() as u32);
+
+        // The trailer is missing
+        assert!(inspect(Cursor::new(&encoded[..25])).is_err());
+        assert!(inspect_stream(&encoded[..25]).is_err());
+        assert!(inspect(Cursor::new(&encoded[..encoded.len() - 4])).is_err());
+
+        // The trailer of the first member is returned
+        let mut multi = encoded.clone();
+        multi.extend(encode(b"Hello").unwrap());
+        multi.extend_from_slice(b"trailing data");
+        let (h2, t2) = inspect(Cursor::new(&multi[..])).unwrap();
+        assert_eq!(h2.filename(), header.filename());
+        assert_eq!(t2, t0);
+        assert_eq!(inspect_stream(&multi[..]).unwrap().1, t0);
+
+        // A broken `ISIZE` is not returned
+        let mut broken = encoded.clone();
+        let len = broken.len();
+        broken[len - 1] ^= 1;
+        assert!(inspect(Cursor::new(&broken[..])).is_err());
+    }
+
+    #[test]
+    fn member_scanner_works() {
+        use std::io::{Cursor, Read};
+        let first = encode(b"Hello").unwrap();
+        let mut encoded = first.clone();
+        encoded.extend(encode(b"World!").unwrap());
+
+        let members = MemberScanner::new(&encoded[..])
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].start(), 0);
+        assert_eq!(members[0].end(), first.len() as u64);
+        assert_eq!(members[1].start(), first.len() as u64);
+        assert_eq!(members[1].end(), encoded.len() as u64);
+
+        let mut reader = MemberReader::new(Cursor::new(&encoded[..]));
+        let members = reader.members().collect::<io::Result<Vec<_>>>().unwrap();
+        let mut source = reader.into_inner();
+        let mut buf = Vec::new();
+        members[0]
+            .decode(&mut source)
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        assert_eq!(buf, b"Hello");
+
+        // A truncated member is reported as an error
+        let mut scanner = MemberScanner::new(&encoded[..encoded.len() - 1]);
+        assert!(scanner.next().unwrap().is_ok());
+        assert!(scanner.next().unwrap().is_err());
+        assert!(scanner.next().is_none());
+    }
+
     #[test]
     /// See: https://github.com/sile/libflate/issues/15 and https://github.com/RazrFalcon/usvg/issues/20
     fn issue_15_1() {
@@ -1281,4 +4546,100 @@ mod test {
             assert_eq!(decoder.header().extra_field(), Some(&extra_field));
         }
     }
+
+    #[test]
+    fn auto_text_flag_works() {
+        let text = "こんにちは, World! ".repeat(100).into_bytes();
+        let binary = (0..10_000).map(|i| (i % 256) as u8).collect::<Vec<_>>();
+        let header = HeaderBuilder::new().text().finish();
+        for (plain, is_text) in [(&text, true), (&binary, false), (&Vec::new(), false)] {
+            for chunk_size in [1, 7, 1000] {
+                let options = EncodeOptions::new()
+                    .header(header.clone())
+                    .auto_text_flag(true);
+                let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+                for chunk in plain.chunks(chunk_size) {
+                    encoder.write_all(chunk).unwrap();
+                }
+                let encoded = encoder.finish().into_result().unwrap();
+
+                let decoder = Decoder::new(&encoded[..]).unwrap();
+                assert_eq!(decoder.header().is_text_hint(), is_text);
+                assert_eq!(&decode(&encoded).unwrap(), plain);
+            }
+        }
+
+        // The pending header is written by `into_inner`
+        let options = EncodeOptions::new().auto_text_flag(true);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        encoder.write_all(&binary[200..300]).unwrap();
+        let inner = encoder.into_inner();
+        let decoder = Decoder::new(&inner[..]).unwrap();
+        assert!(!decoder.header().is_text_hint());
+    }
+
+    #[test]
+    fn so_far_counters_work() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i))
+            .collect::<String>()
+            .into_bytes();
+        let options = EncodeOptions::new().block_size(4096);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        let mut last_written = encoder.compressed_bytes_written_so_far();
+        for (i, chunk) in plain.chunks(1000).enumerate() {
+            encoder.write_all(chunk).unwrap();
+            let written = &plain[..i * 1000 + chunk.len()];
+            assert_eq!(
+                encoder.uncompressed_crc32_so_far(),
+                crc32::update(crc32::INITIAL_VALUE, written)
+            );
+            let compressed = encoder.compressed_bytes_written_so_far();
+            assert_eq!(compressed, encoder.as_inner_ref().len() as u64);
+            assert!(compressed >= last_written);
+            last_written = compressed;
+        }
+        assert!(last_written > 10);
+
+        let encoded = encoder.finish().into_result().unwrap();
+        assert!(encoded.len() as u64 > last_written + 8);
+        assert_eq!(decode(&encoded).unwrap(), plain);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_decoder_works() {
+        use util::{poll_read_to_end, PendingReader};
+        let plain = include_bytes!("../README.md");
+        let encoded = encode(plain).unwrap();
+        let decoder = AsyncDecoder::new(PendingReader::new(&encoded[..]));
+        assert_eq!(poll_read_to_end(decoder).unwrap(), &plain[..]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_encoder_works() {
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+        use tokio::io::AsyncWrite;
+        use util::PendingWriter;
+
+        let plain = include_bytes!("../README.md");
+        let mut encoder = AsyncEncoder::new(PendingWriter::new(Vec::new())).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        for chunk in plain.chunks(100) {
+            while Pin::new(&mut encoder)
+                .poll_write(&mut cx, chunk)
+                .is_pending()
+            {}
+        }
+        while Pin::new(&mut encoder).poll_shutdown(&mut cx).is_pending() {}
+        let encoded = encoder.into_inner().into_inner();
+        assert_eq!(decode(&encoded).unwrap(), &plain[..]);
+
+        match Pin::new(&mut AsyncEncoder::new(Vec::new()).unwrap()).poll_shutdown(&mut cx) {
+            Poll::Ready(Ok(())) => {}
+            _ => panic!(),
+        }
+    }
 }
@@ -0,0 +1,104 @@
+//! Transcoding from the GZIP format to the ZLIB format.
+//!
+//! # Examples
+//! ```
+//! use std::io::Read;
+//! use libflate::{gzip, gzip_to_zlib, zlib};
+//!
+//! let gzip_data = gzip::compress(b"Hello World!");
+//! let mut zlib_data = Vec::new();
+//! gzip_to_zlib::transcode(&gzip_data[..], &mut zlib_data).unwrap();
+//!
+//! let mut decoded_data = Vec::new();
+//! zlib::Decoder::new(&zlib_data[..]).unwrap().read_to_end(&mut decoded_data).unwrap();
+//! assert_eq!(decoded_data, b"Hello World!");
+//! ```
+use std::io::{self, Read, Write};
+
+use adler32;
+use crc32;
+use deflate;
+use gzip;
+use lz77;
+use util::TeeReader;
+use zlib;
+use Error;
+
+/// Reads a GZIP stream from `src` and writes the equivalent ZLIB stream to `dst`.
+///
+/// The DEFLATE payload is copied to `dst` as is, so the data are not compressed again.
+/// To compute the Adler-32 checksum of the ZLIB trailer, the payload is decoded while being copied
+/// (the decoded data are discarded).
+/// The CRC-32 and `ISIZE` of the GZIP trailer are verified against the decoded data.
+///
+/// Only the first member of a multi-member GZIP stream is transcoded.
+pub fn transcode<R, W>(mut src: R, mut dst: W) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    gzip::Header::read_from(&mut src)?;
+    zlib::Header::from_lz77(&lz77::DefaultLz77Encoder::new()).write_to(&mut dst)?;
+
+    let mut adler32 = adler32::Adler32::new();
+    let mut crc32 = crc32::Crc32::new();
+    let mut input_size = 0u32;
+    let mut decoder = deflate::Decoder::new(TeeReader::new(&mut src, &mut dst));
+    let mut buf = [0; 4096];
+    loop {
+        let size = decoder.read(&mut buf)?;
+        if size == 0 {
+            break;
+        }
+        adler32.update(&buf[..size]);
+        crc32.update(&buf[..size]);
+        input_size = input_size.wrapping_add(size as u32);
+    }
+
+    let trailer = gzip::Trailer::read_from(&mut src)?;
+    if trailer.crc32() != crc32.value() {
+        return Err(Error::ChecksumMismatch {
+            expected: trailer.crc32(),
+            got: crc32.value(),
+        }
+        .into());
+    }
+    if trailer.input_size() != input_size {
+        return Err(Error::SizeMismatch {
+            expected: trailer.input_size(),
+            got: input_size,
+        }
+        .into());
+    }
+    dst.write_all(&adler32.value().to_be_bytes())?;
+    dst.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transcode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let gzip_data = gzip::compress(&plain);
+        let mut zlib_data = Vec::new();
+        transcode(&gzip_data[..], &mut zlib_data).unwrap();
+        assert_eq!(zlib::decompress(&zlib_data).unwrap(), plain);
+
+        // The DEFLATE payload is kept as is
+        assert_eq!(zlib_data.len() + 18 - 6, gzip_data.len());
+        assert_eq!(
+            &zlib_data[2..zlib_data.len() - 4],
+            &gzip_data[10..gzip_data.len() - 8]
+        );
+
+        let mut corrupted = gzip_data.clone();
+        let len = corrupted.len();
+        corrupted[len - 8] ^= 1;
+        assert!(transcode(&corrupted[..], &mut Vec::new()).is_err());
+    }
+}
@@ -1,12 +1,18 @@
-use std::cmp;
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::cmp;
 /// Length-limited Huffman Codes
 ///
-use std::io;
+use io;
 
 use bit;
+use Error;
 
 const MAX_BITWIDTH: u8 = 15;
 
+/// The value of the entries of a decoding table which no code is mapped to.
+pub const UNMAPPED: u16 = MAX_BITWIDTH as u16 + 1;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Code {
     pub width: u8,
@@ -66,7 +72,7 @@ impl DecoderBuilder {
     pub fn new(max_bitwidth: u8, eob_symbol: Option<u16>) -> Self {
         debug_assert!(max_bitwidth <= MAX_BITWIDTH);
         DecoderBuilder {
-            table: vec![u16::from(MAX_BITWIDTH) + 1; 1 << max_bitwidth],
+            table: vec![UNMAPPED; 1 << max_bitwidth],
             eob_symbol,
             eob_bitwidth: max_bitwidth,
             max_bitwidth,
@@ -92,12 +98,8 @@ impl Builder for DecoderBuilder {
         let code_be = code.inverse_endian();
         for padding in 0..(1 << (self.max_bitwidth - code.width)) {
             let i = ((padding << code.width) | code_be.bits) as usize;
-            if self.table[i] != u16::from(MAX_BITWIDTH) + 1 {
-                let message = format!(
-                    "Bit region conflict: i={}, old_value={}, new_value={}, symbol={}, code={:?}",
-                    i, self.table[i], value, symbol, code
-                );
-                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            if self.table[i] != UNMAPPED {
+                return Err(Error::HuffmanCodeOversubscribed.into());
             }
             self.table[i] = value;
         }
@@ -105,20 +107,42 @@ impl Builder for DecoderBuilder {
     }
     fn finish(self) -> Self::Instance {
         Decoder {
-            table: self.table,
+            table: Cow::Owned(self.table),
             eob_bitwidth: self.eob_bitwidth,
             max_bitwidth: self.max_bitwidth,
         }
     }
 }
 
-#[derive(Debug)]
+/// A table-driven Huffman decoder.
+///
+/// `table` has `2^max_bitwidth` entries indexed by the next `max_bitwidth` bits (in reading order),
+/// and each entry holds a symbol and the width of its code, so a code is decoded by a single lookup.
+/// The first lookup peeks only `eob_bitwidth` bits because the codes of frequent symbols
+/// (which are not longer than the end-of-block code in practice) can be resolved without
+/// requiring more bits from the stream.
+#[derive(Debug, PartialEq, Eq)]
 pub struct Decoder {
-    table: Vec<u16>,
+    table: Cow<'static, [u16]>,
     eob_bitwidth: u8,
     max_bitwidth: u8,
 }
 impl Decoder {
+    /// Makes a decoder from a table built in advance (e.g., at compile time).
+    ///
+    /// `table` must have the same layout as the ones built by `DecoderBuilder`.
+    pub const fn from_static_table(
+        table: &'static [u16],
+        eob_bitwidth: u8,
+        max_bitwidth: u8,
+    ) -> Self {
+        Decoder {
+            table: Cow::Borrowed(table),
+            eob_bitwidth,
+            max_bitwidth,
+        }
+    }
+
     #[inline(always)]
     pub fn decode<R>(&self, reader: &mut bit::BitReader<R>) -> io::Result<u16>
     where
@@ -142,10 +166,10 @@ impl Decoder {
             value = self.table[code as usize];
             bitwidth = (value & 0b1_1111) as u8;
             if bitwidth > self.max_bitwidth {
-                reader.set_last_error(invalid_data_error!("Invalid huffman coded stream"));
+                reader.set_last_error(Error::HuffmanCodeUndersubscribed.into());
             }
         }
-        reader.skip_bits(bitwidth as u8);
+        reader.skip_bits(bitwidth);
         value >> 5
     }
 }
@@ -164,22 +188,32 @@ impl EncoderBuilder {
         let symbol_count = bitwidthes
             .iter()
             .enumerate()
-            .filter(|e| *e.1 > 0)
-            .last()
+            .rfind(|e| *e.1 > 0)
             .map_or(0, |e| e.0)
             + 1;
         let builder = Self::new(symbol_count);
         builder.restore_canonical_huffman_codes(bitwidthes)
     }
     pub fn from_frequencies(symbol_frequencies: &[usize], max_bitwidth: u8) -> io::Result<Encoder> {
-        let max_bitwidth = cmp::min(
-            max_bitwidth,
-            ordinary_huffman_codes::calc_optimal_max_bitwidth(symbol_frequencies),
-        );
-        let code_bitwidthes = length_limited_huffman_codes::calc(max_bitwidth, symbol_frequencies);
+        let code_bitwidthes = calc_length_limited_bitwidthes(symbol_frequencies, max_bitwidth);
         Self::from_bitwidthes(&code_bitwidthes)
     }
 }
+
+/// Calculates the bitwidthes of the optimal Huffman codes whose bitwidthes do not exceed `max_bitwidth`.
+pub fn calc_length_limited_bitwidthes(symbol_frequencies: &[usize], max_bitwidth: u8) -> Vec<u8> {
+    let max_bitwidth = cmp::min(
+        max_bitwidth,
+        ordinary_huffman_codes::calc_optimal_max_bitwidth(symbol_frequencies),
+    );
+    length_limited_huffman_codes::calc(max_bitwidth, symbol_frequencies)
+}
+
+/// Calculates the maximum bitwidth of the (unlimited) optimal Huffman codes.
+#[cfg(test)]
+pub fn calc_optimal_max_bitwidth(symbol_frequencies: &[usize]) -> u8 {
+    ordinary_huffman_codes::calc_optimal_max_bitwidth(symbol_frequencies)
+}
 impl Builder for EncoderBuilder {
     type Instance = Encoder;
     fn set_mapping(&mut self, symbol: u16, code: Code) -> io::Result<()> {
@@ -227,14 +261,14 @@ impl Encoder {
 
 #[allow(dead_code)]
 mod ordinary_huffman_codes {
-    use std::cmp;
-    use std::collections::BinaryHeap;
+    use alloc::collections::BinaryHeap;
+    use core::cmp;
 
     pub fn calc_optimal_max_bitwidth(frequencies: &[usize]) -> u8 {
         let mut heap = BinaryHeap::new();
         for &freq in frequencies.iter().filter(|&&f| f > 0) {
             let weight = -(freq as isize);
-            heap.push((weight, 0 as u8));
+            heap.push((weight, 0_u8));
         }
         while heap.len() > 1 {
             let (weight1, width1) = heap.pop().unwrap();
@@ -246,7 +280,8 @@ mod ordinary_huffman_codes {
     }
 }
 mod length_limited_huffman_codes {
-    use std::mem;
+    use alloc::vec::Vec;
+    use core::mem;
 
     #[derive(Debug, Clone)]
     struct Node {
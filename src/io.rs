@@ -0,0 +1,340 @@
+//! A minimal subset of `std::io` for `no_std` environments.
+//!
+//! This module is available only if the `std` feature is disabled.
+//! The traits and types have the same names and semantics as their `std::io` counterparts
+//! (if the `std` feature is enabled, `std::io` is used instead).
+//!
+//! `Read` is implemented for `&[u8]`, and `Write` is implemented for `Vec<u8>` and `&mut [u8]`.
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp;
+use core::error;
+use core::fmt;
+
+/// A specialized `Result` type for I/O operations.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// A list specifying general categories of I/O error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Data not valid for the operation were encountered.
+    InvalidData,
+
+    /// A parameter was incorrect.
+    InvalidInput,
+
+    /// An operation could not be completed because an "end of file" was reached prematurely.
+    UnexpectedEof,
+
+    /// An error returned when an operation could not be completed because a call to `write`
+    /// returned `Ok(0)`.
+    WriteZero,
+
+    /// This operation was interrupted.
+    Interrupted,
+
+    /// A custom error that does not fall under any other I/O error kind.
+    Other,
+}
+impl ErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::InvalidData => "invalid data",
+            ErrorKind::InvalidInput => "invalid input parameter",
+            ErrorKind::UnexpectedEof => "unexpected end of file",
+            ErrorKind::WriteZero => "write zero",
+            ErrorKind::Interrupted => "operation interrupted",
+            ErrorKind::Other => "other error",
+        }
+    }
+}
+
+/// The error type for I/O operations.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    error: Option<Box<dyn error::Error + Send + Sync>>,
+}
+impl Error {
+    /// Makes a new I/O error from a known kind of error as well as an arbitrary error payload.
+    pub fn new<E>(kind: ErrorKind, error: E) -> Self
+    where
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        Error {
+            kind,
+            error: Some(error.into()),
+        }
+    }
+
+    /// Makes a new I/O error of `ErrorKind::Other` from an arbitrary error payload.
+    pub fn other<E>(error: E) -> Self
+    where
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        Self::new(ErrorKind::Other, error)
+    }
+
+    /// Returns the corresponding `ErrorKind` for this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns a reference to the inner error wrapped by this error (if any).
+    pub fn get_ref(&self) -> Option<&(dyn error::Error + Send + Sync + 'static)> {
+        self.error.as_deref()
+    }
+
+    /// Consumes the `Error`, returning its inner error (if any).
+    pub fn into_inner(self) -> Option<Box<dyn error::Error + Send + Sync>> {
+        self.error
+    }
+}
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error { kind, error: None }
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.error {
+            Some(ref e) => e.fmt(f),
+            None => write!(f, "{}", self.kind.as_str()),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.error.as_ref().and_then(|e| e.source())
+    }
+}
+
+/// The `Read` trait allows for reading bytes from a source.
+pub trait Read {
+    /// Pull some bytes from this source into the specified buffer, returning how many bytes were read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Read the exact number of bytes required to fill `buf`.
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => buf = &mut buf[n..],
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read all bytes until EOF in this source, placing them into `buf`.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let start = buf.len();
+        let mut chunk = [0; 1024];
+        loop {
+            match self.read(&mut chunk) {
+                Ok(0) => return Ok(buf.len() - start),
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Creates an adapter which will read at most `limit` bytes from it.
+    fn take(self, limit: u64) -> Take<Self>
+    where
+        Self: Sized,
+    {
+        Take { inner: self, limit }
+    }
+
+    /// Creates a "by reference" adapter for this instance of `Read`.
+    fn by_ref(&mut self) -> &mut Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+}
+impl<R: Read + ?Sized> Read for &mut R {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+impl<R: Read + ?Sized> Read for Box<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+impl Read for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let size = cmp::min(buf.len(), self.len());
+        let (a, b) = self.split_at(size);
+        buf[..size].copy_from_slice(a);
+        *self = b;
+        Ok(size)
+    }
+}
+
+/// Reader adapter which limits the bytes read from an underlying reader.
+///
+/// This is created by `Read::take`.
+#[derive(Debug)]
+pub struct Take<R> {
+    inner: R,
+    limit: u64,
+}
+impl<R> Take<R> {
+    /// Returns the number of bytes that can be read before this instance will return EOF.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Consumes the `Take`, returning the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+impl<R: Read> Read for Take<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let max = cmp::min(buf.len() as u64, self.limit) as usize;
+        let size = self.inner.read(&mut buf[..max])?;
+        self.limit -= size as u64;
+        Ok(size)
+    }
+}
+
+/// A trait for objects which are byte-oriented sinks.
+pub trait Write {
+    /// Write a buffer into this writer, returning how many bytes were written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    /// Flush this output stream, ensuring that all intermediately buffered contents reach their destination.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Attempts to write an entire buffer into this writer.
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => buf = &buf[n..],
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a formatted string into this writer.
+    fn write_fmt(&mut self, args: fmt::Arguments) -> Result<()> {
+        struct Adapter<'a, W: ?Sized + 'a> {
+            inner: &'a mut W,
+            error: Option<Error>,
+        }
+        impl<'a, W: Write + ?Sized> fmt::Write for Adapter<'a, W> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.inner.write_all(s.as_bytes()).map_err(|e| {
+                    self.error = Some(e);
+                    fmt::Error
+                })
+            }
+        }
+
+        let mut adapter = Adapter {
+            inner: self,
+            error: None,
+        };
+        match fmt::write(&mut adapter, args) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(adapter
+                .error
+                .unwrap_or_else(|| Error::new(ErrorKind::Other, "formatter error"))),
+        }
+    }
+
+    /// Creates a "by reference" adapter for this instance of `Write`.
+    fn by_ref(&mut self) -> &mut Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+}
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        (**self).write(buf)
+    }
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+impl<W: Write + ?Sized> Write for Box<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        (**self).write(buf)
+    }
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+impl Write for Vec<u8> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+impl Write for &mut [u8] {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let size = cmp::min(buf.len(), self.len());
+        let (a, b) = core::mem::take(self).split_at_mut(size);
+        a.copy_from_slice(&buf[..size]);
+        *self = b;
+        Ok(size)
+    }
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A writer which will move data into the void.
+///
+/// This is created by `sink()`.
+#[derive(Debug, Default)]
+pub struct Sink;
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Creates an instance of a writer which will successfully consume all data.
+pub fn sink() -> Sink {
+    Sink
+}
+
+/// Copies the entire contents of a reader into a writer.
+pub fn copy<R, W>(reader: &mut R, writer: &mut W) -> Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut buf = [0; 1024];
+    let mut written = 0;
+    loop {
+        let size = match reader.read(&mut buf) {
+            Ok(0) => return Ok(written),
+            Ok(size) => size,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..size])?;
+        written += size as u64;
+    }
+}
@@ -1,20 +1,51 @@
 //! A Rust implementation of DEFLATE algorithm and related formats (ZLIB, GZIP).
+//!
+//! # `no_std` support
+//!
+//! If the default `std` feature is disabled, this crate is `no_std` and only requires the `alloc` crate.
+//! In that case, the `deflate`, `lz77`, `adler32` and `crc32` modules are available,
+//! and `io` provides the minimal `Read` and `Write` traits used by them
+//! (e.g., `deflate::Decoder` can read from `&[u8]` and `deflate::Encoder` can write to `Vec<u8>`).
+#![cfg_attr(not(feature = "std"), no_std)]
 #![warn(missing_docs)]
-extern crate adler32;
+extern crate adler32 as rolling_adler32;
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
 extern crate crc32fast;
 extern crate libflate_lz77;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 extern crate rle_decode_fast;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+#[cfg(feature = "std")]
 extern crate take_mut;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 
+pub use error::{DecodeWarning, Error, ErrorContext};
 pub use finish::Finish;
 
 macro_rules! invalid_data_error {
     ($fmt:expr) => { invalid_data_error!("{}", $fmt) };
     ($fmt:expr, $($arg:tt)*) => {
-        ::std::io::Error::new(::std::io::ErrorKind::InvalidData, format!($fmt, $($arg)*))
+        ::io::Error::new(::io::ErrorKind::InvalidData, format!($fmt, $($arg)*))
     }
 }
 
+#[cfg_attr(not(feature = "std"), allow(unused_macros))]
+macro_rules! invalid_input_error {
+    ($fmt:expr) => { invalid_input_error!("{}", $fmt) };
+    ($fmt:expr, $($arg:tt)*) => {
+        ::io::Error::new(::io::ErrorKind::InvalidInput, format!($fmt, $($arg)*))
+    }
+}
+
+#[cfg_attr(not(feature = "std"), allow(unused_macros))]
 macro_rules! finish_try {
     ($e:expr) => {
         match $e.unwrap() {
@@ -24,14 +55,34 @@ macro_rules! finish_try {
     };
 }
 
+pub mod adler32;
+#[cfg(feature = "std")]
+pub mod bgzf;
+pub mod crc32;
 pub mod deflate;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod finish;
+#[cfg(feature = "std")]
 pub mod gzip;
+#[cfg(feature = "std")]
+pub mod gzip_to_zlib;
+#[cfg(not(feature = "std"))]
+pub mod io;
 pub mod lz77;
+#[cfg(feature = "std")]
 pub mod non_blocking;
+#[cfg(feature = "std")]
 pub mod zlib;
+#[cfg(feature = "std")]
+pub mod zlib_to_gzip;
+
+#[cfg(feature = "std")]
+use std::io;
 
+#[cfg(feature = "tokio")]
+mod async_io;
 mod bit;
-mod checksum;
+mod error;
 mod huffman;
-mod util;
+pub mod util;
@@ -7,6 +7,9 @@ pub use libflate_lz77::*;
 mod tests {
     use super::*;
     use deflate::symbol::Symbol;
+    use deflate::{Decoder, EncodeOptions, Encoder};
+    use std::cmp;
+    use std::io::{Read, Write};
 
     #[test]
     // See: https://github.com/sile/libflate/issues/21
@@ -26,4 +29,329 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn window_snapshot_works() {
+        let plain = (0..50_000)
+            .map(|i| format!("{} ", i % 3000))
+            .collect::<String>()
+            .into_bytes();
+
+        let mut enc = DefaultLz77Encoder::new();
+        let mut sink = Vec::<Symbol>::new();
+        enc.encode(&plain, &mut sink);
+        enc.flush(&mut sink);
+        let snapshot = enc.window_snapshot();
+        let window = &plain[plain.len() - MAX_WINDOW_SIZE as usize..];
+        assert_eq!(&snapshot[2..], window);
+
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(window).unwrap();
+        let without_snapshot = encoder.finish().into_result().unwrap();
+
+        let mut enc = DefaultLz77Encoder::new();
+        enc.window_restore(&snapshot).unwrap();
+        let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::with_lz77(enc)).unwrap();
+        encoder.write_all(window).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert!(encoded.len() * 10 < without_snapshot.len());
+
+        let mut decoded = Vec::new();
+        Decoder::with_dictionary(&encoded[..], window)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, window);
+
+        let mut enc = DefaultLz77Encoder::new();
+        assert!(enc.window_restore(&[]).is_err());
+        assert!(enc.window_restore(&[0, 15]).is_err());
+        assert!(enc.window_restore(&[1, 8, 0]).is_ok());
+        assert!(NoCompressionLz77Encoder::new()
+            .window_restore(&snapshot)
+            .is_err());
+    }
+
+    #[test]
+    fn window_snapshot_after_multiple_flushes_works() {
+        let plain = (0..20_000).map(|i| (i * 7 % 251) as u8).collect::<Vec<_>>();
+        let mut enc = DefaultLz77Encoder::with_window_size(1000);
+        let mut sink = Vec::<Symbol>::new();
+        let mut offset = 0;
+        for size in [10, 500, 1000, 1023, 3000, 1, 700].iter().cycle().take(30) {
+            let end = cmp::min(offset + size, plain.len());
+            enc.encode(&plain[offset..end], &mut sink);
+            enc.flush(&mut sink);
+            offset = end;
+
+            let snapshot = enc.window_snapshot();
+            let window = &plain[offset.saturating_sub(1000)..offset];
+            assert_eq!(&snapshot[2..], window);
+        }
+    }
+
+    #[test]
+    fn hash_bytes_works() {
+        // Many 3-byte repeats which differ at the fourth byte
+        let plain = (0..30_000u32)
+            .flat_map(|i| vec![b'a', b'b', b'c', (i * 31 % 251) as u8])
+            .collect::<Vec<_>>();
+        for hash_bytes in 3..=4 {
+            let lz77 = DefaultLz77EncoderBuilder::new()
+                .hash_bytes(hash_bytes)
+                .build();
+            assert_eq!(lz77.hash_bytes(), hash_bytes);
+            let mut encoder =
+                Encoder::with_options(Vec::new(), EncodeOptions::with_lz77(lz77)).unwrap();
+            encoder.write_all(&plain).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+            assert!(encoded.len() < plain.len() / 2);
+
+            let mut decoded = Vec::new();
+            Decoder::new(&encoded[..])
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, plain);
+        }
+        assert_eq!(DefaultLz77Encoder::with_hash_bytes(5).hash_bytes(), 4);
+    }
+
+    #[test]
+    fn hash_bits_works() {
+        let plain = (0..20_000)
+            .map(|i| format!("{} ", i * i % 997))
+            .collect::<String>()
+            .into_bytes();
+        let mut sizes = Vec::new();
+        for hash_bits in [9, 10, 15, 16] {
+            let lz77 = DefaultLz77EncoderBuilder::new()
+                .hash_bits(hash_bits)
+                .build();
+            assert_eq!(lz77.hash_bits(), hash_bits);
+            let mut encoder =
+                Encoder::with_options(Vec::new(), EncodeOptions::with_lz77(lz77)).unwrap();
+            encoder.write_all(&plain).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+            sizes.push(encoded.len());
+
+            let mut decoded = Vec::new();
+            Decoder::new(&encoded[..])
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, plain);
+        }
+        assert!(sizes[0] >= sizes[2]);
+        assert_eq!(DefaultLz77Encoder::with_hash_bits(1).hash_bits(), 9);
+        assert_eq!(DefaultLz77Encoder::with_hash_bits(20).hash_bits(), 16);
+    }
+
+    #[test]
+    fn with_dictionary_works() {
+        let dict = (0..10_000)
+            .map(|i| format!("{} ", i * 31 % 9973))
+            .collect::<String>()
+            .into_bytes();
+        assert!(dict.len() > MAX_WINDOW_SIZE as usize);
+        let plain = dict[dict.len() - 20_000..dict.len() - 10_000].to_vec();
+
+        let mut lz77 = DefaultLz77Encoder::with_dictionary(&dict);
+        let mut codes = Vec::<Code>::new();
+        lz77.encode(&plain, &mut codes);
+        lz77.flush(&mut codes);
+        assert_eq!(
+            codes[0],
+            Code::Pointer {
+                length: 258,
+                backward_distance: 20_000
+            }
+        );
+
+        let options = EncodeOptions::with_lz77(DefaultLz77Encoder::with_dictionary(&dict));
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        encoder.write_all(&plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert!(encoded.len() < 200);
+
+        let mut decoded = Vec::new();
+        Decoder::with_dictionary(&encoded[..], &dict)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain);
+    }
+
+    #[test]
+    fn min_match_len_works() {
+        let plain = (0..20_000)
+            .map(|i| format!("{} ", i * i % 997))
+            .collect::<String>()
+            .into_bytes();
+        for lazy_match in [false, true] {
+            for min_match_len in [3, 4] {
+                let mut lz77 = DefaultLz77EncoderBuilder::new()
+                    .lazy_match(lazy_match)
+                    .min_match_len(min_match_len)
+                    .build();
+                assert_eq!(lz77.min_match_len(), min_match_len);
+                let mut codes = Vec::<Code>::new();
+                lz77.encode(&plain, &mut codes);
+                lz77.flush(&mut codes);
+                let shortest = codes
+                    .iter()
+                    .filter_map(|c| match *c {
+                        Code::Pointer { length, .. } => Some(length),
+                        Code::Literal(_) => None,
+                    })
+                    .min();
+                assert_eq!(shortest, Some(u16::from(min_match_len)));
+
+                let options = EncodeOptions::with_lz77(lz77);
+                let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+                encoder.write_all(&plain).unwrap();
+                let encoded = encoder.finish().into_result().unwrap();
+                let mut decoded = Vec::new();
+                Decoder::new(&encoded[..])
+                    .read_to_end(&mut decoded)
+                    .unwrap();
+                assert_eq!(decoded, plain);
+            }
+        }
+        assert_eq!(DefaultLz77Encoder::with_min_match_len(1).min_match_len(), 3);
+        assert_eq!(DefaultLz77Encoder::with_min_match_len(9).min_match_len(), 4);
+    }
+
+    #[test]
+    fn sink_combinators_work() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i % 100))
+            .collect::<String>()
+            .into_bytes();
+        let mut all = Vec::<Code>::new();
+        let mut literals = Vec::<Code>::new();
+        let mut symbols = Vec::<Symbol>::new();
+        {
+            let filter = FilterSink::new(&mut literals, |code: &Code| {
+                matches!(code, Code::Literal(_))
+            });
+            let mut sink = TeeSink(&mut all, TeeSink(filter, &mut symbols));
+            let mut encoder = DefaultLz77Encoder::new();
+            encoder.encode(&plain, &mut sink);
+            encoder.flush(&mut sink);
+        }
+        assert_eq!(all.len(), symbols.len());
+        let expected_literals = all
+            .iter()
+            .filter(|c| matches!(c, Code::Literal(_)))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(!expected_literals.is_empty());
+        assert!(expected_literals.len() < all.len());
+        assert_eq!(literals, expected_literals);
+    }
+
+    #[test]
+    fn stats_sink_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i % 100))
+            .collect::<String>()
+            .into_bytes();
+        let mut codes = Vec::<Code>::new();
+        let mut sink = TeeSink(StatsSink::new(), &mut codes);
+        let mut encoder = DefaultLz77Encoder::new();
+        encoder.encode(&plain, &mut sink);
+        encoder.flush(&mut sink);
+        let TeeSink(sink, _) = sink;
+
+        let mut expected = Lz77Stats::default();
+        for code in &codes {
+            expected.record(code);
+        }
+        assert!(expected.match_count > 0);
+        assert_eq!(*sink.as_stats(), expected);
+        let stats = sink.into_stats();
+        assert_eq!(
+            stats.literal_count + stats.total_match_length,
+            plain.len() as u64
+        );
+    }
+
+    #[test]
+    fn suffix_array_works() {
+        let mut enc = SuffixArrayLz77Encoder::new();
+        let mut sink = Vec::<Code>::new();
+        enc.encode(b"aaaaa", &mut sink);
+        enc.flush(&mut sink);
+        assert_eq!(
+            sink,
+            [
+                Code::Literal(b'a'),
+                Code::Pointer {
+                    length: 4,
+                    backward_distance: 1
+                }
+            ]
+        );
+
+        // The nearest one among the longest matches is chosen
+        let mut sink = Vec::<Code>::new();
+        enc.reset();
+        enc.encode(b"abcdXabcdYabcdXabcd", &mut sink);
+        enc.flush(&mut sink);
+        assert_eq!(
+            sink.last(),
+            Some(&Code::Pointer {
+                length: 9,
+                backward_distance: 10
+            })
+        );
+
+        let plain = (0..20_000)
+            .map(|i| format!("{} ", i * i % 997))
+            .collect::<String>()
+            .into_bytes();
+
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(&plain).unwrap();
+        let default_size = encoder.finish().into_result().unwrap().len();
+
+        let options = EncodeOptions::suffix_array_lz77()
+            .block_size(30_000)
+            .dictionary(&plain[..1000]);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        for chunk in plain.chunks(10_000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let encoded = encoder.finish().into_result().unwrap();
+        assert!(encoded.len() < default_size);
+
+        let mut decoded = Vec::new();
+        Decoder::with_dictionary(&encoded[..], &plain[..1000])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain);
+    }
+
+    #[test]
+    fn zopfli_works() {
+        let plain = (0..20_000)
+            .map(|i| format!("{} ", i * i % 997))
+            .collect::<String>()
+            .into_bytes();
+
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(&plain).unwrap();
+        let default_size = encoder.finish().into_result().unwrap().len();
+
+        let options = EncodeOptions::zopfli_lz77().block_size(30_000);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        for chunk in plain.chunks(10_000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let encoded = encoder.finish().into_result().unwrap();
+        assert!(encoded.len() < default_size);
+
+        let mut decoded = Vec::new();
+        Decoder::new(&encoded[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain);
+    }
 }
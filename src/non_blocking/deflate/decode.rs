@@ -6,6 +6,7 @@ use std::io::Read;
 use deflate::symbol::{self, HuffmanCodec};
 use lz77;
 use non_blocking::transaction::TransactionalBitReader;
+use Error;
 /// DEFLATE decoder which supports non-blocking I/O.
 #[derive(Debug)]
 pub struct Decoder<R> {
@@ -87,9 +88,7 @@ impl<R: Read> Read for Decoder<R> {
                         0b01 => DecoderState::LoadFixedHuffmanCode,
                         0b10 => DecoderState::LoadDynamicHuffmanCode,
                         0b11 => {
-                            return Err(invalid_data_error!(
-                                "btype 0x11 of DEFLATE is reserved(error) value"
-                            ));
+                            return Err(Error::InvalidBlockType.into());
                         }
                         _ => unreachable!(),
                     }
@@ -304,10 +303,7 @@ mod test {
 
     #[test]
     fn non_blocking_io_for_large_text_works() {
-        let text: String = (0..10000)
-            .into_iter()
-            .map(|i| format!("test {}", i))
-            .collect();
+        let text: String = (0..10000).map(|i| format!("test {}", i)).collect();
 
         let mut encoder = ::deflate::Encoder::new(Vec::new());
         io::copy(&mut text.as_bytes(), &mut encoder).unwrap();
@@ -320,7 +316,8 @@ mod test {
 
     #[test]
     fn non_compressed_non_blocking_io_works() {
-        let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::new().no_compression());
+        let mut encoder =
+            Encoder::with_options(Vec::new(), EncodeOptions::new().no_compression()).unwrap();
         io::copy(&mut &b"Hello World!"[..], &mut encoder).unwrap();
         let encoded_data = encoder.finish().into_result().unwrap();
 
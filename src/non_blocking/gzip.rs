@@ -22,16 +22,17 @@
 //! ```
 use std::io::{self, Read};
 
-use checksum;
+use crc32;
 use gzip::{Header, Trailer};
 use non_blocking::deflate;
+use Error;
 
 /// GZIP decoder which supports non-blocking I/O.
 #[derive(Debug)]
 pub struct Decoder<R> {
     header: Option<Header>,
     reader: deflate::Decoder<R>,
-    crc32: checksum::Crc32,
+    crc32: crc32::Crc32,
     eos: bool,
 }
 impl<R: Read> Decoder<R> {
@@ -58,7 +59,7 @@ impl<R: Read> Decoder<R> {
         Decoder {
             header: None,
             reader: deflate::Decoder::new(inner),
-            crc32: checksum::Crc32::new(),
+            crc32: crc32::Crc32::new(),
             eos: false,
         }
     }
@@ -137,11 +138,10 @@ impl<R: Read> Read for Decoder<R> {
                 // so that random data from fuzzer can reach actually interesting code
                 // Compilation flag 'fuzzing' is automatically set by all 3 Rust fuzzers.
                 if cfg!(not(fuzzing)) && trailer.crc32() != self.crc32.value() {
-                    Err(invalid_data_error!(
-                        "CRC32 mismatched: value={}, expected={}",
-                        self.crc32.value(),
-                        trailer.crc32()
-                    ))
+                    Err(io::Error::from(Error::ChecksumMismatch {
+                        expected: trailer.crc32(),
+                        got: self.crc32.value(),
+                    }))
                 } else {
                     Ok(0)
                 }
@@ -181,5 +181,4 @@ mod test {
         // decode_all(encoded).unwrap();
         assert_eq!(decode_all(encoded).unwrap(), decoded.to_vec());
     }
-
 }
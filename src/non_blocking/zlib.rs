@@ -22,16 +22,17 @@
 //! ```
 use std::io::{self, Read};
 
-use checksum;
+use adler32;
 use non_blocking::deflate;
 use zlib::Header;
+use Error;
 
 /// ZLIB decoder which supports non-blocking I/O.
 #[derive(Debug)]
 pub struct Decoder<R> {
     header: Option<Header>,
     reader: deflate::Decoder<R>,
-    adler32: checksum::Adler32,
+    adler32: adler32::Adler32,
     eos: bool,
 }
 impl<R: Read> Decoder<R> {
@@ -57,7 +58,7 @@ impl<R: Read> Decoder<R> {
         Decoder {
             header: None,
             reader: deflate::Decoder::new(inner),
-            adler32: checksum::Adler32::new(),
+            adler32: adler32::Adler32::new(),
             eos: false,
         }
     }
@@ -137,11 +138,10 @@ impl<R: Read> Read for Decoder<R> {
                 // so that random data from fuzzer can reach actually interesting code
                 // Compilation flag 'fuzzing' is automatically set by all 3 Rust fuzzers.
                 if cfg!(not(fuzzing)) && adler32 != self.adler32.value() {
-                    Err(invalid_data_error!(
-                        "Adler32 checksum mismatched: value={}, expected={}",
-                        self.adler32.value(),
-                        adler32
-                    ))
+                    Err(io::Error::from(Error::ChecksumMismatch {
+                        expected: adler32,
+                        got: self.adler32.value(),
+                    }))
                 } else {
                     Ok(0)
                 }
@@ -1,8 +1,296 @@
-#[cfg(test)]
-use std::io::{self, Read};
+//! Utility I/O adapters.
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+use io;
+
+/// A writer which counts the bytes written to the inner stream.
+///
+/// This is useful to measure the size of the compressed data
+/// (e.g., by wrapping the inner stream of an encoder).
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::deflate::Encoder;
+/// use libflate::util::CountingWriter;
+///
+/// let mut encoder = Encoder::new(CountingWriter::new(Vec::new()));
+/// encoder.write_all(b"Hello World!").unwrap();
+/// let writer = encoder.finish().into_result().unwrap();
+/// assert_eq!(writer.bytes_written(), writer.as_inner_ref().len() as u64);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+impl<W> CountingWriter<W> {
+    /// Makes a new writer instance.
+    pub fn new(inner: W) -> Self {
+        CountingWriter { inner, count: 0 }
+    }
+
+    /// Returns the number of the bytes written so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwraps this `CountingWriter`, returning the inner stream.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+impl<W> io::Write for CountingWriter<W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let size = self.inner.write(buf)?;
+        self.count += size as u64;
+        Ok(size)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader which counts the bytes read from the inner stream.
+///
+/// This is useful to measure the size of the uncompressed data given to an encoder.
+///
+/// # Examples
+/// ```
+/// use std::io;
+/// use libflate::deflate::Encoder;
+/// use libflate::util::CountingReader;
+///
+/// let mut reader = CountingReader::new(&b"Hello World!"[..]);
+/// let mut encoder = Encoder::new(Vec::new());
+/// io::copy(&mut reader, &mut encoder).unwrap();
+/// assert_eq!(reader.bytes_read(), 12);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+impl<R> CountingReader<R> {
+    /// Makes a new reader instance.
+    pub fn new(inner: R) -> Self {
+        CountingReader { inner, count: 0 }
+    }
+
+    /// Makes a new reader instance which starts counting from `count`.
+    #[cfg(feature = "std")]
+    pub(crate) fn with_count(inner: R, count: u64) -> Self {
+        CountingReader { inner, count }
+    }
+
+    /// Returns the number of the bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this `CountingReader`, returning the inner stream.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+impl<R> io::Read for CountingReader<R>
+where
+    R: io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.count += size as u64;
+        Ok(size)
+    }
+}
+
+/// A writer which writes the same data to two inner streams.
+///
+/// This is useful to process the data written to an encoder in another way at the same time
+/// (e.g., to compute the checksum of the uncompressed data for deduplication).
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use libflate::crc32::Crc32;
+/// use libflate::deflate::Encoder;
+/// use libflate::util::TeeWriter;
+///
+/// let mut writer = TeeWriter::new(Encoder::new(Vec::new()), Vec::new());
+/// writer.write_all(b"Hello World!").unwrap();
+/// let (encoder, plain) = writer.into_inner();
+/// let _encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let mut crc32 = Crc32::new();
+/// crc32.update(&plain);
+/// assert_eq!(crc32.value(), 0x1C291CA3);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct TeeWriter<W1, W2> {
+    w1: W1,
+    w2: W2,
+}
+impl<W1, W2> TeeWriter<W1, W2> {
+    /// Makes a new writer instance.
+    pub fn new(w1: W1, w2: W2) -> Self {
+        TeeWriter { w1, w2 }
+    }
+
+    /// Returns the immutable references to the inner streams.
+    pub fn as_inner_ref(&self) -> (&W1, &W2) {
+        (&self.w1, &self.w2)
+    }
+
+    /// Returns the mutable references to the inner streams.
+    pub fn as_inner_mut(&mut self) -> (&mut W1, &mut W2) {
+        (&mut self.w1, &mut self.w2)
+    }
+
+    /// Unwraps this `TeeWriter`, returning the inner streams.
+    pub fn into_inner(self) -> (W1, W2) {
+        (self.w1, self.w2)
+    }
+}
+impl<W1, W2> io::Write for TeeWriter<W1, W2>
+where
+    W1: io::Write,
+    W2: io::Write,
+{
+    /// Writes `buf` to the first stream, and then writes all the bytes accepted by it to the second stream.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let size = self.w1.write(buf)?;
+        self.w2.write_all(&buf[..size])?;
+        Ok(size)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.w1.flush()?;
+        self.w2.flush()
+    }
+}
+
+/// An adapter which decompresses the data into a caller-provided buffer chunk by chunk.
+///
+/// Each chunk is the part of the buffer filled by one `read` call of the decoder,
+/// so the decompressed data can be processed in fixed-size pieces without any allocation.
+///
+/// Since every chunk borrows the same buffer, this cannot implement `Iterator`;
+/// use `next_chunk` in a `while let` loop instead.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::{self, Decoder};
+/// use libflate::util::BufDecompress;
+///
+/// let encoded_data = deflate::compress(b"Hello World!");
+/// let mut buf = [0; 5];
+/// let mut chunks = BufDecompress::new(Decoder::new(&encoded_data[..]), &mut buf);
+/// let mut decoded_data = Vec::new();
+/// while let Some(chunk) = chunks.next_chunk() {
+///     let chunk = chunk.unwrap();
+///     assert!(chunk.len() <= 5);
+///     decoded_data.extend_from_slice(chunk);
+/// }
+/// assert_eq!(decoded_data, b"Hello World!");
+/// ```
+#[derive(Debug)]
+pub struct BufDecompress<'a, D> {
+    decoder: D,
+    buf: &'a mut [u8],
+    eos: bool,
+}
+impl<'a, D> BufDecompress<'a, D>
+where
+    D: io::Read,
+{
+    /// Makes a new adapter instance which reads from `decoder` into `buf`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is empty.
+    pub fn new(decoder: D, buf: &'a mut [u8]) -> Self {
+        assert!(!buf.is_empty(), "The buffer must not be empty");
+        BufDecompress {
+            decoder,
+            buf,
+            eos: false,
+        }
+    }
+
+    /// Reads the next chunk of the decompressed data.
+    ///
+    /// Returns `None` if the end of the stream has been reached.
+    /// `ErrorKind::Interrupted` errors are retried.
+    pub fn next_chunk(&mut self) -> Option<io::Result<&[u8]>> {
+        if self.eos {
+            return None;
+        }
+        loop {
+            match self.decoder.read(self.buf) {
+                Ok(0) => {
+                    self.eos = true;
+                    return None;
+                }
+                Ok(size) => return Some(Ok(&self.buf[..size])),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// Unwraps this `BufDecompress`, returning the decoder.
+    pub fn into_inner(self) -> D {
+        self.decoder
+    }
+}
+
+/// A reader which writes the bytes read from `inner` to `writer`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct TeeReader<R, W> {
+    inner: R,
+    writer: W,
+}
+#[cfg(feature = "std")]
+impl<R: Read, W: Write> TeeReader<R, W> {
+    pub fn new(inner: R, writer: W) -> Self {
+        TeeReader { inner, writer }
+    }
+}
+#[cfg(feature = "std")]
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.writer.write_all(&buf[..size])?;
+        Ok(size)
+    }
+}
 
 #[cfg(test)]
-pub struct WouldBlockReader<R> {
+pub(crate) struct WouldBlockReader<R> {
     inner: R,
     do_block: bool,
 }
@@ -36,7 +324,7 @@ impl<R: Read> Read for WouldBlockReader<R> {
 }
 
 #[cfg(test)]
-pub fn nb_read_to_end<R: Read>(mut reader: R) -> io::Result<Vec<u8>> {
+pub(crate) fn nb_read_to_end<R: Read>(mut reader: R) -> io::Result<Vec<u8>> {
     let mut buf = vec![0; 1024];
     let mut offset = 0;
     loop {
@@ -60,3 +348,214 @@ pub fn nb_read_to_end<R: Read>(mut reader: R) -> io::Result<Vec<u8>> {
     }
     Ok(buf)
 }
+
+/// An `AsyncRead` that returns `Poll::Pending` and a single byte alternately.
+#[cfg(all(test, feature = "tokio"))]
+pub(crate) struct PendingReader<R> {
+    inner: WouldBlockReader<R>,
+}
+#[cfg(all(test, feature = "tokio"))]
+impl<R: Read> PendingReader<R> {
+    pub fn new(inner: R) -> Self {
+        PendingReader {
+            inner: WouldBlockReader::new(inner),
+        }
+    }
+}
+#[cfg(all(test, feature = "tokio"))]
+impl<R: Read + Unpin> ::tokio::io::AsyncRead for PendingReader<R> {
+    fn poll_read(
+        self: ::std::pin::Pin<&mut Self>,
+        cx: &mut ::std::task::Context,
+        buf: &mut ::tokio::io::ReadBuf,
+    ) -> ::std::task::Poll<io::Result<()>> {
+        use std::task::Poll;
+        match self.get_mut().inner.read(buf.initialize_unfilled()) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(e)),
+            Ok(size) => {
+                buf.advance(size);
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+pub(crate) fn poll_read_to_end<R: ::tokio::io::AsyncRead + Unpin>(
+    mut reader: R,
+) -> io::Result<Vec<u8>> {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut buf = vec![0; 1024];
+    let mut offset = 0;
+    loop {
+        let mut read_buf = ::tokio::io::ReadBuf::new(&mut buf[offset..]);
+        match Pin::new(&mut reader).poll_read(&mut cx, &mut read_buf) {
+            Poll::Pending => {}
+            Poll::Ready(Err(e)) => return Err(e),
+            Poll::Ready(Ok(())) => {
+                let size = read_buf.filled().len();
+                if size == 0 {
+                    buf.truncate(offset);
+                    return Ok(buf);
+                }
+                offset += size;
+                if offset == buf.len() {
+                    buf.resize(offset * 2, 0);
+                }
+            }
+        }
+    }
+}
+
+/// An `AsyncWrite` that returns `Poll::Pending` and writes a single byte alternately.
+#[cfg(all(test, feature = "tokio"))]
+pub(crate) struct PendingWriter<W> {
+    inner: W,
+    do_block: bool,
+}
+#[cfg(all(test, feature = "tokio"))]
+impl<W: io::Write> PendingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        PendingWriter {
+            inner,
+            do_block: false,
+        }
+    }
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+#[cfg(all(test, feature = "tokio"))]
+impl<W: io::Write + Unpin> ::tokio::io::AsyncWrite for PendingWriter<W> {
+    fn poll_write(
+        self: ::std::pin::Pin<&mut Self>,
+        cx: &mut ::std::task::Context,
+        buf: &[u8],
+    ) -> ::std::task::Poll<io::Result<usize>> {
+        use std::task::Poll;
+        let this = self.get_mut();
+        this.do_block = !this.do_block;
+        if this.do_block {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else {
+            Poll::Ready(this.inner.write(&buf[..buf.len().min(1)]))
+        }
+    }
+    fn poll_flush(
+        self: ::std::pin::Pin<&mut Self>,
+        _cx: &mut ::std::task::Context,
+    ) -> ::std::task::Poll<io::Result<()>> {
+        ::std::task::Poll::Ready(self.get_mut().inner.flush())
+    }
+    fn poll_shutdown(
+        self: ::std::pin::Pin<&mut Self>,
+        cx: &mut ::std::task::Context,
+    ) -> ::std::task::Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[derive(Debug, Default)]
+    struct FlushCountingWriter {
+        buf: Vec<u8>,
+        flushes: usize,
+    }
+    impl Write for FlushCountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            // Accepts at most 3 bytes at once
+            let size = buf.len().min(3);
+            self.buf.extend_from_slice(&buf[..size]);
+            Ok(size)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn counting_writer_works() {
+        let mut writer = CountingWriter::new(FlushCountingWriter::default());
+        assert_eq!(writer.write(b"Hello World!").unwrap(), 3);
+        assert_eq!(writer.bytes_written(), 3);
+        writer.write_all(b"lo World!").unwrap();
+        assert_eq!(writer.bytes_written(), 12);
+        writer.flush().unwrap();
+        assert_eq!(writer.as_inner_ref().flushes, 1);
+        assert_eq!(writer.into_inner().buf, b"Hello World!");
+    }
+
+    #[test]
+    fn tee_writer_works() {
+        let mut writer = TeeWriter::new(FlushCountingWriter::default(), Vec::new());
+        assert_eq!(writer.write(b"Hello World!").unwrap(), 3);
+        assert_eq!(writer.as_inner_ref().1, b"Hel");
+        writer.write_all(b"lo World!").unwrap();
+        writer.flush().unwrap();
+        let (w1, w2) = writer.into_inner();
+        assert_eq!(w1.buf, b"Hello World!");
+        assert_eq!(w1.flushes, 1);
+        assert_eq!(w2, b"Hello World!");
+
+        let mut buf = [0; 4];
+        let mut writer = TeeWriter::new(Vec::new(), &mut buf[..]);
+        assert!(writer.write_all(b"Hello World!").is_err());
+    }
+
+    #[test]
+    fn buf_decompress_works() {
+        use deflate;
+
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i))
+            .collect::<String>()
+            .into_bytes();
+        let encoded = deflate::compress(&plain);
+        let mut buf = [0; 100];
+        let mut chunks = BufDecompress::new(deflate::Decoder::new(&encoded[..]), &mut buf);
+        let mut decoded = Vec::new();
+        while let Some(chunk) = chunks.next_chunk() {
+            let chunk = chunk.unwrap();
+            assert!(!chunk.is_empty() && chunk.len() <= 100);
+            decoded.extend_from_slice(chunk);
+        }
+        assert!(chunks.next_chunk().is_none());
+        assert_eq!(decoded, plain);
+
+        let mut buf = [0; 100];
+        let decoder = deflate::Decoder::new(&encoded[..encoded.len() / 2]);
+        let mut chunks = BufDecompress::new(decoder, &mut buf);
+        let mut result = Ok(());
+        while let Some(chunk) = chunks.next_chunk() {
+            if let Err(e) = chunk {
+                result = Err(e);
+                break;
+            }
+        }
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn counting_reader_works() {
+        let mut reader = CountingReader::new(&b"Hello World!"[..]);
+        let mut buf = [0; 5];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.bytes_read(), 5);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(reader.bytes_read(), 12);
+        assert_eq!(rest, b" World!");
+    }
+}
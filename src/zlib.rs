@@ -21,13 +21,26 @@
 //! ```
 use std::io;
 
-use checksum;
+use adler32;
+#[cfg(feature = "tokio")]
+use async_io::{self, PollReader};
 use deflate;
 use finish::{Complete, Finish};
 use lz77;
+#[cfg(feature = "tokio")]
+use non_blocking;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, ReadBuf};
+use {DecodeWarning, Error};
 
 const COMPRESSION_METHOD_DEFLATE: u8 = 8;
 
+const F_DICT: u8 = 0b10_0000;
+
 /// Compression levels defined by the ZLIB format.
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub enum CompressionLevel {
@@ -177,6 +190,7 @@ impl Lz77WindowSize {
 pub struct Header {
     window_size: Lz77WindowSize,
     compression_level: CompressionLevel,
+    dictionary_id: Option<u32>,
 }
 impl Header {
     /// Returns the LZ77 window size stored in the header.
@@ -187,13 +201,18 @@ impl Header {
     pub fn compression_level(&self) -> CompressionLevel {
         self.compression_level.clone()
     }
-    fn from_lz77<E>(lz77: &E) -> Self
+    /// Returns the Adler-32 checksum of the preset dictionary if the `FDICT` flag is set.
+    pub fn dictionary_id(&self) -> Option<u32> {
+        self.dictionary_id
+    }
+    pub(crate) fn from_lz77<E>(lz77: &E) -> Self
     where
         E: lz77::Lz77Encode,
     {
         Header {
             compression_level: From::from(lz77.compression_level()),
             window_size: Lz77WindowSize::from_u16(lz77.window_size()),
+            dictionary_id: None,
         }
     }
     pub(crate) fn read_from<R>(mut reader: R) -> io::Result<Self>
@@ -216,55 +235,171 @@ impl Header {
         let compression_method = cmf & 0b1111;
         let compression_info = cmf >> 4;
         if compression_method != COMPRESSION_METHOD_DEFLATE {
-            return Err(invalid_data_error!(
-                "Compression methods other than DEFLATE(8) are \
-                 unsupported: method={}",
-                compression_method
-            ));
+            return Err(Error::UnsupportedCompressionMethod(compression_method).into());
         }
         let window_size = Lz77WindowSize::from_u4(compression_info).ok_or_else(|| {
             invalid_data_error!("CINFO above 7 are not allowed: value={}", compression_info)
         })?;
 
-        let dict_flag = (flg & 0b100_000) != 0;
-        if dict_flag {
+        let dict_flag = (flg & F_DICT) != 0;
+        let dictionary_id = if dict_flag {
             let mut buf = [0; 4];
             reader.read_exact(&mut buf)?;
-            let dictionary_id = u32::from_be_bytes(buf);
-            return Err(invalid_data_error!(
-                "Preset dictionaries are not supported: \
-                 dictionary_id=0x{:X}",
-                dictionary_id
-            ));
-        }
+            Some(u32::from_be_bytes(buf))
+        } else {
+            None
+        };
         let compression_level = CompressionLevel::from_u2(flg >> 6);
         Ok(Header {
             window_size,
             compression_level,
+            dictionary_id,
         })
     }
-    fn write_to<W>(&self, mut writer: W) -> io::Result<()>
+    pub(crate) fn write_to<W>(&self, mut writer: W) -> io::Result<()>
     where
         W: io::Write,
     {
         let cmf = (self.window_size.as_u4() << 4) | COMPRESSION_METHOD_DEFLATE;
         let mut flg = self.compression_level.as_u2() << 6;
+        if self.dictionary_id.is_some() {
+            flg |= F_DICT;
+        }
         let check = (u16::from(cmf) << 8) + u16::from(flg);
         if check % 31 != 0 {
             flg += (31 - check % 31) as u8;
         }
         writer.write_all(&[cmf, flg])?;
+        if let Some(dictionary_id) = self.dictionary_id {
+            writer.write_all(&dictionary_id.to_be_bytes())?;
+        }
         Ok(())
     }
 }
 
+/// Options for a ZLIB decoder.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct DecodeOptions {
+    options: deflate::DecodeOptions,
+    lenient: bool,
+    skip_checksum: bool,
+}
+impl DecodeOptions {
+    /// Makes a default instance.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::zlib::{Decoder, DecodeOptions};
+    ///
+    /// let options = DecodeOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Specifies the options of the underlying DEFLATE decoder.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate;
+    /// use libflate::zlib::DecodeOptions;
+    ///
+    /// let options = DecodeOptions::new().deflate_options(deflate::DecodeOptions::new().window_bits(10));
+    /// assert_eq!(options.get_deflate_options().get_window_bits(), 10);
+    /// ```
+    pub fn deflate_options(mut self, options: deflate::DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the options of the underlying DEFLATE decoder.
+    pub fn get_deflate_options(&self) -> &deflate::DecodeOptions {
+        &self.options
+    }
+
+    /// Enables the lenient mode.
+    ///
+    /// In the lenient mode, a mismatch of the Adler-32 checksum in the trailer
+    /// is not an error but is recorded as a warning (see `Decoder::warnings`).
+    ///
+    /// Errors in the compressed data itself (e.g., an invalid huffman code or block type)
+    /// are reported as errors even in the lenient mode.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::zlib::{self, Decoder, DecodeOptions};
+    /// use libflate::DecodeWarning;
+    ///
+    /// let mut encoded_data = zlib::compress(b"Hello World!");
+    /// let len = encoded_data.len();
+    /// encoded_data[len - 1] ^= 1; // Corrupts the Adler-32 in the trailer
+    ///
+    /// let options = DecodeOptions::new().lenient();
+    /// let mut decoder = Decoder::with_options(&encoded_data[..], options).unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// assert!(matches!(decoder.warnings(), [DecodeWarning::ChecksumMismatch { .. }]));
+    /// ```
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Returns `true` if the lenient mode is enabled, otherwise `false`.
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Skips the computation and the verification of the Adler-32 checksum.
+    ///
+    /// This is useful if the integrity of the data is checked by other means.
+    /// The trailer is still read (so the stream must not be truncated), but its value is not verified.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::zlib::{self, Decoder, DecodeOptions};
+    ///
+    /// let mut encoded_data = zlib::compress(b"Hello World!");
+    /// let len = encoded_data.len();
+    /// encoded_data[len - 1] ^= 1; // Corrupts the Adler-32 in the trailer
+    ///
+    /// let options = DecodeOptions::new().skip_checksum();
+    /// let mut decoder = Decoder::with_options(&encoded_data[..], options).unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// assert!(decoder.warnings().is_empty());
+    /// ```
+    pub fn skip_checksum(mut self) -> Self {
+        self.skip_checksum = true;
+        self
+    }
+
+    /// Returns `true` if the verification of the checksum is skipped, otherwise `false`.
+    pub fn is_checksum_skipped(&self) -> bool {
+        self.skip_checksum
+    }
+}
+impl From<deflate::DecodeOptions> for DecodeOptions {
+    fn from(options: deflate::DecodeOptions) -> Self {
+        DecodeOptions::new().deflate_options(options)
+    }
+}
+
 /// ZLIB decoder.
 #[derive(Debug)]
 pub struct Decoder<R> {
     header: Header,
     reader: deflate::Decoder<R>,
-    adler32: checksum::Adler32,
+    adler32: adler32::Adler32,
     eos: bool,
+    lenient: bool,
+    skip_checksum: bool,
+    trailer: Option<u32>,
+    warnings: Vec<DecodeWarning>,
 }
 impl<R> Decoder<R>
 where
@@ -288,13 +423,130 @@ where
     ///
     /// assert_eq!(buf, b"Hello World!");
     /// ```
-    pub fn new(mut inner: R) -> io::Result<Self> {
+    pub fn new(inner: R) -> io::Result<Self> {
+        Self::with_options(inner, DecodeOptions::new())
+    }
+
+    /// Makes a new decoder instance with specified options.
+    ///
+    /// `options` can be either `zlib::DecodeOptions` or `deflate::DecodeOptions`.
+    /// If the window size declared in the ZLIB header exceeds the limit of `options`,
+    /// an error will be returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::DecodeOptions;
+    /// use libflate::zlib::Decoder;
+    ///
+    /// // The header declares 32 KB window
+    /// let encoded_data = [120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47,
+    ///                     202, 73, 81, 4, 0, 28, 73, 4, 62];
+    ///
+    /// let options = DecodeOptions::new().window_bits(15);
+    /// assert!(Decoder::with_options(&encoded_data[..], options).is_ok());
+    ///
+    /// let options = DecodeOptions::new().window_bits(14);
+    /// assert!(Decoder::with_options(&encoded_data[..], options).is_err());
+    /// ```
+    pub fn with_options<O>(inner: R, options: O) -> io::Result<Self>
+    where
+        O: Into<DecodeOptions>,
+    {
+        Self::with_options_and_dictionary(inner, options.into(), None)
+    }
+
+    /// Makes a new decoder instance which outputs at most `limit` bytes.
+    ///
+    /// Once the decoded data exceeds `limit` bytes, reading returns an error of `io::ErrorKind::Other`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{ErrorKind, Read};
+    /// use libflate::zlib::Decoder;
+    ///
+    /// let encoded_data = [120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47,
+    ///                     202, 73, 81, 4, 0, 28, 73, 4, 62];
+    ///
+    /// let mut decoder = Decoder::with_max_output(&encoded_data[..], 5).unwrap();
+    /// let error = decoder.read_to_end(&mut Vec::new()).err().unwrap();
+    /// assert_eq!(error.kind(), ErrorKind::Other);
+    /// ```
+    pub fn with_max_output(inner: R, limit: u64) -> io::Result<Self> {
+        Self::with_options(inner, deflate::DecodeOptions::new().max_output(limit))
+    }
+
+    /// Makes a new decoder instance with the preset dictionary.
+    ///
+    /// If the stream requires a preset dictionary (i.e., the `FDICT` flag is set),
+    /// the Adler-32 checksum of `dict` is verified against the dictionary identifier in the header,
+    /// and `dict` is loaded into the sliding window of the DEFLATE decoder.
+    /// If the checksums differ, an error of `io::ErrorKind::InvalidData` will be returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::zlib::{Decoder, Encoder, EncodeOptions};
+    ///
+    /// let dict = b"Hello World!";
+    /// let options = EncodeOptions::new().dictionary(dict);
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::with_dictionary(&encoded_data[..], dict).unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    ///
+    /// assert!(Decoder::new(&encoded_data[..]).is_err());
+    /// assert!(Decoder::with_dictionary(&encoded_data[..], b"Hello").is_err());
+    /// ```
+    pub fn with_dictionary(inner: R, dict: &[u8]) -> io::Result<Self> {
+        Self::with_options_and_dictionary(inner, DecodeOptions::new(), Some(dict))
+    }
+
+    fn with_options_and_dictionary(
+        mut inner: R,
+        options: DecodeOptions,
+        dict: Option<&[u8]>,
+    ) -> io::Result<Self> {
         let header = Header::read_from(&mut inner)?;
+        let max_window_size = 1u32 << options.options.get_window_bits();
+        if u32::from(header.window_size.to_u16()) > max_window_size {
+            return Err(invalid_data_error!(
+                "The window size of the stream is too large: max={}, actual={}",
+                max_window_size,
+                header.window_size.to_u16()
+            ));
+        }
+        let mut reader = deflate::Decoder::with_options(inner, options.options);
+        if let Some(dictionary_id) = header.dictionary_id {
+            let dict = dict.ok_or_else(|| {
+                invalid_data_error!(
+                    "The stream requires a preset dictionary: dictionary_id=0x{:X}",
+                    dictionary_id
+                )
+            })?;
+            let mut adler32 = adler32::Adler32::new();
+            adler32.update(dict);
+            if adler32.value() != dictionary_id {
+                return Err(invalid_data_error!(
+                    "Preset dictionary mismatched: dictionary_id=0x{:X}, adler32(dict)=0x{:X}",
+                    dictionary_id,
+                    adler32.value()
+                ));
+            }
+            reader.set_dictionary(dict);
+        }
         Ok(Decoder {
             header,
-            reader: deflate::Decoder::new(inner),
-            adler32: checksum::Adler32::new(),
+            reader,
+            adler32: adler32::Adler32::new(),
             eos: false,
+            lenient: options.lenient,
+            skip_checksum: options.skip_checksum,
+            trailer: None,
+            warnings: Vec::new(),
         })
     }
 
@@ -315,6 +567,15 @@ where
         &self.header
     }
 
+    /// Returns the warnings detected so far in the lenient mode.
+    ///
+    /// The trailer is verified after the end of the compressed data is reached,
+    /// so the warnings about it are available after the stream is consumed.
+    /// If the lenient mode is disabled, this always returns an empty slice.
+    pub fn warnings(&self) -> &[DecodeWarning] {
+        &self.warnings
+    }
+
     /// Returns the immutable reference to the inner stream.
     pub fn as_inner_ref(&self) -> &R {
         self.reader.as_inner_ref()
@@ -341,6 +602,41 @@ where
     pub fn into_inner(self) -> R {
         self.reader.into_inner()
     }
+
+    /// Reads the rest of the stream and returns the Adler-32 checksum of the decoded data.
+    ///
+    /// The remaining decoded data are discarded.
+    /// If the checksum does not match the trailer, an error of `io::ErrorKind::InvalidData` is returned
+    /// (in the lenient mode, the mismatch is not an error and the computed checksum is returned).
+    /// If the checksum is skipped (see `DecodeOptions::skip_checksum`),
+    /// the unverified value of the trailer is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::zlib::{self, Decoder};
+    ///
+    /// let encoded_data = zlib::compress(b"Hello World!");
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
+    /// let mut buf = [0; 5];
+    /// decoder.read_exact(&mut buf).unwrap();
+    /// assert_eq!(decoder.finish().unwrap(), 0x1C49_043E);
+    ///
+    /// let mut corrupted = encoded_data.clone();
+    /// let len = corrupted.len();
+    /// corrupted[len - 1] ^= 1;
+    /// let decoder = Decoder::new(&corrupted[..]).unwrap();
+    /// assert!(decoder.finish().is_err());
+    /// ```
+    pub fn finish(mut self) -> io::Result<u32> {
+        io::copy(&mut self, &mut io::sink())?;
+        if self.skip_checksum {
+            Ok(self.trailer.expect("Never fails"))
+        } else {
+            Ok(self.adler32.value())
+        }
+    }
 }
 impl<R> io::Read for Decoder<R>
 where
@@ -356,21 +652,26 @@ where
                 let mut buf = [0; 4];
                 self.reader.as_inner_mut().read_exact(&mut buf)?;
                 let adler32 = u32::from_be_bytes(buf);
+                self.trailer = Some(adler32);
 
                 // checksum verification is skipped during fuzzing
                 // so that random data from fuzzer can reach actually interesting code
                 // Compilation flag 'fuzzing' is automatically set by all 3 Rust fuzzers.
-                if cfg!(not(fuzzing)) && adler32 != self.adler32.value() {
-                    Err(invalid_data_error!(
-                        "Adler32 checksum mismatched: value={}, expected={}",
-                        self.adler32.value(),
-                        adler32
-                    ))
-                } else {
-                    Ok(0)
+                if cfg!(not(fuzzing)) && !self.skip_checksum && adler32 != self.adler32.value() {
+                    let warning = DecodeWarning::ChecksumMismatch {
+                        expected: adler32,
+                        got: self.adler32.value(),
+                    };
+                    if !self.lenient {
+                        return Err(warning.into_error().into());
+                    }
+                    self.warnings.push(warning);
                 }
+                Ok(0)
             } else {
-                self.adler32.update(&buf[..read_size]);
+                if !self.skip_checksum {
+                    self.adler32.update(&buf[..read_size]);
+                }
                 Ok(read_size)
             }
         }
@@ -378,7 +679,7 @@ where
 }
 
 /// Options for a ZLIB encoder.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct EncodeOptions<E>
 where
     E: lz77::Lz77Encode,
@@ -474,6 +775,136 @@ where
         self.options = self.options.fixed_huffman_codes();
         self
     }
+
+    /// Specifies the preset dictionary.
+    ///
+    /// The `FDICT` flag and the Adler-32 checksum of `dict` are written to the header,
+    /// and the LZ77 encoder is initialized with `dict`.
+    /// The encoded stream must be decoded by `Decoder::with_dictionary` with the same dictionary.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::zlib::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().dictionary(b"Hello World!");
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// assert_eq!(encoder.header().dictionary_id(), Some(0x1C49043E));
+    /// ```
+    pub fn dictionary(mut self, dict: &[u8]) -> Self {
+        let mut adler32 = adler32::Adler32::new();
+        adler32.update(dict);
+        self.header.dictionary_id = Some(adler32.value());
+        self.options = self.options.dictionary(dict);
+        self
+    }
+}
+impl<E> EncodeOptions<E>
+where
+    E: lz77::Lz77Encode + 'static,
+{
+    /// Erases the type of the LZ77 encoder by boxing it.
+    ///
+    /// See `deflate::EncodeOptions::type_erased` for more details.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::zlib::{Encoder, EncodeOptions};
+    /// use libflate::lz77::Lz77Encode;
+    ///
+    /// let options: EncodeOptions<Box<dyn Lz77Encode>> = EncodeOptions::new().type_erased();
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// ```
+    pub fn type_erased(self) -> EncodeOptions<Box<dyn lz77::Lz77Encode>> {
+        EncodeOptions {
+            header: self.header,
+            options: self.options.type_erased(),
+        }
+    }
+}
+impl<E> From<deflate::EncodeOptions<E>> for EncodeOptions<E>
+where
+    E: lz77::Lz77Encode,
+{
+    /// Makes ZLIB options which encode the payload with `options`.
+    ///
+    /// The header is derived from `options`:
+    /// the compression level and the window size are taken from the LZ77 encoder
+    /// (`CompressionLevel::Fastest` and the default window size if LZ77 compression is disabled),
+    /// and the `FDICT` flag and the dictionary ID are set if a preset dictionary is specified.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::{deflate, zlib};
+    ///
+    /// let options = deflate::EncodeOptions::new().block_size(4096).dictionary(b"Hello World!");
+    /// let encoder = zlib::Encoder::with_options(Vec::new(), options.into()).unwrap();
+    /// assert_eq!(encoder.header().dictionary_id(), Some(0x1C49043E));
+    /// ```
+    fn from(options: deflate::EncodeOptions<E>) -> Self {
+        let mut header = match options.get_lz77() {
+            Some(lz77) => Header::from_lz77(lz77),
+            None => Header {
+                compression_level: CompressionLevel::Fastest,
+                ..Header::from_lz77(&lz77::DefaultLz77Encoder::new())
+            },
+        };
+        if !options.get_dictionary().is_empty() {
+            header.dictionary_id = Some(adler32::update(
+                adler32::INITIAL_VALUE,
+                options.get_dictionary(),
+            ));
+        }
+        EncodeOptions { header, options }
+    }
+}
+
+/// Returns the upper bound of the size of ZLIB encoded data of `uncompressed_len` bytes.
+///
+/// This is `deflate::compressed_size_bound` plus the size of the header and the trailer.
+///
+/// # Examples
+/// ```
+/// use libflate::{deflate, zlib};
+///
+/// assert_eq!(zlib::compressed_size_bound(100), deflate::compressed_size_bound(100) + 6);
+/// ```
+pub fn compressed_size_bound(uncompressed_len: usize) -> usize {
+    // 2 bytes header and 4 bytes ADLER32 checksum
+    deflate::compressed_size_bound(uncompressed_len).saturating_add(2 + 4)
+}
+
+/// Encodes `input` into a ZLIB stream with the default options.
+///
+/// # Examples
+/// ```
+/// use libflate::zlib;
+///
+/// let encoded_data = zlib::compress(b"Hello World!");
+/// assert_eq!(zlib::decompress(&encoded_data).unwrap(), b"Hello World!");
+/// ```
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+    let buf = Vec::with_capacity(compressed_size_bound(input.len()));
+    let mut encoder = Encoder::new(buf).expect("Never fails");
+    encoder.write_all(input).expect("Never fails");
+    encoder.finish().into_result().expect("Never fails")
+}
+
+/// Decodes a ZLIB stream `input`.
+///
+/// # Examples
+/// ```
+/// use libflate::zlib;
+///
+/// let encoded_data = [120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47,
+///                     202, 73, 81, 4, 0, 28, 73, 4, 62];
+/// assert_eq!(zlib::decompress(&encoded_data).unwrap(), b"Hello World!");
+/// ```
+pub fn decompress(input: &[u8]) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+    let mut buf = Vec::with_capacity(input.len().saturating_mul(2));
+    Decoder::new(input)?.read_to_end(&mut buf)?;
+    Ok(buf)
 }
 
 /// ZLIB encoder.
@@ -481,7 +912,7 @@ where
 pub struct Encoder<W, E = lz77::DefaultLz77Encoder> {
     header: Header,
     writer: deflate::Encoder<W, E>,
-    adler32: checksum::Adler32,
+    adler32: adler32::Adler32,
 }
 impl<W> Encoder<W, lz77::DefaultLz77Encoder>
 where
@@ -500,8 +931,8 @@ where
     /// encoder.write_all(b"Hello World!").unwrap();
     ///
     /// assert_eq!(encoder.finish().into_result().unwrap(),
-    ///            vec![120, 156, 5, 192, 49, 13, 0, 0, 8, 3, 65, 43, 224, 6, 7, 24, 128,
-    ///                 237, 147, 38, 245, 63, 244, 230, 65, 181, 50, 215, 1, 28, 73, 4, 62]);
+    ///            vec![120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0,
+    ///                 28, 73, 4, 62]);
     /// ```
     pub fn new(inner: W) -> io::Result<Self> {
         Self::with_options(inner, EncodeOptions::default())
@@ -529,12 +960,13 @@ where
     ///            [120, 1, 1, 12, 0, 243, 255, 72, 101, 108, 108, 111, 32, 87, 111,
     ///             114, 108, 100, 33, 28, 73, 4, 62]);
     /// ```
-    pub fn with_options(mut inner: W, options: EncodeOptions<E>) -> io::Result<Self> {
-        options.header.write_to(&mut inner)?;
+    pub fn with_options(inner: W, options: EncodeOptions<E>) -> io::Result<Self> {
+        let mut writer = deflate::Encoder::with_options(inner, options.options)?;
+        options.header.write_to(writer.as_inner_mut())?;
         Ok(Encoder {
             header: options.header,
-            writer: deflate::Encoder::with_options(inner, options.options),
-            adler32: checksum::Adler32::new(),
+            writer,
+            adler32: adler32::Adler32::new(),
         })
     }
 
@@ -562,8 +994,8 @@ where
     /// encoder.write_all(b"Hello World!").unwrap();
     ///
     /// assert_eq!(encoder.finish().into_result().unwrap(),
-    ///            vec![120, 156, 5, 192, 49, 13, 0, 0, 8, 3, 65, 43, 224, 6, 7, 24, 128,
-    ///                 237, 147, 38, 245, 63, 244, 230, 65, 181, 50, 215, 1, 28, 73, 4, 62]);
+    ///            vec![120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0,
+    ///                 28, 73, 4, 62]);
     /// ```
     ///
     /// # Note
@@ -631,6 +1063,59 @@ where
     }
 }
 
+/// ZLIB decoder which implements `tokio::io::AsyncRead`.
+///
+/// See `deflate::AsyncDecoder` for details.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncDecoder<R> {
+    decoder: non_blocking::zlib::Decoder<PollReader<R>>,
+}
+#[cfg(feature = "tokio")]
+impl<R> AsyncDecoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Makes a new decoder instance.
+    ///
+    /// `inner` is to be decoded ZLIB stream.
+    pub fn new(inner: R) -> Self {
+        AsyncDecoder {
+            decoder: non_blocking::zlib::Decoder::new(PollReader::new(inner)),
+        }
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        self.decoder.as_inner_ref().as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        self.decoder.as_inner_mut().as_inner_mut()
+    }
+
+    /// Unwraps this `AsyncDecoder`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.decoder.into_inner().into_inner()
+    }
+}
+#[cfg(feature = "tokio")]
+impl<R> AsyncRead for AsyncDecoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.decoder.as_inner_mut().set_waker(cx.waker());
+        async_io::poll_read(&mut this.decoder, buf)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -667,6 +1152,7 @@ mod test {
             Header {
                 window_size: Lz77WindowSize::KB32,
                 compression_level: CompressionLevel::Default,
+                dictionary_id: None,
             }
         );
 
@@ -713,6 +1199,116 @@ mod test {
         assert_eq!(decode_all(&encoded).unwrap(), plain);
     }
 
+    #[test]
+    fn dictionary_works() {
+        let dict = include_bytes!("../README.md");
+        let plain = &dict[100..1000];
+
+        let mut encoder =
+            Encoder::with_options(Vec::new(), EncodeOptions::new().dictionary(dict)).unwrap();
+        io::copy(&mut &plain[..], &mut encoder).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert!(encoded.len() < default_encode(plain).unwrap().len() / 4);
+
+        let mut decoder = Decoder::with_dictionary(&encoded[..], dict).unwrap();
+        let mut buf = Vec::new();
+        io::copy(&mut decoder, &mut buf).unwrap();
+        assert_eq!(buf, plain);
+
+        // A dictionary is required
+        let e = Decoder::new(&encoded[..]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        // Mismatched dictionary
+        let e = Decoder::with_dictionary(&encoded[..], &dict[1..]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        // A dictionary is ignored if the stream does not require it
+        let encoded = default_encode(plain).unwrap();
+        let mut decoder = Decoder::with_dictionary(&encoded[..], dict).unwrap();
+        let mut buf = Vec::new();
+        io::copy(&mut decoder, &mut buf).unwrap();
+        assert_eq!(buf, plain);
+    }
+
+    #[test]
+    fn from_deflate_options_works() {
+        let dict = b"Hello World!";
+        assert_eq!(
+            EncodeOptions::from(deflate::EncodeOptions::new()),
+            EncodeOptions::new()
+        );
+        assert_eq!(
+            EncodeOptions::from(deflate::EncodeOptions::new().no_compression()),
+            EncodeOptions::new().no_compression()
+        );
+        assert_eq!(
+            EncodeOptions::from(
+                deflate::EncodeOptions::new()
+                    .block_size(4096)
+                    .fixed_huffman_codes()
+                    .dictionary(dict)
+            ),
+            EncodeOptions::new()
+                .block_size(4096)
+                .fixed_huffman_codes()
+                .dictionary(dict)
+        );
+        let lz77 = lz77::DefaultLz77Encoder::with_window_size(1024);
+        let options = EncodeOptions::from(deflate::EncodeOptions::with_lz77(lz77.clone()));
+        assert_eq!(options, EncodeOptions::with_lz77(lz77));
+        assert_eq!(options.header.window_size(), Lz77WindowSize::KB1);
+    }
+
+    #[test]
+    fn window_bits_works() {
+        let plain = (0..10_000).map(|i| (i % 1500) as u8).collect::<Vec<_>>();
+        let lz77 = lz77::DefaultLz77EncoderBuilder::new()
+            .window_bits(10)
+            .build();
+        let mut encoder =
+            Encoder::with_options(Vec::new(), EncodeOptions::with_lz77(lz77)).unwrap();
+        io::copy(&mut &plain[..], &mut encoder).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let options = deflate::DecodeOptions::new().window_bits(10);
+        let mut decoder = Decoder::with_options(&encoded[..], options).unwrap();
+        assert_eq!(decoder.header().window_size(), Lz77WindowSize::KB1);
+        let mut buf = Vec::new();
+        io::copy(&mut decoder, &mut buf).unwrap();
+        assert_eq!(buf, plain);
+
+        let options = deflate::DecodeOptions::new().window_bits(9);
+        assert!(Decoder::with_options(&encoded[..], options).is_err());
+    }
+
+    #[test]
+    fn finish_works() {
+        let plain = (0..10_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let mut encoded = ::zlib::compress(&plain);
+        let mut expected = adler32::Adler32::new();
+        expected.update(&plain);
+
+        let decoder = Decoder::new(&encoded[..]).unwrap();
+        assert_eq!(decoder.finish().unwrap(), expected.value());
+
+        let len = encoded.len();
+        encoded[len - 4] ^= 1;
+        let decoder = Decoder::new(&encoded[..]).unwrap();
+        assert_eq!(
+            decoder.finish().err().unwrap().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let options = DecodeOptions::new().skip_checksum();
+        let decoder = Decoder::with_options(&encoded[..], options).unwrap();
+        assert_eq!(decoder.finish().unwrap(), expected.value() ^ 0x0100_0000);
+
+        let options = DecodeOptions::new().skip_checksum();
+        let decoder = Decoder::with_options(&encoded[..len - 1], options).unwrap();
+        assert!(decoder.finish().is_err());
+    }
+
     #[test]
     fn encoder_auto_finish_works() {
         let plain = b"Hello World! Hello ZLIB!!";
@@ -751,23 +1347,36 @@ mod test {
 
         let encoded =
             include_bytes!("../data/issues_16/crash-1bb6d408475a5bd57247ee40f290830adfe2086e");
+        let e = decode_all(&encoded[..]).err().map(Error::from).unwrap();
         assert_eq!(
-            decode_all(&encoded[..]).err().map(|e| e.to_string()),
-            Some("The value of HDIST is too big: max=30, actual=32".to_owned())
+            e.without_context().to_string(),
+            "The value of HDIST is too big: max=30, actual=32"
         );
 
         let encoded =
             include_bytes!("../data/issues_16/crash-369e8509a0e76356f4549c292ceedee429cfe125");
+        let e = decode_all(&encoded[..]).err().map(Error::from).unwrap();
         assert_eq!(
-            decode_all(&encoded[..]).err().map(|e| e.to_string()),
-            Some("The value of HDIST is too big: max=30, actual=32".to_owned())
+            e.without_context().to_string(),
+            "The value of HDIST is too big: max=30, actual=32"
         );
 
         let encoded =
             include_bytes!("../data/issues_16/crash-e75959d935650306881140df7f6d1d73e33425cb");
+        let e = decode_all(&encoded[..]).err().map(Error::from).unwrap();
         assert_eq!(
-            decode_all(&encoded[..]).err().map(|e| e.to_string()),
-            Some("The value of HDIST is too big: max=30, actual=32".to_owned())
+            e.without_context().to_string(),
+            "The value of HDIST is too big: max=30, actual=32"
         );
     }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_decoder_works() {
+        use util::{poll_read_to_end, PendingReader};
+        let plain = include_bytes!("../README.md");
+        let encoded = default_encode(plain).unwrap();
+        let decoder = AsyncDecoder::new(PendingReader::new(&encoded[..]));
+        assert_eq!(poll_read_to_end(decoder).unwrap(), &plain[..]);
+    }
 }
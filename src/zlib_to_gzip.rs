@@ -0,0 +1,112 @@
+//! Transcoding from the ZLIB format to the GZIP format.
+//!
+//! # Examples
+//! ```
+//! use std::io::Read;
+//! use libflate::{gzip, zlib, zlib_to_gzip};
+//!
+//! let zlib_data = zlib::compress(b"Hello World!");
+//! let mut gzip_data = Vec::new();
+//! zlib_to_gzip::transcode(&zlib_data[..], &mut gzip_data).unwrap();
+//!
+//! let mut decoded_data = Vec::new();
+//! gzip::Decoder::new(&gzip_data[..]).unwrap().read_to_end(&mut decoded_data).unwrap();
+//! assert_eq!(decoded_data, b"Hello World!");
+//! ```
+use std::io::{self, Read, Write};
+
+use adler32;
+use crc32;
+use deflate;
+use gzip;
+use util::TeeReader;
+use zlib;
+use Error;
+
+/// Reads a ZLIB stream from `src` and writes the equivalent GZIP stream to `dst`.
+///
+/// The DEFLATE payload is copied to `dst` as is, so the data are not compressed again.
+/// To compute the CRC-32 and `ISIZE` of the GZIP trailer, the payload is decoded while being copied
+/// (the decoded data are discarded).
+/// The Adler-32 checksum of the ZLIB trailer is verified against the decoded data.
+///
+/// The written GZIP stream has the default header (see `gzip::HeaderBuilder`).
+/// A ZLIB stream which requires a preset dictionary cannot be transcoded,
+/// because the GZIP format has no way to refer to the dictionary.
+pub fn transcode<R, W>(mut src: R, mut dst: W) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    let header = zlib::Header::read_from(&mut src)?;
+    if let Some(id) = header.dictionary_id() {
+        return Err(invalid_data_error!(
+            "A ZLIB stream with a preset dictionary cannot be transcoded: dictionary_id={}",
+            id
+        ));
+    }
+    gzip::HeaderBuilder::new().finish().write_to(&mut dst)?;
+
+    let mut adler32 = adler32::Adler32::new();
+    let mut crc32 = crc32::Crc32::new();
+    let mut input_size = 0u32;
+    let mut decoder = deflate::Decoder::new(TeeReader::new(&mut src, &mut dst));
+    let mut buf = [0; 4096];
+    loop {
+        let size = decoder.read(&mut buf)?;
+        if size == 0 {
+            break;
+        }
+        adler32.update(&buf[..size]);
+        crc32.update(&buf[..size]);
+        input_size = input_size.wrapping_add(size as u32);
+    }
+
+    let mut buf = [0; 4];
+    src.read_exact(&mut buf)?;
+    let expected = u32::from_be_bytes(buf);
+    if expected != adler32.value() {
+        return Err(Error::ChecksumMismatch {
+            expected,
+            got: adler32.value(),
+        }
+        .into());
+    }
+    gzip::Trailer::new(crc32.value(), input_size).write_to(&mut dst)?;
+    dst.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transcode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let zlib_data = zlib::compress(&plain);
+        let mut gzip_data = Vec::new();
+        transcode(&zlib_data[..], &mut gzip_data).unwrap();
+        assert_eq!(gzip::decompress(&gzip_data).unwrap(), plain);
+
+        // The DEFLATE payload is kept as is
+        assert_eq!(
+            &zlib_data[2..zlib_data.len() - 4],
+            &gzip_data[10..gzip_data.len() - 8]
+        );
+
+        let mut corrupted = zlib_data.clone();
+        let len = corrupted.len();
+        corrupted[len - 1] ^= 1;
+        assert!(transcode(&corrupted[..], &mut Vec::new()).is_err());
+
+        let dict = b"Hello";
+        let options = zlib::EncodeOptions::new().dictionary(dict);
+        let mut encoder = zlib::Encoder::with_options(Vec::new(), options).unwrap();
+        encoder.write_all(b"Hello World!").unwrap();
+        let zlib_data = encoder.finish().into_result().unwrap();
+        assert!(transcode(&zlib_data[..], &mut Vec::new()).is_err());
+    }
+}